@@ -1,5 +1,8 @@
+use std::future::Future;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use log::warn;
 use sea_orm::{ConnectionTrait, DatabaseConnection, Statement};
 use solana_client::nonblocking::rpc_client::RpcClient;
 use utoipa::openapi::{ObjectBuilder, RefOr, Schema, SchemaType};
@@ -8,8 +11,26 @@ use utoipa::ToSchema;
 use crate::api::method::get_validity_proof::GetValidityProofRequestDocumentation;
 use crate::api::method::utils::GetNonPaginatedSignaturesResponse;
 use crate::common::typedefs::unsigned_integer::UnsignedInteger;
+use crate::common::{Commitment, RetryBackoffConfig};
+use crate::ingester::IngesterConfig;
 
+use super::method::decode_compressed_account::{
+    decode_compressed_account, DecodeCompressedAccountRequest, DecodeCompressedAccountResponse,
+};
 use super::method::get_compressed_account::AccountResponse;
+use super::method::get_compressed_account_and_proof_by_address::{
+    get_compressed_account_and_proof_by_address, GetCompressedAccountAndProofByAddressRequest,
+    GetCompressedAccountAndProofByAddressResponse,
+};
+use super::method::get_compressed_accounts_by_lamport_range::{
+    get_compressed_accounts_by_lamport_range, GetCompressedAccountsByLamportRangeRequest,
+    GetCompressedAccountsByLamportRangeResponse,
+};
+use super::method::get_compressed_accounts_by_owner_grouped_by_tree::{
+    get_compressed_accounts_by_owner_grouped_by_tree,
+    GetCompressedAccountsByOwnerGroupedByTreeRequest,
+    GetCompressedAccountsByOwnerGroupedByTreeResponse,
+};
 use super::method::get_compressed_balance_by_owner::{
     get_compressed_balance_by_owner, GetCompressedBalanceByOwnerRequest,
 };
@@ -30,29 +51,63 @@ use super::method::get_compression_signatures_for_owner::{
 use super::method::get_compression_signatures_for_token_owner::{
     get_compression_signatures_for_token_owner, GetCompressionSignaturesForTokenOwnerRequest,
 };
+use super::method::get_ingestion_status::{get_ingestion_status, GetIngestionStatusResponse};
+use super::method::get_instruction_validity_proof::{
+    get_instruction_validity_proof, GetInstructionValidityProofResponse,
+};
+use super::method::get_latest_compressed_accounts::{
+    get_latest_compressed_accounts, GetLatestCompressedAccountsRequest,
+    GetLatestCompressedAccountsResponse,
+};
 use super::method::get_latest_compression_signatures::get_latest_compression_signatures;
 use super::method::get_latest_non_voting_signatures::get_latest_non_voting_signatures;
 use super::method::get_multiple_new_address_proofs::{
     get_multiple_new_address_proofs, get_multiple_new_address_proofs_v2, AddressList,
     AddressListWithTrees, GetMultipleNewAddressProofsResponse,
 };
+use super::method::get_new_address_proof::{
+    get_new_address_proof, GetNewAddressProofRequest, GetNewAddressProofResponse,
+};
+use super::method::get_recent_parse_errors::{
+    get_recent_parse_errors, GetRecentParseErrorsRequest, GetRecentParseErrorsResponse,
+};
+use super::method::get_recently_spent_accounts::{
+    get_recently_spent_accounts, GetRecentlySpentAccountsRequest, GetRecentlySpentAccountsResponse,
+};
+use super::method::get_transaction_account_mapping::{
+    get_transaction_account_mapping, GetTransactionAccountMappingRequest,
+    GetTransactionAccountMappingResponse,
+};
 use super::method::get_transaction_with_compression_info::{
     get_transaction_with_compression_info, GetTransactionRequest, GetTransactionResponse,
 };
+use super::method::get_tree_nodes::{get_tree_nodes, GetTreeNodesRequest, GetTreeNodesResponse};
+use super::method::get_tree_size::{get_tree_size, GetTreeSizeRequest, GetTreeSizeResponse};
+use super::method::get_unspent_account_set::{
+    get_unspent_account_set, GetUnspentAccountSetRequest, GetUnspentAccountSetResponse,
+};
 use super::method::get_validity_proof::{
     get_validity_proof, GetValidityProofRequest, GetValidityProofResponse,
 };
+use super::method::reindex_slot::{reindex_slot, ReindexSlotRequest};
 use super::method::utils::{AccountBalanceResponse, GetPaginatedSignaturesResponse, HashRequest};
 use super::method::utils::{
     GetLatestSignaturesRequest, GetNonPaginatedSignaturesResponseWithError,
 };
+use super::method::verify_proof::{verify_proof, VerifyProofRequest, VerifyProofResponse};
 use super::{
     error::PhotonApiError,
     method::{
-        get_compressed_account::get_compressed_account,
+        get_compressed_account::{
+            get_compressed_account, get_compressed_account_with_rpc_fallback,
+        },
         get_compressed_account_balance::get_compressed_account_balance,
         get_compressed_account_proof::{
-            get_compressed_account_proof, GetCompressedAccountProofResponse,
+            get_compressed_account_proof, get_compressed_account_proof_path,
+            GetCompressedAccountProofPathResponse, GetCompressedAccountProofResponse,
+        },
+        get_compressed_account_summary::{
+            get_compressed_account_summary, GetCompressedAccountSummaryResponse,
         },
         get_compressed_accounts_by_owner::{
             get_compressed_accounts_by_owner, GetCompressedAccountsByOwnerRequest,
@@ -63,8 +118,12 @@ use super::{
         },
         get_compressed_token_accounts_by_delegate::get_compressed_account_token_accounts_by_delegate,
         get_compressed_token_accounts_by_owner::get_compressed_token_accounts_by_owner,
-        get_indexer_health::get_indexer_health,
+        get_indexer_health::{
+            get_indexer_health, get_indexer_health_status, GetIndexerHealthStatusResponse,
+            HEALTH_CHECK_SLOT_DISTANCE,
+        },
         get_indexer_slot::get_indexer_slot,
+        get_indexer_stats::{get_indexer_stats, GetIndexerStatsResponse, IndexerStatsCache},
         get_multiple_compressed_account_proofs::{
             get_multiple_compressed_account_proofs, GetMultipleCompressedAccountProofsResponse,
             HashList,
@@ -84,6 +143,16 @@ pub struct PhotonApi {
     db_conn: Arc<DatabaseConnection>,
     rpc_client: Arc<RpcClient>,
     prover_url: String,
+    verify_proof_roots: bool,
+    proofs_disabled: bool,
+    max_proof_batch_size: Option<usize>,
+    rpc_fallback: bool,
+    slow_query_threshold: Option<Duration>,
+    max_slot_lag: u64,
+    indexer_stats_cache: IndexerStatsCache,
+    ingester_config: IngesterConfig,
+    retry_backoff: RetryBackoffConfig,
+    commitment: Commitment,
 }
 
 impl PhotonApi {
@@ -96,6 +165,119 @@ impl PhotonApi {
             db_conn,
             rpc_client,
             prover_url,
+            verify_proof_roots: false,
+            proofs_disabled: false,
+            max_proof_batch_size: None,
+            rpc_fallback: false,
+            slow_query_threshold: None,
+            max_slot_lag: HEALTH_CHECK_SLOT_DISTANCE as u64,
+            indexer_stats_cache: IndexerStatsCache::default(),
+            ingester_config: IngesterConfig::default(),
+            retry_backoff: RetryBackoffConfig::default(),
+            commitment: Commitment::default(),
+        }
+    }
+
+    /// Verify the validity proofs returned by the prover against the stored state and address tree
+    /// roots, and reject proofs whose state tree roots were evicted from the on-chain root history
+    /// while the prover was generating them.
+    pub fn with_proof_root_verification(mut self, verify_proof_roots: bool) -> Self {
+        self.verify_proof_roots = verify_proof_roots;
+        self
+    }
+
+    /// Fail validity proof requests without connecting to the prover, for deployments that do not
+    /// run one.
+    pub fn with_proofs_disabled(mut self, proofs_disabled: bool) -> Self {
+        self.proofs_disabled = proofs_disabled;
+        self
+    }
+
+    /// Reject batch proof requests for more than `max_proof_batch_size` hashes and addresses, so
+    /// that a single request cannot overload the database and the prover.
+    pub fn with_max_proof_batch_size(mut self, max_proof_batch_size: Option<usize>) -> Self {
+        self.max_proof_batch_size = max_proof_batch_size;
+        self
+    }
+
+    /// Serve accounts that are not indexed yet from the RPC node. Such responses are flagged with
+    /// their source.
+    pub fn with_rpc_fallback(mut self, rpc_fallback: bool) -> Self {
+        self.rpc_fallback = rpc_fallback;
+        self
+    }
+
+    /// Log the API calls that take longer than `slow_query_threshold`, so that slow queries can be
+    /// found.
+    pub fn with_slow_query_threshold(mut self, slow_query_threshold: Option<Duration>) -> Self {
+        self.slow_query_threshold = slow_query_threshold;
+        self
+    }
+
+    /// Report the indexer as behind once it lags the RPC node by more than `max_slot_lag` slots.
+    pub fn with_max_slot_lag(mut self, max_slot_lag: u64) -> Self {
+        self.max_slot_lag = max_slot_lag;
+        self
+    }
+
+    /// Parse and persist the slots re-indexed through the admin API, and parse the transactions
+    /// whose compression info is requested, in the same way as the indexer.
+    pub fn with_ingester_config(mut self, ingester_config: IngesterConfig) -> Self {
+        self.ingester_config = ingester_config;
+        self
+    }
+
+    /// Back off between the retries of the RPC requests made on behalf of API calls in the same way
+    /// as the indexer.
+    pub fn with_retry_backoff(mut self, retry_backoff: RetryBackoffConfig) -> Self {
+        self.retry_backoff = retry_backoff;
+        self
+    }
+
+    /// Fetch the slots re-indexed through the admin API at the same commitment level as the
+    /// indexer.
+    pub fn with_commitment(mut self, commitment: Commitment) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    /// Awaits `query`, logging it at warn level along with `method_name` if it takes longer than
+    /// the slow query threshold.
+    pub async fn log_if_slow<T>(&self, method_name: &str, query: impl Future<Output = T>) -> T {
+        let start = Instant::now();
+        let result = query.await;
+        let elapsed = start.elapsed();
+        if let Some(slow_query_threshold) = self.slow_query_threshold {
+            if elapsed > slow_query_threshold {
+                warn!(
+                    "Slow query: {} took {} ms",
+                    method_name,
+                    elapsed.as_millis()
+                );
+            }
+        }
+        result
+    }
+
+    fn check_proofs_enabled(&self) -> Result<(), PhotonApiError> {
+        match self.proofs_disabled {
+            true => Err(PhotonApiError::ProverNotConfigured(
+                "Validity proofs are disabled on this node".to_string(),
+            )),
+            false => Ok(()),
+        }
+    }
+
+    fn check_proof_batch_size(&self, batch_size: usize) -> Result<(), PhotonApiError> {
+        match self.max_proof_batch_size {
+            Some(max_proof_batch_size) if batch_size > max_proof_batch_size => {
+                Err(PhotonApiError::ValidationError(format!(
+                    "Too many hashes and addresses requested {}. Maximum allowed per proof \
+                    request: {}",
+                    batch_size, max_proof_batch_size
+                )))
+            }
+            _ => Ok(()),
         }
     }
 }
@@ -133,6 +315,14 @@ impl PhotonApi {
         &self,
         request: CompressedAccountRequest,
     ) -> Result<AccountResponse, PhotonApiError> {
+        if self.rpc_fallback {
+            return get_compressed_account_with_rpc_fallback(
+                &self.db_conn,
+                &self.rpc_client,
+                request,
+            )
+            .await;
+        }
         get_compressed_account(&self.db_conn, request).await
     }
 
@@ -143,10 +333,25 @@ impl PhotonApi {
         get_compressed_account_proof(&self.db_conn, request).await
     }
 
+    pub async fn get_compressed_account_proof_path(
+        &self,
+        request: HashRequest,
+    ) -> Result<GetCompressedAccountProofPathResponse, PhotonApiError> {
+        get_compressed_account_proof_path(&self.db_conn, request).await
+    }
+
+    pub async fn get_compressed_account_summary(
+        &self,
+        request: CompressedAccountRequest,
+    ) -> Result<GetCompressedAccountSummaryResponse, PhotonApiError> {
+        get_compressed_account_summary(&self.db_conn, request).await
+    }
+
     pub async fn get_multiple_compressed_account_proofs(
         &self,
         request: HashList,
     ) -> Result<GetMultipleCompressedAccountProofsResponse, PhotonApiError> {
+        self.check_proof_batch_size(request.0.len())?;
         get_multiple_compressed_account_proofs(self.db_conn.as_ref(), request).await
     }
 
@@ -154,6 +359,7 @@ impl PhotonApi {
         &self,
         request: AddressList,
     ) -> Result<GetMultipleNewAddressProofsResponse, PhotonApiError> {
+        self.check_proof_batch_size(request.0.len())?;
         get_multiple_new_address_proofs(self.db_conn.as_ref(), request).await
     }
 
@@ -161,6 +367,7 @@ impl PhotonApi {
         &self,
         request: AddressListWithTrees,
     ) -> Result<GetMultipleNewAddressProofsResponse, PhotonApiError> {
+        self.check_proof_batch_size(request.0.len())?;
         get_multiple_new_address_proofs_v2(self.db_conn.as_ref(), request).await
     }
 
@@ -214,13 +421,34 @@ impl PhotonApi {
     }
 
     pub async fn get_indexer_health(&self) -> Result<String, PhotonApiError> {
-        get_indexer_health(self.db_conn.as_ref(), &self.rpc_client).await
+        get_indexer_health(self.db_conn.as_ref(), &self.rpc_client, self.max_slot_lag).await
+    }
+
+    pub async fn get_indexer_health_status(
+        &self,
+    ) -> Result<GetIndexerHealthStatusResponse, PhotonApiError> {
+        get_indexer_health_status(self.db_conn.as_ref(), &self.rpc_client, self.max_slot_lag).await
     }
 
     pub async fn get_indexer_slot(&self) -> Result<UnsignedInteger, PhotonApiError> {
         get_indexer_slot(self.db_conn.as_ref()).await
     }
 
+    pub async fn get_indexer_stats(&self) -> Result<GetIndexerStatsResponse, PhotonApiError> {
+        get_indexer_stats(self.db_conn.as_ref(), &self.indexer_stats_cache).await
+    }
+
+    pub async fn get_ingestion_status(&self) -> Result<GetIngestionStatusResponse, PhotonApiError> {
+        get_ingestion_status(self.db_conn.as_ref()).await
+    }
+
+    pub async fn decode_compressed_account(
+        &self,
+        request: DecodeCompressedAccountRequest,
+    ) -> Result<DecodeCompressedAccountResponse, PhotonApiError> {
+        decode_compressed_account(self.db_conn.as_ref(), request).await
+    }
+
     pub async fn get_compressed_accounts_by_owner(
         &self,
         request: GetCompressedAccountsByOwnerRequest,
@@ -274,15 +502,30 @@ impl PhotonApi {
         &self,
         request: GetTransactionRequest,
     ) -> Result<GetTransactionResponse, PhotonApiError> {
-        get_transaction_with_compression_info(self.db_conn.as_ref(), &self.rpc_client, request)
-            .await
+        get_transaction_with_compression_info(
+            self.db_conn.as_ref(),
+            &self.rpc_client,
+            request,
+            &self.ingester_config.parser,
+        )
+        .await
     }
 
     pub async fn get_validity_proof(
         &self,
         request: GetValidityProofRequest,
     ) -> Result<GetValidityProofResponse, PhotonApiError> {
-        get_validity_proof(self.db_conn.as_ref(), &self.prover_url, request).await
+        self.check_proofs_enabled()?;
+        self.check_proof_batch_size(
+            request.hashes.len() + request.newAddresses.len() + request.newAddressesWithTrees.len(),
+        )?;
+        get_validity_proof(
+            self.db_conn.as_ref(),
+            &self.prover_url,
+            self.verify_proof_roots,
+            request,
+        )
+        .await
     }
 
     pub async fn get_latest_compression_signatures(
@@ -299,6 +542,122 @@ impl PhotonApi {
         get_latest_non_voting_signatures(self.db_conn.as_ref(), request).await
     }
 
+    pub async fn reindex_slot(
+        &self,
+        request: ReindexSlotRequest,
+    ) -> Result<UnsignedInteger, PhotonApiError> {
+        reindex_slot(
+            self.db_conn.as_ref(),
+            &self.rpc_client,
+            request,
+            &self.ingester_config,
+            self.retry_backoff,
+            self.commitment,
+        )
+        .await
+    }
+
+    pub async fn get_recently_spent_accounts(
+        &self,
+        request: GetRecentlySpentAccountsRequest,
+    ) -> Result<GetRecentlySpentAccountsResponse, PhotonApiError> {
+        get_recently_spent_accounts(self.db_conn.as_ref(), request).await
+    }
+
+    pub async fn get_new_address_proof(
+        &self,
+        request: GetNewAddressProofRequest,
+    ) -> Result<GetNewAddressProofResponse, PhotonApiError> {
+        get_new_address_proof(self.db_conn.as_ref(), request).await
+    }
+
+    pub async fn get_latest_compressed_accounts(
+        &self,
+        request: GetLatestCompressedAccountsRequest,
+    ) -> Result<GetLatestCompressedAccountsResponse, PhotonApiError> {
+        get_latest_compressed_accounts(self.db_conn.as_ref(), request).await
+    }
+
+    pub async fn get_compressed_accounts_by_owner_grouped_by_tree(
+        &self,
+        request: GetCompressedAccountsByOwnerGroupedByTreeRequest,
+    ) -> Result<GetCompressedAccountsByOwnerGroupedByTreeResponse, PhotonApiError> {
+        get_compressed_accounts_by_owner_grouped_by_tree(self.db_conn.as_ref(), request).await
+    }
+
+    pub async fn get_compressed_accounts_by_lamport_range(
+        &self,
+        request: GetCompressedAccountsByLamportRangeRequest,
+    ) -> Result<GetCompressedAccountsByLamportRangeResponse, PhotonApiError> {
+        get_compressed_accounts_by_lamport_range(self.db_conn.as_ref(), request).await
+    }
+
+    pub async fn verify_proof(
+        &self,
+        request: VerifyProofRequest,
+    ) -> Result<VerifyProofResponse, PhotonApiError> {
+        verify_proof(self.db_conn.as_ref(), request).await
+    }
+
+    pub async fn get_transaction_account_mapping(
+        &self,
+        request: GetTransactionAccountMappingRequest,
+    ) -> Result<GetTransactionAccountMappingResponse, PhotonApiError> {
+        get_transaction_account_mapping(self.db_conn.as_ref(), request).await
+    }
+
+    pub async fn get_tree_size(
+        &self,
+        request: GetTreeSizeRequest,
+    ) -> Result<GetTreeSizeResponse, PhotonApiError> {
+        get_tree_size(self.db_conn.as_ref(), request).await
+    }
+
+    pub async fn get_instruction_validity_proof(
+        &self,
+        request: GetValidityProofRequest,
+    ) -> Result<GetInstructionValidityProofResponse, PhotonApiError> {
+        self.check_proofs_enabled()?;
+        self.check_proof_batch_size(
+            request.hashes.len() + request.newAddresses.len() + request.newAddressesWithTrees.len(),
+        )?;
+        get_instruction_validity_proof(
+            self.db_conn.as_ref(),
+            &self.prover_url,
+            self.verify_proof_roots,
+            request,
+        )
+        .await
+    }
+
+    pub async fn get_compressed_account_and_proof_by_address(
+        &self,
+        request: GetCompressedAccountAndProofByAddressRequest,
+    ) -> Result<GetCompressedAccountAndProofByAddressResponse, PhotonApiError> {
+        get_compressed_account_and_proof_by_address(self.db_conn.as_ref(), request).await
+    }
+
+    pub async fn get_tree_nodes(
+        &self,
+        request: GetTreeNodesRequest,
+    ) -> Result<GetTreeNodesResponse, PhotonApiError> {
+        get_tree_nodes(self.db_conn.as_ref(), request).await
+    }
+
+    pub async fn get_recent_parse_errors(
+        &self,
+        request: GetRecentParseErrorsRequest,
+    ) -> Result<GetRecentParseErrorsResponse, PhotonApiError> {
+        get_recent_parse_errors(self.db_conn.as_ref(), request).await
+    }
+
+    pub async fn get_unspent_account_set(
+        &self,
+        request: GetUnspentAccountSetRequest,
+    ) -> Result<GetUnspentAccountSetResponse, PhotonApiError> {
+        get_unspent_account_set(self.db_conn.as_ref(), request).await
+    }
+
     pub fn method_api_specs() -> Vec<OpenApiSpec> {
         vec![
             OpenApiSpec {
@@ -366,6 +725,11 @@ impl PhotonApi {
                 request: Some(HashRequest::schema().1),
                 response: GetCompressedAccountProofResponse::schema().1,
             },
+            OpenApiSpec {
+                name: "getCompressedAccountProofPath".to_string(),
+                request: Some(HashRequest::schema().1),
+                response: GetCompressedAccountProofPathResponse::schema().1,
+            },
             OpenApiSpec {
                 name: "getMultipleCompressedAccountProofs".to_string(),
                 request: Some(HashList::schema().1),
@@ -428,11 +792,101 @@ impl PhotonApi {
                         .build(),
                 )),
             },
+            OpenApiSpec {
+                name: "getIndexerHealthStatus".to_string(),
+                request: None,
+                response: GetIndexerHealthStatusResponse::schema().1,
+            },
             OpenApiSpec {
                 name: "getIndexerSlot".to_string(),
                 request: None,
                 response: UnsignedInteger::schema().1,
             },
+            OpenApiSpec {
+                name: "getIndexerStats".to_string(),
+                request: None,
+                response: GetIndexerStatsResponse::schema().1,
+            },
+            OpenApiSpec {
+                name: "getIngestionStatus".to_string(),
+                request: None,
+                response: GetIngestionStatusResponse::schema().1,
+            },
+            OpenApiSpec {
+                name: "decodeCompressedAccount".to_string(),
+                request: Some(DecodeCompressedAccountRequest::schema().1),
+                response: DecodeCompressedAccountResponse::schema().1,
+            },
+            OpenApiSpec {
+                name: "getRecentlySpentAccounts".to_string(),
+                request: Some(GetRecentlySpentAccountsRequest::schema().1),
+                response: GetRecentlySpentAccountsResponse::schema().1,
+            },
+            OpenApiSpec {
+                name: "getNewAddressProof".to_string(),
+                request: Some(GetNewAddressProofRequest::schema().1),
+                response: GetNewAddressProofResponse::schema().1,
+            },
+            OpenApiSpec {
+                name: "getLatestCompressedAccounts".to_string(),
+                request: Some(GetLatestCompressedAccountsRequest::schema().1),
+                response: GetLatestCompressedAccountsResponse::schema().1,
+            },
+            OpenApiSpec {
+                name: "getCompressedAccountsByOwnerGroupedByTree".to_string(),
+                request: Some(GetCompressedAccountsByOwnerGroupedByTreeRequest::schema().1),
+                response: GetCompressedAccountsByOwnerGroupedByTreeResponse::schema().1,
+            },
+            OpenApiSpec {
+                name: "getCompressedAccountsByLamportRange".to_string(),
+                request: Some(GetCompressedAccountsByLamportRangeRequest::schema().1),
+                response: GetCompressedAccountsByLamportRangeResponse::schema().1,
+            },
+            OpenApiSpec {
+                name: "verifyProof".to_string(),
+                request: Some(VerifyProofRequest::schema().1),
+                response: VerifyProofResponse::schema().1,
+            },
+            OpenApiSpec {
+                name: "getTransactionAccountMapping".to_string(),
+                request: Some(GetTransactionAccountMappingRequest::schema().1),
+                response: GetTransactionAccountMappingResponse::schema().1,
+            },
+            OpenApiSpec {
+                name: "getTreeSize".to_string(),
+                request: Some(GetTreeSizeRequest::schema().1),
+                response: GetTreeSizeResponse::schema().1,
+            },
+            OpenApiSpec {
+                name: "getInstructionValidityProof".to_string(),
+                request: Some(GetValidityProofRequestDocumentation::schema().1),
+                response: GetInstructionValidityProofResponse::schema().1,
+            },
+            OpenApiSpec {
+                name: "getCompressedAccountAndProofByAddress".to_string(),
+                request: Some(GetCompressedAccountAndProofByAddressRequest::schema().1),
+                response: GetCompressedAccountAndProofByAddressResponse::schema().1,
+            },
+            OpenApiSpec {
+                name: "getTreeNodes".to_string(),
+                request: Some(GetTreeNodesRequest::schema().1),
+                response: GetTreeNodesResponse::schema().1,
+            },
+            OpenApiSpec {
+                name: "getRecentParseErrors".to_string(),
+                request: Some(GetRecentParseErrorsRequest::schema().1),
+                response: GetRecentParseErrorsResponse::schema().1,
+            },
+            OpenApiSpec {
+                name: "getUnspentAccountSet".to_string(),
+                request: Some(GetUnspentAccountSetRequest::schema().1),
+                response: GetUnspentAccountSetResponse::schema().1,
+            },
+            OpenApiSpec {
+                name: "getCompressedAccountSummary".to_string(),
+                request: Some(CompressedAccountRequest::adjusted_schema()),
+                response: GetCompressedAccountSummaryResponse::schema().1,
+            },
         ]
     }
 }
@@ -0,0 +1,129 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use hyper::body::HttpBody;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde::de::IgnoredAny;
+use tower::{Layer, Service};
+
+/// Default maximum number of calls in a JSON-RPC batch request.
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 100;
+
+// Same as the default maximum request body size of the jsonrpsee server, which rejects larger
+// requests anyway.
+const MAX_REQUEST_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+// JSON-RPC error code of invalid requests.
+const INVALID_REQUEST_CODE: i32 = -32600;
+
+/// Rejects JSON-RPC batch requests with more than `max_batch_size` calls before they reach the
+/// server, so that a single request cannot make the server run an unbounded number of queries.
+#[derive(Debug, Clone, Copy)]
+pub struct BatchSizeLimitLayer {
+    max_batch_size: usize,
+}
+
+impl BatchSizeLimitLayer {
+    pub fn new(max_batch_size: usize) -> Self {
+        Self { max_batch_size }
+    }
+}
+
+impl<S> Layer<S> for BatchSizeLimitLayer {
+    type Service = BatchSizeLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BatchSizeLimit {
+            inner,
+            max_batch_size: self.max_batch_size,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchSizeLimit<S> {
+    inner: S,
+    max_batch_size: usize,
+}
+
+impl<S> Service<Request<Body>> for BatchSizeLimit<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // The inner service was polled ready, so we call it and leave a clone in its place.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let max_batch_size = self.max_batch_size;
+        // Only POST requests carry JSON-RPC calls. Other requests, such as WebSocket upgrades, are
+        // passed through untouched.
+        if request.method() != Method::POST {
+            return Box::pin(inner.call(request));
+        }
+        Box::pin(async move {
+            let (parts, mut body) = request.into_parts();
+            let mut bytes = Vec::new();
+            while let Some(chunk) = body.data().await {
+                match chunk {
+                    Ok(chunk) => bytes.extend_from_slice(&chunk),
+                    Err(_) => return Ok(error_response(StatusCode::BAD_REQUEST, "Invalid body")),
+                }
+                if bytes.len() > MAX_REQUEST_BODY_SIZE {
+                    return Ok(error_response(
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        "Request is too big",
+                    ));
+                }
+            }
+            if let Some(batch_size) = batch_size(&bytes) {
+                if batch_size > max_batch_size {
+                    return Ok(error_response(
+                        StatusCode::OK,
+                        &format!(
+                            "Batch of {} calls exceeds the maximum batch size of {}",
+                            batch_size, max_batch_size
+                        ),
+                    ));
+                }
+            }
+            inner
+                .call(Request::from_parts(parts, Body::from(bytes)))
+                .await
+        })
+    }
+}
+
+// Returns the number of calls of a batch request, or `None` if the body is not a batch.
+fn batch_size(body: &[u8]) -> Option<usize> {
+    let first_byte = body.iter().find(|byte| !byte.is_ascii_whitespace());
+    if first_byte != Some(&b'[') {
+        return None;
+    }
+    serde_json::from_slice::<Vec<IgnoredAny>>(body)
+        .ok()
+        .map(|calls| calls.len())
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Body> {
+    let body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "error": { "code": INVALID_REQUEST_CODE, "message": message },
+        "id": null,
+    });
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
@@ -21,6 +21,14 @@ pub enum PhotonApiError {
     UnexpectedError(String),
     #[error("Node is behind {0} slots")]
     StaleSlot(u64),
+    #[error("Invalid Proof: {0}")]
+    InvalidProof(String),
+    #[error("Balance Overflow: {0} does not fit into an unsigned 64-bit integer")]
+    BalanceOverflow(String),
+    #[error("Prover Not Configured: {0}")]
+    ProverNotConfigured(String),
+    #[error("Prover Error: {0}")]
+    ProverError(String),
 }
 
 // TODO: Simplify error conversions and ensure we adhere
@@ -52,6 +60,31 @@ impl From<PhotonApiError> for RpcError {
                 }
                 invalid_request(val)
             }
+            PhotonApiError::InvalidProof(_) => {
+                metric! {
+                    statsd_count!("invalid_proof_api_error", 1);
+                }
+                invalid_request(val)
+            }
+            PhotonApiError::BalanceOverflow(_) => {
+                metric! {
+                    statsd_count!("balance_overflow_api_error", 1);
+                }
+                invalid_request(val)
+            }
+            PhotonApiError::ProverNotConfigured(_) => {
+                metric! {
+                    statsd_count!("prover_not_configured_api_error", 1);
+                }
+                invalid_request(val)
+            }
+            PhotonApiError::ProverError(ref e) => {
+                error!("Prover error: {}", e);
+                metric! {
+                    statsd_count!("prover_api_error", 1);
+                }
+                invalid_request(val)
+            }
             PhotonApiError::DatabaseError(e) => {
                 error!("Internal server database error: {}", e);
                 metric! {
@@ -0,0 +1,348 @@
+use byteorder::{ByteOrder, LittleEndian};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use solana_sdk::pubkey::Pubkey;
+use utoipa::ToSchema;
+
+use crate::common::typedefs::hash::Hash;
+use crate::dao::generated::accounts;
+
+use super::super::error::PhotonApiError;
+use super::utils::{parse_account_model, Context};
+
+// IDLs are supplied by the caller, so we bound the work a single request can cause.
+pub const MAX_IDL_SIZE_BYTES: usize = 64 * 1024;
+const MAX_TYPE_DEPTH: usize = 32;
+// Lengths are bounded by the size of the data, but elements such as empty structs do not consume
+// any data, so nested arrays of them could otherwise decode a number of values exponential in the
+// nesting depth.
+const MAX_DECODED_VALUES: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct DecodeCompressedAccountRequest {
+    pub hash: Hash,
+    /// Anchor IDL describing the account layout.
+    #[schema(value_type = Object)]
+    pub idl: Value,
+    /// Name of the IDL account type to decode the data as. If omitted, the type is selected by
+    /// matching the account discriminator.
+    #[serde(default)]
+    pub account_type: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct DecodedAccount {
+    pub account_type: String,
+    /// Decoded fields. 64-bit and 128-bit integers are returned as strings to avoid precision
+    /// loss in JSON clients.
+    #[schema(value_type = Object)]
+    pub fields: Value,
+}
+
+// We do not use generics to simplify documentation generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct DecodeCompressedAccountResponse {
+    pub context: Context,
+    pub value: Option<DecodedAccount>,
+}
+
+pub async fn decode_compressed_account(
+    conn: &DatabaseConnection,
+    request: DecodeCompressedAccountRequest,
+) -> Result<DecodeCompressedAccountResponse, PhotonApiError> {
+    let context = Context::extract(conn).await?;
+    let DecodeCompressedAccountRequest {
+        hash,
+        idl,
+        account_type,
+    } = request;
+
+    let idl_size = serde_json::to_vec(&idl)
+        .map_err(|e| PhotonApiError::ValidationError(format!("Invalid IDL: {}", e)))?
+        .len();
+    if idl_size > MAX_IDL_SIZE_BYTES {
+        return Err(PhotonApiError::ValidationError(format!(
+            "IDL is {} bytes. The maximum supported size is {} bytes",
+            idl_size, MAX_IDL_SIZE_BYTES
+        )));
+    }
+
+    let account = accounts::Entity::find()
+        .filter(accounts::Column::Hash.eq::<Vec<u8>>(hash.into()))
+        .one(conn)
+        .await?
+        .map(parse_account_model)
+        .transpose()?;
+    let account = match account {
+        Some(account) => account,
+        None => {
+            return Ok(DecodeCompressedAccountResponse {
+                context,
+                value: None,
+            })
+        }
+    };
+    let data = account.data.ok_or(PhotonApiError::ValidationError(
+        "Account has no data to decode".to_string(),
+    ))?;
+
+    let idl_accounts = idl
+        .get("accounts")
+        .and_then(Value::as_array)
+        .ok_or(invalid_idl("missing accounts"))?;
+    let idl_account = match &account_type {
+        Some(account_type) => idl_accounts
+            .iter()
+            .find(|idl_account| type_name(idl_account) == Some(account_type.as_str())),
+        None => idl_accounts.iter().find(|idl_account| {
+            type_name(idl_account)
+                .map(|name| account_discriminator(name) == data.discriminator.0)
+                .unwrap_or(false)
+        }),
+    }
+    .ok_or(PhotonApiError::ValidationError(
+        "No matching account type found in IDL".to_string(),
+    ))?;
+    let account_type = type_name(idl_account)
+        .ok_or(invalid_idl("account without name"))?
+        .to_string();
+
+    // Anchor >= 0.30 IDLs declare account layouts in `types` and only reference them by name.
+    let type_definition = match idl_account.get("type") {
+        Some(type_definition) => type_definition,
+        None => find_defined_type(&idl, &account_type)?,
+    };
+    let mut decoder = Decoder {
+        idl: &idl,
+        data: &data.data.0,
+        offset: 0,
+        remaining_values: MAX_DECODED_VALUES,
+    };
+    let fields = decoder.decode_type_definition(type_definition, 0)?;
+
+    Ok(DecodeCompressedAccountResponse {
+        context,
+        value: Some(DecodedAccount {
+            account_type,
+            fields,
+        }),
+    })
+}
+
+fn invalid_idl(reason: &str) -> PhotonApiError {
+    PhotonApiError::ValidationError(format!("Invalid IDL: {}", reason))
+}
+
+fn type_name(value: &Value) -> Option<&str> {
+    value.get("name").and_then(Value::as_str)
+}
+
+fn account_discriminator(account_name: &str) -> u64 {
+    let hash = solana_sdk::hash::hash(format!("account:{}", account_name).as_bytes());
+    LittleEndian::read_u64(&hash.to_bytes()[..8])
+}
+
+fn find_defined_type<'a>(idl: &'a Value, name: &str) -> Result<&'a Value, PhotonApiError> {
+    idl.get("types")
+        .and_then(Value::as_array)
+        .and_then(|types| types.iter().find(|t| type_name(t) == Some(name)))
+        .and_then(|t| t.get("type"))
+        .ok_or(invalid_idl(&format!("undefined type {}", name)))
+}
+
+struct Decoder<'a> {
+    idl: &'a Value,
+    data: &'a [u8],
+    offset: usize,
+    remaining_values: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn count_values(&mut self, count: usize) -> Result<(), PhotonApiError> {
+        if count > self.remaining_values {
+            return Err(PhotonApiError::ValidationError(format!(
+                "Account data decodes to more than {} values",
+                MAX_DECODED_VALUES
+            )));
+        }
+        self.remaining_values -= count;
+        Ok(())
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], PhotonApiError> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(PhotonApiError::ValidationError(
+                "Account data is shorter than the IDL layout".to_string(),
+            ))?;
+        let bytes = &self.data[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn read_length(&mut self) -> Result<usize, PhotonApiError> {
+        let length = LittleEndian::read_u32(self.take(4)?) as usize;
+        // Every element takes at least one byte, which bounds lengths read from the data.
+        if length > self.data.len() - self.offset {
+            return Err(PhotonApiError::ValidationError(
+                "Account data is shorter than the IDL layout".to_string(),
+            ));
+        }
+        Ok(length)
+    }
+
+    fn decode_type_definition(
+        &mut self,
+        type_definition: &'a Value,
+        depth: usize,
+    ) -> Result<Value, PhotonApiError> {
+        match type_definition.get("kind").and_then(Value::as_str) {
+            Some("struct") => {
+                let fields = type_definition
+                    .get("fields")
+                    .and_then(Value::as_array)
+                    .ok_or(invalid_idl("struct without fields"))?;
+                self.decode_fields(fields, depth)
+            }
+            Some("enum") => {
+                let variants = type_definition
+                    .get("variants")
+                    .and_then(Value::as_array)
+                    .ok_or(invalid_idl("enum without variants"))?;
+                let variant_index = self.take(1)?[0] as usize;
+                let variant =
+                    variants
+                        .get(variant_index)
+                        .ok_or(PhotonApiError::ValidationError(format!(
+                            "Invalid enum variant {}",
+                            variant_index
+                        )))?;
+                let variant_name = type_name(variant)
+                    .ok_or(invalid_idl("enum variant without name"))?
+                    .to_string();
+                match variant.get("fields").and_then(Value::as_array) {
+                    Some(fields) => {
+                        let mut decoded = Map::new();
+                        decoded.insert(variant_name, self.decode_fields(fields, depth)?);
+                        Ok(Value::Object(decoded))
+                    }
+                    None => Ok(Value::String(variant_name)),
+                }
+            }
+            _ => Err(invalid_idl("unsupported type definition kind")),
+        }
+    }
+
+    fn decode_fields(
+        &mut self,
+        fields: &'a [Value],
+        depth: usize,
+    ) -> Result<Value, PhotonApiError> {
+        // Tuple fields are listed as bare types without names.
+        if fields.iter().all(|field| field.get("name").is_none()) {
+            return fields
+                .iter()
+                .map(|field| self.decode_type(field, depth + 1))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array);
+        }
+        let mut decoded = Map::new();
+        for field in fields {
+            let name = type_name(field).ok_or(invalid_idl("field without name"))?;
+            let field_type = field.get("type").ok_or(invalid_idl("field without type"))?;
+            decoded.insert(name.to_string(), self.decode_type(field_type, depth + 1)?);
+        }
+        Ok(Value::Object(decoded))
+    }
+
+    fn decode_type(&mut self, idl_type: &'a Value, depth: usize) -> Result<Value, PhotonApiError> {
+        if depth > MAX_TYPE_DEPTH {
+            return Err(invalid_idl("type nesting is too deep"));
+        }
+        self.count_values(1)?;
+        if let Some(primitive) = idl_type.as_str() {
+            return self.decode_primitive(primitive);
+        }
+        if let Some(inner) = idl_type.get("vec") {
+            let length = self.read_length()?;
+            return (0..length)
+                .map(|_| self.decode_type(inner, depth + 1))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array);
+        }
+        if let Some(inner) = idl_type.get("option") {
+            return match self.take(1)?[0] {
+                0 => Ok(Value::Null),
+                _ => self.decode_type(inner, depth + 1),
+            };
+        }
+        if let Some(array) = idl_type.get("array").and_then(Value::as_array) {
+            let (inner, length) = match array.as_slice() {
+                [inner, length] => (
+                    inner,
+                    length.as_u64().ok_or(invalid_idl("array without length"))?,
+                ),
+                _ => return Err(invalid_idl("malformed array type")),
+            };
+            if length as usize > self.data.len() - self.offset {
+                return Err(PhotonApiError::ValidationError(
+                    "Account data is shorter than the IDL layout".to_string(),
+                ));
+            }
+            return (0..length)
+                .map(|_| self.decode_type(inner, depth + 1))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array);
+        }
+        if let Some(defined) = idl_type.get("defined") {
+            let name = defined
+                .as_str()
+                .or_else(|| type_name(defined))
+                .ok_or(invalid_idl("malformed defined type"))?;
+            let type_definition = find_defined_type(self.idl, name)?;
+            return self.decode_type_definition(type_definition, depth + 1);
+        }
+        Err(invalid_idl("unsupported type"))
+    }
+
+    fn decode_primitive(&mut self, primitive: &str) -> Result<Value, PhotonApiError> {
+        Ok(match primitive {
+            "bool" => Value::Bool(self.take(1)?[0] != 0),
+            "u8" => Value::from(self.take(1)?[0]),
+            "i8" => Value::from(self.take(1)?[0] as i8),
+            "u16" => Value::from(LittleEndian::read_u16(self.take(2)?)),
+            "i16" => Value::from(LittleEndian::read_i16(self.take(2)?)),
+            "u32" => Value::from(LittleEndian::read_u32(self.take(4)?)),
+            "i32" => Value::from(LittleEndian::read_i32(self.take(4)?)),
+            "f32" => Value::from(LittleEndian::read_f32(self.take(4)?)),
+            "f64" => Value::from(LittleEndian::read_f64(self.take(8)?)),
+            "u64" => Value::String(LittleEndian::read_u64(self.take(8)?).to_string()),
+            "i64" => Value::String(LittleEndian::read_i64(self.take(8)?).to_string()),
+            "u128" => Value::String(LittleEndian::read_u128(self.take(16)?).to_string()),
+            "i128" => Value::String(LittleEndian::read_i128(self.take(16)?).to_string()),
+            "publicKey" | "pubkey" => {
+                let bytes: [u8; 32] = self.take(32)?.try_into().unwrap();
+                Value::String(Pubkey::from(bytes).to_string())
+            }
+            "string" => {
+                let length = self.read_length()?;
+                let bytes = self.take(length)?;
+                Value::String(String::from_utf8(bytes.to_vec()).map_err(|_| {
+                    PhotonApiError::ValidationError("Invalid UTF-8 string".to_string())
+                })?)
+            }
+            "bytes" => {
+                let length = self.read_length()?;
+                self.count_values(length)?;
+                Value::Array(self.take(length)?.iter().map(|b| Value::from(*b)).collect())
+            }
+            _ => return Err(invalid_idl(&format!("unsupported type {}", primitive))),
+        })
+    }
+}
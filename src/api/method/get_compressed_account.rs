@@ -1,19 +1,38 @@
 use crate::common::typedefs::account::Account;
 use crate::dao::generated::accounts;
 
+use log::warn;
 use sea_orm::{DatabaseConnection, EntityTrait, QueryFilter};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_request::RpcRequest;
 use utoipa::ToSchema;
 
 use super::super::error::PhotonApiError;
 use super::utils::{parse_account_model, AccountDataTable, CompressedAccountRequest, Context};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum AccountSource {
+    /// The account was not indexed yet and was fetched from the RPC node.
+    Rpc,
+}
+
 // We do not use generics to simply documentation generation.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
 #[serde(deny_unknown_fields, rename_all = "camelCase")]
 pub struct AccountResponse {
     pub context: Context,
     pub value: Option<Account>,
+    /// Only set when the account was not served from the index.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<AccountSource>,
+}
+
+#[derive(Deserialize)]
+struct RpcAccountResponse {
+    context: Context,
+    value: Option<Account>,
 }
 
 pub async fn get_compressed_account(
@@ -32,5 +51,47 @@ pub async fn get_compressed_account(
     Ok(AccountResponse {
         value: { account },
         context,
+        source: None,
     })
 }
+
+/// Serves accounts that are not indexed yet, for instance because they were just created, from
+/// the compression API of the RPC node. Fallback failures are logged and the indexed response,
+/// which has no account, is returned.
+pub async fn get_compressed_account_with_rpc_fallback(
+    conn: &DatabaseConnection,
+    rpc_client: &RpcClient,
+    request: CompressedAccountRequest,
+) -> Result<AccountResponse, PhotonApiError> {
+    let response = get_compressed_account(conn, request.clone()).await?;
+    if response.value.is_some() {
+        return Ok(response);
+    }
+
+    let params = serde_json::to_value(&request).map_err(|e| {
+        PhotonApiError::UnexpectedError(format!("Failed to serialize request: {}", e))
+    })?;
+    let rpc_response = rpc_client
+        .send::<RpcAccountResponse>(
+            RpcRequest::Custom {
+                method: "getCompressedAccount",
+            },
+            params,
+        )
+        .await;
+    match rpc_response {
+        Ok(RpcAccountResponse {
+            context,
+            value: Some(account),
+        }) => Ok(AccountResponse {
+            context,
+            value: Some(account),
+            source: Some(AccountSource::Rpc),
+        }),
+        Ok(_) => Ok(response),
+        Err(e) => {
+            warn!("RPC fallback for getCompressedAccount failed: {}", e);
+            Ok(response)
+        }
+    }
+}
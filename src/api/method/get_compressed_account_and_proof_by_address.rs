@@ -0,0 +1,76 @@
+use sea_orm::{
+    ConnectionTrait, DatabaseBackend, DatabaseConnection, EntityTrait, QueryFilter, Statement,
+    TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::typedefs::account::Account;
+use crate::common::typedefs::serializable_pubkey::SerializablePubkey;
+use crate::dao::generated::accounts;
+use crate::ingester::persist::persisted_state_tree::{
+    get_multiple_compressed_leaf_proofs, MerkleProofWithContext,
+};
+
+use super::super::error::PhotonApiError;
+use super::utils::{parse_account_model, AccountDataTable, AccountIdentifier, Context};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetCompressedAccountAndProofByAddressRequest {
+    pub address: SerializablePubkey,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct AccountWithProof {
+    pub account: Account,
+    pub proof: MerkleProofWithContext,
+}
+
+// We do not use generics to simplify documentation generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetCompressedAccountAndProofByAddressResponse {
+    pub context: Context,
+    pub value: AccountWithProof,
+}
+
+/// Returns the unspent account at an address together with its proof, so that the account can
+/// be spent with a single call. Both are read in one transaction, so the proof is consistent with
+/// the returned account.
+pub async fn get_compressed_account_and_proof_by_address(
+    conn: &DatabaseConnection,
+    request: GetCompressedAccountAndProofByAddressRequest,
+) -> Result<GetCompressedAccountAndProofByAddressResponse, PhotonApiError> {
+    let context = Context::extract(conn).await?;
+    let id = AccountIdentifier::Address(request.address);
+    let tx = conn.begin().await?;
+    if tx.get_database_backend() == DatabaseBackend::Postgres {
+        tx.execute(Statement::from_string(
+            tx.get_database_backend(),
+            "SET TRANSACTION ISOLATION LEVEL REPEATABLE READ;".to_string(),
+        ))
+        .await?;
+    }
+    let account = accounts::Entity::find()
+        .filter(id.filter(AccountDataTable::Accounts))
+        .one(&tx)
+        .await?
+        .ok_or(id.not_found_error())
+        .and_then(parse_account_model)?;
+    let proof = get_multiple_compressed_leaf_proofs(&tx, vec![account.hash.clone()])
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(PhotonApiError::RecordNotFound(format!(
+            "Proof of account {} not found",
+            request.address
+        )))?;
+    tx.commit().await?;
+
+    Ok(GetCompressedAccountAndProofByAddressResponse {
+        context,
+        value: AccountWithProof { account, proof },
+    })
+}
@@ -2,8 +2,10 @@ use sea_orm::{ConnectionTrait, DatabaseBackend, DatabaseConnection, Statement, T
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+use crate::common::typedefs::hash::Hash;
+use crate::common::typedefs::serializable_pubkey::SerializablePubkey;
 use crate::ingester::persist::persisted_state_tree::{
-    get_multiple_compressed_leaf_proofs, MerkleProofWithContext,
+    get_multiple_compressed_leaf_proofs, leaf_index_to_node_index, MerkleProofWithContext,
 };
 
 use super::{
@@ -18,6 +20,55 @@ pub struct GetCompressedAccountProofResponse {
     pub value: MerkleProofWithContext,
 }
 
+/// A Merkle proof laid out for programs that verify proofs with explicit node positions instead
+/// of deriving them from the leaf index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct MerkleProofPath {
+    pub hash: Hash,
+    pub leaf_index: u32,
+    pub merkle_tree: SerializablePubkey,
+    pub root: Hash,
+    pub root_seq: u64,
+    /// Sibling hashes ordered from the leaf level up to the level below the root.
+    pub siblings: Vec<Hash>,
+    /// Node index of each sibling, where the root has index 1 and the children of node `i` are
+    /// `2i` and `2i + 1`.
+    pub sibling_indices: Vec<u64>,
+    /// For each level, `false` if the path node is the left child and `true` if it is the right
+    /// child. These are the bits of the leaf index, starting from the least significant bit.
+    pub path_directions: Vec<bool>,
+}
+
+impl From<MerkleProofWithContext> for MerkleProofPath {
+    fn from(proof: MerkleProofWithContext) -> Self {
+        let tree_height = (proof.proof.len() + 1) as u32;
+        let node_index = leaf_index_to_node_index(proof.leafIndex, tree_height) as u64;
+        let levels = 0..proof.proof.len();
+        MerkleProofPath {
+            hash: proof.hash,
+            leaf_index: proof.leafIndex,
+            merkle_tree: proof.merkleTree,
+            root: proof.root,
+            root_seq: proof.rootSeq,
+            sibling_indices: levels
+                .clone()
+                .map(|level| (node_index >> level) ^ 1)
+                .collect(),
+            path_directions: levels.map(|level| (node_index >> level) & 1 == 1).collect(),
+            siblings: proof.proof,
+        }
+    }
+}
+
+// We do not use generics to simplify documentation generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetCompressedAccountProofPathResponse {
+    pub context: Context,
+    pub value: MerkleProofPath,
+}
+
 pub async fn get_compressed_account_proof(
     conn: &DatabaseConnection,
     request: HashRequest,
@@ -46,3 +97,15 @@ pub async fn get_compressed_account_proof(
     tx.commit().await?;
     res
 }
+
+pub async fn get_compressed_account_proof_path(
+    conn: &DatabaseConnection,
+    request: HashRequest,
+) -> Result<GetCompressedAccountProofPathResponse, PhotonApiError> {
+    let GetCompressedAccountProofResponse { context, value } =
+        get_compressed_account_proof(conn, request).await?;
+    Ok(GetCompressedAccountProofPathResponse {
+        context,
+        value: value.into(),
+    })
+}
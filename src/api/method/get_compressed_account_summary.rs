@@ -0,0 +1,85 @@
+use sea_orm::sea_query::Expr;
+use sea_orm::{DatabaseConnection, EntityTrait, FromQueryResult, QueryFilter, QuerySelect};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Decimal;
+use utoipa::ToSchema;
+
+use crate::common::typedefs::hash::Hash;
+use crate::common::typedefs::serializable_pubkey::SerializablePubkey;
+use crate::common::typedefs::unsigned_integer::UnsignedInteger;
+use crate::dao::generated::accounts;
+
+use super::super::error::PhotonApiError;
+use super::utils::{parse_decimal, AccountDataTable, CompressedAccountRequest, Context};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct AccountSummary {
+    pub hash: Hash,
+    pub address: Option<SerializablePubkey>,
+    pub owner: SerializablePubkey,
+    pub lamports: UnsignedInteger,
+    /// Size in bytes of the account data, 0 for accounts without data. Accounts whose data was
+    /// truncated when indexed report the size of the indexed data.
+    pub data_size: UnsignedInteger,
+    pub data_hash: Option<Hash>,
+}
+
+#[derive(FromQueryResult)]
+struct AccountSummaryModel {
+    hash: Vec<u8>,
+    address: Option<Vec<u8>>,
+    owner: Vec<u8>,
+    lamports: Decimal,
+    data_size: Option<i64>,
+    data_hash: Option<Vec<u8>>,
+}
+
+// We do not use generics to simplify documentation generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetCompressedAccountSummaryResponse {
+    pub context: Context,
+    pub value: Option<AccountSummary>,
+}
+
+/// Returns a summary of an unspent account without its data, so that clients can check that they
+/// hold the right version of an account without downloading the data.
+pub async fn get_compressed_account_summary(
+    conn: &DatabaseConnection,
+    request: CompressedAccountRequest,
+) -> Result<GetCompressedAccountSummaryResponse, PhotonApiError> {
+    let context = Context::extract(conn).await?;
+    let id = request.parse_id()?;
+    let summary = accounts::Entity::find()
+        .select_only()
+        .column(accounts::Column::Hash)
+        .column(accounts::Column::Address)
+        .column(accounts::Column::Owner)
+        .column(accounts::Column::Lamports)
+        .column_as(Expr::cust("CAST(LENGTH(data) AS BIGINT)"), "data_size")
+        .column(accounts::Column::DataHash)
+        .filter(id.filter(AccountDataTable::Accounts))
+        .into_model::<AccountSummaryModel>()
+        .one(conn)
+        .await?
+        .map(|model| {
+            Ok::<_, PhotonApiError>(AccountSummary {
+                hash: Hash::try_from(model.hash)?,
+                address: model
+                    .address
+                    .map(SerializablePubkey::try_from)
+                    .transpose()?,
+                owner: SerializablePubkey::try_from(model.owner)?,
+                lamports: UnsignedInteger(parse_decimal(model.lamports)?),
+                data_size: UnsignedInteger(model.data_size.unwrap_or(0) as u64),
+                data_hash: model.data_hash.map(Hash::try_from).transpose()?,
+            })
+        })
+        .transpose()?;
+
+    Ok(GetCompressedAccountSummaryResponse {
+        context,
+        value: summary,
+    })
+}
@@ -0,0 +1,124 @@
+use byteorder::{ByteOrder, LittleEndian};
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Decimal;
+use utoipa::ToSchema;
+
+use crate::common::typedefs::account::Account;
+use crate::common::typedefs::bs58_string::Base58String;
+use crate::common::typedefs::serializable_pubkey::SerializablePubkey;
+use crate::common::typedefs::unsigned_integer::UnsignedInteger;
+use crate::dao::generated::accounts;
+
+use super::super::error::PhotonApiError;
+use super::utils::{parse_account_model, Context, Limit};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetCompressedAccountsByLamportRangeRequest {
+    #[serde(default)]
+    pub owner: Option<SerializablePubkey>,
+    /// Minimum lamports, inclusive.
+    pub min_lamports: UnsignedInteger,
+    /// Maximum lamports, inclusive.
+    pub max_lamports: UnsignedInteger,
+    #[serde(default)]
+    pub cursor: Option<Base58String>,
+    #[serde(default)]
+    pub limit: Option<Limit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct PaginatedLamportRangeAccountList {
+    pub items: Vec<Account>,
+    pub cursor: Option<Base58String>,
+}
+
+// We do not use generics to simplify documentation generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetCompressedAccountsByLamportRangeResponse {
+    pub context: Context,
+    pub value: PaginatedLamportRangeAccountList,
+}
+
+/// Returns the unspent accounts whose lamports lie within the given range, optionally only those
+/// of a single owner. Accounts are ordered by lamports and then by hash.
+pub async fn get_compressed_accounts_by_lamport_range(
+    conn: &DatabaseConnection,
+    request: GetCompressedAccountsByLamportRangeRequest,
+) -> Result<GetCompressedAccountsByLamportRangeResponse, PhotonApiError> {
+    let context = Context::extract(conn).await?;
+    let GetCompressedAccountsByLamportRangeRequest {
+        owner,
+        min_lamports,
+        max_lamports,
+        cursor,
+        limit,
+    } = request;
+    if min_lamports > max_lamports {
+        return Err(PhotonApiError::ValidationError(format!(
+            "Minimum lamports {} exceed maximum lamports {}",
+            min_lamports.0, max_lamports.0
+        )));
+    }
+    let limit = limit.unwrap_or_default().value();
+
+    let mut filter = Condition::all()
+        .add(accounts::Column::Spent.eq(false))
+        .add(accounts::Column::Lamports.gte(Decimal::from(min_lamports.0)))
+        .add(accounts::Column::Lamports.lte(Decimal::from(max_lamports.0)));
+    if let Some(owner) = owner {
+        filter = filter.add(accounts::Column::Owner.eq(owner.to_bytes_vec()));
+    }
+    if let Some(cursor) = cursor {
+        let bytes = cursor.0;
+        let expected_cursor_length = 8 + 32;
+        if bytes.len() != expected_cursor_length {
+            return Err(PhotonApiError::ValidationError(format!(
+                "Invalid cursor length. Expected {}. Received {}.",
+                expected_cursor_length,
+                bytes.len()
+            )));
+        }
+        let (lamports, hash) = bytes.split_at(8);
+        let lamports = Decimal::from(LittleEndian::read_u64(lamports));
+        filter = filter.add(
+            Condition::any()
+                .add(accounts::Column::Lamports.gt(lamports))
+                .add(
+                    Condition::all()
+                        .add(accounts::Column::Lamports.eq(lamports))
+                        .add(accounts::Column::Hash.gt::<Vec<u8>>(hash.to_vec())),
+                ),
+        );
+    }
+
+    let items = accounts::Entity::find()
+        .filter(filter)
+        .order_by_asc(accounts::Column::Lamports)
+        .order_by_asc(accounts::Column::Hash)
+        .limit(limit)
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(parse_account_model)
+        .collect::<Result<Vec<_>, PhotonApiError>>()?;
+
+    let cursor = match items.len() < limit as usize {
+        true => None,
+        false => items.last().map(|item| {
+            let mut bytes = item.lamports.0.to_le_bytes().to_vec();
+            bytes.extend_from_slice(&item.hash.to_vec());
+            Base58String(bytes)
+        }),
+    };
+
+    Ok(GetCompressedAccountsByLamportRangeResponse {
+        context,
+        value: PaginatedLamportRangeAccountList { items, cursor },
+    })
+}
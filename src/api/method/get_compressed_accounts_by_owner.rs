@@ -64,6 +64,16 @@ pub struct DataSlice {
     pub length: usize,
 }
 
+/// Order of the returned accounts. Ties in slot or lamports are ordered by descending hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountOrder {
+    SlotDesc,
+    LamportsDesc,
+    #[default]
+    HashAsc,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, Default)]
 #[serde(deny_unknown_fields, rename_all = "camelCase")]
 #[allow(non_snake_case)]
@@ -77,6 +87,14 @@ pub struct GetCompressedAccountsByOwnerRequest {
     pub cursor: Option<Hash>,
     #[serde(default)]
     pub limit: Option<Limit>,
+    /// Only return accounts with data if true, or only lamport-only accounts without data if false.
+    #[serde(default)]
+    pub hasData: Option<bool>,
+    #[serde(default)]
+    pub orderBy: AccountOrder,
+    /// Only return accounts created in a finalized slot.
+    #[serde(default)]
+    pub finalizedOnly: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema, Default)]
@@ -104,6 +122,9 @@ pub async fn get_compressed_accounts_by_owner(
         limit,
         filters,
         dataSlice,
+        hasData,
+        orderBy,
+        finalizedOnly,
     } = request;
 
     if filters.len() > MAX_FILTERS {
@@ -157,6 +178,14 @@ pub async fn get_compressed_accounts_by_owner(
     let mut filters_strings = vec![];
     filters_strings.push(format!("owner = {owner_string}"));
     filters_strings.push("spent = false".to_string());
+    match hasData {
+        Some(true) => filters_strings.push("data IS NOT NULL".to_string()),
+        Some(false) => filters_strings.push("data IS NULL".to_string()),
+        None => {}
+    }
+    if finalizedOnly {
+        filters_strings.push("finalized = true".to_string());
+    }
 
     for filter_selector in filters {
         match filter_selector.into_filter_instance()? {
@@ -186,7 +215,20 @@ pub async fn get_compressed_accounts_by_owner(
 
     if let Some(cursor) = cursor {
         let cursor_string = bytes_to_sql_format(conn.get_database_backend(), cursor.into());
-        filters_strings.push(format!("hash > {cursor_string}"));
+        let filter_string = match orderBy {
+            AccountOrder::HashAsc => format!("hash > {cursor_string}"),
+            AccountOrder::SlotDesc | AccountOrder::LamportsDesc => {
+                // The cursor is the hash of the last returned account, so its sort key is looked
+                // up to continue after it.
+                let column = order_column(orderBy);
+                let cursor_key =
+                    format!("(SELECT {column} FROM accounts WHERE hash = {cursor_string})");
+                format!(
+                    "({column} < {cursor_key} OR ({column} = {cursor_key} AND hash < {cursor_string}))"
+                )
+            }
+        };
+        filters_strings.push(filter_string);
     }
 
     let mut query_limit = PAGE_LIMIT;
@@ -217,6 +259,14 @@ pub async fn get_compressed_accounts_by_owner(
         })
         .unwrap_or("data".to_string());
 
+    let order_by = match orderBy {
+        AccountOrder::HashAsc => "accounts.hash ASC".to_string(),
+        AccountOrder::SlotDesc | AccountOrder::LamportsDesc => format!(
+            "accounts.{} DESC, accounts.hash DESC",
+            order_column(orderBy)
+        ),
+    };
+
     let raw_sql = format!(
         "
         SELECT 
@@ -232,10 +282,13 @@ pub async fn get_compressed_accounts_by_owner(
             spent,
             prev_spent,
             lamports,
-            discriminator
+            discriminator,
+            slot_spent,
+            data_truncated,
+            finalized
         FROM accounts
         WHERE {filters}
-        ORDER BY accounts.hash ASC
+        ORDER BY {order_by}
         LIMIT {query_limit}
     "
     );
@@ -262,3 +315,11 @@ pub async fn get_compressed_accounts_by_owner(
         value: PaginatedAccountList { items, cursor },
     })
 }
+
+fn order_column(order: AccountOrder) -> &'static str {
+    match order {
+        AccountOrder::SlotDesc => "slot_created",
+        AccountOrder::LamportsDesc => "lamports",
+        AccountOrder::HashAsc => "hash",
+    }
+}
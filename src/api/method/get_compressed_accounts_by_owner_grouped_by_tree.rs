@@ -0,0 +1,124 @@
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::typedefs::account::Account;
+use crate::common::typedefs::bs58_string::Base58String;
+use crate::common::typedefs::serializable_pubkey::SerializablePubkey;
+use crate::dao::generated::accounts;
+
+use super::super::error::PhotonApiError;
+use super::utils::{parse_account_model, Context, Limit};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetCompressedAccountsByOwnerGroupedByTreeRequest {
+    pub owner: SerializablePubkey,
+    #[serde(default)]
+    pub cursor: Option<Base58String>,
+    /// Maximum number of accounts, across all groups, to return.
+    #[serde(default)]
+    pub limit: Option<Limit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct TreeAccounts {
+    pub tree: SerializablePubkey,
+    pub accounts: Vec<Account>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct PaginatedTreeAccountsList {
+    pub items: Vec<TreeAccounts>,
+    pub cursor: Option<Base58String>,
+}
+
+// We do not use generics to simplify documentation generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetCompressedAccountsByOwnerGroupedByTreeResponse {
+    pub context: Context,
+    pub value: PaginatedTreeAccountsList,
+}
+
+/// Returns the unspent accounts of an owner grouped by the tree they are stored in, so that
+/// clients can pick accounts from as few trees as possible. Groups are ordered by tree and the
+/// accounts in each group by hash. A tree's accounts may continue in the first group of the next
+/// page.
+pub async fn get_compressed_accounts_by_owner_grouped_by_tree(
+    conn: &DatabaseConnection,
+    request: GetCompressedAccountsByOwnerGroupedByTreeRequest,
+) -> Result<GetCompressedAccountsByOwnerGroupedByTreeResponse, PhotonApiError> {
+    let context = Context::extract(conn).await?;
+    let GetCompressedAccountsByOwnerGroupedByTreeRequest {
+        owner,
+        cursor,
+        limit,
+    } = request;
+    let limit = limit.unwrap_or_default().value();
+
+    let mut filter = Condition::all()
+        .add(accounts::Column::Owner.eq(owner.to_bytes_vec()))
+        .add(accounts::Column::Spent.eq(false));
+    if let Some(cursor) = cursor {
+        let bytes = cursor.0;
+        let expected_cursor_length = 32 + 32;
+        if bytes.len() != expected_cursor_length {
+            return Err(PhotonApiError::ValidationError(format!(
+                "Invalid cursor length. Expected {}. Received {}.",
+                expected_cursor_length,
+                bytes.len()
+            )));
+        }
+        let (tree, hash) = bytes.split_at(32);
+        filter = filter.add(
+            Condition::any()
+                .add(accounts::Column::Tree.gt::<Vec<u8>>(tree.to_vec()))
+                .add(
+                    Condition::all()
+                        .add(accounts::Column::Tree.eq::<Vec<u8>>(tree.to_vec()))
+                        .add(accounts::Column::Hash.gt::<Vec<u8>>(hash.to_vec())),
+                ),
+        );
+    }
+
+    let accounts = accounts::Entity::find()
+        .filter(filter)
+        .order_by_asc(accounts::Column::Tree)
+        .order_by_asc(accounts::Column::Hash)
+        .limit(limit)
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(parse_account_model)
+        .collect::<Result<Vec<_>, PhotonApiError>>()?;
+
+    let cursor = match accounts.len() < limit as usize {
+        true => None,
+        false => accounts.last().map(|account| {
+            let mut bytes = account.tree.to_bytes_vec();
+            bytes.extend_from_slice(&account.hash.to_vec());
+            Base58String(bytes)
+        }),
+    };
+
+    let mut items: Vec<TreeAccounts> = Vec::new();
+    for account in accounts {
+        match items.last_mut() {
+            Some(group) if group.tree == account.tree => group.accounts.push(account),
+            _ => items.push(TreeAccounts {
+                tree: account.tree,
+                accounts: vec![account],
+            }),
+        }
+    }
+
+    Ok(GetCompressedAccountsByOwnerGroupedByTreeResponse {
+        context,
+        value: PaginatedTreeAccountsList { items, cursor },
+    })
+}
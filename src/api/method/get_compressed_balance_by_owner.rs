@@ -7,7 +7,7 @@ use utoipa::ToSchema;
 
 use super::super::error::PhotonApiError;
 use super::utils::Context;
-use super::utils::{parse_decimal, AccountBalanceResponse, LamportModel};
+use super::utils::{parse_balance, AccountBalanceResponse, LamportModel};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
 #[serde(rename_all = "camelCase")]
@@ -15,6 +15,8 @@ pub struct GetCompressedBalanceByOwnerRequest {
     pub owner: SerializablePubkey,
 }
 
+/// Returns the sum of the lamports of all unspent accounts of the owner. Fails with a balance
+/// overflow error if the sum exceeds u64::MAX.
 pub async fn get_compressed_balance_by_owner(
     conn: &DatabaseConnection,
     request: GetCompressedBalanceByOwnerRequest,
@@ -30,10 +32,16 @@ pub async fn get_compressed_balance_by_owner(
         .all(conn)
         .await?
         .iter()
-        .map(|x| parse_decimal(x.lamports))
+        .map(|x| parse_balance(x.lamports))
         .collect::<Result<Vec<u64>, PhotonApiError>>()?;
 
-    let total_balance = balances.iter().sum::<u64>();
+    let total_balance = balances
+        .iter()
+        .try_fold(0_u64, |total, balance| total.checked_add(*balance))
+        .ok_or(PhotonApiError::BalanceOverflow(format!(
+            "Total balance of owner {}",
+            owner
+        )))?;
 
     Ok(AccountBalanceResponse {
         value: UnsignedInteger(total_balance),
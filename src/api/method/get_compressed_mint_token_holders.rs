@@ -9,7 +9,7 @@ use crate::common::typedefs::unsigned_integer::UnsignedInteger;
 use crate::dao::generated::token_owner_balances;
 
 use super::super::error::PhotonApiError;
-use super::utils::{parse_decimal, Context, Limit, PAGE_LIMIT};
+use super::utils::{parse_balance, Context, Limit, PAGE_LIMIT};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub struct OwnerBalance {
@@ -39,6 +39,8 @@ pub struct GetCompressedMintTokenHoldersRequest {
     pub limit: Option<Limit>,
 }
 
+/// Returns the holders of a mint ordered by balance, where each balance is summed over all of the
+/// holder's unspent token accounts. Fails with a balance overflow error if a sum exceeds u64::MAX.
 pub async fn get_compressed_mint_token_holders(
     conn: &DatabaseConnection,
     request: GetCompressedMintTokenHoldersRequest,
@@ -87,7 +89,7 @@ pub async fn get_compressed_mint_token_holders(
         .map(|token_owner_balance| {
             Ok(OwnerBalance {
                 owner: token_owner_balance.owner.try_into()?,
-                balance: UnsignedInteger(parse_decimal(token_owner_balance.amount)?),
+                balance: UnsignedInteger(parse_balance(token_owner_balance.amount)?),
             })
         })
         .collect::<Result<Vec<OwnerBalance>, PhotonApiError>>()?;
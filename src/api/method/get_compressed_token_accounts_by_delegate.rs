@@ -17,11 +17,13 @@ pub async fn get_compressed_account_token_accounts_by_delegate(
         mint,
         cursor,
         limit,
+        exclude_native,
     } = request;
     let options = GetCompressedTokenAccountsByAuthorityOptions {
         mint,
         cursor,
         limit,
+        exclude_native,
     };
     fetch_token_accounts(conn, Authority::Delegate(delegate), options).await
 }
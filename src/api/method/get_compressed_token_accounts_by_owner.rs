@@ -15,11 +15,13 @@ pub async fn get_compressed_token_accounts_by_owner(
         mint,
         cursor,
         limit,
+        exclude_native,
     } = request;
     let options = GetCompressedTokenAccountsByAuthorityOptions {
         mint,
         cursor,
         limit,
+        exclude_native,
     };
     fetch_token_accounts(conn, Authority::Owner(owner), options).await
 }
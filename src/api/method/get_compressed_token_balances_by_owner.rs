@@ -8,7 +8,7 @@ use crate::common::typedefs::unsigned_integer::UnsignedInteger;
 use crate::dao::generated::token_owner_balances;
 
 use super::super::error::PhotonApiError;
-use super::utils::{parse_decimal, Context, Limit, PAGE_LIMIT};
+use super::utils::{parse_balance, Context, Limit, PAGE_LIMIT};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub struct TokenBalance {
@@ -39,6 +39,8 @@ pub struct GetCompressedTokenBalancesByOwnerRequest {
     pub limit: Option<Limit>,
 }
 
+/// Returns the owner's balance of each mint, summed over all of the owner's unspent token
+/// accounts. Fails with a balance overflow error if a sum exceeds u64::MAX.
 pub async fn get_compressed_token_balances_by_owner(
     conn: &DatabaseConnection,
     request: GetCompressedTokenBalancesByOwnerRequest,
@@ -80,7 +82,7 @@ pub async fn get_compressed_token_balances_by_owner(
         .map(|token_owner_balance| {
             Ok(TokenBalance {
                 mint: token_owner_balance.mint.try_into()?,
-                balance: UnsignedInteger(parse_decimal(token_owner_balance.amount)?),
+                balance: UnsignedInteger(parse_balance(token_owner_balance.amount)?),
             })
         })
         .collect::<Result<Vec<TokenBalance>, PhotonApiError>>()?;
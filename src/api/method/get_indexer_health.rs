@@ -1,27 +1,86 @@
 use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 use solana_client::nonblocking::rpc_client::RpcClient;
 
+use crate::common::typedefs::unsigned_integer::UnsignedInteger;
+
 use super::super::error::PhotonApiError;
 use super::utils::Context;
 
-// TODO: Make this an environment variable.
+/// Default number of slots the indexer may lag behind the RPC node while still being healthy.
 pub const HEALTH_CHECK_SLOT_DISTANCE: i64 = 20;
 
+/// Whether the indexer is caught up with the RPC node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum IndexerHealthStatus {
+    /// The indexer lags the RPC node by at most the maximum slot lag.
+    #[default]
+    Healthy,
+    /// The indexer lags the RPC node by more than the maximum slot lag.
+    Behind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct IndexerHealth {
+    pub last_indexed_slot: UnsignedInteger,
+    pub rpc_slot: UnsignedInteger,
+    /// Number of slots the indexer lags behind the RPC node.
+    pub slot_lag: UnsignedInteger,
+    pub status: IndexerHealthStatus,
+}
+
+// We do not use generics to simplify documentation generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetIndexerHealthStatusResponse {
+    pub context: Context,
+    pub value: IndexerHealth,
+}
+
 // TODO: Make sure that get_indexer_health formatting matches the Solana RPC formatting.
 pub async fn get_indexer_health(
     conn: &DatabaseConnection,
     rpc: &RpcClient,
+    max_slot_lag: u64,
 ) -> Result<String, PhotonApiError> {
+    let health = get_indexer_health_status(conn, rpc, max_slot_lag)
+        .await?
+        .value;
+    if health.status == IndexerHealthStatus::Behind {
+        return Err(PhotonApiError::StaleSlot(health.slot_lag.0));
+    }
+    Ok("ok".to_string())
+}
+
+/// Reports the last indexed slot, the current slot of the RPC node and the lag between them, so
+/// that load balancers can route reads away from lagging replicas.
+pub async fn get_indexer_health_status(
+    conn: &DatabaseConnection,
+    rpc: &RpcClient,
+    max_slot_lag: u64,
+) -> Result<GetIndexerHealthStatusResponse, PhotonApiError> {
     let context = Context::extract(conn).await?;
-    let slot = rpc
+    let rpc_slot = rpc
         .get_slot()
         .await
         .map_err(|e| PhotonApiError::UnexpectedError(format!("RPC error: {}", e)))?;
 
-    let slots_behind = slot as i64 - context.slot as i64;
-    if slots_behind > HEALTH_CHECK_SLOT_DISTANCE {
-        return Err(PhotonApiError::StaleSlot(slots_behind as u64));
-    }
-    Ok("ok".to_string())
+    let slot_lag = rpc_slot.saturating_sub(context.slot);
+    let status = match slot_lag > max_slot_lag {
+        true => IndexerHealthStatus::Behind,
+        false => IndexerHealthStatus::Healthy,
+    };
+    Ok(GetIndexerHealthStatusResponse {
+        value: IndexerHealth {
+            last_indexed_slot: UnsignedInteger(context.slot),
+            rpc_slot: UnsignedInteger(rpc_slot),
+            slot_lag: UnsignedInteger(slot_lag),
+            status,
+        },
+        context,
+    })
 }
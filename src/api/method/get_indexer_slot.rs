@@ -1,12 +1,14 @@
 use sea_orm::DatabaseConnection;
 
 use crate::common::typedefs::unsigned_integer::UnsignedInteger;
+use crate::ingester::indexer::fetch_last_indexed_slot;
 
 use super::super::error::PhotonApiError;
-use super::utils::Context;
 
+/// Returns the highest slot the indexer has processed, or 0 if nothing has been indexed yet.
 pub async fn get_indexer_slot(
     conn: &DatabaseConnection,
 ) -> Result<UnsignedInteger, PhotonApiError> {
-    Ok(UnsignedInteger(Context::extract(conn).await?.slot))
+    let slot = fetch_last_indexed_slot(conn).await?.unwrap_or_default();
+    Ok(UnsignedInteger(slot as u64))
 }
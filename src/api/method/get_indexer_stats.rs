@@ -0,0 +1,99 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::typedefs::unsigned_integer::UnsignedInteger;
+use crate::dao::generated::{accounts, blocks, indexed_trees, state_trees};
+use crate::ingester::indexer::OptionalContextModel;
+
+use super::super::error::PhotonApiError;
+use super::utils::Context;
+
+// Counting rows requires scanning large tables, so we serve cached stats for a short while.
+const INDEXER_STATS_CACHE_TTL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct IndexerStats {
+    pub unspent_accounts: UnsignedInteger,
+    pub spent_accounts: UnsignedInteger,
+    pub state_trees: UnsignedInteger,
+    pub address_trees: UnsignedInteger,
+    pub state_tree_nodes: UnsignedInteger,
+    pub first_indexed_slot: UnsignedInteger,
+    pub last_indexed_slot: UnsignedInteger,
+}
+
+// We do not use generics to simplify documentation generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetIndexerStatsResponse {
+    pub context: Context,
+    pub value: IndexerStats,
+}
+
+#[derive(Default)]
+pub struct IndexerStatsCache(Mutex<Option<(Instant, GetIndexerStatsResponse)>>);
+
+pub async fn get_indexer_stats(
+    conn: &DatabaseConnection,
+    cache: &IndexerStatsCache,
+) -> Result<GetIndexerStatsResponse, PhotonApiError> {
+    if let Some((cached_at, response)) = cache.0.lock().unwrap().as_ref() {
+        if cached_at.elapsed() < INDEXER_STATS_CACHE_TTL {
+            return Ok(response.clone());
+        }
+    }
+
+    let context = Context::extract(conn).await?;
+    let unspent_accounts = accounts::Entity::find()
+        .filter(accounts::Column::Spent.eq(false))
+        .count(conn)
+        .await?;
+    let spent_accounts = accounts::Entity::find()
+        .filter(accounts::Column::Spent.eq(true))
+        .count(conn)
+        .await?;
+    let state_trees = state_trees::Entity::find()
+        .select_only()
+        .column(state_trees::Column::Tree)
+        .distinct()
+        .count(conn)
+        .await?;
+    let address_trees = indexed_trees::Entity::find()
+        .select_only()
+        .column(indexed_trees::Column::Tree)
+        .distinct()
+        .count(conn)
+        .await?;
+    let state_tree_nodes = state_trees::Entity::find().count(conn).await?;
+    let first_indexed_slot = blocks::Entity::find()
+        .select_only()
+        .column_as(Expr::col(blocks::Column::Slot).min(), "slot")
+        .into_model::<OptionalContextModel>()
+        .one(conn)
+        .await?
+        .and_then(|model| model.slot)
+        .unwrap_or_default();
+
+    let response = GetIndexerStatsResponse {
+        value: IndexerStats {
+            unspent_accounts: UnsignedInteger(unspent_accounts as u64),
+            spent_accounts: UnsignedInteger(spent_accounts as u64),
+            state_trees: UnsignedInteger(state_trees as u64),
+            address_trees: UnsignedInteger(address_trees as u64),
+            state_tree_nodes: UnsignedInteger(state_tree_nodes as u64),
+            first_indexed_slot: UnsignedInteger(first_indexed_slot as u64),
+            last_indexed_slot: UnsignedInteger(context.slot),
+        },
+        context,
+    };
+    *cache.0.lock().unwrap() = Some((Instant::now(), response.clone()));
+    Ok(response)
+}
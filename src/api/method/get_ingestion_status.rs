@@ -0,0 +1,51 @@
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::typedefs::unix_timestamp::UnixTimestamp;
+use crate::common::typedefs::unsigned_integer::UnsignedInteger;
+use crate::ingester::fetchers::status::{
+    get_ingestion_status_snapshot, BlockSource, GrpcConnectionState,
+};
+
+use super::super::error::PhotonApiError;
+use super::utils::Context;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct IngestionStatus {
+    pub source: BlockSource,
+    pub grpc_connection_state: GrpcConnectionState,
+    pub last_block_received_at: Option<UnixTimestamp>,
+    /// Number of failed RPC block fetches in the last five minutes.
+    pub recent_rpc_fetch_errors: UnsignedInteger,
+    /// Number of gRPC connection, subscription and stream errors in the last five minutes.
+    pub recent_grpc_errors: UnsignedInteger,
+}
+
+// We do not use generics to simplify documentation generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetIngestionStatusResponse {
+    pub context: Context,
+    pub value: IngestionStatus,
+}
+
+/// Reports whether the indexer currently consumes blocks from gRPC or RPC, together with the
+/// health of the connections it ingests from.
+pub async fn get_ingestion_status(
+    conn: &DatabaseConnection,
+) -> Result<GetIngestionStatusResponse, PhotonApiError> {
+    let context = Context::extract(conn).await?;
+    let status = get_ingestion_status_snapshot();
+    Ok(GetIngestionStatusResponse {
+        context,
+        value: IngestionStatus {
+            source: status.source,
+            grpc_connection_state: status.grpc_connection_state,
+            last_block_received_at: status.last_block_received_at.map(UnixTimestamp),
+            recent_rpc_fetch_errors: UnsignedInteger(status.recent_rpc_fetch_errors),
+            recent_grpc_errors: UnsignedInteger(status.recent_grpc_errors),
+        },
+    })
+}
@@ -0,0 +1,62 @@
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::api::error::PhotonApiError;
+
+use super::{
+    get_validity_proof::{
+        get_validity_proof, CompressedProof, GetValidityProofRequest, GetValidityProofResponse,
+    },
+    utils::Context,
+};
+
+/// A validity proof in the layout of the proof argument of the Light system program instructions.
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+#[allow(non_snake_case)]
+pub struct InstructionValidityProof {
+    pub compressedProof: CompressedProof,
+    /// Indices of the proven roots in the root histories of their trees, in the order of the
+    /// hashes and then the new addresses of the request.
+    pub rootIndices: Vec<u16>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetInstructionValidityProofResponse {
+    pub value: InstructionValidityProof,
+    pub context: Context,
+}
+
+/// Generates a validity proof like `getValidityProof`, packaged so that it can be passed to the
+/// Light system program without any client-side reformatting.
+pub async fn get_instruction_validity_proof(
+    conn: &DatabaseConnection,
+    prover_url: &str,
+    verify_proof_roots: bool,
+    request: GetValidityProofRequest,
+) -> Result<GetInstructionValidityProofResponse, PhotonApiError> {
+    let GetValidityProofResponse { value, context } =
+        get_validity_proof(conn, prover_url, verify_proof_roots, request).await?;
+    let root_indices = value
+        .rootIndices
+        .into_iter()
+        .map(|root_index| {
+            u16::try_from(root_index).map_err(|_| {
+                PhotonApiError::UnexpectedError(format!(
+                    "Root index {} does not fit into an instruction",
+                    root_index
+                ))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(GetInstructionValidityProofResponse {
+        value: InstructionValidityProof {
+            compressedProof: value.compressedProof,
+            rootIndices: root_indices,
+        },
+        context,
+    })
+}
@@ -0,0 +1,107 @@
+use byteorder::{ByteOrder, LittleEndian};
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::typedefs::account::Account;
+use crate::common::typedefs::bs58_string::Base58String;
+use crate::dao::generated::accounts;
+
+use super::super::error::PhotonApiError;
+use super::utils::{parse_account_model, Context, Limit};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetLatestCompressedAccountsRequest {
+    #[serde(default)]
+    pub cursor: Option<Base58String>,
+    #[serde(default)]
+    pub limit: Option<Limit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct PaginatedLatestAccountList {
+    pub items: Vec<Account>,
+    pub cursor: Option<Base58String>,
+}
+
+// We do not use generics to simplify documentation generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetLatestCompressedAccountsResponse {
+    pub context: Context,
+    pub value: PaginatedLatestAccountList,
+}
+
+/// Returns the most recently created unspent accounts across all owners, newest first. Accounts
+/// are ordered by the slot in which they were created, then by sequence number and hash.
+pub async fn get_latest_compressed_accounts(
+    conn: &DatabaseConnection,
+    request: GetLatestCompressedAccountsRequest,
+) -> Result<GetLatestCompressedAccountsResponse, PhotonApiError> {
+    let context = Context::extract(conn).await?;
+    let GetLatestCompressedAccountsRequest { cursor, limit } = request;
+    let limit = limit.unwrap_or_default().value();
+
+    let mut filter = Condition::all().add(accounts::Column::Spent.eq(false));
+    if let Some(cursor) = cursor {
+        let bytes = cursor.0;
+        let expected_cursor_length = 8 + 8 + 32;
+        if bytes.len() != expected_cursor_length {
+            return Err(PhotonApiError::ValidationError(format!(
+                "Invalid cursor length. Expected {}. Received {}.",
+                expected_cursor_length,
+                bytes.len()
+            )));
+        }
+        let (slot, rest) = bytes.split_at(8);
+        let (seq, hash) = rest.split_at(8);
+        let slot = LittleEndian::read_u64(slot) as i64;
+        let seq = LittleEndian::read_u64(seq) as i64;
+        filter = filter.add(
+            Condition::any()
+                .add(accounts::Column::SlotCreated.lt(slot))
+                .add(
+                    Condition::all()
+                        .add(accounts::Column::SlotCreated.eq(slot))
+                        .add(accounts::Column::Seq.lt(seq)),
+                )
+                .add(
+                    Condition::all()
+                        .add(accounts::Column::SlotCreated.eq(slot))
+                        .add(accounts::Column::Seq.eq(seq))
+                        .add(accounts::Column::Hash.lt::<Vec<u8>>(hash.to_vec())),
+                ),
+        );
+    }
+
+    let items = accounts::Entity::find()
+        .filter(filter)
+        .order_by_desc(accounts::Column::SlotCreated)
+        .order_by_desc(accounts::Column::Seq)
+        .order_by_desc(accounts::Column::Hash)
+        .limit(limit)
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(parse_account_model)
+        .collect::<Result<Vec<_>, PhotonApiError>>()?;
+
+    let cursor = match items.len() < limit as usize {
+        true => None,
+        false => items.last().map(|item| {
+            let mut bytes = item.slot_created.0.to_le_bytes().to_vec();
+            bytes.extend_from_slice(&item.seq.0.to_le_bytes());
+            bytes.extend_from_slice(&item.hash.to_vec());
+            Base58String(bytes)
+        }),
+    };
+
+    Ok(GetLatestCompressedAccountsResponse {
+        context,
+        value: PaginatedLatestAccountList { items, cursor },
+    })
+}
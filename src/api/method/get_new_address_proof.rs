@@ -0,0 +1,78 @@
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseBackend, DatabaseConnection, EntityTrait, QueryFilter,
+    Statement, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::typedefs::serializable_pubkey::SerializablePubkey;
+use crate::dao::generated::indexed_trees;
+
+use super::super::error::PhotonApiError;
+use super::get_multiple_new_address_proofs::{
+    get_multiple_new_address_proofs_helper, AddressWithTree, MerkleContextWithNewAddressProof,
+    ADDRESS_TREE_ADDRESS,
+};
+use super::utils::Context;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetNewAddressProofRequest {
+    pub address: SerializablePubkey,
+    /// Address tree to prove non-inclusion in. Defaults to the main address tree.
+    #[serde(default)]
+    pub tree: Option<SerializablePubkey>,
+}
+
+// We do not use generics to simplify documentation generation.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetNewAddressProofResponse {
+    pub context: Context,
+    pub value: MerkleContextWithNewAddressProof,
+}
+
+/// Returns the non-inclusion proof needed to create an account at a new address: the low element
+/// whose range covers the address, the next element and the proof path of the low element.
+pub async fn get_new_address_proof(
+    conn: &DatabaseConnection,
+    request: GetNewAddressProofRequest,
+) -> Result<GetNewAddressProofResponse, PhotonApiError> {
+    let GetNewAddressProofRequest { address, tree } = request;
+    let tree = tree.unwrap_or(SerializablePubkey::from(ADDRESS_TREE_ADDRESS));
+
+    let context = Context::extract(conn).await?;
+    let tx = conn.begin().await?;
+    if tx.get_database_backend() == DatabaseBackend::Postgres {
+        tx.execute(Statement::from_string(
+            tx.get_database_backend(),
+            "SET TRANSACTION ISOLATION LEVEL REPEATABLE READ;".to_string(),
+        ))
+        .await?;
+    }
+
+    let existing_address = indexed_trees::Entity::find()
+        .filter(
+            indexed_trees::Column::Tree
+                .eq(tree.to_bytes_vec())
+                .and(indexed_trees::Column::Value.eq(address.to_bytes_vec())),
+        )
+        .one(&tx)
+        .await?;
+    if existing_address.is_some() {
+        return Err(PhotonApiError::ValidationError(format!(
+            "Address {} already exists in tree {}",
+            address, tree
+        )));
+    }
+
+    let mut new_address_proofs =
+        get_multiple_new_address_proofs_helper(&tx, vec![AddressWithTree { address, tree }])
+            .await?;
+    tx.commit().await?;
+
+    Ok(GetNewAddressProofResponse {
+        context,
+        value: new_address_proofs.remove(0),
+    })
+}
@@ -0,0 +1,58 @@
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::typedefs::serializable_signature::SerializableSignature;
+use crate::common::typedefs::unsigned_integer::UnsignedInteger;
+use crate::ingester::parser::error_samples::get_recent_parse_errors as get_parse_error_samples;
+
+use super::super::error::PhotonApiError;
+use super::utils::{Context, Limit};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetRecentParseErrorsRequest {
+    #[serde(default)]
+    pub limit: Option<Limit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct ParseError {
+    pub signature: SerializableSignature,
+    pub slot: UnsignedInteger,
+    pub error: String,
+    /// Length in bytes of the event data that failed to parse.
+    pub data_length: Option<UnsignedInteger>,
+}
+
+// We do not use generics to simplify documentation generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetRecentParseErrorsResponse {
+    pub context: Context,
+    pub value: Vec<ParseError>,
+}
+
+/// Returns the most recent transactions that the indexer failed to parse, newest first. Only the
+/// last failures since the indexer started are kept.
+pub async fn get_recent_parse_errors(
+    conn: &DatabaseConnection,
+    request: GetRecentParseErrorsRequest,
+) -> Result<GetRecentParseErrorsResponse, PhotonApiError> {
+    let context = Context::extract(conn).await?;
+    let limit = request.limit.unwrap_or_default().value();
+    let value = get_parse_error_samples(limit as usize)
+        .into_iter()
+        .map(|sample| ParseError {
+            signature: SerializableSignature(sample.signature),
+            slot: UnsignedInteger(sample.slot),
+            error: sample.error,
+            data_length: sample
+                .data_length
+                .map(|data_length| UnsignedInteger(data_length as u64)),
+        })
+        .collect();
+
+    Ok(GetRecentParseErrorsResponse { context, value })
+}
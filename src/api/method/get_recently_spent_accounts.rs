@@ -0,0 +1,128 @@
+use byteorder::{ByteOrder, LittleEndian};
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, FromQueryResult, QueryFilter,
+    QueryOrder, QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::typedefs::bs58_string::Base58String;
+use crate::common::typedefs::hash::Hash;
+use crate::common::typedefs::unsigned_integer::UnsignedInteger;
+use crate::dao::generated::accounts;
+
+use super::super::error::PhotonApiError;
+use super::utils::{Context, Limit};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetRecentlySpentAccountsRequest {
+    /// Only accounts spent at or after this slot are returned.
+    pub since_slot: UnsignedInteger,
+    #[serde(default)]
+    pub cursor: Option<Base58String>,
+    #[serde(default)]
+    pub limit: Option<Limit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct SpentAccount {
+    pub hash: Hash,
+    pub slot_spent: UnsignedInteger,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct PaginatedSpentAccountList {
+    pub items: Vec<SpentAccount>,
+    pub cursor: Option<Base58String>,
+}
+
+#[derive(FromQueryResult)]
+struct SpentAccountModel {
+    hash: Vec<u8>,
+    // Postgres and SQLlite do not support u64 as return type. We need to use i64 and cast it to u64.
+    slot_spent: i64,
+}
+
+// We do not use generics to simplify documentation generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetRecentlySpentAccountsResponse {
+    pub context: Context,
+    pub value: PaginatedSpentAccountList,
+}
+
+/// Returns the hashes of accounts spent at or after a slot, ordered by the slot in which they were
+/// spent. Intended for clients that cache account data and need to invalidate spent accounts.
+pub async fn get_recently_spent_accounts(
+    conn: &DatabaseConnection,
+    request: GetRecentlySpentAccountsRequest,
+) -> Result<GetRecentlySpentAccountsResponse, PhotonApiError> {
+    let context = Context::extract(conn).await?;
+    let GetRecentlySpentAccountsRequest {
+        since_slot,
+        cursor,
+        limit,
+    } = request;
+    let limit = limit.unwrap_or_default().value();
+
+    let mut filter = Condition::all().add(accounts::Column::SlotSpent.gte(since_slot.0 as i64));
+    if let Some(cursor) = cursor {
+        let bytes = cursor.0;
+        let expected_cursor_length = 8 + 32;
+        if bytes.len() != expected_cursor_length {
+            return Err(PhotonApiError::ValidationError(format!(
+                "Invalid cursor length. Expected {}. Received {}.",
+                expected_cursor_length,
+                bytes.len()
+            )));
+        }
+        let (slot, hash) = bytes.split_at(8);
+        let slot = LittleEndian::read_u64(slot) as i64;
+        filter = filter.add(
+            Condition::any()
+                .add(accounts::Column::SlotSpent.gt(slot))
+                .add(
+                    Condition::all()
+                        .add(accounts::Column::SlotSpent.eq(slot))
+                        .add(accounts::Column::Hash.gt::<Vec<u8>>(hash.to_vec())),
+                ),
+        );
+    }
+
+    let items = accounts::Entity::find()
+        .select_only()
+        .column(accounts::Column::Hash)
+        .column(accounts::Column::SlotSpent)
+        .filter(filter)
+        .order_by_asc(accounts::Column::SlotSpent)
+        .order_by_asc(accounts::Column::Hash)
+        .limit(limit)
+        .into_model::<SpentAccountModel>()
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|model| {
+            Ok(SpentAccount {
+                hash: Hash::try_from(model.hash)?,
+                slot_spent: UnsignedInteger(model.slot_spent as u64),
+            })
+        })
+        .collect::<Result<Vec<_>, PhotonApiError>>()?;
+
+    let cursor = match items.len() < limit as usize {
+        true => None,
+        false => items.last().map(|item| {
+            let mut bytes = item.slot_spent.0.to_le_bytes().to_vec();
+            bytes.extend_from_slice(&item.hash.to_vec());
+            Base58String(bytes)
+        }),
+    };
+
+    Ok(GetRecentlySpentAccountsResponse {
+        context,
+        value: PaginatedSpentAccountList { items, cursor },
+    })
+}
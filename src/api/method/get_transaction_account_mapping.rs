@@ -0,0 +1,60 @@
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::typedefs::hash::Hash;
+use crate::common::typedefs::serializable_signature::SerializableSignature;
+use crate::dao::generated::account_mappings;
+
+use super::super::error::PhotonApiError;
+use super::utils::Context;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetTransactionAccountMappingRequest {
+    pub signature: SerializableSignature,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct AccountMapping {
+    /// Hash of the account spent by the transaction.
+    pub input_hash: Hash,
+    /// Hash of the account created by the transaction that supersedes the input.
+    pub output_hash: Hash,
+}
+
+// We do not use generics to simplify documentation generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetTransactionAccountMappingResponse {
+    pub context: Context,
+    pub value: Vec<AccountMapping>,
+}
+
+/// Returns which account created by a transaction supersedes which account spent by it. An input
+/// is superseded by the output with the same address, or, for accounts without an address, by an
+/// output of the same owner. Inputs that are closed and outputs that are new are not mapped.
+pub async fn get_transaction_account_mapping(
+    conn: &DatabaseConnection,
+    request: GetTransactionAccountMappingRequest,
+) -> Result<GetTransactionAccountMappingResponse, PhotonApiError> {
+    let context = Context::extract(conn).await?;
+    let signature: [u8; 64] = request.signature.0.into();
+
+    let value = account_mappings::Entity::find()
+        .filter(account_mappings::Column::Signature.eq(signature.to_vec()))
+        .order_by_asc(account_mappings::Column::InputHash)
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|model| {
+            Ok(AccountMapping {
+                input_hash: model.input_hash.try_into()?,
+                output_hash: model.output_hash.try_into()?,
+            })
+        })
+        .collect::<Result<Vec<_>, PhotonApiError>>()?;
+
+    Ok(GetTransactionAccountMappingResponse { context, value })
+}
@@ -1,6 +1,6 @@
 use crate::common::typedefs::serializable_signature::SerializableSignature;
 use crate::common::typedefs::token_data::TokenData;
-use crate::ingester::parser::parse_transaction;
+use crate::ingester::parser::{parse_transaction, ParserConfig};
 use crate::ingester::persist::parse_token_data;
 use crate::{common::typedefs::account::Account, dao::generated::accounts::Model};
 
@@ -127,6 +127,7 @@ pub async fn get_transaction_helper(
     conn: &DatabaseConnection,
     signature: SerializableSignature,
     txn: EncodedConfirmedTransactionWithStatusMeta,
+    parser_config: &ParserConfig,
 ) -> Result<GetTransactionResponse, PhotonApiError> {
     // Ignore if tx failed or meta is missed
     let meta = txn.transaction.meta.as_ref();
@@ -142,6 +143,7 @@ pub async fn get_transaction_helper(
             PhotonApiError::UnexpectedError(format!("Failed to parse transaction {}", signature.0))
         })?,
         slot,
+        parser_config,
     )
     .map_err(|_e| {
         PhotonApiError::UnexpectedError(format!("Failed to parse transaction {}", signature.0))
@@ -179,6 +181,7 @@ pub async fn get_transaction_with_compression_info(
     conn: &DatabaseConnection,
     rpc_client: &RpcClient,
     request: GetTransactionRequest,
+    parser_config: &ParserConfig,
 ) -> Result<GetTransactionResponse, PhotonApiError> {
     let txn: EncodedConfirmedTransactionWithStatusMeta = rpc_client
         .send(
@@ -192,5 +195,5 @@ pub async fn get_transaction_with_compression_info(
                 request.signature.0, e
             ))
         })?;
-    get_transaction_helper(conn, request.signature, txn).await
+    get_transaction_helper(conn, request.signature, txn, parser_config).await
 }
@@ -0,0 +1,137 @@
+use byteorder::{ByteOrder, LittleEndian};
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::typedefs::bs58_string::Base58String;
+use crate::common::typedefs::hash::Hash;
+use crate::common::typedefs::serializable_pubkey::SerializablePubkey;
+use crate::common::typedefs::unsigned_integer::UnsignedInteger;
+use crate::dao::generated::state_trees;
+
+use super::super::error::PhotonApiError;
+use super::utils::{Context, Limit};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetTreeNodesRequest {
+    pub tree: SerializablePubkey,
+    /// Only nodes last updated after this sequence number are returned. All nodes are returned
+    /// by default.
+    #[serde(default)]
+    pub from_seq: Option<UnsignedInteger>,
+    #[serde(default)]
+    pub cursor: Option<Base58String>,
+    #[serde(default)]
+    pub limit: Option<Limit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct TreeNode {
+    /// Index of the node, where the root has index 1 and the children of node `i` are `2i` and
+    /// `2i + 1`.
+    pub node_index: UnsignedInteger,
+    /// Level of the node, where leaves are at level 0.
+    pub level: UnsignedInteger,
+    /// Only set for leaves.
+    pub leaf_index: Option<UnsignedInteger>,
+    pub hash: Hash,
+    /// Sequence number of the latest update of the node.
+    pub seq: UnsignedInteger,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct PaginatedTreeNodeList {
+    pub items: Vec<TreeNode>,
+    pub cursor: Option<Base58String>,
+}
+
+// We do not use generics to simplify documentation generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetTreeNodesResponse {
+    pub context: Context,
+    pub value: PaginatedTreeNodeList,
+}
+
+/// Returns the nodes of a state tree that were updated after a sequence number, so that clients
+/// that keep a local copy of the tree can sync it incrementally. Nodes are ordered by sequence
+/// number, then by node index.
+pub async fn get_tree_nodes(
+    conn: &DatabaseConnection,
+    request: GetTreeNodesRequest,
+) -> Result<GetTreeNodesResponse, PhotonApiError> {
+    let context = Context::extract(conn).await?;
+    let GetTreeNodesRequest {
+        tree,
+        from_seq,
+        cursor,
+        limit,
+    } = request;
+    let limit = limit.unwrap_or_default().value();
+
+    let mut filter = Condition::all().add(state_trees::Column::Tree.eq::<Vec<u8>>(tree.into()));
+    if let Some(from_seq) = from_seq {
+        filter = filter.add(state_trees::Column::Seq.gt(from_seq.0 as i64));
+    }
+    if let Some(cursor) = cursor {
+        let bytes = cursor.0;
+        let expected_cursor_length = 16;
+        if bytes.len() != expected_cursor_length {
+            return Err(PhotonApiError::ValidationError(format!(
+                "Invalid cursor length. Expected {}. Received {}.",
+                expected_cursor_length,
+                bytes.len()
+            )));
+        }
+        let (seq, node_index) = bytes.split_at(8);
+        let seq = LittleEndian::read_u64(seq) as i64;
+        let node_index = LittleEndian::read_u64(node_index) as i64;
+        filter = filter.add(
+            Condition::any().add(state_trees::Column::Seq.gt(seq)).add(
+                Condition::all()
+                    .add(state_trees::Column::Seq.eq(seq))
+                    .add(state_trees::Column::NodeIdx.gt(node_index)),
+            ),
+        );
+    }
+
+    let items = state_trees::Entity::find()
+        .filter(filter)
+        .order_by_asc(state_trees::Column::Seq)
+        .order_by_asc(state_trees::Column::NodeIdx)
+        .limit(limit)
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|node| {
+            Ok(TreeNode {
+                node_index: UnsignedInteger(node.node_idx as u64),
+                level: UnsignedInteger(node.level as u64),
+                leaf_index: node
+                    .leaf_idx
+                    .map(|leaf_idx| UnsignedInteger(leaf_idx as u64)),
+                hash: Hash::try_from(node.hash)?,
+                seq: UnsignedInteger(node.seq as u64),
+            })
+        })
+        .collect::<Result<Vec<_>, PhotonApiError>>()?;
+
+    let cursor = match items.len() < limit as usize {
+        true => None,
+        false => items.last().map(|item| {
+            let mut bytes = item.seq.0.to_le_bytes().to_vec();
+            bytes.extend_from_slice(&item.node_index.0.to_le_bytes());
+            Base58String(bytes)
+        }),
+    };
+
+    Ok(GetTreeNodesResponse {
+        context,
+        value: PaginatedTreeNodeList { items, cursor },
+    })
+}
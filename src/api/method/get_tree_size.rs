@@ -0,0 +1,77 @@
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::typedefs::serializable_pubkey::SerializablePubkey;
+use crate::common::typedefs::unsigned_integer::UnsignedInteger;
+use crate::dao::generated::{indexed_trees, state_trees};
+use crate::ingester::persist::TREE_HEIGHT;
+
+use super::super::error::PhotonApiError;
+use super::get_multiple_new_address_proofs::ADDRESS_TREE_HEIGHT;
+use super::utils::Context;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetTreeSizeRequest {
+    pub tree: SerializablePubkey,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct TreeSize {
+    /// Number of leaves of the tree.
+    pub leaves: UnsignedInteger,
+    /// Maximum number of leaves of the tree given its height, if the tree is indexed.
+    pub capacity: Option<UnsignedInteger>,
+}
+
+// We do not use generics to simplify documentation generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetTreeSizeResponse {
+    pub context: Context,
+    pub value: TreeSize,
+}
+
+/// Returns the number of leaves of a state or address tree along with its capacity, which helps
+/// to detect trees that are nearly full.
+pub async fn get_tree_size(
+    conn: &DatabaseConnection,
+    request: GetTreeSizeRequest,
+) -> Result<GetTreeSizeResponse, PhotonApiError> {
+    let context = Context::extract(conn).await?;
+    let tree: Vec<u8> = request.tree.into();
+
+    let leaves = state_trees::Entity::find()
+        .filter(
+            state_trees::Column::Tree
+                .eq(tree.clone())
+                .and(state_trees::Column::Level.eq(0)),
+        )
+        .count(conn)
+        .await?;
+    let capacity = match leaves {
+        0 => None,
+        _ => {
+            let is_address_tree = indexed_trees::Entity::find()
+                .filter(indexed_trees::Column::Tree.eq(tree))
+                .one(conn)
+                .await?
+                .is_some();
+            let height = match is_address_tree {
+                true => ADDRESS_TREE_HEIGHT,
+                false => TREE_HEIGHT,
+            };
+            Some(UnsignedInteger(1 << (height - 1)))
+        }
+    };
+
+    Ok(GetTreeSizeResponse {
+        context,
+        value: TreeSize {
+            leaves: UnsignedInteger(leaves as u64),
+            capacity,
+        },
+    })
+}
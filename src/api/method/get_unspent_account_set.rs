@@ -0,0 +1,94 @@
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::typedefs::account::Account;
+use crate::common::typedefs::bs58_string::Base58String;
+use crate::common::typedefs::serializable_pubkey::SerializablePubkey;
+use crate::dao::generated::accounts;
+
+use super::super::error::PhotonApiError;
+use super::utils::{parse_account_model, Context, Limit};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetUnspentAccountSetRequest {
+    /// Only the unspent accounts of this tree are returned. The accounts of all trees are
+    /// returned by default.
+    #[serde(default)]
+    pub tree: Option<SerializablePubkey>,
+    #[serde(default)]
+    pub cursor: Option<Base58String>,
+    #[serde(default)]
+    pub limit: Option<Limit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct PaginatedUnspentAccountList {
+    pub items: Vec<Account>,
+    pub cursor: Option<Base58String>,
+}
+
+// We do not use generics to simplify documentation generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct GetUnspentAccountSetResponse {
+    pub context: Context,
+    pub value: PaginatedUnspentAccountList,
+}
+
+/// Returns the set of unspent accounts, optionally only those of a single tree, for
+/// reconciliation against the indexed state. Accounts are ordered by hash, so that the cursor
+/// stays valid while accounts are created and spent between pages.
+pub async fn get_unspent_account_set(
+    conn: &DatabaseConnection,
+    request: GetUnspentAccountSetRequest,
+) -> Result<GetUnspentAccountSetResponse, PhotonApiError> {
+    let context = Context::extract(conn).await?;
+    let GetUnspentAccountSetRequest {
+        tree,
+        cursor,
+        limit,
+    } = request;
+    let limit = limit.unwrap_or_default().value();
+
+    let mut filter = Condition::all().add(accounts::Column::Spent.eq(false));
+    if let Some(tree) = tree {
+        filter = filter.add(accounts::Column::Tree.eq::<Vec<u8>>(tree.into()));
+    }
+    if let Some(cursor) = cursor {
+        let bytes = cursor.0;
+        let expected_cursor_length = 32;
+        if bytes.len() != expected_cursor_length {
+            return Err(PhotonApiError::ValidationError(format!(
+                "Invalid cursor length. Expected {}. Received {}.",
+                expected_cursor_length,
+                bytes.len()
+            )));
+        }
+        filter = filter.add(accounts::Column::Hash.gt::<Vec<u8>>(bytes));
+    }
+
+    let items = accounts::Entity::find()
+        .filter(filter)
+        .order_by_asc(accounts::Column::Hash)
+        .limit(limit)
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(parse_account_model)
+        .collect::<Result<Vec<_>, PhotonApiError>>()?;
+
+    let cursor = match items.len() < limit as usize {
+        true => None,
+        false => items.last().map(|item| Base58String(item.hash.to_vec())),
+    };
+
+    Ok(GetUnspentAccountSetResponse {
+        context,
+        value: PaginatedUnspentAccountList { items, cursor },
+    })
+}
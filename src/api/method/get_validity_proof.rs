@@ -1,6 +1,7 @@
 use crate::{
     api::error::PhotonApiError,
     common::typedefs::{hash::Hash, serializable_pubkey::SerializablePubkey},
+    dao::generated::state_trees,
     ingester::persist::persisted_state_tree::{
         get_multiple_compressed_leaf_proofs, MerkleProofWithContext,
     },
@@ -8,9 +9,14 @@ use crate::{
 use lazy_static::lazy_static;
 use num_bigint::BigUint;
 use reqwest::Client;
-use sea_orm::{ConnectionTrait, DatabaseBackend, DatabaseConnection, Statement, TransactionTrait};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseBackend, DatabaseConnection, EntityTrait, QueryFilter,
+    Statement, TransactionTrait,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::str::FromStr;
+use std::time::Duration;
 use utoipa::ToSchema;
 
 use super::{
@@ -29,6 +35,8 @@ lazy_static! {
 }
 
 pub const STATE_TREE_QUEUE_SIZE: u64 = 2400;
+// Proof generation for large batches takes several seconds, so the timeout is generous.
+pub const PROVER_TIMEOUT: Duration = Duration::from_secs(60);
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -116,7 +124,7 @@ struct HexBatchInputsForProver {
 pub struct CompressedProofWithContext {
     pub compressedProof: CompressedProof,
     roots: Vec<String>,
-    rootIndices: Vec<u64>,
+    pub rootIndices: Vec<u64>,
     leafIndices: Vec<u32>,
     leaves: Vec<String>,
     merkleTrees: Vec<String>,
@@ -148,11 +156,13 @@ struct ProofABC {
     c: Vec<u8>,
 }
 
+/// A Groth16 proof with compressed points, as expected by the Light system program: `a` and `c`
+/// are 32 bytes long and `b` is 64 bytes long.
 #[derive(Serialize, Deserialize, ToSchema, Default)]
 pub struct CompressedProof {
-    a: Vec<u8>,
-    b: Vec<u8>,
-    c: Vec<u8>,
+    pub a: Vec<u8>,
+    pub b: Vec<u8>,
+    pub c: Vec<u8>,
 }
 
 fn deserialize_hex_string_to_bytes(hex_str: &str) -> Vec<u8> {
@@ -276,9 +286,125 @@ pub struct GetValidityProofResponse {
     pub context: Context,
 }
 
+/// Checks that the state tree roots a proof was generated for are still part of the on-chain root
+/// history. Proving can take a while and the Light system program rejects proofs for roots that
+/// have already been evicted from the root history.
+pub async fn verify_proof_roots_are_current(
+    conn: &DatabaseConnection,
+    account_proofs: &[MerkleProofWithContext],
+) -> Result<(), PhotonApiError> {
+    if account_proofs.is_empty() {
+        return Ok(());
+    }
+    let trees = account_proofs
+        .iter()
+        .map(|proof| proof.merkleTree.to_bytes_vec())
+        .collect::<Vec<Vec<u8>>>();
+    let latest_root_seqs = state_trees::Entity::find()
+        .filter(
+            state_trees::Column::Tree
+                .is_in(trees)
+                .and(state_trees::Column::NodeIdx.eq(1)),
+        )
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|root| (root.tree, root.seq as u64))
+        .collect::<HashMap<Vec<u8>, u64>>();
+
+    for proof in account_proofs {
+        let latest_root_seq = latest_root_seqs
+            .get(&proof.merkleTree.to_bytes_vec())
+            .copied()
+            .ok_or(PhotonApiError::RecordNotFound(format!(
+                "Root not found for tree {}",
+                proof.merkleTree
+            )))?;
+        if latest_root_seq.saturating_sub(proof.rootSeq) >= STATE_TREE_QUEUE_SIZE {
+            return Err(PhotonApiError::InvalidProof(format!(
+                "Proof for account {} was generated for root sequence {} of tree {}, which is no \
+                longer in the root history. Latest root sequence is {}",
+                proof.hash, proof.rootSeq, proof.merkleTree, latest_root_seq
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Verifies the proof returned by the prover against the roots and leaves read from the database,
+/// the same way the Light system program does. A proof that the prover generated for other roots,
+/// for instance stale ones, would otherwise only be rejected once the client submits it.
+pub fn verify_prover_proof(
+    account_proofs: &[MerkleProofWithContext],
+    new_address_proofs: &[MerkleContextWithNewAddressProof],
+    proof: &CompressedProof,
+) -> Result<(), PhotonApiError> {
+    let proof = light_verifier::CompressedProof {
+        a: proof_point(&proof.a)?,
+        b: proof_point(&proof.b)?,
+        c: proof_point(&proof.c)?,
+    };
+    let roots = account_proofs
+        .iter()
+        .map(|proof| <[u8; 32]>::from(proof.root.clone()))
+        .collect::<Vec<_>>();
+    let leaves = account_proofs
+        .iter()
+        .map(|proof| <[u8; 32]>::from(proof.hash.clone()))
+        .collect::<Vec<_>>();
+    let address_roots = new_address_proofs
+        .iter()
+        .map(|proof| <[u8; 32]>::from(proof.root.clone()))
+        .collect::<Vec<_>>();
+    let addresses = new_address_proofs
+        .iter()
+        .map(|proof| proof.address.0.to_bytes())
+        .collect::<Vec<_>>();
+
+    let result = if addresses.is_empty() {
+        light_verifier::verify_merkle_proof_zkp(&roots, &leaves, &proof)
+    } else if roots.is_empty() {
+        light_verifier::verify_create_addresses_zkp(&address_roots, &addresses, &proof)
+    } else {
+        light_verifier::verify_create_addresses_and_merkle_proof_zkp(
+            &roots,
+            &leaves,
+            &address_roots,
+            &addresses,
+            &proof,
+        )
+    };
+    result.map_err(|e| {
+        PhotonApiError::InvalidProof(format!(
+            "The proof returned by the prover does not verify against the stored roots: {:?}",
+            e
+        ))
+    })
+}
+
+fn proof_point<const N: usize>(bytes: &[u8]) -> Result<[u8; N], PhotonApiError> {
+    bytes.try_into().map_err(|_| {
+        PhotonApiError::InvalidProof("The prover returned a malformed proof".to_string())
+    })
+}
+
+fn prover_error(prover_url: &str, e: reqwest::Error) -> PhotonApiError {
+    if e.is_connect() {
+        PhotonApiError::ProverNotConfigured(format!("The prover at {} is unreachable", prover_url))
+    } else if e.is_timeout() {
+        PhotonApiError::ProverError(format!(
+            "The prover did not respond within {} seconds",
+            PROVER_TIMEOUT.as_secs()
+        ))
+    } else {
+        PhotonApiError::ProverError(format!("Error fetching proof {}", e))
+    }
+}
+
 pub async fn get_validity_proof(
     conn: &DatabaseConnection,
     prover_url: &str,
+    verify_proof_roots: bool,
     mut request: GetValidityProofRequest,
 ) -> Result<GetValidityProofResponse, PhotonApiError> {
     if request.hashes.is_empty()
@@ -306,7 +432,10 @@ pub async fn get_validity_proof(
     }
 
     let context = Context::extract(conn).await?;
-    let client = Client::new();
+    let client = Client::builder()
+        .timeout(PROVER_TIMEOUT)
+        .build()
+        .map_err(|e| PhotonApiError::UnexpectedError(format!("Error building client {}", e)))?;
     let tx = conn.begin().await?;
     if tx.get_database_backend() == DatabaseBackend::Postgres {
         tx.execute(Statement::from_string(
@@ -345,19 +474,18 @@ pub async fn get_validity_proof(
         .header("Content-Type", "application/json")
         .send()
         .await
-        .map_err(|e| PhotonApiError::UnexpectedError(format!("Error fetching proof {}", e)))?;
-
-    if !res.status().is_success() {
-        return Err(PhotonApiError::UnexpectedError(format!(
-            "Error fetching proof {:?}",
-            res.text().await,
+        .map_err(|e| prover_error(prover_url, e))?;
+
+    let status = res.status();
+    if !status.is_success() {
+        return Err(PhotonApiError::ProverError(format!(
+            "The prover responded with status {}: {}",
+            status,
+            res.text().await.unwrap_or_default(),
         )));
     }
 
-    let text = res
-        .text()
-        .await
-        .map_err(|e| PhotonApiError::UnexpectedError(format!("Error fetching proof {}", e)))?;
+    let text = res.text().await.map_err(|e| prover_error(prover_url, e))?;
 
     let proof: GnarkProofJson = serde_json::from_str(&text).map_err(|e| {
         PhotonApiError::UnexpectedError(format!(
@@ -371,6 +499,11 @@ pub async fn get_validity_proof(
     #[allow(non_snake_case)]
     let compressedProof = negate_and_compress_proof(proof);
 
+    if verify_proof_roots {
+        verify_prover_proof(&account_proofs, &new_address_proofs, &compressedProof)?;
+        verify_proof_roots_are_current(conn, &account_proofs).await?;
+    }
+
     let compressed_proof_with_context = CompressedProofWithContext {
         compressedProof,
         roots: account_proofs
@@ -1,7 +1,12 @@
+pub mod decode_compressed_account;
 pub mod get_compressed_account;
+pub mod get_compressed_account_and_proof_by_address;
 pub mod get_compressed_account_balance;
 pub mod get_compressed_account_proof;
+pub mod get_compressed_account_summary;
+pub mod get_compressed_accounts_by_lamport_range;
 pub mod get_compressed_accounts_by_owner;
+pub mod get_compressed_accounts_by_owner_grouped_by_tree;
 pub mod get_compressed_balance_by_owner;
 pub mod get_compressed_mint_token_holders;
 pub mod get_compressed_token_account_balance;
@@ -14,11 +19,25 @@ pub mod get_compression_signatures_for_owner;
 pub mod get_compression_signatures_for_token_owner;
 pub mod get_indexer_health;
 pub mod get_indexer_slot;
+pub mod get_indexer_stats;
+pub mod get_ingestion_status;
+pub mod get_instruction_validity_proof;
+pub mod get_latest_compressed_accounts;
 pub mod get_latest_compression_signatures;
 pub mod get_latest_non_voting_signatures;
 pub mod get_multiple_compressed_account_proofs;
 pub mod get_multiple_compressed_accounts;
 pub mod get_multiple_new_address_proofs;
+pub mod get_new_address_proof;
+pub mod get_recent_parse_errors;
+pub mod get_recently_spent_accounts;
+pub mod get_transaction_account_mapping;
 pub mod get_transaction_with_compression_info;
+pub mod get_tree_nodes;
+pub mod get_tree_size;
+pub mod get_unspent_account_set;
 pub mod get_validity_proof;
+pub mod reindex_slot;
+pub mod subscribe_compressed_account;
+pub mod verify_proof;
 pub mod utils;
@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use utoipa::ToSchema;
+
+use crate::common::typedefs::unsigned_integer::UnsignedInteger;
+use crate::common::{Commitment, RetryBackoffConfig};
+use crate::ingester::fetchers::poller::fetch_block_with_retries;
+use crate::ingester::{reindex_block, IngesterConfig};
+
+use super::super::error::PhotonApiError;
+
+// Number of attempts made to fetch the block before the call fails, so that it does not hang
+// while the RPC node is unreachable.
+const FETCH_BLOCK_ATTEMPTS: u32 = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct ReindexSlotRequest {
+    pub slot: UnsignedInteger,
+}
+
+/// Admin method that re-indexes a slot from RPC, replacing the state persisted for it.
+pub async fn reindex_slot(
+    conn: &DatabaseConnection,
+    rpc_client: &Arc<RpcClient>,
+    request: ReindexSlotRequest,
+    ingester_config: &IngesterConfig,
+    retry_backoff: RetryBackoffConfig,
+    commitment: Commitment,
+) -> Result<UnsignedInteger, PhotonApiError> {
+    let slot = request.slot.0;
+    let block = fetch_block_with_retries(
+        rpc_client.clone(),
+        slot,
+        commitment,
+        FETCH_BLOCK_ATTEMPTS,
+        retry_backoff,
+    )
+    .await
+    .map_err(|e| PhotonApiError::UnexpectedError(format!("Failed to fetch slot {}: {}", slot, e)))?
+    .ok_or(PhotonApiError::RecordNotFound(format!(
+        "Slot {} was skipped",
+        slot
+    )))?;
+    reindex_block(conn, &block, ingester_config)
+        .await
+        .map_err(|e| PhotonApiError::UnexpectedError(format!("Failed to reindex slot: {}", e)))?;
+    Ok(UnsignedInteger(slot))
+}
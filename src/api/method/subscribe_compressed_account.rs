@@ -0,0 +1,27 @@
+use async_stream::stream;
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+
+use crate::common::typedefs::hash::Hash;
+use crate::ingester::sink::{subscribe_to_account_changes, StateUpdateMessage};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct SubscribeCompressedAccountRequest {
+    pub hash: Hash,
+}
+
+/// Streams the changes of an account as they are persisted, that is its creation and its
+/// spending. The stream ends when the subscriber falls too far behind to receive every change, so
+/// that clients can fetch the account again instead of silently missing a change.
+pub fn subscribe_compressed_account(
+    request: SubscribeCompressedAccountRequest,
+) -> impl Stream<Item = StateUpdateMessage> {
+    // We subscribe before the stream is first polled so that no change is missed in between.
+    let mut receiver = subscribe_to_account_changes(request.hash);
+    stream! {
+        while let Some(message) = receiver.recv().await {
+            yield message;
+        }
+    }
+}
@@ -14,6 +14,8 @@ use sea_orm::{
     QueryOrder, QuerySelect, Statement, Value,
 };
 use serde::{de, Deserialize, Deserializer, Serialize};
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Signature;
 
 use sqlx::types::Decimal;
@@ -27,6 +29,8 @@ use super::super::error::PhotonApiError;
 use sea_orm_migration::sea_query::Expr;
 
 pub const PAGE_LIMIT: u64 = 1000;
+// Mint of wrapped SOL. Compressed token accounts for this mint hold native SOL.
+pub const NATIVE_MINT: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
 
 pub fn parse_decimal(value: Decimal) -> Result<u64, PhotonApiError> {
     value
@@ -35,6 +39,17 @@ pub fn parse_decimal(value: Decimal) -> Result<u64, PhotonApiError> {
         .map_err(|_| PhotonApiError::UnexpectedError("Invalid decimal value".to_string()))
 }
 
+/// Parses a balance summed over many accounts. Sums are stored with more precision than a u64,
+/// since the lamports or token amounts of several accounts can exceed u64::MAX. The API returns
+/// balances as u64, so larger sums are reported as a [`PhotonApiError::BalanceOverflow`] instead
+/// of being truncated.
+pub fn parse_balance(value: Decimal) -> Result<u64, PhotonApiError> {
+    if value > Decimal::from(u64::MAX) {
+        return Err(PhotonApiError::BalanceOverflow(value.to_string()));
+    }
+    parse_decimal(value)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
 pub struct Limit(u64);
 
@@ -139,6 +154,9 @@ pub fn parse_account_model(account: accounts::Model) -> Result<Account, PhotonAp
             discriminator: UnsignedInteger(parse_decimal(discriminator)?),
         }),
         (None, None, None) => None,
+        // The data of accounts rejected for exceeding the maximum account data size is not
+        // persisted.
+        (None, Some(_), Some(_)) if account.data_truncated => None,
         _ => {
             return Err(PhotonApiError::UnexpectedError(
                 "Invalid account data".to_string(),
@@ -159,6 +177,7 @@ pub fn parse_account_model(account: accounts::Model) -> Result<Account, PhotonAp
         lamports: UnsignedInteger(parse_decimal(account.lamports)?),
         slot_created: UnsignedInteger(account.slot_created as u64),
         seq: UnsignedInteger(account.seq as u64),
+        data_truncated: account.data_truncated,
     })
 }
 
@@ -195,6 +214,7 @@ pub struct GetCompressedTokenAccountsByAuthorityOptions {
     pub mint: Option<SerializablePubkey>,
     pub cursor: Option<Base58String>,
     pub limit: Option<Limit>,
+    pub exclude_native: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
@@ -207,6 +227,9 @@ pub struct GetCompressedTokenAccountsByOwner {
     pub cursor: Option<Base58String>,
     #[serde(default)]
     pub limit: Option<Limit>,
+    /// Exclude wrapped SOL token accounts from the results.
+    #[serde(default)]
+    pub exclude_native: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
@@ -219,6 +242,9 @@ pub struct GetCompressedTokenAccountsByDelegate {
     pub cursor: Option<Base58String>,
     #[serde(default)]
     pub limit: Option<Limit>,
+    /// Exclude wrapped SOL token accounts from the results.
+    #[serde(default)]
+    pub exclude_native: bool,
 }
 
 #[derive(FromQueryResult)]
@@ -260,6 +286,9 @@ pub async fn fetch_token_accounts(
     if let Some(mint) = options.mint {
         filter = filter.and(token_accounts::Column::Mint.eq::<Vec<u8>>(mint.into()));
     }
+    if options.exclude_native {
+        filter = filter.and(token_accounts::Column::Mint.ne(NATIVE_MINT.to_bytes().to_vec()));
+    }
     if let Some(cursor) = options.cursor {
         let bytes = cursor.0;
         let expected_cursor_length = 64;
@@ -290,8 +319,6 @@ pub async fn fetch_token_accounts(
         .order_by(token_accounts::Column::Mint, sea_orm::Order::Asc)
         .order_by(token_accounts::Column::Hash, sea_orm::Order::Asc)
         .limit(limit)
-        .order_by(token_accounts::Column::Mint, sea_orm::Order::Asc)
-        .order_by(token_accounts::Column::Hash, sea_orm::Order::Asc)
         .all(conn)
         .await?
         .drain(..)
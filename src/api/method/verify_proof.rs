@@ -0,0 +1,93 @@
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::typedefs::hash::Hash;
+use crate::common::typedefs::serializable_pubkey::SerializablePubkey;
+use crate::common::typedefs::unsigned_integer::UnsignedInteger;
+use crate::dao::generated::state_trees;
+use crate::ingester::persist::persisted_state_tree::{compute_root, MAX_HEIGHT};
+
+use super::super::error::PhotonApiError;
+use super::utils::Context;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct VerifyProofRequest {
+    /// Hash of the leaf that the proof is for.
+    pub leaf: Hash,
+    /// Siblings along the path of the leaf, ordered from the leaf level up. The root is not
+    /// included.
+    pub siblings: Vec<Hash>,
+    pub leaf_index: UnsignedInteger,
+    pub tree: SerializablePubkey,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct ProofVerification {
+    /// Whether the proof resolves to the current root of the tree.
+    pub valid: bool,
+    pub root: Hash,
+    pub root_seq: UnsignedInteger,
+}
+
+// We do not use generics to simplify documentation generation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct VerifyProofResponse {
+    pub context: Context,
+    pub value: ProofVerification,
+}
+
+/// Checks whether a proof, e.g. one cached by a client, still resolves to the current root of its
+/// tree. Returns the current root and its sequence number along with the result.
+pub async fn verify_proof(
+    conn: &DatabaseConnection,
+    request: VerifyProofRequest,
+) -> Result<VerifyProofResponse, PhotonApiError> {
+    let context = Context::extract(conn).await?;
+    let VerifyProofRequest {
+        leaf,
+        siblings,
+        leaf_index,
+        tree,
+    } = request;
+    if siblings.is_empty() || siblings.len() >= MAX_HEIGHT {
+        return Err(PhotonApiError::ValidationError(format!(
+            "Invalid number of siblings. Expected between 1 and {}. Received {}.",
+            MAX_HEIGHT - 1,
+            siblings.len()
+        )));
+    }
+    if leaf_index.0 >= 1 << siblings.len() {
+        return Err(PhotonApiError::ValidationError(format!(
+            "Leaf index {} is out of bounds for a tree with {} siblings",
+            leaf_index.0,
+            siblings.len()
+        )));
+    }
+
+    let root = state_trees::Entity::find()
+        .filter(
+            state_trees::Column::Tree
+                .eq(tree.to_bytes_vec())
+                .and(state_trees::Column::NodeIdx.eq(1)),
+        )
+        .one(conn)
+        .await?
+        .ok_or(PhotonApiError::RecordNotFound(format!(
+            "Tree {} not found",
+            tree
+        )))?;
+    let computed_root = compute_root(&leaf, &siblings, leaf_index.0 as u32)?;
+
+    Ok(VerifyProofResponse {
+        context,
+        value: ProofVerification {
+            valid: computed_root == root.hash,
+            root: Hash::try_from(root.hash)?,
+            root_seq: UnsignedInteger(root.seq as u64),
+        },
+    })
+}
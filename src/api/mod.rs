@@ -1,4 +1,5 @@
 pub mod api;
+pub mod batch_limit;
 pub mod error;
 pub mod method;
 pub mod rpc_server;
@@ -1,35 +1,92 @@
+use std::future::Future;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
 
-use hyper::Method;
+use cadence_macros::{statsd_count, statsd_histogram};
+use hyper::{header::HeaderValue, Method};
 use jsonrpsee::{
+    core::Error,
     server::{middleware::proxy_get_request::ProxyGetRequestLayer, ServerBuilder, ServerHandle},
+    types::Params,
     RpcModule,
 };
 use log::debug;
-use tower_http::cors::{Any, CorsLayer};
+use serde::Serialize;
+use tokio_rustls::rustls::ServerConfig;
+use tower_http::cors::{AllowOrigin, CorsLayer};
 
 use super::api::PhotonApi;
-
-pub async fn run_server(api: PhotonApi, port: u16) -> Result<ServerHandle, anyhow::Error> {
+use super::batch_limit::BatchSizeLimitLayer;
+use super::method::subscribe_compressed_account::subscribe_compressed_account;
+use crate::common::tls::serve_tls;
+use crate::metric;
+
+pub async fn run_server(
+    api: PhotonApi,
+    port: u16,
+    enable_admin_api: bool,
+    max_batch_size: usize,
+    cors_allowed_origins: &[String],
+    tls_config: Option<Arc<ServerConfig>>,
+) -> Result<ServerHandle, anyhow::Error> {
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    // jsonrpsee cannot terminate TLS, so with TLS the server only listens on localhost and a TLS
+    // endpoint on the public port forwards the decrypted connections to it.
+    let server_addr = match tls_config {
+        Some(_) => SocketAddr::from(([127, 0, 0, 1], 0)),
+        None => addr,
+    };
     let cors = CorsLayer::new()
         .allow_methods([Method::POST, Method::GET])
-        .allow_origin(Any)
+        .allow_origin(parse_allowed_origins(cors_allowed_origins)?)
         .allow_headers([hyper::header::CONTENT_TYPE]);
     let middleware = tower::ServiceBuilder::new()
         .layer(cors)
         .layer(ProxyGetRequestLayer::new("/liveness", "liveness")?)
         .layer(ProxyGetRequestLayer::new("/readiness", "readiness")?)
-        .layer(ProxyGetRequestLayer::new("/health", "health")?);
+        .layer(ProxyGetRequestLayer::new("/health", "health")?)
+        .layer(BatchSizeLimitLayer::new(max_batch_size));
     let server = ServerBuilder::default()
         .set_middleware(middleware)
-        .build(addr)
+        .build(server_addr)
         .await?;
-    let rpc_module = build_rpc_module(api)?;
-    server.start(rpc_module).map_err(|e| anyhow::anyhow!(e))
+    let tls_handle = match tls_config {
+        Some(tls_config) => Some(serve_tls(addr, server.local_addr()?, tls_config).await?),
+        None => None,
+    };
+    let rpc_module = build_rpc_module(api, enable_admin_api)?;
+    let handle = server.start(rpc_module).map_err(|e| anyhow::anyhow!(e))?;
+    if let Some(tls_handle) = tls_handle {
+        let server_handle = handle.clone();
+        tokio::spawn(async move {
+            server_handle.stopped().await;
+            tls_handle.abort();
+        });
+    }
+    Ok(handle)
+}
+
+// `*` allows any origin. Browsers refuse credentialed requests to servers that allow any origin,
+// which is fine since the API does not use credentials.
+fn parse_allowed_origins(origins: &[String]) -> Result<AllowOrigin, anyhow::Error> {
+    if origins.iter().any(|origin| origin == "*") {
+        return Ok(AllowOrigin::any());
+    }
+    let origins = origins
+        .iter()
+        .map(|origin| {
+            HeaderValue::from_str(origin)
+                .map_err(|_| anyhow::anyhow!("Invalid CORS allowed origin: {}", origin))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(AllowOrigin::list(origins))
 }
 
-fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>, anyhow::Error> {
+fn build_rpc_module(
+    api_and_indexer: PhotonApi,
+    enable_admin_api: bool,
+) -> Result<RpcModule<PhotonApi>, anyhow::Error> {
     let mut module = RpcModule::new(api_and_indexer);
 
     module.register_async_method("liveness", |_rpc_params, rpc_context| async move {
@@ -50,8 +107,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         api.health().await.map_err(Into::into)
     })?;
 
-
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getCompressedAccount",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -62,7 +119,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getCompressedAccountProof",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -73,7 +131,20 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
+        "getCompressedAccountProofPath",
+        |rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            let payload = rpc_params.parse()?;
+            api.get_compressed_account_proof_path(payload)
+                .await
+                .map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
         "getMultipleCompressedAccountProofs",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -84,7 +155,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getCompressedTokenAccountsByOwner",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -95,7 +167,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getCompressedTokenAccountsByDelegate",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -106,7 +179,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getCompressedBalanceByOwner",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -117,7 +191,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getCompressedTokenBalancesByOwner",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -128,7 +203,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getCompressedTokenAccountBalance",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -139,7 +215,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getCompressedBalance",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -150,7 +227,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getCompressedAccountBalance",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -161,20 +239,230 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method("getIndexerHealth", |_rpc_params, rpc_context| async move {
-        rpc_context
-            .as_ref()
-            .get_indexer_health()
-            .await
-            .map_err(Into::into)
-    })?;
+    register_api_method(
+        &mut module,
+        "getIndexerHealth",
+        |_rpc_params, rpc_context| async move {
+            rpc_context
+                .as_ref()
+                .get_indexer_health()
+                .await
+                .map_err(Into::into)
+        },
+    )?;
 
-    module.register_async_method("getIndexerSlot", |_rpc_params, rpc_context| async move {
-        let api = rpc_context.as_ref();
-        api.get_indexer_slot().await.map_err(Into::into)
-    })?;
+    register_api_method(
+        &mut module,
+        "getIndexerHealthStatus",
+        |_rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            api.get_indexer_health_status().await.map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
+        "getIndexerSlot",
+        |_rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            api.get_indexer_slot().await.map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
+        "getIndexerStats",
+        |_rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            api.get_indexer_stats().await.map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
+        "getIngestionStatus",
+        |_rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            api.get_ingestion_status().await.map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
+        "decodeCompressedAccount",
+        |rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            let payload = rpc_params.parse()?;
+            api.decode_compressed_account(payload)
+                .await
+                .map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
+        "getRecentlySpentAccounts",
+        |rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            let payload = rpc_params.parse()?;
+            api.get_recently_spent_accounts(payload)
+                .await
+                .map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
+        "getNewAddressProof",
+        |rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            let payload = rpc_params.parse()?;
+            api.get_new_address_proof(payload).await.map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
+        "getLatestCompressedAccounts",
+        |rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            let payload = rpc_params.parse()?;
+            api.get_latest_compressed_accounts(payload)
+                .await
+                .map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
+        "getCompressedAccountsByOwnerGroupedByTree",
+        |rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            let payload = rpc_params.parse()?;
+            api.get_compressed_accounts_by_owner_grouped_by_tree(payload)
+                .await
+                .map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
+        "getCompressedAccountsByLamportRange",
+        |rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            let payload = rpc_params.parse()?;
+            api.get_compressed_accounts_by_lamport_range(payload)
+                .await
+                .map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
+        "verifyProof",
+        |rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            let payload = rpc_params.parse()?;
+            api.verify_proof(payload)
+                .await
+                .map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
+        "getTransactionAccountMapping",
+        |rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            let payload = rpc_params.parse()?;
+            api.get_transaction_account_mapping(payload)
+                .await
+                .map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
+        "getTreeSize",
+        |rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            let payload = rpc_params.parse()?;
+            api.get_tree_size(payload).await.map_err(Into::into)
+        },
+    )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
+        "getInstructionValidityProof",
+        |rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            let payload = rpc_params.parse()?;
+            api.get_instruction_validity_proof(payload)
+                .await
+                .map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
+        "getCompressedAccountAndProofByAddress",
+        |rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            let payload = rpc_params.parse()?;
+            api.get_compressed_account_and_proof_by_address(payload)
+                .await
+                .map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
+        "getTreeNodes",
+        |rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            let payload = rpc_params.parse()?;
+            api.get_tree_nodes(payload).await.map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
+        "getRecentParseErrors",
+        |rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            let payload = rpc_params.parse()?;
+            api.get_recent_parse_errors(payload)
+                .await
+                .map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
+        "getUnspentAccountSet",
+        |rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            let payload = rpc_params.parse()?;
+            api.get_unspent_account_set(payload)
+                .await
+                .map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
+        "getCompressedAccountSummary",
+        |rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            let payload = rpc_params.parse()?;
+            api.get_compressed_account_summary(payload)
+                .await
+                .map_err(Into::into)
+        },
+    )?;
+
+    register_api_method(
+        &mut module,
         "getCompressedAccountsByOwner",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -185,7 +473,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getMultipleCompressedAccounts",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -196,7 +485,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getCompressionSignaturesForAccount",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -207,7 +497,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getCompressionSignaturesForAddress",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -218,7 +509,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getCompressionSignaturesForOwner",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -229,7 +521,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getCompressionSignaturesForTokenOwner",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -240,7 +533,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getTransactionWithCompressionInfo",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -250,13 +544,18 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
                 .map_err(Into::into)
         },
     )?;
-    module.register_async_method("getValidityProof", |rpc_params, rpc_context| async move {
-        let api = rpc_context.as_ref();
-        let payload = rpc_params.parse()?;
-        api.get_validity_proof(payload).await.map_err(Into::into)
-    })?;
+    register_api_method(
+        &mut module,
+        "getValidityProof",
+        |rpc_params, rpc_context| async move {
+            let api = rpc_context.as_ref();
+            let payload = rpc_params.parse()?;
+            api.get_validity_proof(payload).await.map_err(Into::into)
+        },
+    )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getLatestCompressionSignatures",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -267,7 +566,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getLatestNonVotingSignatures",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -278,7 +578,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getMultipleNewAddressProofs",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -289,7 +590,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getMultipleNewAddressProofsV2",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -299,7 +601,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
                 .map_err(Into::into)
         },
     )?;
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getCompressedMintTokenHolders",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -310,7 +613,8 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
-    module.register_async_method(
+    register_api_method(
+        &mut module,
         "getCompressedTokenBalancesByOwnerV2",
         |rpc_params, rpc_context| async move {
             let api = rpc_context.as_ref();
@@ -321,5 +625,82 @@ fn build_rpc_module(api_and_indexer: PhotonApi) -> Result<RpcModule<PhotonApi>,
         },
     )?;
 
+    // Subscriptions are served over WebSocket connections. The task that forwards the changes ends
+    // when the client unsubscribes or disconnects.
+    module.register_subscription(
+        "subscribeCompressedAccount",
+        "compressedAccountNotification",
+        "unsubscribeCompressedAccount",
+        |rpc_params, mut sink, _rpc_context| {
+            let payload = match rpc_params.parse() {
+                Ok(payload) => payload,
+                Err(e) => {
+                    sink.reject(e)?;
+                    return Ok(());
+                }
+            };
+            let changes = Box::pin(subscribe_compressed_account(payload));
+            sink.accept()?;
+            tokio::spawn(async move {
+                let closed = sink.pipe_from_stream(changes).await;
+                sink.close(closed);
+            });
+            Ok(())
+        },
+    )?;
+
+    // Admin methods mutate indexed state, so they are only exposed when explicitly enabled.
+    if enable_admin_api {
+        register_api_method(
+            &mut module,
+            "reindexSlot",
+            |rpc_params, rpc_context| async move {
+                let api = rpc_context.as_ref();
+                let payload = rpc_params.parse()?;
+                api.reindex_slot(payload).await.map_err(Into::into)
+            },
+        )?;
+    }
+
     Ok(module)
 }
+
+// Registers an API method whose calls are logged when they take longer than the slow query
+// threshold of the API.
+fn register_api_method<R, Fun, Fut>(
+    module: &mut RpcModule<PhotonApi>,
+    method_name: &'static str,
+    callback: Fun,
+) -> Result<(), Error>
+where
+    R: Serialize + Send + Sync + 'static,
+    Fut: Future<Output = Result<R, Error>> + Send,
+    Fun: (Fn(Params<'static>, Arc<PhotonApi>) -> Fut) + Clone + Send + Sync + 'static,
+{
+    module.register_async_method(method_name, move |rpc_params, rpc_context| {
+        let callback = callback.clone();
+        async move {
+            let api = rpc_context.clone();
+            let start = Instant::now();
+            let result = api
+                .log_if_slow(method_name, callback(rpc_params, rpc_context))
+                .await;
+            // Metrics are tagged with the static method name so that nothing is allocated per
+            // request. Errors are also counted by kind when they are converted from PhotonApiError.
+            metric! {
+                statsd_histogram!(
+                    "api_method_latency_ms",
+                    start.elapsed().as_millis() as u64,
+                    "method" => method_name
+                );
+            }
+            if result.is_err() {
+                metric! {
+                    statsd_count!("api_method_errors", 1, "method" => method_name);
+                }
+            }
+            result
+        }
+    })?;
+    Ok(())
+}
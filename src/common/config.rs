@@ -0,0 +1,129 @@
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use clap::{error::ErrorKind, parser::ValueSource, Arg, ArgAction, ArgMatches, Command, Parser};
+
+// Id of the argument that points to the config file.
+const CONFIG_ARG_ID: &str = "config";
+
+/// Parses the arguments of the process like `T::parse`, but also reads the TOML file passed with
+/// `--config`, if any. Exits with a usage error when the arguments or the file are invalid.
+pub fn parse_args_with_config_file<T: Parser>() -> T {
+    parse_args_with_config_file_from(std::env::args_os()).unwrap_or_else(|e| e.exit())
+}
+
+/// Parses `args` into `T`, taking the values of arguments not passed on the command line from the
+/// TOML file passed with `--config`. The keys of the file are the names of the fields of `T`, so
+/// command line flags take precedence over the file and the file over the defaults.
+pub fn parse_args_with_config_file_from<T: Parser>(
+    args: impl IntoIterator<Item = impl Into<OsString>>,
+) -> Result<T, clap::Error> {
+    let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+    let matches = T::command().try_get_matches_from(&args)?;
+    let config_path = match matches.try_get_one::<PathBuf>(CONFIG_ARG_ID) {
+        Ok(Some(config_path)) => config_path,
+        _ => return T::from_arg_matches(&matches),
+    };
+
+    let mut command = T::command();
+    let config_args = read_config_file(&command, &matches, config_path)
+        .map_err(|e| command.error(ErrorKind::InvalidValue, e))?;
+
+    // The file arguments are inserted right after the program name, so that they precede the
+    // subcommand, if any.
+    let mut merged_args = args[..1].to_vec();
+    merged_args.extend(config_args.into_iter().map(OsString::from));
+    merged_args.extend_from_slice(&args[1..]);
+    T::try_parse_from(merged_args)
+}
+
+fn read_config_file(
+    command: &Command,
+    matches: &ArgMatches,
+    config_path: &Path,
+) -> Result<Vec<String>, String> {
+    let contents = std::fs::read_to_string(config_path).map_err(|e| {
+        format!(
+            "Failed to read config file {}: {}",
+            config_path.display(),
+            e
+        )
+    })?;
+    let table: toml::value::Table = toml::from_str(&contents).map_err(|e| {
+        format!(
+            "Failed to parse config file {}: {}",
+            config_path.display(),
+            e
+        )
+    })?;
+    config_args(command, matches, &table)
+}
+
+/// Converts the keys of a config file into command line arguments, skipping the arguments that
+/// were passed on the command line.
+fn config_args(
+    command: &Command,
+    matches: &ArgMatches,
+    table: &toml::value::Table,
+) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    for (key, value) in table {
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == key.as_str())
+            .filter(|arg| is_config_key(arg))
+            .ok_or_else(|| {
+                format!(
+                    "Unknown key `{}` in config file. Valid keys are: {}",
+                    key,
+                    valid_keys(command).join(", ")
+                )
+            })?;
+        if matches.value_source(key) == Some(ValueSource::CommandLine) {
+            continue;
+        }
+        let flag = format!("--{}", arg.get_long().unwrap());
+
+        let values = match value {
+            toml::Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                toml::Value::Boolean(value) if matches!(arg.get_action(), ArgAction::SetTrue) => {
+                    if *value {
+                        args.push(flag.clone());
+                    }
+                    continue;
+                }
+                toml::Value::String(value) => value.clone(),
+                toml::Value::Integer(value) => value.to_string(),
+                toml::Value::Float(value) => value.to_string(),
+                toml::Value::Boolean(value) => value.to_string(),
+                _ => {
+                    return Err(format!(
+                        "Invalid value for key `{}` in config file: {}",
+                        key, value
+                    ));
+                }
+            };
+            args.push(format!("{}={}", flag, value));
+        }
+    }
+    Ok(args)
+}
+
+fn valid_keys(command: &Command) -> Vec<&str> {
+    command
+        .get_arguments()
+        .filter(|arg| is_config_key(arg))
+        .map(|arg| arg.get_id().as_str())
+        .collect()
+}
+
+// Arguments without a long flag and the config file argument itself cannot be set in the file.
+fn is_config_key(arg: &Arg) -> bool {
+    arg.get_long().is_some() && arg.get_id() != CONFIG_ARG_ID
+}
@@ -0,0 +1,114 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use async_trait::async_trait;
+use cadence_macros::statsd_count;
+use log::warn;
+use solana_client::{
+    client_error::{ClientErrorKind, Result as ClientResult},
+    rpc_request::RpcRequest,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+
+use crate::metric;
+
+struct Endpoint {
+    sender: Box<dyn RpcSender + Send + Sync>,
+    consecutive_failures: AtomicU64,
+}
+
+/// Sends RPC requests to one of several endpoints, moving on to the next endpoint when a request
+/// fails because of a connection error or rate limit. Requests keep going to the last endpoint that
+/// answered, so that a failed endpoint is only retried once the others fail too.
+pub struct FailoverRpcSender {
+    endpoints: Vec<Endpoint>,
+    current_endpoint: AtomicUsize,
+}
+
+impl FailoverRpcSender {
+    pub fn new(senders: Vec<Box<dyn RpcSender + Send + Sync>>) -> Self {
+        assert!(!senders.is_empty(), "At least one RPC endpoint is required");
+        Self {
+            endpoints: senders
+                .into_iter()
+                .map(|sender| Endpoint {
+                    sender,
+                    consecutive_failures: AtomicU64::new(0),
+                })
+                .collect(),
+            current_endpoint: AtomicUsize::new(0),
+        }
+    }
+
+    /// Number of consecutive failed requests of each endpoint, 0 for healthy endpoints.
+    pub fn consecutive_failures(&self) -> Vec<u64> {
+        self.endpoints
+            .iter()
+            .map(|endpoint| endpoint.consecutive_failures.load(Ordering::Relaxed))
+            .collect()
+    }
+}
+
+// Errors after which the request is sent to the next endpoint. Other errors, such as a skipped
+// slot, are answers of the endpoint and are returned as is.
+fn is_endpoint_error(kind: &ClientErrorKind) -> bool {
+    matches!(kind, ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_))
+}
+
+#[async_trait]
+impl RpcSender for FailoverRpcSender {
+    async fn send(
+        &self,
+        request: RpcRequest,
+        params: serde_json::Value,
+    ) -> ClientResult<serde_json::Value> {
+        let first_endpoint = self.current_endpoint.load(Ordering::Relaxed);
+        let mut last_error = None;
+        for attempt in 0..self.endpoints.len() {
+            let index = (first_endpoint + attempt) % self.endpoints.len();
+            let endpoint = &self.endpoints[index];
+            match endpoint.sender.send(request, params.clone()).await {
+                Ok(response) => {
+                    endpoint.consecutive_failures.store(0, Ordering::Relaxed);
+                    self.current_endpoint.store(index, Ordering::Relaxed);
+                    return Ok(response);
+                }
+                Err(e) if is_endpoint_error(&e.kind) => {
+                    endpoint
+                        .consecutive_failures
+                        .fetch_add(1, Ordering::Relaxed);
+                    metric! {
+                        statsd_count!("rpc_endpoint_failure", 1);
+                    }
+                    // Endpoints are identified by their position, since RPC URLs often contain
+                    // API keys.
+                    warn!(
+                        "RPC endpoint {} of {} failed, trying the next endpoint: {}",
+                        index + 1,
+                        self.endpoints.len(),
+                        e
+                    );
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error.unwrap())
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        let mut stats = RpcTransportStats::default();
+        for endpoint in &self.endpoints {
+            let endpoint_stats = endpoint.sender.get_transport_stats();
+            stats.request_count += endpoint_stats.request_count;
+            stats.elapsed_time += endpoint_stats.elapsed_time;
+            stats.rate_limited_time += endpoint_stats.rate_limited_time;
+        }
+        stats
+    }
+
+    fn url(&self) -> String {
+        self.endpoints[self.current_endpoint.load(Ordering::Relaxed)]
+            .sender
+            .url()
+    }
+}
@@ -1,17 +1,30 @@
 use core::fmt;
-use std::{env, net::UdpSocket, path::PathBuf, sync::Arc, thread::sleep, time::Duration};
+use std::{
+    env, future::Future, net::UdpSocket, path::PathBuf, sync::Arc, thread::sleep, time::Duration,
+};
 
 use cadence::{BufferedUdpMetricSink, QueuingMetricSink, StatsdClient};
 use cadence_macros::set_global_default;
 use clap::{Parser, ValueEnum};
+use failover::FailoverRpcSender;
+use prometheus::RecordingMetricSink;
+use rand::Rng;
 use sea_orm::{DatabaseConnection, SqlxPostgresConnector};
-use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcBlockConfig};
+use solana_client::{
+    http_sender::HttpSender, nonblocking::rpc_client::RpcClient, rpc_client::RpcClientConfig,
+    rpc_config::RpcBlockConfig, rpc_sender::RpcSender,
+};
 use solana_sdk::commitment_config::CommitmentConfig;
 use solana_transaction_status::{TransactionDetails, UiTransactionEncoding};
 use sqlx::{
     postgres::{PgConnectOptions, PgPoolOptions},
-    PgPool,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous},
+    PgPool, SqlitePool,
 };
+pub mod config;
+pub mod failover;
+pub mod prometheus;
+pub mod tls;
 pub mod typedefs;
 
 pub fn relative_project_path(path: &str) -> PathBuf {
@@ -30,9 +43,10 @@ macro_rules! metric {
     };
 }
 
-pub fn setup_metrics(metrics_endpoint: Option<String>) {
-    if let Some(metrics_endpoint) = metrics_endpoint {
-        let env = env::var("ENV").unwrap_or("dev".to_string());
+/// Sends metrics to the statsd server at `metrics_endpoint`, if any. If `record_metrics` is set,
+/// metrics are also recorded in memory so that they can be exported in the Prometheus format.
+pub fn setup_metrics(metrics_endpoint: Option<String>, record_metrics: bool) {
+    let queuing_sink = metrics_endpoint.map(|metrics_endpoint| {
         let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
         socket.set_nonblocking(true).unwrap();
         let (host, port) = {
@@ -41,20 +55,84 @@ pub fn setup_metrics(metrics_endpoint: Option<String>) {
         };
         let port = port.parse::<u16>().unwrap();
         let udp_sink = BufferedUdpMetricSink::from((host, port), socket).unwrap();
-        let queuing_sink = QueuingMetricSink::from(udp_sink);
-        let builder = StatsdClient::builder("photon", queuing_sink);
-        let client = builder.with_tag("env", env).build();
-        set_global_default(client);
+        QueuingMetricSink::from(udp_sink)
+    });
+    let builder = match (queuing_sink, record_metrics) {
+        (queuing_sink, true) => {
+            StatsdClient::builder("photon", RecordingMetricSink::new(queuing_sink))
+        }
+        (Some(queuing_sink), false) => StatsdClient::builder("photon", queuing_sink),
+        (None, false) => return,
+    };
+    let env = env::var("ENV").unwrap_or("dev".to_string());
+    let client = builder.with_tag("env", env).build();
+    set_global_default(client);
+}
+
+pub const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 100;
+pub const DEFAULT_RETRY_MAX_DELAY_MS: u64 = 10_000;
+
+/// Configures the delays between the retries of a failing RPC request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryBackoffConfig {
+    /// Delay in milliseconds before the first retry.
+    pub base_delay_ms: u64,
+    /// Maximum delay in milliseconds that the delay doubles up to.
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryBackoffConfig {
+    fn default() -> Self {
+        Self {
+            base_delay_ms: DEFAULT_RETRY_BASE_DELAY_MS,
+            max_delay_ms: DEFAULT_RETRY_MAX_DELAY_MS,
+        }
     }
 }
 
-pub async fn get_genesis_hash_with_infinite_retry(rpc_client: &RpcClient) -> String {
+/// Delays between the retries of a failing RPC request. The delay doubles after each failure up
+/// to the max delay, and is randomized so that indexers failing at the same time do not retry in
+/// lockstep. Each request starts again from the base delay.
+#[derive(Debug)]
+pub struct RetryBackoff {
+    config: RetryBackoffConfig,
+    attempts: u32,
+}
+
+impl RetryBackoff {
+    pub fn new(config: RetryBackoffConfig) -> Self {
+        Self {
+            config,
+            attempts: 0,
+        }
+    }
+
+    /// Returns a delay between half and all of the current backoff delay.
+    pub fn next_delay(&mut self) -> Duration {
+        let RetryBackoffConfig {
+            base_delay_ms,
+            max_delay_ms,
+        } = self.config;
+        let delay_ms = base_delay_ms
+            .saturating_mul(1u64 << self.attempts.min(63))
+            .min(max_delay_ms.max(base_delay_ms));
+        self.attempts = self.attempts.saturating_add(1);
+        let jitter_ms = rand::thread_rng().gen_range(0..=delay_ms / 2);
+        Duration::from_millis(delay_ms - delay_ms / 2 + jitter_ms)
+    }
+}
+
+pub async fn get_genesis_hash_with_infinite_retry(
+    rpc_client: &RpcClient,
+    retry_backoff: RetryBackoffConfig,
+) -> String {
+    let mut backoff = RetryBackoff::new(retry_backoff);
     loop {
         match rpc_client.get_genesis_hash().await {
             Ok(genesis_hash) => return genesis_hash.to_string(),
             Err(e) => {
                 log::error!("Failed to fetch genesis hash: {}", e);
-                sleep(Duration::from_secs(5));
+                tokio::time::sleep(backoff.next_delay()).await;
             }
         }
     }
@@ -77,14 +155,117 @@ pub async fn fetch_block_parent_slot(rpc_client: &RpcClient, slot: u64) -> u64 {
         .parent_slot
 }
 
-pub async fn get_network_start_slot(rpc_client: &RpcClient) -> u64 {
-    let genesis_hash = get_genesis_hash_with_infinite_retry(rpc_client).await;
+/// Determines where indexing starts on networks whose genesis hash is not known to Photon, such
+/// as localnets and testnet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum UnknownNetworkStartSlot {
+    /// Start from the first block available on the RPC node.
+    #[default]
+    FirstAvailableBlock,
+    /// Start from the current slot.
+    Latest,
+    /// Start from genesis.
+    Genesis,
+}
+
+impl fmt::Display for UnknownNetworkStartSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnknownNetworkStartSlot::FirstAvailableBlock => write!(f, "first-available-block"),
+            UnknownNetworkStartSlot::Latest => write!(f, "latest"),
+            UnknownNetworkStartSlot::Genesis => write!(f, "genesis"),
+        }
+    }
+}
+
+/// Commitment level of the slots and blocks read from the RPC node and the gRPC stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Commitment {
+    /// Blocks may still be orphaned by a reorg. RPC block fetches use the confirmed level, since
+    /// `getBlock` does not support processed blocks.
+    Processed,
+    #[default]
+    Confirmed,
+    /// Blocks cannot be orphaned by a reorg.
+    Finalized,
+}
+
+impl Commitment {
+    /// Commitment of `getBlock` requests, which reject the processed level.
+    pub fn block_commitment_config(&self) -> CommitmentConfig {
+        match self {
+            Commitment::Processed | Commitment::Confirmed => CommitmentConfig::confirmed(),
+            Commitment::Finalized => CommitmentConfig::finalized(),
+        }
+    }
+}
+
+impl From<Commitment> for CommitmentConfig {
+    fn from(commitment: Commitment) -> Self {
+        match commitment {
+            Commitment::Processed => CommitmentConfig::processed(),
+            Commitment::Confirmed => CommitmentConfig::confirmed(),
+            Commitment::Finalized => CommitmentConfig::finalized(),
+        }
+    }
+}
+
+impl fmt::Display for Commitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Commitment::Processed => write!(f, "processed"),
+            Commitment::Confirmed => write!(f, "confirmed"),
+            Commitment::Finalized => write!(f, "finalized"),
+        }
+    }
+}
+
+pub async fn fetch_first_available_block_with_infinite_retry(client: &RpcClient) -> u64 {
+    loop {
+        match client.get_first_available_block().await {
+            Ok(slot) => {
+                return slot;
+            }
+            Err(e) => {
+                log::error!("Failed to fetch first available block: {}", e);
+                sleep(Duration::from_secs(5));
+            }
+        }
+    }
+}
+
+/// Returns the slot after which indexing starts when nothing has been indexed yet.
+pub async fn get_network_start_slot(
+    rpc_client: &RpcClient,
+    unknown_network_start_slot: UnknownNetworkStartSlot,
+    retry_backoff: RetryBackoffConfig,
+) -> u64 {
+    let genesis_hash = get_genesis_hash_with_infinite_retry(rpc_client, retry_backoff).await;
     match genesis_hash.as_str() {
         // Devnet
         "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG" => 319998226 - 1,
         // Mainnet
         "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d" => 286193746 - 1,
-        _ => 0,
+        _ => {
+            let start_slot = match unknown_network_start_slot {
+                UnknownNetworkStartSlot::FirstAvailableBlock => {
+                    fetch_first_available_block_with_infinite_retry(rpc_client)
+                        .await
+                        .saturating_sub(1)
+                }
+                UnknownNetworkStartSlot::Latest => {
+                    fetch_current_slot_with_infinite_retry(rpc_client).await
+                }
+                UnknownNetworkStartSlot::Genesis => 0,
+            };
+            log::info!(
+                "Unknown genesis hash {}. Starting after slot {} ({})",
+                genesis_hash,
+                start_slot,
+                unknown_network_start_slot
+            );
+            start_slot
+        }
     }
 }
 
@@ -113,19 +294,90 @@ pub fn setup_logging(logging_format: LoggingFormat) {
     }
 }
 
-pub async fn setup_pg_pool(database_url: &str, max_connections: u32) -> PgPool {
-    let options: PgConnectOptions = database_url.parse().unwrap();
-    PgPoolOptions::new()
-        .max_connections(max_connections)
-        .connect_with(options)
-        .await
-        .unwrap()
+pub const DEFAULT_DB_CONNECT_RETRIES: u32 = 10;
+const DB_CONNECT_RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+const DB_CONNECT_RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+// Retries connecting to the database up to `retries` times, so that Photon can start before its
+// database is ready.
+async fn connect_with_retries<T, F, Fut>(connect: F, retries: u32) -> Result<T, sqlx::Error>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    let mut delay = DB_CONNECT_RETRY_BASE_DELAY;
+    let mut attempt = 0;
+    loop {
+        match connect().await {
+            Ok(pool) => return Ok(pool),
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                log::warn!(
+                    "Failed to connect to the database, retrying in {:?} ({}/{}): {}",
+                    delay,
+                    attempt,
+                    retries,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(DB_CONNECT_RETRY_MAX_DELAY);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+pub async fn setup_pg_pool(
+    database_url: &str,
+    max_connections: u32,
+    connect_retries: u32,
+) -> Result<PgPool, sqlx::Error> {
+    let options: PgConnectOptions = database_url.parse()?;
+    connect_with_retries(
+        || {
+            PgPoolOptions::new()
+                .max_connections(max_connections)
+                .connect_with(options.clone())
+        },
+        connect_retries,
+    )
+    .await
 }
 
-pub async fn setup_pg_connection(database_url: &str, max_connections: u32) -> DatabaseConnection {
-    SqlxPostgresConnector::from_sqlx_postgres_pool(
-        setup_pg_pool(database_url, max_connections).await,
+// SQLite only allows a single writer at a time, so writers wait for the lock instead of failing
+// with `database is locked` when the indexer and the API write concurrently.
+const SQLITE_BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub async fn setup_sqlite_pool(
+    database_url: &str,
+    max_connections: u32,
+    connect_retries: u32,
+) -> Result<SqlitePool, sqlx::Error> {
+    // WAL lets readers, such as the API, proceed while the indexer is writing.
+    let options: SqliteConnectOptions = database_url
+        .parse::<SqliteConnectOptions>()?
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal)
+        .busy_timeout(SQLITE_BUSY_TIMEOUT);
+    connect_with_retries(
+        || {
+            SqlitePoolOptions::new()
+                .max_connections(max_connections)
+                .min_connections(1)
+                .connect_with(options.clone())
+        },
+        connect_retries,
     )
+    .await
+}
+
+pub async fn setup_pg_connection(
+    database_url: &str,
+    max_connections: u32,
+) -> Result<DatabaseConnection, sqlx::Error> {
+    Ok(SqlxPostgresConnector::from_sqlx_postgres_pool(
+        setup_pg_pool(database_url, max_connections, DEFAULT_DB_CONNECT_RETRIES).await?,
+    ))
 }
 
 pub async fn fetch_current_slot_with_infinite_retry(client: &RpcClient) -> u64 {
@@ -143,9 +395,30 @@ pub async fn fetch_current_slot_with_infinite_retry(client: &RpcClient) -> u64 {
 }
 
 pub fn get_rpc_client(rpc_url: &str) -> Arc<RpcClient> {
-    Arc::new(RpcClient::new_with_timeout_and_commitment(
-        rpc_url.to_string(),
-        Duration::from_secs(90),
-        CommitmentConfig::confirmed(),
+    get_rpc_client_with_commitment(rpc_url, Commitment::default())
+}
+
+/// Creates an RPC client for `rpc_url`, which may be a comma separated list of URLs. With several
+/// URLs, requests fail over to the next URL on connection errors and rate limits.
+pub fn get_rpc_client_with_commitment(rpc_url: &str, commitment: Commitment) -> Arc<RpcClient> {
+    let timeout = Duration::from_secs(90);
+    let rpc_urls: Vec<&str> = rpc_url.split(',').map(str::trim).collect();
+    if rpc_urls.len() == 1 {
+        return Arc::new(RpcClient::new_with_timeout_and_commitment(
+            rpc_url.to_string(),
+            timeout,
+            commitment.into(),
+        ));
+    }
+    let senders = rpc_urls
+        .into_iter()
+        .map(|rpc_url| {
+            Box::new(HttpSender::new_with_timeout(rpc_url, timeout))
+                as Box<dyn RpcSender + Send + Sync>
+        })
+        .collect();
+    Arc::new(RpcClient::new_sender(
+        FailoverRpcSender::new(senders),
+        RpcClientConfig::with_commitment(commitment.into()),
     ))
 }
@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+use cadence::MetricSink;
+use once_cell::sync::Lazy;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MetricKind {
+    Counter,
+    Gauge,
+}
+
+impl MetricKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+        }
+    }
+}
+
+// Recorded metrics keyed by Prometheus metric name and rendered labels.
+static RECORDED_METRICS: Lazy<Mutex<BTreeMap<(String, String), (MetricKind, f64)>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// Records the counters and gauges emitted through the statsd client, so that their values can be
+/// written out in the Prometheus text format, before forwarding them to `inner`, if any.
+pub struct RecordingMetricSink<S> {
+    inner: Option<S>,
+}
+
+impl<S: MetricSink> RecordingMetricSink<S> {
+    pub fn new(inner: Option<S>) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S: MetricSink> MetricSink for RecordingMetricSink<S> {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        record_metric(metric);
+        match &self.inner {
+            Some(inner) => inner.emit(metric),
+            None => Ok(metric.len()),
+        }
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        match &self.inner {
+            Some(inner) => inner.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+// Metrics are formatted as `name:value|type`, optionally followed by `|@rate` and `|#tags`.
+// Only counters and gauges are recorded. Histograms are only forwarded to the inner sink.
+fn record_metric(metric: &str) {
+    let mut parts = metric.split('|');
+    let (name, value) = match parts.next().and_then(|part| part.rsplit_once(':')) {
+        Some((name, value)) => (name, value),
+        None => return,
+    };
+    let value = match value.parse::<f64>() {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+    let kind = match parts.next() {
+        Some("c") => MetricKind::Counter,
+        Some("g") => MetricKind::Gauge,
+        _ => return,
+    };
+    let labels = parts
+        .find_map(|part| part.strip_prefix('#'))
+        .map(|tags| {
+            tags.split(',')
+                .map(|tag| match tag.split_once(':') {
+                    Some((key, value)) => format!("{}=\"{}\"", sanitize_name(key), value),
+                    None => format!("{}=\"\"", sanitize_name(tag)),
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .unwrap_or_default();
+
+    let mut recorded_metrics = RECORDED_METRICS.lock().unwrap();
+    let recorded_metric = recorded_metrics
+        .entry((sanitize_name(name), labels))
+        .or_insert((kind, 0.0));
+    match kind {
+        MetricKind::Counter => recorded_metric.1 += value,
+        MetricKind::Gauge => recorded_metric.1 = value,
+    }
+}
+
+fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c,
+            false => '_',
+        })
+        .collect()
+}
+
+/// Renders all recorded metrics in the Prometheus text format.
+pub fn render_prometheus_metrics() -> String {
+    let recorded_metrics = RECORDED_METRICS.lock().unwrap();
+    let mut output = String::new();
+    let mut previous_name = None;
+    for ((name, labels), (kind, value)) in recorded_metrics.iter() {
+        if previous_name != Some(name) {
+            output.push_str(&format!("# TYPE {} {}\n", name, kind.as_str()));
+            previous_name = Some(name);
+        }
+        match labels.is_empty() {
+            true => output.push_str(&format!("{} {}\n", name, value)),
+            false => output.push_str(&format!("{}{{{}}} {}\n", name, labels, value)),
+        }
+    }
+    output
+}
+
+pub fn write_prometheus_metrics(path: &Path) -> io::Result<()> {
+    std::fs::write(path, render_prometheus_metrics())
+}
@@ -0,0 +1,86 @@
+use std::{fs::File, io::BufReader, net::SocketAddr, path::Path, sync::Arc, time::Duration};
+
+use log::{debug, error};
+use rustls_pemfile::Item;
+use tokio::{
+    io::copy_bidirectional,
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+use tokio_rustls::{
+    rustls::{Certificate, PrivateKey, ServerConfig},
+    TlsAcceptor,
+};
+
+/// Loads the TLS configuration of a server from a PEM certificate chain and a PEM private key.
+pub fn load_tls_config(cert_path: &Path, key_path: &Path) -> Result<Arc<ServerConfig>, String> {
+    let certs: Vec<Certificate> = read_pem_file(cert_path)?
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::X509Certificate(cert) => Some(Certificate(cert)),
+            _ => None,
+        })
+        .collect();
+    if certs.is_empty() {
+        return Err(format!("No certificate found in {}", cert_path.display()));
+    }
+    let key = read_pem_file(key_path)?
+        .into_iter()
+        .find_map(|item| match item {
+            Item::RSAKey(key) | Item::PKCS8Key(key) | Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| format!("No private key found in {}", key_path.display()))?;
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("Invalid TLS certificate or private key: {}", e))?;
+    Ok(Arc::new(config))
+}
+
+fn read_pem_file(path: &Path) -> Result<Vec<Item>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    rustls_pemfile::read_all(&mut BufReader::new(file))
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+/// Accepts TLS connections on `addr` and forwards their decrypted traffic to the plain server
+/// listening on `backend_addr`. This adds TLS to servers that cannot terminate it themselves.
+pub async fn serve_tls(
+    addr: SocketAddr,
+    backend_addr: SocketAddr,
+    tls_config: Arc<ServerConfig>,
+) -> std::io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(addr).await?;
+    let acceptor = TlsAcceptor::from(tls_config);
+    Ok(tokio::spawn(async move {
+        loop {
+            let (stream, peer_addr) = match listener.accept().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    error!("Failed to accept TLS connection: {}", e);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                if let Err(e) = forward_tls_connection(acceptor, stream, backend_addr).await {
+                    debug!("TLS connection from {} failed: {}", peer_addr, e);
+                }
+            });
+        }
+    }))
+}
+
+async fn forward_tls_connection(
+    acceptor: TlsAcceptor,
+    stream: TcpStream,
+    backend_addr: SocketAddr,
+) -> std::io::Result<()> {
+    let mut tls_stream = acceptor.accept(stream).await?;
+    let mut backend_stream = TcpStream::connect(backend_addr).await?;
+    copy_bidirectional(&mut tls_stream, &mut backend_stream).await?;
+    Ok(())
+}
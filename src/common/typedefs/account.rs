@@ -1,4 +1,4 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use utoipa::ToSchema;
 
@@ -7,7 +7,7 @@ use super::{
     unsigned_integer::UnsignedInteger,
 };
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
 #[serde(deny_unknown_fields, rename_all = "camelCase")]
 pub struct Account {
     pub hash: Hash,
@@ -19,9 +19,13 @@ pub struct Account {
     pub leaf_index: UnsignedInteger,
     pub seq: UnsignedInteger,
     pub slot_created: UnsignedInteger,
+    /// Whether the data of the account exceeded the maximum account data size of the indexer, in
+    /// which case it was cut or omitted.
+    #[serde(default)]
+    pub data_truncated: bool,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
 #[serde(deny_unknown_fields, rename_all = "camelCase")]
 pub struct AccountData {
     pub discriminator: UnsignedInteger,
@@ -21,6 +21,9 @@ pub struct Model {
     pub lamports: Decimal,
     #[sea_orm(column_type = "Decimal(Some((20, 0)))", nullable)]
     pub discriminator: Option<Decimal>,
+    pub slot_spent: Option<i64>,
+    pub data_truncated: bool,
+    pub finalized: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -2,11 +2,13 @@
 
 pub mod prelude;
 
+pub mod account_mappings;
 pub mod account_transactions;
 pub mod accounts;
 pub mod blocks;
 pub mod indexed_trees;
 pub mod owner_balances;
+pub mod raw_events;
 pub mod state_tree_histories;
 pub mod state_trees;
 pub mod token_accounts;
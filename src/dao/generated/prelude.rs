@@ -1,10 +1,12 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.6
 
+pub use super::account_mappings::Entity as AccountMappings;
 pub use super::account_transactions::Entity as AccountTransactions;
 pub use super::accounts::Entity as Accounts;
 pub use super::blocks::Entity as Blocks;
 pub use super::indexed_trees::Entity as IndexedTrees;
 pub use super::owner_balances::Entity as OwnerBalances;
+pub use super::raw_events::Entity as RawEvents;
 pub use super::state_tree_histories::Entity as StateTreeHistories;
 pub use super::state_trees::Entity as StateTrees;
 pub use super::token_accounts::Entity as TokenAccounts;
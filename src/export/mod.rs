@@ -0,0 +1,203 @@
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+
+use crate::common::typedefs::serializable_pubkey::SerializablePubkey;
+use crate::common::typedefs::token_data::AccountState;
+use crate::dao::generated::{accounts, token_accounts};
+
+// Rows are fetched in pages so that exports of large databases run in constant memory.
+const EXPORT_BATCH_SIZE: u64 = 1000;
+
+pub const ACCOUNTS_CSV_HEADER: &str = "hash,address,owner,tree,leaf_index,seq,slot_created,\
+    spent,slot_spent,lamports,discriminator,data_hash,data";
+
+pub const TOKEN_ACCOUNTS_CSV_HEADER: &str =
+    "hash,owner,mint,delegate,state,amount,spent,slot_created,tlv";
+
+/// Parquet is not supported yet since it would require an Arrow dependency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportTable {
+    /// Compressed accounts.
+    Accounts,
+    /// Compressed token accounts, along with the slot in which their account was created.
+    TokenAccounts,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExportFilter {
+    pub owner: Option<SerializablePubkey>,
+    /// Only applies to token accounts.
+    pub mint: Option<SerializablePubkey>,
+    /// First slot, inclusive, in which exported accounts were created.
+    pub start_slot: Option<u64>,
+    /// Last slot, inclusive, in which exported accounts were created.
+    pub end_slot: Option<u64>,
+}
+
+impl ExportFilter {
+    fn slot_created_condition(&self) -> Condition {
+        let mut condition = Condition::all();
+        if let Some(start_slot) = self.start_slot {
+            condition = condition.add(accounts::Column::SlotCreated.gte(start_slot as i64));
+        }
+        if let Some(end_slot) = self.end_slot {
+            condition = condition.add(accounts::Column::SlotCreated.lte(end_slot as i64));
+        }
+        condition
+    }
+}
+
+// Keys and hashes are encoded in base58 and binary data in base64, so none of the fields contain
+// separators or quotes that would need escaping.
+fn base58(bytes: &[u8]) -> String {
+    bs58::encode(bytes).into_string()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    #[allow(deprecated)]
+    base64::encode(bytes)
+}
+
+fn optional<T>(value: Option<T>, encode: impl Fn(T) -> String) -> String {
+    value.map(encode).unwrap_or_default()
+}
+
+/// Writes the rows of `table` matching `filter` to `writer` and returns the number of exported
+/// rows. Rows are ordered by hash.
+pub async fn export(
+    db: &DatabaseConnection,
+    format: ExportFormat,
+    table: ExportTable,
+    filter: &ExportFilter,
+    writer: &mut impl Write,
+) -> Result<u64> {
+    match (format, table) {
+        (ExportFormat::Csv, ExportTable::Accounts) => export_accounts_csv(db, filter, writer).await,
+        (ExportFormat::Csv, ExportTable::TokenAccounts) => {
+            export_token_accounts_csv(db, filter, writer).await
+        }
+    }
+}
+
+async fn export_accounts_csv(
+    db: &DatabaseConnection,
+    filter: &ExportFilter,
+    writer: &mut impl Write,
+) -> Result<u64> {
+    if filter.mint.is_some() {
+        return Err(anyhow!("The mint filter only applies to token accounts"));
+    }
+    let mut condition = filter.slot_created_condition();
+    if let Some(owner) = filter.owner {
+        condition = condition.add(accounts::Column::Owner.eq(owner.to_bytes_vec()));
+    }
+
+    writeln!(writer, "{}", ACCOUNTS_CSV_HEADER)?;
+    let mut cursor: Option<Vec<u8>> = None;
+    let mut exported_rows = 0;
+    loop {
+        let mut page_condition = condition.clone();
+        if let Some(cursor) = cursor {
+            page_condition = page_condition.add(accounts::Column::Hash.gt(cursor));
+        }
+        let page = accounts::Entity::find()
+            .filter(page_condition)
+            .order_by_asc(accounts::Column::Hash)
+            .limit(EXPORT_BATCH_SIZE)
+            .all(db)
+            .await?;
+        for account in page.iter() {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                base58(&account.hash),
+                optional(account.address.as_deref(), base58),
+                base58(&account.owner),
+                base58(&account.tree),
+                account.leaf_index,
+                account.seq,
+                account.slot_created,
+                account.spent,
+                optional(account.slot_spent, |value| value.to_string()),
+                account.lamports,
+                optional(account.discriminator, |value| value.to_string()),
+                optional(account.data_hash.as_deref(), base58),
+                optional(account.data.as_deref(), base64),
+            )?;
+        }
+        exported_rows += page.len() as u64;
+        cursor = match page.last() {
+            Some(account) if page.len() as u64 == EXPORT_BATCH_SIZE => Some(account.hash.clone()),
+            _ => break,
+        };
+    }
+    writer.flush()?;
+    Ok(exported_rows)
+}
+
+async fn export_token_accounts_csv(
+    db: &DatabaseConnection,
+    filter: &ExportFilter,
+    writer: &mut impl Write,
+) -> Result<u64> {
+    let mut condition = filter.slot_created_condition();
+    if let Some(owner) = filter.owner {
+        condition = condition.add(token_accounts::Column::Owner.eq(owner.to_bytes_vec()));
+    }
+    if let Some(mint) = filter.mint {
+        condition = condition.add(token_accounts::Column::Mint.eq(mint.to_bytes_vec()));
+    }
+
+    writeln!(writer, "{}", TOKEN_ACCOUNTS_CSV_HEADER)?;
+    let mut cursor: Option<Vec<u8>> = None;
+    let mut exported_rows = 0;
+    loop {
+        let mut page_condition = condition.clone();
+        if let Some(cursor) = cursor {
+            page_condition = page_condition.add(token_accounts::Column::Hash.gt(cursor));
+        }
+        let page = token_accounts::Entity::find()
+            .find_also_related(accounts::Entity)
+            .filter(page_condition)
+            .order_by_asc(token_accounts::Column::Hash)
+            .limit(EXPORT_BATCH_SIZE)
+            .all(db)
+            .await?;
+        for (token_account, account) in page.iter() {
+            let state = AccountState::try_from(token_account.state as u8)
+                .map_err(|_| anyhow!("Invalid token account state {}", token_account.state))?;
+            writeln!(
+                writer,
+                "{},{},{},{},{:?},{},{},{},{}",
+                base58(&token_account.hash),
+                base58(&token_account.owner),
+                base58(&token_account.mint),
+                optional(token_account.delegate.as_deref(), base58),
+                state,
+                token_account.amount,
+                token_account.spent,
+                optional(account.as_ref(), |account| account.slot_created.to_string()),
+                optional(token_account.tlv.as_deref(), base64),
+            )?;
+        }
+        exported_rows += page.len() as u64;
+        cursor = match page.last() {
+            Some((token_account, _)) if page.len() as u64 == EXPORT_BATCH_SIZE => {
+                Some(token_account.hash.clone())
+            }
+            _ => break,
+        };
+    }
+    writer.flush()?;
+    Ok(exported_rows)
+}
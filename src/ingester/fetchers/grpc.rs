@@ -1,10 +1,14 @@
+use std::convert::Infallible;
+use std::fmt;
 use std::pin::Pin;
+use std::str::FromStr;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::{collections::HashMap, time::Duration};
 
 use async_stream::stream;
 use cadence_macros::statsd_count;
+use clap::ValueEnum;
 use futures::future::{select, Either};
 use futures::sink::SinkExt;
 use futures::{pin_mut, Stream, StreamExt};
@@ -28,7 +32,12 @@ use yellowstone_grpc_proto::solana::storage::confirmed_block::InnerInstructions;
 
 use crate::api::method::get_indexer_health::HEALTH_CHECK_SLOT_DISTANCE;
 use crate::common::typedefs::hash::Hash;
-use crate::ingester::fetchers::poller::get_block_poller_stream;
+use crate::common::{Commitment, RetryBackoffConfig};
+use crate::ingester::fetchers::poller::{get_block_poller_stream, is_next_block};
+use crate::ingester::fetchers::status::{
+    record_grpc_error, set_block_source, set_grpc_connection_state, BlockSource,
+    GrpcConnectionState,
+};
 use crate::ingester::typedefs::block_info::{
     BlockInfo, BlockMetadata, Instruction, InstructionGroup, TransactionInfo,
 };
@@ -36,22 +45,67 @@ use crate::ingester::typedefs::block_info::{
 use crate::metric;
 use crate::monitor::{start_latest_slot_updater, LATEST_SLOT};
 
+/// Number of failed attempts to connect to the gRPC endpoint after which it is considered
+/// unreachable, as long as no connection has succeeded yet.
+pub const GRPC_STARTUP_CONNECT_ATTEMPTS: u32 = 3;
+
+/// What to do when the gRPC endpoint cannot be reached when indexing starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum GrpcUnreachablePolicy {
+    /// Keep retrying to connect to gRPC while blocks are fetched over RPC.
+    #[default]
+    Retry,
+    /// Give up on gRPC and only fetch blocks over RPC.
+    Fallback,
+}
+
+/// Token sent in the `x-token` header to authenticate with hosted Yellowstone gRPC endpoints. The
+/// token is redacted when formatted, so that it never ends up in the logs.
+#[derive(Clone, PartialEq, Eq)]
+pub struct GrpcXToken(String);
+
+impl FromStr for GrpcXToken {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(GrpcXToken(s.to_string()))
+    }
+}
+
+impl fmt::Debug for GrpcXToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "GrpcXToken(<redacted>)")
+    }
+}
+
+impl fmt::Display for GrpcXToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<redacted>")
+    }
+}
+
 pub fn get_grpc_stream_with_rpc_fallback(
     endpoint: String,
-    auth_header: String,
+    x_token: Option<GrpcXToken>,
     rpc_client: Arc<RpcClient>,
     mut last_indexed_slot: u64,
     max_concurrent_block_fetches: usize,
+    unreachable_policy: GrpcUnreachablePolicy,
+    commitment: Commitment,
+    retry_backoff: RetryBackoffConfig,
 ) -> impl Stream<Item = Vec<BlockInfo>> {
     stream! {
         start_latest_slot_updater(rpc_client.clone()).await;
-        let grpc_stream = get_grpc_block_stream(endpoint, auth_header);
+        let grpc_stream = get_grpc_block_stream(endpoint, x_token, unreachable_policy, commitment);
         pin_mut!(grpc_stream);
+        set_block_source(BlockSource::Rpc);
         let mut rpc_poll_stream:  Option<Pin<Box<dyn Stream<Item = Vec<BlockInfo>> + Send>>> = Some(
             Box::pin(get_block_poller_stream(
                 rpc_client.clone(),
                 last_indexed_slot,
                 max_concurrent_block_fetches,
+                commitment,
+                retry_backoff,
             ))
         );
 
@@ -62,7 +116,7 @@ pub fn get_grpc_stream_with_rpc_fallback(
                     match select(grpc_stream.next(), rpc_poll_stream_value.next()).await {
                         Either::Left((Some(grpc_block), _)) => {
                             let slot = grpc_block.metadata.slot;
-                            if grpc_block.metadata.parent_slot == last_indexed_slot {
+                            if is_next_block(&grpc_block, last_indexed_slot) {
                                 last_indexed_slot = grpc_block.metadata.slot;
                                 yield vec![grpc_block];
                                 metric! {
@@ -71,12 +125,13 @@ pub fn get_grpc_stream_with_rpc_fallback(
                                 if is_healthy(slot) {
                                     info!("Switching to gRPC block fetching since Photon is up-to-date");
                                     rpc_poll_stream = None;
+                                    set_block_source(BlockSource::Grpc);
                                 }
                             }
                         }
-                        Either::Left((None, _)) => {
-                            panic!("gRPC stream ended unexpectedly");
-                        }
+                        // The gRPC stream only ends when the endpoint is unreachable and the
+                        // policy is to fall back to RPC.
+                        Either::Left((None, _)) => break,
                         Either::Right((Some(rpc_blocks), _)) => {
                             let rpc_blocks: Vec<BlockInfo> = rpc_blocks
                                 .into_iter()
@@ -86,9 +141,8 @@ pub fn get_grpc_stream_with_rpc_fallback(
                                 continue;
                             }
                             let blocks_len = rpc_blocks.len();
-                            let parent_slot = rpc_blocks.first().unwrap().metadata.parent_slot;
                             let last_slot = rpc_blocks.last().unwrap().metadata.slot;
-                            if parent_slot == last_indexed_slot {
+                            if is_next_block(rpc_blocks.first().unwrap(), last_indexed_slot) {
                                 last_indexed_slot = last_slot;
                                 yield rpc_blocks;
                                 metric! {
@@ -109,17 +163,21 @@ pub fn get_grpc_stream_with_rpc_fallback(
                             metric! {
                                 statsd_count!("grpc_timeout", 1);
                             }
+                            record_grpc_error();
                             info!("gRPC stream timed out, enabling RPC block fetching");
+                            set_block_source(BlockSource::Rpc);
                             rpc_poll_stream = Some(Box::pin(get_block_poller_stream(
                                 rpc_client.clone(),
                                 last_indexed_slot,
                                 max_concurrent_block_fetches,
+                                commitment,
+                                retry_backoff,
                             )));
                             continue;
                         }
                     };
                     let slot = block.metadata.slot;
-                    if block.metadata.parent_slot == last_indexed_slot {
+                    if is_next_block(&block, last_indexed_slot) {
                         last_indexed_slot = block.metadata.slot;
                         yield vec![block];
                     } else {
@@ -127,10 +185,13 @@ pub fn get_grpc_stream_with_rpc_fallback(
                             statsd_count!("grpc_out_of_order", 1);
                         }
                         info!("Switching to RPC block fetching");
+                        set_block_source(BlockSource::Rpc);
                         rpc_poll_stream = Some(Box::pin(get_block_poller_stream(
                             rpc_client.clone(),
                             last_indexed_slot,
                             max_concurrent_block_fetches,
+                            commitment,
+                            retry_backoff,
                         )));
                         continue;
                     }
@@ -139,17 +200,37 @@ pub fn get_grpc_stream_with_rpc_fallback(
                         metric! {
                             statsd_count!("grpc_stale", 1);
                         }
+                        set_block_source(BlockSource::Rpc);
                         rpc_poll_stream = Some(Box::pin(get_block_poller_stream(
                             rpc_client.clone(),
                             last_indexed_slot,
                             max_concurrent_block_fetches,
+                            commitment,
+                            retry_backoff,
                         )));
                     }
                 }
             }
+        }
 
-
+        info!("Fetching blocks over RPC only");
+        if let Some(rpc_poll_stream) = rpc_poll_stream.as_mut() {
+            while let Some(rpc_blocks) = rpc_poll_stream.next().await {
+                let rpc_blocks: Vec<BlockInfo> = rpc_blocks
+                    .into_iter()
+                    .filter(|b| b.metadata.slot > last_indexed_slot)
+                    .collect();
+                if let Some(last_block) = rpc_blocks.last() {
+                    last_indexed_slot = last_block.metadata.slot;
+                    let blocks_len = rpc_blocks.len();
+                    yield rpc_blocks;
+                    metric! {
+                        statsd_count!("rpc_block_indexed", blocks_len as i64);
+                    }
+                }
+            }
         }
+        panic!("RPC stream ended unexpectedly");
     }
 }
 
@@ -157,36 +238,59 @@ fn is_healthy(slot: u64) -> bool {
     (LATEST_SLOT.load(Ordering::SeqCst) as i64 - slot as i64) <= HEALTH_CHECK_SLOT_DISTANCE
 }
 
-fn get_grpc_block_stream(endpoint: String, auth_header: String) -> impl Stream<Item = BlockInfo> {
+/// Streams blocks from the gRPC endpoint, reconnecting whenever the connection fails. If the
+/// endpoint cannot be reached before a connection ever succeeded and the policy is to fall back to
+/// RPC, the stream ends instead.
+pub fn get_grpc_block_stream(
+    endpoint: String,
+    x_token: Option<GrpcXToken>,
+    unreachable_policy: GrpcUnreachablePolicy,
+    commitment: Commitment,
+) -> impl Stream<Item = BlockInfo> {
     stream! {
+        let mut connected = false;
+        let mut failed_connect_attempts = 0;
         loop {
             let mut grpc_tx;
             let mut grpc_rx;
+            set_grpc_connection_state(GrpcConnectionState::Connecting);
             {
                 let grpc_client =
-                    build_geyser_client(endpoint.clone(), auth_header.clone()).await;
+                    build_geyser_client(endpoint.clone(), x_token.clone()).await;
                 if let Err(e) = grpc_client {
                     error!("Error connecting to gRPC, waiting one second then retrying connect: {}", e);
                     metric! {
                         statsd_count!("grpc_connect_error", 1);
                     }
+                    record_grpc_error();
+                    set_grpc_connection_state(GrpcConnectionState::Disconnected);
+                    if !connected && is_unreachable(&endpoint, &mut failed_connect_attempts, unreachable_policy) {
+                        return;
+                    }
                     sleep(Duration::from_secs(1)).await;
                     continue;
                 }
                 let subscription = grpc_client
                     .unwrap()
-                    .subscribe_with_request(Some(get_block_subscribe_request()))
+                    .subscribe_with_request(Some(get_block_subscribe_request(commitment)))
                     .await;
                 if let Err(e) = subscription {
                     error!("Error subscribing to gRPC stream, waiting one second then retrying connect: {}", e);
                     metric! {
                         statsd_count!("grpc_subscribe_error", 1);
                     }
+                    record_grpc_error();
+                    set_grpc_connection_state(GrpcConnectionState::Disconnected);
+                    if !connected && is_unreachable(&endpoint, &mut failed_connect_attempts, unreachable_policy) {
+                        return;
+                    }
                     sleep(Duration::from_secs(1)).await;
                     continue;
                 }
                 (grpc_tx, grpc_rx) = subscription.unwrap();
             }
+            set_grpc_connection_state(GrpcConnectionState::Connected);
+            connected = true;
             while let Some(message) = grpc_rx.next().await {
                 match message {
                     Ok(message) => match message.update_oneof {
@@ -206,6 +310,7 @@ fn get_grpc_block_stream(endpoint: String, auth_header: String) -> impl Stream<I
                                 metric! {
                                     statsd_count!("grpc_ping_error", 1);
                                 }
+                                record_grpc_error();
                                 break;
                             }
                         }
@@ -221,21 +326,53 @@ fn get_grpc_block_stream(endpoint: String, auth_header: String) -> impl Stream<I
                         metric! {
                             statsd_count!("grpc_resubscribe", 1);
                         }
+                        record_grpc_error();
                         break;
                     }
                 }
             }
+            set_grpc_connection_state(GrpcConnectionState::Disconnected);
         sleep(Duration::from_secs(1)).await;
         }
     }
 }
 
+// Counts a failed attempt to connect to the gRPC endpoint before any connection succeeded and
+// returns whether to give up on the endpoint.
+fn is_unreachable(
+    endpoint: &str,
+    failed_connect_attempts: &mut u32,
+    unreachable_policy: GrpcUnreachablePolicy,
+) -> bool {
+    *failed_connect_attempts += 1;
+    if *failed_connect_attempts != GRPC_STARTUP_CONNECT_ATTEMPTS {
+        return false;
+    }
+    error!(
+        "gRPC endpoint {} is unreachable after {} attempts",
+        endpoint, failed_connect_attempts
+    );
+    metric! {
+        statsd_count!("grpc_unreachable", 1);
+    }
+    match unreachable_policy {
+        GrpcUnreachablePolicy::Retry => {
+            error!("Retrying to connect to gRPC while fetching blocks over RPC");
+            false
+        }
+        GrpcUnreachablePolicy::Fallback => {
+            error!("Falling back to RPC block fetching");
+            true
+        }
+    }
+}
+
 async fn build_geyser_client(
     endpoint: String,
-    auth_header: String,
+    x_token: Option<GrpcXToken>,
 ) -> GeyserGrpcBuilderResult<GeyserGrpcClient<impl Interceptor>> {
     GeyserGrpcClient::build_from_shared(endpoint)?
-        .x_token(Some(auth_header))?
+        .x_token(x_token.map(|x_token| x_token.0))?
         .connect_timeout(Duration::from_secs(10))
         .max_decoding_message_size(8388608)
         .timeout(Duration::from_secs(10))
@@ -251,7 +388,12 @@ fn generate_random_string(len: usize) -> String {
         .collect()
 }
 
-fn get_block_subscribe_request() -> SubscribeRequest {
+fn get_block_subscribe_request(commitment: Commitment) -> SubscribeRequest {
+    let commitment = match commitment {
+        Commitment::Processed => CommitmentLevel::Processed,
+        Commitment::Confirmed => CommitmentLevel::Confirmed,
+        Commitment::Finalized => CommitmentLevel::Finalized,
+    };
     SubscribeRequest {
         blocks: HashMap::from_iter(vec![(
             generate_random_string(20),
@@ -262,7 +404,7 @@ fn get_block_subscribe_request() -> SubscribeRequest {
                 include_entries: Some(false),
             },
         )]),
-        commitment: Some(CommitmentLevel::Confirmed.into()),
+        commitment: Some(commitment.into()),
         ..Default::default()
     }
 }
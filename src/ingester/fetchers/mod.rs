@@ -1,35 +1,61 @@
 use std::sync::Arc;
 
 use async_stream::stream;
+use cadence_macros::statsd_gauge;
 use futures::{pin_mut, Stream, StreamExt};
 use solana_client::nonblocking::rpc_client::RpcClient;
+use tokio::sync::mpsc;
 
+use crate::common::{Commitment, RetryBackoffConfig};
+use crate::metric;
 
 use super::typedefs::block_info::BlockInfo;
 
 pub mod grpc;
 pub mod poller;
+pub mod status;
 
-use grpc::get_grpc_stream_with_rpc_fallback;
+use grpc::{get_grpc_stream_with_rpc_fallback, GrpcUnreachablePolicy, GrpcXToken};
 use poller::get_block_poller_stream;
+use status::{record_blocks_received, reset_ingestion_status, BlockSource, GrpcConnectionState};
 
 pub struct BlockStreamConfig {
     pub rpc_client: Arc<RpcClient>,
     pub geyser_url: Option<String>,
+    pub grpc_x_token: Option<GrpcXToken>,
+    pub grpc_unreachable_policy: GrpcUnreachablePolicy,
     pub max_concurrent_block_fetches: usize,
     pub last_indexed_slot: u64,
+    pub fetch_ahead_window: usize,
+    pub commitment: Commitment,
+    pub retry_backoff: RetryBackoffConfig,
 }
 
 impl BlockStreamConfig {
     pub fn load_block_stream(&self) -> impl Stream<Item = Vec<BlockInfo>> {
+        self.init_ingestion_status();
+        fetch_ahead(self.load_unbuffered_block_stream(), self.fetch_ahead_window)
+    }
+
+    /// Resets the reported ingestion status to the source this configuration streams blocks from.
+    pub fn init_ingestion_status(&self) {
+        match self.geyser_url {
+            Some(_) => reset_ingestion_status(BlockSource::Grpc, GrpcConnectionState::Connecting),
+            None => reset_ingestion_status(BlockSource::Rpc, GrpcConnectionState::NotConfigured),
+        }
+    }
+
+    fn load_unbuffered_block_stream(&self) -> impl Stream<Item = Vec<BlockInfo>> {
         let grpc_stream = self.geyser_url.as_ref().map(|geyser_url| {
-            let auth_header = std::env::var("GRPC_X_TOKEN").unwrap();
             get_grpc_stream_with_rpc_fallback(
                 geyser_url.clone(),
-                auth_header,
+                self.grpc_x_token.clone(),
                 self.rpc_client.clone(),
                 self.last_indexed_slot,
                 self.max_concurrent_block_fetches,
+                self.grpc_unreachable_policy,
+                self.commitment,
+                self.retry_backoff,
             )
         });
 
@@ -38,6 +64,8 @@ impl BlockStreamConfig {
                 self.rpc_client.clone(),
                 self.last_indexed_slot,
                 self.max_concurrent_block_fetches,
+                self.commitment,
+                self.retry_backoff,
             ))
         } else {
             None
@@ -48,7 +76,10 @@ impl BlockStreamConfig {
                 pin_mut!(grpc_stream);
                 loop {
                     match grpc_stream.next().await {
-                        Some(blocks) => yield blocks,
+                        Some(blocks) => {
+                            record_blocks_received();
+                            yield blocks;
+                        }
                         None => break,
                     }
                 }
@@ -58,7 +89,10 @@ impl BlockStreamConfig {
                 pin_mut!(poller_stream);
                 loop {
                     match poller_stream.next().await {
-                        Some(blocks) => yield blocks,
+                        Some(blocks) => {
+                            record_blocks_received();
+                            yield blocks;
+                        }
                         None => break,
                     }
                 }
@@ -66,3 +100,30 @@ impl BlockStreamConfig {
         }
     }
 }
+
+/// Polls `block_stream` from a background task and buffers up to `window` batches of blocks ahead
+/// of the consumer, so that blocks keep being fetched while earlier batches are being persisted.
+pub fn fetch_ahead(
+    block_stream: impl Stream<Item = Vec<BlockInfo>> + Send + 'static,
+    window: usize,
+) -> impl Stream<Item = Vec<BlockInfo>> {
+    let (sender, mut receiver) = mpsc::channel(window.max(1));
+    tokio::spawn(async move {
+        pin_mut!(block_stream);
+        while let Some(blocks) = block_stream.next().await {
+            if sender.send(blocks).await.is_err() {
+                // The consumer was dropped, so there is no one left to fetch blocks for.
+                break;
+            }
+            let buffered_batches = (sender.max_capacity() - sender.capacity()) as u64;
+            metric! {
+                statsd_gauge!("fetch_ahead_buffered_batches", buffered_batches);
+            }
+        }
+    });
+    stream! {
+        while let Some(blocks) = receiver.recv().await {
+            yield blocks;
+        }
+    }
+}
@@ -1,20 +1,25 @@
 use std::{
     collections::BTreeMap,
     sync::{atomic::Ordering, Arc},
+    time::Instant,
 };
 
 use async_stream::stream;
-use cadence_macros::statsd_count;
+use cadence_macros::{statsd_count, statsd_histogram};
 use futures::{pin_mut, Stream, StreamExt};
 use solana_client::{
-    nonblocking::rpc_client::RpcClient, rpc_config::RpcBlockConfig, rpc_request::RpcError,
+    client_error::ClientError, nonblocking::rpc_client::RpcClient, rpc_config::RpcBlockConfig,
+    rpc_request::RpcError,
 };
 
-use solana_sdk::commitment_config::CommitmentConfig;
 use solana_transaction_status::{TransactionDetails, UiTransactionEncoding};
 
 use crate::{
-    ingester::typedefs::block_info::{parse_ui_confirmed_blocked, BlockInfo},
+    common::{Commitment, RetryBackoff, RetryBackoffConfig},
+    ingester::{
+        fetchers::status::record_rpc_fetch_error,
+        typedefs::block_info::{parse_ui_confirmed_blocked, BlockInfo},
+    },
     metric,
     monitor::{start_latest_slot_updater, LATEST_SLOT},
 };
@@ -40,6 +45,8 @@ pub fn get_block_poller_stream(
     rpc_client: Arc<RpcClient>,
     mut last_indexed_slot: u64,
     max_concurrent_block_fetches: usize,
+    commitment: Commitment,
+    retry_backoff: RetryBackoffConfig,
 ) -> impl Stream<Item = Vec<BlockInfo>> {
     stream! {
         let start_slot = match last_indexed_slot {
@@ -51,7 +58,7 @@ pub fn get_block_poller_stream(
         let block_stream = slot_stream
             .map(|slot| {
                 let rpc_client = rpc_client.clone();
-                async move { fetch_block_with_infinite_retries(rpc_client.clone(), slot).await }
+                async move { fetch_block_with_infinite_retries(rpc_client.clone(), slot, commitment, retry_backoff).await }
             })
             .buffer_unordered(max_concurrent_block_fetches);
         pin_mut!(block_stream);
@@ -83,7 +90,7 @@ fn pop_cached_blocks_to_index(
             None => break,
         };
         let block: &BlockInfo = block_cache.get(&min_slot).unwrap();
-        if block.metadata.parent_slot == last_indexed_slot {
+        if is_next_block(block, last_indexed_slot) {
             last_indexed_slot = block.metadata.slot;
             blocks.push(block.clone());
             block_cache.remove(&min_slot);
@@ -96,47 +103,104 @@ fn pop_cached_blocks_to_index(
     (blocks, last_indexed_slot)
 }
 
+/// Whether a block can be indexed after `last_indexed_slot`, either because it extends the indexed
+/// chain, or because it forks off it, in which case the slots after its parent were orphaned by a
+/// reorg.
+pub fn is_next_block(block: &BlockInfo, last_indexed_slot: u64) -> bool {
+    let (slot, parent_slot) = (block.metadata.slot, block.metadata.parent_slot);
+    parent_slot == last_indexed_slot
+        || (parent_slot < last_indexed_slot && slot > last_indexed_slot)
+}
+
 pub async fn fetch_block_with_infinite_retries(
     rpc_client: Arc<RpcClient>,
     slot: u64,
+    commitment: Commitment,
+    retry_backoff: RetryBackoffConfig,
 ) -> Option<BlockInfo> {
+    let mut backoff = RetryBackoff::new(retry_backoff);
     loop {
-        match rpc_client
-            .get_block_with_config(
-                slot,
-                RpcBlockConfig {
-                    encoding: Some(UiTransactionEncoding::Base64),
-                    transaction_details: Some(TransactionDetails::Full),
-                    rewards: None,
-                    commitment: Some(CommitmentConfig::confirmed()),
-                    max_supported_transaction_version: Some(0),
-                },
-            )
-            .await
-        {
-            Ok(block) => {
-                metric! {
-                    statsd_count!("rpc_block_fetched", 1);
-                }
-                return Some(parse_ui_confirmed_blocked(block, slot).unwrap());
+        match fetch_block(&rpc_client, slot, commitment).await {
+            Ok(block) => return block,
+            Err(_) => tokio::time::sleep(backoff.next_delay()).await,
+        }
+    }
+}
+
+/// Fetches a block like `fetch_block_with_infinite_retries`, but gives up and returns the last
+/// error after `max_attempts` failed attempts.
+pub async fn fetch_block_with_retries(
+    rpc_client: Arc<RpcClient>,
+    slot: u64,
+    commitment: Commitment,
+    max_attempts: u32,
+    retry_backoff: RetryBackoffConfig,
+) -> Result<Option<BlockInfo>, ClientError> {
+    let mut backoff = RetryBackoff::new(retry_backoff);
+    let mut attempt = 1;
+    loop {
+        match fetch_block(&rpc_client, slot, commitment).await {
+            Err(e) if attempt < max_attempts => {
+                log::warn!(
+                    "Failed to fetch block {} (attempt {} of {}): {}",
+                    slot,
+                    attempt,
+                    max_attempts,
+                    e
+                );
+                attempt += 1;
+                tokio::time::sleep(backoff.next_delay()).await;
             }
-            Err(e) => {
-                if let solana_client::client_error::ClientErrorKind::RpcError(
-                    RpcError::RpcResponseError { code, .. },
-                ) = e.kind
-                {
-                    if SKIPPED_BLOCK_ERRORS.contains(&code) {
-                        metric! {
-                            statsd_count!("rpc_skipped_block", 1);
-                        }
-                        log::info!("Skipped block: {}", slot);
-                        return None;
+            result => return result,
+        }
+    }
+}
+
+// Fetches a block in a single attempt. Returns `None` if the slot was skipped.
+async fn fetch_block(
+    rpc_client: &RpcClient,
+    slot: u64,
+    commitment: Commitment,
+) -> Result<Option<BlockInfo>, ClientError> {
+    let start = Instant::now();
+    match rpc_client
+        .get_block_with_config(
+            slot,
+            RpcBlockConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                transaction_details: Some(TransactionDetails::Full),
+                rewards: None,
+                commitment: Some(commitment.block_commitment_config()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .await
+    {
+        Ok(block) => {
+            metric! {
+                statsd_count!("rpc_block_fetched", 1);
+                statsd_histogram!("rpc_block_fetch_latency_ms", start.elapsed().as_millis() as u64);
+            }
+            Ok(Some(parse_ui_confirmed_blocked(block, slot).unwrap()))
+        }
+        Err(e) => {
+            if let solana_client::client_error::ClientErrorKind::RpcError(
+                RpcError::RpcResponseError { code, .. },
+            ) = &e.kind
+            {
+                if SKIPPED_BLOCK_ERRORS.contains(code) {
+                    metric! {
+                        statsd_count!("rpc_skipped_block", 1);
                     }
+                    log::info!("Skipped block: {}", slot);
+                    return Ok(None);
                 }
-                metric! {
-                    statsd_count!("rpc_block_fetch_failed", 1);
-                }
             }
+            metric! {
+                statsd_count!("rpc_block_fetch_failed", 1);
+            }
+            record_rpc_fetch_error();
+            Err(e)
         }
     }
 }
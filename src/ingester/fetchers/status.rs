@@ -0,0 +1,137 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Fetch errors are reported for a sliding window so that old incidents do not mask recovery.
+const RECENT_ERRORS_WINDOW_SECS: u64 = 300;
+
+/// Where the indexer is currently consuming blocks from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum BlockSource {
+    /// Indexing is disabled.
+    #[default]
+    None,
+    /// Blocks are polled from RPC, either because gRPC is not configured or because the indexer
+    /// fell back to RPC while gRPC is behind or unavailable.
+    Rpc,
+    /// Blocks are streamed from gRPC.
+    Grpc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum GrpcConnectionState {
+    #[default]
+    NotConfigured,
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct IngestionStatusSnapshot {
+    pub source: BlockSource,
+    pub grpc_connection_state: GrpcConnectionState,
+    /// Unix timestamp, in seconds, at which the indexer last received blocks.
+    pub last_block_received_at: Option<u64>,
+    pub recent_rpc_fetch_errors: u64,
+    pub recent_grpc_errors: u64,
+}
+
+/// Error counts bucketed by second, so that memory stays bounded when errors are retried in a
+/// tight loop.
+#[derive(Default)]
+struct RecentErrors(VecDeque<(u64, u64)>);
+
+impl RecentErrors {
+    fn record(&mut self, now: u64) {
+        match self.0.back_mut() {
+            Some((second, count)) if *second == now => *count += 1,
+            _ => self.0.push_back((now, 1)),
+        }
+        self.prune(now);
+    }
+
+    fn prune(&mut self, now: u64) {
+        while let Some((second, _)) = self.0.front() {
+            if second + RECENT_ERRORS_WINDOW_SECS > now {
+                break;
+            }
+            self.0.pop_front();
+        }
+    }
+
+    fn count(&mut self, now: u64) -> u64 {
+        self.prune(now);
+        self.0.iter().map(|(_, count)| count).sum()
+    }
+}
+
+#[derive(Default)]
+struct IngestionStatus {
+    source: BlockSource,
+    grpc_connection_state: GrpcConnectionState,
+    last_block_received_at: Option<u64>,
+    rpc_fetch_errors: RecentErrors,
+    grpc_errors: RecentErrors,
+}
+
+static INGESTION_STATUS: Lazy<Mutex<IngestionStatus>> =
+    Lazy::new(|| Mutex::new(IngestionStatus::default()));
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Resets the ingestion status when a new block stream is started.
+pub fn reset_ingestion_status(source: BlockSource, grpc_connection_state: GrpcConnectionState) {
+    *INGESTION_STATUS.lock().unwrap() = IngestionStatus {
+        source,
+        grpc_connection_state,
+        ..Default::default()
+    };
+}
+
+pub fn set_block_source(source: BlockSource) {
+    INGESTION_STATUS.lock().unwrap().source = source;
+}
+
+pub fn set_grpc_connection_state(grpc_connection_state: GrpcConnectionState) {
+    INGESTION_STATUS.lock().unwrap().grpc_connection_state = grpc_connection_state;
+}
+
+pub fn record_blocks_received() {
+    INGESTION_STATUS.lock().unwrap().last_block_received_at = Some(now());
+}
+
+pub fn record_rpc_fetch_error() {
+    INGESTION_STATUS
+        .lock()
+        .unwrap()
+        .rpc_fetch_errors
+        .record(now());
+}
+
+pub fn record_grpc_error() {
+    INGESTION_STATUS.lock().unwrap().grpc_errors.record(now());
+}
+
+pub fn get_ingestion_status_snapshot() -> IngestionStatusSnapshot {
+    let now = now();
+    let mut status = INGESTION_STATUS.lock().unwrap();
+    IngestionStatusSnapshot {
+        source: status.source,
+        grpc_connection_state: status.grpc_connection_state,
+        last_block_received_at: status.last_block_received_at,
+        recent_rpc_fetch_errors: status.rpc_fetch_errors.count(now),
+        recent_grpc_errors: status.grpc_errors.count(now),
+    }
+}
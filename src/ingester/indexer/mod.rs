@@ -2,13 +2,22 @@ use std::{sync::Arc, thread::sleep, time::Duration};
 
 use async_std::stream::StreamExt;
 use futures::{pin_mut, Stream};
-use log::info;
-use sea_orm::{sea_query::Expr, DatabaseConnection, EntityTrait, FromQueryResult, QuerySelect};
+use log::{info, warn};
+use sea_orm::{
+    sea_query::Expr, DatabaseConnection, DbErr, EntityTrait, FromQueryResult, QuerySelect,
+};
 use solana_client::nonblocking::rpc_client::RpcClient;
 
 use crate::{
-    common::fetch_current_slot_with_infinite_retry, dao::generated::blocks,
-    ingester::index_block_batch_with_infinite_retries,
+    common::{
+        fetch_block_parent_slot, fetch_current_slot_with_infinite_retry, get_network_start_slot,
+        RetryBackoffConfig, UnknownNetworkStartSlot,
+    },
+    dao::generated::blocks,
+    ingester::{
+        index_block_batch_with_infinite_retries, rollback_orphaned_slots_with_infinite_retries,
+        IngesterConfig,
+    },
 };
 
 use super::typedefs::block_info::BlockInfo;
@@ -21,23 +30,25 @@ pub struct OptionalContextModel {
     pub slot: Option<i64>,
 }
 
+/// Returns the highest indexed slot, or `None` if nothing has been indexed yet.
+pub async fn fetch_last_indexed_slot(db_conn: &DatabaseConnection) -> Result<Option<i64>, DbErr> {
+    let context = blocks::Entity::find()
+        .select_only()
+        .column_as(Expr::col(blocks::Column::Slot).max(), "slot")
+        .into_model::<OptionalContextModel>()
+        .one(db_conn)
+        .await?;
+    Ok(context
+        .expect("Always expected maximum query to return a result")
+        .slot)
+}
+
 pub async fn fetch_last_indexed_slot_with_infinite_retry(
     db_conn: &DatabaseConnection,
 ) -> Option<i64> {
     loop {
-        let context = blocks::Entity::find()
-            .select_only()
-            .column_as(Expr::col(blocks::Column::Slot).max(), "slot")
-            .into_model::<OptionalContextModel>()
-            .one(db_conn)
-            .await;
-
-        match context {
-            Ok(context) => {
-                return context
-                    .expect("Always expected maximum query to return a result")
-                    .slot
-            }
+        match fetch_last_indexed_slot(db_conn).await {
+            Ok(slot) => return slot,
             Err(e) => {
                 log::error!("Failed to fetch current slot from database: {}", e);
                 sleep(Duration::from_secs(5));
@@ -46,16 +57,44 @@ pub async fn fetch_last_indexed_slot_with_infinite_retry(
     }
 }
 
+/// Resolves the slot after which indexing starts. This is the current slot if `start_slot` is
+/// "latest" and the parent of `start_slot` if it is a slot. Without a start slot, indexing resumes
+/// after the last slot in the database, or starts from the network start slot if nothing has been
+/// indexed yet.
+pub async fn resolve_last_indexed_slot(
+    db_conn: &DatabaseConnection,
+    rpc_client: &RpcClient,
+    start_slot: Option<&str>,
+    unknown_network_start_slot: UnknownNetworkStartSlot,
+    retry_backoff: RetryBackoffConfig,
+) -> u64 {
+    match start_slot {
+        Some("latest") => fetch_current_slot_with_infinite_retry(rpc_client).await,
+        Some(start_slot) => {
+            fetch_block_parent_slot(rpc_client, start_slot.parse::<u64>().unwrap()).await
+        }
+        None => match fetch_last_indexed_slot_with_infinite_retry(db_conn).await {
+            Some(last_indexed_slot) => last_indexed_slot.try_into().unwrap(),
+            None => {
+                get_network_start_slot(rpc_client, unknown_network_start_slot, retry_backoff).await
+            }
+        },
+    }
+}
+
 pub async fn index_block_stream(
     block_stream: impl Stream<Item = Vec<BlockInfo>>,
     db: Arc<DatabaseConnection>,
     rpc_client: Arc<RpcClient>,
     last_indexed_slot_at_start: u64,
     end_slot: Option<u64>,
+    config: &IngesterConfig,
 ) {
     pin_mut!(block_stream);
-    let current_slot =
-        end_slot.unwrap_or(fetch_current_slot_with_infinite_retry(&rpc_client).await);
+    let current_slot = match end_slot {
+        Some(end_slot) => end_slot,
+        None => fetch_current_slot_with_infinite_retry(&rpc_client).await,
+    };
     let number_of_blocks_to_backfill = if current_slot > last_indexed_slot_at_start {
         current_slot - last_indexed_slot_at_start
     } else {
@@ -70,29 +109,64 @@ pub async fn index_block_stream(
     let mut finished_backfill_slot = None;
 
     while let Some(blocks) = block_stream.next().await {
-        let last_slot_in_block = blocks.last().unwrap().metadata.slot;
-        index_block_batch_with_infinite_retries(db.as_ref(), blocks).await;
+        for blocks in split_at_forks(blocks) {
+            // A block whose parent is older than the last indexed slot forks off the indexed
+            // chain, so the slots after its parent were orphaned by a reorg.
+            let parent_slot = blocks.first().unwrap().metadata.parent_slot;
+            if parent_slot < last_indexed_slot {
+                warn!(
+                    "Rolling back slots {}-{} orphaned by a reorg",
+                    parent_slot + 1,
+                    last_indexed_slot
+                );
+                rollback_orphaned_slots_with_infinite_retries(
+                    db.as_ref(),
+                    parent_slot + 1,
+                    last_indexed_slot,
+                )
+                .await;
+                last_indexed_slot = parent_slot;
+            }
+            let last_slot_in_block = blocks.last().unwrap().metadata.slot;
+            index_block_batch_with_infinite_retries(db.as_ref(), blocks, config).await;
 
-        for slot in (last_indexed_slot + 1)..(last_slot_in_block + 1) {
-            let blocks_indexed = slot - last_indexed_slot_at_start;
-            if blocks_indexed < number_of_blocks_to_backfill {
-                if blocks_indexed % PRE_BACKFILL_FREQUENCY == 0 {
-                    info!(
-                        "Backfilled {} / {} blocks",
-                        blocks_indexed, number_of_blocks_to_backfill
-                    );
-                }
-            } else {
-                if finished_backfill_slot.is_none() {
-                    info!("Finished backfilling historical blocks!");
-                    info!("Starting to index new blocks...");
-                    finished_backfill_slot = Some(slot);
-                }
-                if slot % POST_BACKFILL_FREQUENCY == 0 {
-                    info!("Indexed slot {}", slot);
+            for slot in (last_indexed_slot + 1)..(last_slot_in_block + 1) {
+                // Slots before the start can only be indexed again after a reorg.
+                let blocks_indexed = slot.saturating_sub(last_indexed_slot_at_start);
+                if blocks_indexed < number_of_blocks_to_backfill {
+                    if blocks_indexed % PRE_BACKFILL_FREQUENCY == 0 {
+                        info!(
+                            "Backfilled {} / {} blocks",
+                            blocks_indexed, number_of_blocks_to_backfill
+                        );
+                    }
+                } else {
+                    if finished_backfill_slot.is_none() {
+                        info!("Finished backfilling historical blocks!");
+                        info!("Starting to index new blocks...");
+                        finished_backfill_slot = Some(slot);
+                    }
+                    if slot % POST_BACKFILL_FREQUENCY == 0 {
+                        info!("Indexed slot {}", slot);
+                    }
                 }
+                last_indexed_slot = slot;
+            }
+        }
+    }
+}
+
+// Splits a batch of blocks before every block whose parent is older than the block before it,
+// so that each part only extends the chain indexed before it.
+fn split_at_forks(blocks: Vec<BlockInfo>) -> Vec<Vec<BlockInfo>> {
+    let mut parts: Vec<Vec<BlockInfo>> = Vec::new();
+    for block in blocks {
+        match parts.last_mut() {
+            Some(part) if block.metadata.parent_slot >= part.last().unwrap().metadata.slot => {
+                part.push(block)
             }
-            last_indexed_slot = slot;
+            _ => parts.push(vec![block]),
         }
     }
+    parts
 }
@@ -1,50 +1,238 @@
+use std::sync::Arc;
 use std::thread::sleep;
 use std::time::Duration;
 
 use cadence_macros::statsd_count;
 use error::IngesterError;
 
-use parser::parse_transaction;
+use log::info;
+use parser::{parse_raw_events, parse_transactions, ParserConfig};
 use sea_orm::sea_query::OnConflict;
+use sea_orm::ColumnTrait;
 use sea_orm::ConnectionTrait;
 use sea_orm::DatabaseConnection;
 use sea_orm::DatabaseTransaction;
 
 use sea_orm::EntityTrait;
+use sea_orm::FromQueryResult;
+use sea_orm::QueryFilter;
+use sea_orm::QueryOrder;
+use sea_orm::QuerySelect;
 use sea_orm::QueryTrait;
 use sea_orm::Set;
 use sea_orm::TransactionTrait;
+use solana_sdk::signature::Signature;
 
-use self::parser::state_update::StateUpdate;
+use self::parser::state_update::{RawEvent, RawEventKind, StateUpdate};
 use self::persist::persist_state_update;
+use self::persist::PersistConfig;
 use self::persist::MAX_SQL_INSERTS;
+use self::sink::{
+    account_changes, broadcast_account_changes, has_account_change_subscribers,
+    state_update_messages, StateUpdateSink,
+};
 use self::typedefs::block_info::BlockInfo;
 use self::typedefs::block_info::BlockMetadata;
-use crate::dao::generated::blocks;
+use crate::dao::generated::{blocks, raw_events};
 use crate::metric;
 pub mod error;
 pub mod fetchers;
 pub mod indexer;
 pub mod parser;
 pub mod persist;
+pub mod sink;
 pub mod typedefs;
 
-fn derive_block_state_update(block: &BlockInfo) -> Result<StateUpdate, IngesterError> {
+/// Configures how blocks are parsed, persisted and published once they are persisted.
+#[derive(Clone)]
+pub struct IngesterConfig {
+    pub parser: ParserConfig,
+    pub persist: PersistConfig,
+    /// Delay before retrying a block batch that failed because the database is out of space. It
+    /// doubles up to `out_of_space_retry_max_delay` while the database stays out of space.
+    pub out_of_space_retry_base_delay: Duration,
+    pub out_of_space_retry_max_delay: Duration,
+    /// Sink that persisted state updates are published to.
+    pub state_update_sink: Option<Arc<StateUpdateSink>>,
+}
+
+impl Default for IngesterConfig {
+    fn default() -> Self {
+        Self {
+            parser: ParserConfig::default(),
+            persist: PersistConfig::default(),
+            out_of_space_retry_base_delay: Duration::from_secs(1),
+            out_of_space_retry_max_delay: Duration::from_secs(60),
+            state_update_sink: None,
+        }
+    }
+}
+
+fn derive_block_state_update(
+    block: &BlockInfo,
+    config: &ParserConfig,
+) -> Result<StateUpdate, IngesterError> {
     let mut state_updates: Vec<StateUpdate> = Vec::new();
-    for transaction in &block.transactions {
-        state_updates.push(parse_transaction(transaction, block.metadata.slot)?);
+    for state_update in parse_transactions(&block.transactions, block.metadata.slot, config) {
+        match state_update {
+            Ok(state_update) => state_updates.push(state_update),
+            Err(e) => {
+                metric! {
+                    statsd_count!("transaction_parse_errors", 1);
+                }
+                return Err(e);
+            }
+        }
+    }
+    metric! {
+        statsd_count!("transactions_parsed", block.transactions.len() as i64);
     }
     Ok(StateUpdate::merge_updates(state_updates))
 }
 
-pub async fn index_block(db: &DatabaseConnection, block: &BlockInfo) -> Result<(), IngesterError> {
+pub async fn index_block(
+    db: &DatabaseConnection,
+    block: &BlockInfo,
+    config: &IngesterConfig,
+) -> Result<(), IngesterError> {
+    let state_update = derive_block_state_update(block, &config.parser)?;
     let txn = db.begin().await?;
     index_block_metadatas(&txn, vec![&block.metadata]).await?;
-    persist_state_update(&txn, derive_block_state_update(block)?).await?;
+    persist_state_update(&txn, state_update, &config.persist).await?;
     txn.commit().await?;
     Ok(())
 }
 
+/// Replaces the state persisted for the block's slot with the state derived from the block again.
+/// Used to repair a slot that was indexed incorrectly without having to re-index from scratch.
+/// Accounts created in the slot are persisted again from the block, and the ones spent by later
+/// slots are spent again.
+pub async fn reindex_block(
+    db: &DatabaseConnection,
+    block: &BlockInfo,
+    config: &IngesterConfig,
+) -> Result<(), IngesterError> {
+    let state_update = derive_block_state_update(block, &config.parser)?;
+    let reindexed_accounts = state_update
+        .out_accounts
+        .iter()
+        .map(|account| account.hash.clone())
+        .collect::<Vec<_>>();
+    let txn = db.begin().await?;
+    let retained_state =
+        persist::delete_slot_state(&txn, block.metadata.slot, &reindexed_accounts).await?;
+    index_block_metadatas(&txn, vec![&block.metadata]).await?;
+    persist_state_update(&txn, state_update, &config.persist).await?;
+    persist::restore_slot_state(&txn, retained_state).await?;
+    txn.commit().await?;
+    Ok(())
+}
+
+/// Rolls back the state persisted for the slots from `from_slot` to `to_slot`, which were orphaned
+/// by a reorg, so that the blocks of the new fork can be indexed in their place.
+pub async fn rollback_orphaned_slots(
+    db: &DatabaseConnection,
+    from_slot: u64,
+    to_slot: u64,
+) -> Result<(), IngesterError> {
+    let txn = db.begin().await?;
+    persist::rollback_slots(&txn, from_slot, to_slot).await?;
+    txn.commit().await?;
+    metric! {
+        statsd_count!("orphaned_slots_rolled_back", (to_slot - from_slot + 1) as i64);
+    }
+    Ok(())
+}
+
+pub async fn rollback_orphaned_slots_with_infinite_retries(
+    db: &DatabaseConnection,
+    from_slot: u64,
+    to_slot: u64,
+) {
+    loop {
+        match rollback_orphaned_slots(db, from_slot, to_slot).await {
+            Ok(()) => return,
+            Err(e) => {
+                log::error!(
+                    "Failed to roll back orphaned slots {}-{}. Got error {}",
+                    from_slot,
+                    to_slot,
+                    e
+                );
+                sleep(Duration::from_secs(1));
+            }
+        }
+    }
+}
+
+// Number of slots whose raw events are reparsed in a single database transaction.
+const REPARSE_SLOT_BATCH_SIZE: u64 = 100;
+
+#[derive(FromQueryResult)]
+struct RawEventSlotModel {
+    slot: i64,
+}
+
+/// Derives the state of all slots starting from `from_slot` again from their persisted raw events
+/// using the current parser. Derived rows are upserted, so rows that the current parser derives
+/// and the previous one missed are added, but rows are never removed. Returns the number of
+/// reparsed slots.
+pub async fn reparse_raw_events(
+    db: &DatabaseConnection,
+    from_slot: u64,
+    config: &IngesterConfig,
+) -> Result<u64, IngesterError> {
+    let mut next_slot = from_slot as i64;
+    let mut reparsed_slots = 0;
+    loop {
+        let slots = raw_events::Entity::find()
+            .select_only()
+            .column(raw_events::Column::Slot)
+            .distinct()
+            .filter(raw_events::Column::Slot.gte(next_slot))
+            .order_by_asc(raw_events::Column::Slot)
+            .limit(REPARSE_SLOT_BATCH_SIZE)
+            .into_model::<RawEventSlotModel>()
+            .all(db)
+            .await?;
+        let (first_slot, last_slot) = match (slots.first(), slots.last()) {
+            (Some(first), Some(last)) => (first.slot, last.slot),
+            _ => break,
+        };
+
+        let raw_events = raw_events::Entity::find()
+            .filter(raw_events::Column::Slot.between(first_slot, last_slot))
+            .order_by_asc(raw_events::Column::Slot)
+            .order_by_asc(raw_events::Column::Signature)
+            .order_by_asc(raw_events::Column::EventIndex)
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|model| {
+                Ok(RawEvent {
+                    signature: Signature::try_from(model.signature).map_err(|_| {
+                        IngesterError::ParserError("Invalid raw event signature".to_string())
+                    })?,
+                    slot: model.slot as u64,
+                    index: model.event_index as u32,
+                    kind: RawEventKind::try_from(model.kind).map_err(IngesterError::ParserError)?,
+                    data: model.data,
+                })
+            })
+            .collect::<Result<Vec<_>, IngesterError>>()?;
+
+        let txn = db.begin().await?;
+        let state_update = parse_raw_events(raw_events, &config.parser)?;
+        persist_state_update(&txn, state_update, &config.persist).await?;
+        txn.commit().await?;
+
+        reparsed_slots += slots.len() as u64;
+        info!("Reparsed raw events of slots {}-{}", first_slot, last_slot);
+        next_slot = last_slot + 1;
+    }
+    Ok(reparsed_slots)
+}
+
 async fn index_block_metadatas(
     tx: &DatabaseTransaction,
     blocks: Vec<&BlockMetadata>,
@@ -82,6 +270,7 @@ async fn index_block_metadatas(
 pub async fn index_block_batch(
     db: &DatabaseConnection,
     block_batch: &Vec<BlockInfo>,
+    config: &IngesterConfig,
 ) -> Result<(), IngesterError> {
     let blocks_len = block_batch.len();
     let tx = db.begin().await?;
@@ -89,26 +278,83 @@ pub async fn index_block_batch(
     index_block_metadatas(&tx, block_metadatas).await?;
     let mut state_updates = Vec::new();
     for block in block_batch {
-        state_updates.push(derive_block_state_update(block)?);
+        state_updates.push(derive_block_state_update(block, &config.parser)?);
     }
-    persist::persist_state_update(&tx, StateUpdate::merge_updates(state_updates)).await?;
+    let state_update = StateUpdate::merge_updates(state_updates);
+    // Messages are derived before the state update is consumed, but only published once it is
+    // committed.
+    let sink = config.state_update_sink.as_ref();
+    let sink_messages = sink.map(|_| state_update_messages(&state_update));
+    let changes = has_account_change_subscribers().then(|| account_changes(&state_update));
+    persist::persist_state_update(&tx, state_update, &config.persist).await?;
     tx.commit().await?;
+    if let (Some(sink), Some(sink_messages)) = (sink, sink_messages) {
+        sink.publish(sink_messages);
+    }
+    if let Some(changes) = changes {
+        broadcast_account_changes(changes);
+    }
     metric! {
         statsd_count!("blocks_indexed", blocks_len as i64);
     }
     Ok(())
 }
 
+// Messages of the errors returned by SQLite and Postgres when they cannot write because the
+// disk is full.
+const OUT_OF_SPACE_ERROR_MESSAGES: [&str; 3] = [
+    "database or disk is full",
+    "No space left on device",
+    "could not extend file",
+];
+
+pub fn is_out_of_space_error(error: &IngesterError) -> bool {
+    match error {
+        IngesterError::DatabaseError(message) => OUT_OF_SPACE_ERROR_MESSAGES
+            .iter()
+            .any(|out_of_space_message| message.contains(out_of_space_message)),
+        _ => false,
+    }
+}
+
 pub async fn index_block_batch_with_infinite_retries(
     db: &DatabaseConnection,
     block_batch: Vec<BlockInfo>,
+    config: &IngesterConfig,
 ) {
+    let mut out_of_space_retries: u32 = 0;
     loop {
-        match index_block_batch(db, &block_batch).await {
-            Ok(()) => return,
+        match index_block_batch(db, &block_batch, config).await {
+            Ok(()) => {
+                if out_of_space_retries > 0 {
+                    info!("Database has space again. Resuming indexing");
+                }
+                return;
+            }
             Err(e) => {
                 let start_block = block_batch.first().unwrap().metadata.slot;
                 let end_block = block_batch.last().unwrap().metadata.slot;
+                if is_out_of_space_error(&e) {
+                    // Indexing cannot progress until an operator frees space, so retries back off
+                    // instead of hammering the database.
+                    let delay_ms = (config.out_of_space_retry_base_delay.as_millis() as u64)
+                        .saturating_mul(1 << out_of_space_retries.min(32))
+                        .min(config.out_of_space_retry_max_delay.as_millis() as u64);
+                    out_of_space_retries += 1;
+                    log::error!(
+                        "CRITICAL: Database is out of space. Failed to index block batch {}-{}, \
+                        retrying in {}ms. Got error {}",
+                        start_block,
+                        end_block,
+                        delay_ms,
+                        e
+                    );
+                    metric! {
+                        statsd_count!("indexer.db_out_of_space", 1);
+                    }
+                    tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                    continue;
+                }
                 log::error!(
                     "Failed to index block batch {}-{}. Got error {}",
                     start_block,
@@ -0,0 +1,41 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use solana_sdk::signature::Signature;
+
+/// Number of recent parse failures kept in memory. Older failures are dropped.
+pub const MAX_PARSE_ERROR_SAMPLES: usize = 100;
+
+/// A transaction that failed to parse, kept so that operators can inspect recent failures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseErrorSample {
+    pub signature: Signature,
+    pub slot: u64,
+    pub error: String,
+    /// Length of the event data that failed to parse.
+    pub data_length: Option<usize>,
+}
+
+static PARSE_ERROR_SAMPLES: Lazy<Mutex<VecDeque<ParseErrorSample>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(MAX_PARSE_ERROR_SAMPLES)));
+
+pub fn record_parse_error(sample: ParseErrorSample) {
+    let mut samples = PARSE_ERROR_SAMPLES.lock().unwrap();
+    if samples.len() == MAX_PARSE_ERROR_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back(sample);
+}
+
+/// Returns up to `limit` of the most recent parse failures, newest first.
+pub fn get_recent_parse_errors(limit: usize) -> Vec<ParseErrorSample> {
+    PARSE_ERROR_SAMPLES
+        .lock()
+        .unwrap()
+        .iter()
+        .rev()
+        .take(limit)
+        .cloned()
+        .collect()
+}
@@ -1,7 +1,8 @@
 use borsh::BorshDeserialize;
 use byteorder::{ByteOrder, LittleEndian};
+use cadence_macros::statsd_count;
 use indexer_events::{IndexedMerkleTreeEvent, MerkleTreeEvent, NullifierEvent};
-use log::debug;
+use log::{debug, warn};
 use solana_sdk::{pubkey::Pubkey, signature::Signature};
 use state_update::{IndexedTreeLeafUpdate, LeafNullification};
 
@@ -12,14 +13,19 @@ use crate::common::typedefs::{
     serializable_pubkey::SerializablePubkey,
     unsigned_integer::UnsignedInteger,
 };
+use crate::metric;
 
 use super::{error::IngesterError, typedefs::block_info::TransactionInfo};
 
 use self::{
+    error_samples::{record_parse_error, ParseErrorSample},
     indexer_events::{CompressedAccount, PublicTransactionEvent},
-    state_update::{AccountTransaction, StateUpdate, Transaction},
+    state_update::{
+        AccountTransaction, EventAccounts, RawEvent, RawEventKind, StateUpdate, Transaction,
+    },
 };
 
+pub mod error_samples;
 pub mod indexer_events;
 pub mod state_update;
 
@@ -31,8 +37,71 @@ const SYSTEM_PROGRAM: Pubkey = pubkey!("11111111111111111111111111111111");
 const NOOP_PROGRAM_ID: Pubkey = pubkey!("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
 const VOTE_PROGRAM_ID: Pubkey = pubkey!("Vote111111111111111111111111111111111111111");
 
-pub fn parse_transaction(tx: &TransactionInfo, slot: u64) -> Result<StateUpdate, IngesterError> {
+/// Configures how transactions are parsed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParserConfig {
+    /// Maximum number of instructions of an instruction group that are scanned for events. Events
+    /// emitted after the cap are ignored, so the cap is only meant to protect the indexer from
+    /// pathological transactions. Without a cap, all instructions are scanned.
+    pub max_scanned_instructions_per_group: Option<usize>,
+    /// Whether malformed events fail the parsing of their whole transaction. By default, they are
+    /// logged and skipped, so that a single bad event cannot stall the indexer.
+    pub strict_parsing: bool,
+}
+
+// Blocks with fewer transactions per thread are parsed on fewer threads, since spawning a thread
+// costs more than parsing a few transactions.
+const MIN_TRANSACTIONS_PER_PARSING_THREAD: usize = 32;
+
+/// Parses the transactions of a block on up to one thread per core. The results are returned in
+/// the order of the transactions, so that merging the state updates gives the same state as
+/// parsing the transactions one after the other.
+pub fn parse_transactions(
+    transactions: &[TransactionInfo],
+    slot: u64,
+    config: &ParserConfig,
+) -> Vec<Result<StateUpdate, IngesterError>> {
+    let threads = std::thread::available_parallelism()
+        .map(|threads| threads.get())
+        .unwrap_or(1)
+        .min(transactions.len() / MIN_TRANSACTIONS_PER_PARSING_THREAD);
+    if threads <= 1 {
+        return transactions
+            .iter()
+            .map(|tx| parse_transaction(tx, slot, config))
+            .collect();
+    }
+    let chunk_size = transactions.len().div_ceil(threads);
+    std::thread::scope(|scope| {
+        let handles = transactions
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|tx| parse_transaction(tx, slot, config))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    })
+}
+
+pub fn parse_transaction(
+    tx: &TransactionInfo,
+    slot: u64,
+    config: &ParserConfig,
+) -> Result<StateUpdate, IngesterError> {
     let mut state_updates = Vec::new();
+    let mut raw_events = Vec::new();
     let mut is_compression_transaction = false;
 
     let mut logged_transaction = false;
@@ -42,10 +111,40 @@ pub fn parse_transaction(tx: &TransactionInfo, slot: u64) -> Result<StateUpdate,
         ordered_intructions.push(instruction_group.outer_instruction);
         ordered_intructions.extend(instruction_group.inner_instructions);
 
-        for (index, instruction) in ordered_intructions.iter().enumerate() {
-            if ordered_intructions.len() - index > 2 {
-                let next_instruction = &ordered_intructions[index + 1];
-                let next_next_instruction = &ordered_intructions[index + 2];
+        // Events directly follow account compression instructions, so there is no need to scan
+        // past the last of them.
+        let scanned_instructions = match ordered_intructions
+            .iter()
+            .rposition(|instruction| instruction.program_id == ACCOUNT_COMPRESSION_PROGRAM_ID)
+        {
+            Some(index) => index + 1,
+            None => continue,
+        };
+        let max_scanned_instructions = config
+            .max_scanned_instructions_per_group
+            .unwrap_or(usize::MAX);
+        if scanned_instructions > max_scanned_instructions {
+            warn!(
+                "Only scanning the first {} of {} instructions of a group of transaction {}",
+                max_scanned_instructions, scanned_instructions, tx.signature
+            );
+            metric! {
+                statsd_count!("parser.instruction_scan_capped", 1);
+            }
+        }
+
+        for (index, instruction) in ordered_intructions
+            .iter()
+            .enumerate()
+            .take(scanned_instructions.min(max_scanned_instructions))
+        {
+            // An event is emitted by the instructions directly following the account compression
+            // instruction, which may be the last instructions of the group.
+            let next_instruction = ordered_intructions.get(index + 1);
+            let next_next_instruction = ordered_intructions.get(index + 2);
+            if let (Some(next_instruction), Some(next_next_instruction)) =
+                (next_instruction, next_next_instruction)
+            {
                 // We need to check if the account compression instruction contains a noop account to determine
                 // if the instruction emits a noop event. If it doesn't then we want avoid indexing
                 // the following noop instruction because it'll contain either irrelevant or malicious data.
@@ -63,60 +162,46 @@ pub fn parse_transaction(tx: &TransactionInfo, slot: u64) -> Result<StateUpdate,
                     is_compression_transaction = true;
 
                     if tx.error.is_none() {
-                        let public_transaction_event = PublicTransactionEvent::deserialize(
-                            &mut next_next_instruction.data.as_slice(),
-                        )
-                        .map_err(|e| {
-                            IngesterError::ParserError(format!(
-                                "Failed to deserialize PublicTransactionEvent: {}",
-                                e
-                            ))
-                        })?;
-                        let state_update = parse_public_transaction_event(
-                            tx.signature,
+                        let raw_event = RawEvent {
+                            signature: tx.signature,
                             slot,
-                            public_transaction_event,
-                        )?;
-                        state_updates.push(state_update);
+                            index: raw_events.len() as u32,
+                            kind: RawEventKind::PublicTransaction,
+                            data: next_next_instruction.data.clone(),
+                        };
+                        state_updates.push(
+                            parse_raw_event(&raw_event, config)
+                                .map_err(|e| sample_parse_error(&raw_event, e))?,
+                        );
+                        raw_events.push(raw_event);
                     }
                 }
             }
-            if ordered_intructions.len() - index > 1 {
-                let next_instruction = &ordered_intructions[index + 1];
+            if let Some(next_instruction) = next_instruction {
                 if ACCOUNT_COMPRESSION_PROGRAM_ID == instruction.program_id
                     && next_instruction.program_id == NOOP_PROGRAM_ID
                 {
                     is_compression_transaction = true;
                     if tx.error.is_none() {
-                        let merkle_tree_event =
-                            MerkleTreeEvent::deserialize(&mut next_instruction.data.as_slice())
-                                .map_err(|e| {
-                                    IngesterError::ParserError(format!(
-                                        "Failed to deserialize NullifierEvent: {}",
-                                        e
-                                    ))
-                                })?;
-
-                        let state_update = match merkle_tree_event {
-                            MerkleTreeEvent::V2(nullifier_event) => {
-                                parse_nullifier_event(tx.signature, nullifier_event)?
-                            }
-                            MerkleTreeEvent::V3(indexed_merkle_tree_event) => {
-                                parse_indexed_merkle_tree_update(indexed_merkle_tree_event)?
-                            }
-                            _ => {
-                                return Err(IngesterError::ParserError(
-                                    "Expected nullifier event or merkle tree update".to_string(),
-                                ))
-                            }
+                        let raw_event = RawEvent {
+                            signature: tx.signature,
+                            slot,
+                            index: raw_events.len() as u32,
+                            kind: RawEventKind::MerkleTree,
+                            data: next_instruction.data.clone(),
                         };
-                        state_updates.push(state_update);
+                        state_updates.push(
+                            parse_raw_event(&raw_event, config)
+                                .map_err(|e| sample_parse_error(&raw_event, e))?,
+                        );
+                        raw_events.push(raw_event);
                     }
                 }
             }
         }
     }
     let mut state_update = StateUpdate::merge_updates(state_updates);
+    state_update.raw_events = raw_events;
 
     if !is_voting_transaction(tx) || is_compression_transaction {
         state_update.transactions.insert(Transaction {
@@ -130,6 +215,85 @@ pub fn parse_transaction(tx: &TransactionInfo, slot: u64) -> Result<StateUpdate,
     Ok(state_update)
 }
 
+// Keeps a sample of the failure for debugging before the error is propagated.
+fn sample_parse_error(raw_event: &RawEvent, error: IngesterError) -> IngesterError {
+    record_parse_error(ParseErrorSample {
+        signature: raw_event.signature,
+        slot: raw_event.slot,
+        error: error.to_string(),
+        data_length: Some(raw_event.data.len()),
+    });
+    error
+}
+
+/// Derives the state update of a single event emitted by the account compression program.
+pub fn parse_raw_event(
+    raw_event: &RawEvent,
+    config: &ParserConfig,
+) -> Result<StateUpdate, IngesterError> {
+    match raw_event.kind {
+        RawEventKind::PublicTransaction => {
+            let public_transaction_event = PublicTransactionEvent::deserialize(
+                &mut raw_event.data.as_slice(),
+            )
+            .map_err(|e| {
+                IngesterError::ParserError(format!(
+                    "Failed to deserialize PublicTransactionEvent: {}",
+                    e
+                ))
+            })?;
+            parse_public_transaction_event(
+                raw_event.signature,
+                raw_event.slot,
+                public_transaction_event,
+                config,
+            )
+        }
+        RawEventKind::MerkleTree => {
+            let merkle_tree_event = MerkleTreeEvent::deserialize(&mut raw_event.data.as_slice())
+                .map_err(|e| {
+                    IngesterError::ParserError(format!(
+                        "Failed to deserialize NullifierEvent: {}",
+                        e
+                    ))
+                })?;
+
+            match merkle_tree_event {
+                MerkleTreeEvent::V2(nullifier_event) => {
+                    parse_nullifier_event(raw_event.signature, nullifier_event)
+                }
+                MerkleTreeEvent::V3(indexed_merkle_tree_event) => {
+                    parse_indexed_merkle_tree_update(indexed_merkle_tree_event)
+                }
+                _ => Err(IngesterError::ParserError(
+                    "Expected nullifier event or merkle tree update".to_string(),
+                )),
+            }
+        }
+    }
+}
+
+/// Derives the state update of previously persisted raw events. The transactions of the events
+/// are marked as compression transactions since only successful compression transactions emit
+/// events.
+pub fn parse_raw_events(
+    raw_events: Vec<RawEvent>,
+    config: &ParserConfig,
+) -> Result<StateUpdate, IngesterError> {
+    let mut state_updates = Vec::new();
+    for raw_event in raw_events {
+        let mut state_update = parse_raw_event(&raw_event, config)?;
+        state_update.transactions.insert(Transaction {
+            signature: raw_event.signature,
+            slot: raw_event.slot,
+            uses_compression: true,
+            error: None,
+        });
+        state_updates.push(state_update);
+    }
+    Ok(StateUpdate::merge_updates(state_updates))
+}
+
 fn is_voting_transaction(tx: &TransactionInfo) -> bool {
     tx.instruction_groups
         .iter()
@@ -167,6 +331,7 @@ fn parse_account_data(
         leaf_index: UnsignedInteger(leaf_index as u64),
         tree: SerializablePubkey::from(tree),
         seq: UnsignedInteger(seq),
+        data_truncated: false,
     }
 }
 
@@ -239,16 +404,40 @@ fn parse_public_transaction_event(
     tx: Signature,
     slot: u64,
     transaction_event: PublicTransactionEvent,
+    config: &ParserConfig,
 ) -> Result<StateUpdate, IngesterError> {
     let PublicTransactionEvent {
         input_compressed_account_hashes,
         output_compressed_account_hashes,
         output_compressed_accounts,
+        output_leaf_indices,
         pubkey_array,
         sequence_numbers,
         ..
     } = transaction_event;
 
+    // The leaf indices of output accounts are only emitted in this event, so an account without
+    // a leaf index could not be proven. We skip or reject such events instead of dropping some of
+    // their accounts.
+    if output_compressed_account_hashes.len() != output_compressed_accounts.len()
+        || output_leaf_indices.len() != output_compressed_accounts.len()
+    {
+        let error = format!(
+            "Mismatched output accounts: {} accounts, {} hashes and {} leaf indices",
+            output_compressed_accounts.len(),
+            output_compressed_account_hashes.len(),
+            output_leaf_indices.len()
+        );
+        if config.strict_parsing {
+            return Err(IngesterError::ParserError(error));
+        }
+        warn!("Skipping malformed event of transaction {}: {}", tx, error);
+        metric! {
+            statsd_count!("parser.malformed_events_skipped", 1);
+        }
+        return Ok(StateUpdate::new());
+    }
+
     let mut state_update = StateUpdate::new();
 
     let mut tree_to_seq_number = sequence_numbers
@@ -256,14 +445,22 @@ fn parse_public_transaction_event(
         .map(|seq| (seq.pubkey, seq.seq))
         .collect::<std::collections::HashMap<_, _>>();
 
+    let mut event_accounts = EventAccounts {
+        signature: tx,
+        slot,
+        input_hashes: Vec::new(),
+        output_hashes: Vec::new(),
+    };
+
     for hash in input_compressed_account_hashes {
+        event_accounts.input_hashes.push(hash.into());
         state_update.in_accounts.insert(hash.into());
     }
 
     for ((out_account, hash), leaf_index) in output_compressed_accounts
         .into_iter()
         .zip(output_compressed_account_hashes)
-        .zip(transaction_event.output_leaf_indices.iter())
+        .zip(output_leaf_indices.iter())
     {
         let tree = pubkey_array[out_account.merkle_tree_index as usize];
         let seq = tree_to_seq_number
@@ -279,8 +476,12 @@ fn parse_public_transaction_event(
             *seq,
         );
         *seq += 1;
+        event_accounts
+            .output_hashes
+            .push(enriched_account.hash.clone());
         state_update.out_accounts.push(enriched_account);
     }
+    state_update.event_accounts.push(event_accounts);
 
     state_update
         .account_transactions
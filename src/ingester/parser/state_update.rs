@@ -63,6 +63,57 @@ pub struct IndexedTreeLeafUpdate {
     pub seq: u64,
 }
 
+/// Type of event emitted by the account compression program through the noop program.
+#[derive(Hash, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum RawEventKind {
+    PublicTransaction,
+    MerkleTree,
+}
+
+impl From<RawEventKind> for i16 {
+    fn from(kind: RawEventKind) -> i16 {
+        match kind {
+            RawEventKind::PublicTransaction => 0,
+            RawEventKind::MerkleTree => 1,
+        }
+    }
+}
+
+impl TryFrom<i16> for RawEventKind {
+    type Error = String;
+
+    fn try_from(kind: i16) -> Result<Self, Self::Error> {
+        match kind {
+            0 => Ok(RawEventKind::PublicTransaction),
+            1 => Ok(RawEventKind::MerkleTree),
+            _ => Err(format!("Unknown raw event kind {}", kind)),
+        }
+    }
+}
+
+/// Serialized event as it was emitted in a transaction. Raw events are kept so that state can be
+/// derived again with a newer parser without refetching transactions from RPC.
+#[derive(Hash, PartialEq, Eq, Debug, Clone)]
+pub struct RawEvent {
+    pub signature: Signature,
+    pub slot: u64,
+    /// Position of the event among the events of its transaction.
+    pub index: u32,
+    pub kind: RawEventKind,
+    pub data: Vec<u8>,
+}
+
+/// Accounts spent and created by a single public transaction event, in the order in which the
+/// event lists them. The output that supersedes each input is derived from them when persisting,
+/// since the event only contains the hashes of the inputs.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct EventAccounts {
+    pub signature: Signature,
+    pub slot: u64,
+    pub input_hashes: Vec<Hash>,
+    pub output_hashes: Vec<Hash>,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Eq)]
 /// Representation of state update of the compression system that is optimal for simple persistance.
 pub struct StateUpdate {
@@ -72,6 +123,8 @@ pub struct StateUpdate {
     pub transactions: HashSet<Transaction>,
     pub leaf_nullifications: HashSet<LeafNullification>,
     pub indexed_merkle_tree_updates: HashMap<(Pubkey, u64), IndexedTreeLeafUpdate>,
+    pub raw_events: Vec<RawEvent>,
+    pub event_accounts: Vec<EventAccounts>,
 }
 
 impl StateUpdate {
@@ -91,6 +144,8 @@ impl StateUpdate {
             merged
                 .leaf_nullifications
                 .extend(update.leaf_nullifications);
+            merged.raw_events.extend(update.raw_events);
+            merged.event_accounts.extend(update.event_accounts);
 
             for (key, value) in update.indexed_merkle_tree_updates {
                 // Insert only if the seq is higher.
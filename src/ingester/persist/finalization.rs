@@ -0,0 +1,92 @@
+use std::{sync::Arc, time::Duration};
+
+use cadence_macros::statsd_count;
+use log::error;
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QuerySelect, Statement,
+};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use tokio::task::JoinHandle;
+
+use crate::{
+    dao::generated::accounts,
+    ingester::{error::IngesterError, indexer::OptionalContextModel},
+    metric,
+};
+
+// Interval between checks of the finalized slot.
+const FINALIZATION_TRACKING_INTERVAL: Duration = Duration::from_secs(5);
+
+// Number of slots whose accounts are marked as finalized in a single statement, so that catching
+// up on a large backlog does not lock all of its rows at once.
+const FINALIZATION_SLOT_BATCH_SIZE: i64 = 100;
+
+/// Marks the accounts created up to `finalized_slot` as finalized. Returns the number of newly
+/// finalized accounts.
+pub async fn mark_finalized_accounts(
+    db: &DatabaseConnection,
+    finalized_slot: u64,
+) -> Result<u64, IngesterError> {
+    let finalized_slot = finalized_slot as i64;
+    let mut finalized_accounts = 0;
+    // Each batch starts at the lowest slot that still has accounts to finalize, so that slots
+    // without any are skipped.
+    while let Some(start_slot) = fetch_first_unfinalized_slot(db, finalized_slot).await? {
+        let end_slot = finalized_slot.min(start_slot + FINALIZATION_SLOT_BATCH_SIZE - 1);
+        let result = db
+            .execute(Statement::from_sql_and_values(
+                db.get_database_backend(),
+                "UPDATE accounts SET finalized = true \
+                WHERE finalized = false AND slot_created BETWEEN $1 AND $2",
+                vec![start_slot.into(), end_slot.into()],
+            ))
+            .await?;
+        finalized_accounts += result.rows_affected();
+    }
+    metric! {
+        statsd_count!("finalization.accounts_finalized", finalized_accounts);
+    }
+    Ok(finalized_accounts)
+}
+
+// Returns the lowest slot up to `finalized_slot` with accounts that are not finalized yet.
+async fn fetch_first_unfinalized_slot(
+    db: &DatabaseConnection,
+    finalized_slot: i64,
+) -> Result<Option<i64>, IngesterError> {
+    let context = accounts::Entity::find()
+        .select_only()
+        .column_as(Expr::col(accounts::Column::SlotCreated).min(), "slot")
+        .filter(accounts::Column::Finalized.eq(false))
+        .filter(accounts::Column::SlotCreated.lte(finalized_slot))
+        .into_model::<OptionalContextModel>()
+        .one(db)
+        .await?;
+    Ok(context.and_then(|context| context.slot))
+}
+
+/// Periodically marks the accounts whose slot has been finalized in the background, so that
+/// accounts indexed at a lower commitment can be told apart from the finalized ones.
+pub fn continously_track_finalization(
+    db: Arc<DatabaseConnection>,
+    rpc_client: Arc<RpcClient>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match rpc_client
+                .get_slot_with_commitment(CommitmentConfig::finalized())
+                .await
+            {
+                Ok(finalized_slot) => {
+                    if let Err(e) = mark_finalized_accounts(db.as_ref(), finalized_slot).await {
+                        error!("Failed to mark finalized accounts: {}", e);
+                    }
+                }
+                Err(e) => error!("Failed to fetch finalized slot: {}", e),
+            }
+            tokio::time::sleep(FINALIZATION_TRACKING_INTERVAL).await;
+        }
+    })
+}
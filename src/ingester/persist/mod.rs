@@ -1,9 +1,15 @@
 use super::{error, parser::state_update::AccountTransaction};
 use crate::{
     api::method::{get_multiple_new_address_proofs::ADDRESS_TREE_HEIGHT, utils::PAGE_LIMIT},
-    common::typedefs::{account::Account, hash::Hash, token_data::TokenData},
-    dao::generated::{account_transactions, state_tree_histories, state_trees, transactions},
-    ingester::parser::state_update::Transaction,
+    common::typedefs::{
+        account::Account, hash::Hash, serializable_pubkey::SerializablePubkey,
+        token_data::TokenData,
+    },
+    dao::generated::{
+        account_mappings, account_transactions, blocks, raw_events, state_tree_histories,
+        state_trees, transactions,
+    },
+    ingester::parser::state_update::{EventAccounts, RawEvent, Transaction},
     metric,
 };
 use crate::{
@@ -16,31 +22,153 @@ use light_poseidon::{Poseidon, PoseidonBytesHasher};
 use ark_bn254::Fr;
 use borsh::BorshDeserialize;
 use cadence_macros::statsd_count;
-use log::debug;
+use clap::ValueEnum;
+use log::{debug, warn};
 use persisted_indexed_merkle_tree::update_indexed_tree_leaves;
-use persisted_state_tree::{persist_leaf_nodes, LeafNode};
+use persisted_state_tree::{persist_leaf_nodes, rollback_tree_nodes, LeafNode};
 use sea_orm::{
     sea_query::{Expr, OnConflict},
-    ColumnTrait, ConnectionTrait, DatabaseBackend, DatabaseTransaction, EntityTrait, Order,
-    QueryFilter, QueryOrder, QuerySelect, QueryTrait, Set, Statement,
+    ColumnTrait, ConnectionTrait, DatabaseBackend, DatabaseTransaction, EntityTrait,
+    FromQueryResult, Order, QueryFilter, QueryOrder, QuerySelect, QueryTrait, Set, Statement,
 };
-use std::{cmp::max, collections::HashMap};
+use std::{cmp::max, collections::HashMap, fmt};
 
 use error::IngesterError;
 use solana_program::pubkey;
 use solana_sdk::{pubkey::Pubkey, signature::Signature};
 use sqlx::types::Decimal;
+pub mod finalization;
 pub mod persisted_indexed_merkle_tree;
 pub mod persisted_state_tree;
+pub mod proof_verification;
+pub mod signature_retention;
 
 const COMPRESSED_TOKEN_PROGRAM: Pubkey = pubkey!("cTokenmWW8bLPjZEBAUgYy3zKxQZW6VKi7bqNFEVv3m");
-const TREE_HEIGHT: u32 = 27;
+pub const TREE_HEIGHT: u32 = 27;
 // To avoid exceeding the 64k total parameter limit
 pub const MAX_SQL_INSERTS: usize = 500;
+// Accounts are inserted with 16 parameters each, so that batches of this size stay under the
+// 32766 parameter limit of SQLite, which is lower than the one of Postgres.
+pub const MAX_ACCOUNT_INSERT_BATCH_SIZE: usize = 2000;
+
+/// Determines what happens to the rows of accounts that are spent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OnSpend {
+    /// Mark spent accounts as spent so that they remain queryable.
+    #[default]
+    Retain,
+    /// Delete spent accounts, along with their account transactions, to save space. State tree
+    /// nodes are kept since they are still needed to prove the other leaves of the tree.
+    Delete,
+}
+
+impl fmt::Display for OnSpend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OnSpend::Retain => write!(f, "retain"),
+            OnSpend::Delete => write!(f, "delete"),
+        }
+    }
+}
+
+/// Determines what happens to output accounts whose data exceeds the maximum account data size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OversizedAccountData {
+    /// Index the account without its data. The account is still stored, with empty data, so that
+    /// its leaf and transactions keep referencing it.
+    #[default]
+    Reject,
+    /// Index the account with its data cut to the maximum size.
+    Truncate,
+}
+
+impl fmt::Display for OversizedAccountData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OversizedAccountData::Reject => write!(f, "reject"),
+            OversizedAccountData::Truncate => write!(f, "truncate"),
+        }
+    }
+}
+
+/// Configures how state updates are persisted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PersistConfig {
+    /// What happens to the rows of accounts that are spent.
+    pub on_spend: OnSpend,
+    /// Whether the raw events that state updates were derived from are persisted, so that they can
+    /// be reparsed after a parser upgrade.
+    pub persist_raw_events: bool,
+    /// Number of slots within which a signature that is seen again is treated as a duplicate of
+    /// its first sighting. A signature seen again after the window, for example because it was
+    /// included in another block after a reorg, is recorded at its new slot. Without a window, the
+    /// first sighting is always kept.
+    pub signature_dedupe_window: Option<u64>,
+    /// Number of output accounts inserted by a single statement. Larger batches take fewer
+    /// round-trips to the database for blocks with many outputs. The batch size is capped at
+    /// `MAX_ACCOUNT_INSERT_BATCH_SIZE`.
+    pub account_insert_batch_size: usize,
+    /// Maximum size of the data of persisted accounts. Accounts whose data exceeds it are
+    /// persisted according to `oversized_account_data` and flagged as having truncated data.
+    pub max_account_data_bytes: Option<u64>,
+    pub oversized_account_data: OversizedAccountData,
+}
+
+impl Default for PersistConfig {
+    fn default() -> Self {
+        Self {
+            on_spend: OnSpend::default(),
+            persist_raw_events: false,
+            signature_dedupe_window: None,
+            account_insert_batch_size: MAX_SQL_INSERTS,
+            max_account_data_bytes: None,
+            oversized_account_data: OversizedAccountData::default(),
+        }
+    }
+}
+
+// Returns the data of the account to persist and whether it was cut or omitted because it exceeds
+// the maximum account data size.
+fn limit_account_data(account: &Account, config: &PersistConfig) -> (Option<Vec<u8>>, bool) {
+    let data = match account.data.as_ref() {
+        Some(data) => &data.data.0,
+        None => return (None, false),
+    };
+    let max_account_data_bytes = match config.max_account_data_bytes {
+        Some(max_account_data_bytes) if data.len() as u64 > max_account_data_bytes => {
+            max_account_data_bytes
+        }
+        _ => return (Some(data.clone()), false),
+    };
+    if config.oversized_account_data == OversizedAccountData::Truncate {
+        warn!(
+            "Truncating the {} bytes of data of account {} to {} bytes",
+            data.len(),
+            account.hash,
+            max_account_data_bytes
+        );
+        metric! {
+            statsd_count!("oversized_account_data.truncated", 1);
+        }
+        (Some(data[..max_account_data_bytes as usize].to_vec()), true)
+    } else {
+        warn!(
+            "Rejecting the {} bytes of data of account {}, which exceed the maximum of {} bytes",
+            data.len(),
+            account.hash,
+            max_account_data_bytes
+        );
+        metric! {
+            statsd_count!("oversized_account_data.rejected", 1);
+        }
+        (None, true)
+    }
+}
 
 pub async fn persist_state_update(
     txn: &DatabaseTransaction,
     state_update: StateUpdate,
+    config: &PersistConfig,
 ) -> Result<(), IngesterError> {
     if state_update == StateUpdate::default() {
         return Ok(());
@@ -52,6 +180,8 @@ pub async fn persist_state_update(
         transactions,
         leaf_nullifications,
         indexed_merkle_tree_updates,
+        raw_events,
+        event_accounts,
     } = state_update;
 
     let input_accounts_len = in_accounts.len();
@@ -65,17 +195,47 @@ pub async fn persist_state_update(
         out_accounts.len()
     );
     debug!("Persisting output accounts...");
-    for chunk in out_accounts.chunks(MAX_SQL_INSERTS) {
-        append_output_accounts(txn, chunk).await?;
+    let account_insert_batch_size = config
+        .account_insert_batch_size
+        .clamp(1, MAX_ACCOUNT_INSERT_BATCH_SIZE);
+    for chunk in out_accounts.chunks(account_insert_batch_size) {
+        append_output_accounts(txn, chunk, config).await?;
     }
 
+    // Inputs are mapped before they are spent since spent accounts may be deleted.
+    let account_mappings = map_event_accounts(txn, &event_accounts).await?;
+
     debug!("Persisting spent accounts...");
-    for chunk in in_accounts
-        .into_iter()
-        .collect::<Vec<_>>()
-        .chunks(MAX_SQL_INSERTS)
-    {
-        spend_input_accounts(txn, chunk).await?;
+    let delete_on_spend = config.on_spend == OnSpend::Delete;
+    // An account is spent by the latest transaction that references it.
+    let signature_to_slot = transactions
+        .iter()
+        .map(|transaction| (transaction.signature, transaction.slot))
+        .collect::<HashMap<_, _>>();
+    let mut account_to_last_slot: HashMap<&Hash, u64> = HashMap::new();
+    for account_transaction in account_transactions.iter() {
+        if let Some(slot) = signature_to_slot.get(&account_transaction.signature) {
+            account_to_last_slot
+                .entry(&account_transaction.hash)
+                .and_modify(|last_slot| *last_slot = max(*last_slot, *slot))
+                .or_insert(*slot);
+        }
+    }
+    let mut in_accounts_by_slot: HashMap<Option<u64>, Vec<Hash>> = HashMap::new();
+    for hash in in_accounts.iter() {
+        in_accounts_by_slot
+            .entry(account_to_last_slot.get(hash).copied())
+            .or_default()
+            .push(hash.clone());
+    }
+    for (slot_spent, hashes) in in_accounts_by_slot {
+        for chunk in hashes.chunks(MAX_SQL_INSERTS) {
+            if delete_on_spend {
+                delete_input_accounts(txn, chunk).await?;
+            } else {
+                spend_input_accounts(txn, chunk, slot_spent).await?;
+            }
+        }
     }
 
     let account_to_transaction = account_transactions
@@ -143,11 +303,29 @@ pub async fn persist_state_update(
         )
         .collect_vec();
     for chunk in transactions_to_persist.chunks(MAX_SQL_INSERTS) {
-        persist_transactions(txn, chunk).await?;
+        persist_transactions(txn, chunk, config.signature_dedupe_window).await?;
+    }
+
+    if config.persist_raw_events {
+        debug!("Persisting raw events...");
+        for chunk in raw_events.chunks(MAX_SQL_INSERTS) {
+            persist_raw_events(txn, chunk).await?;
+        }
+    }
+
+    debug!("Persisting account mappings...");
+    for chunk in account_mappings.chunks(MAX_SQL_INSERTS) {
+        persist_account_mappings(txn, chunk).await?;
     }
 
     debug!("Persisting account transactions...");
-    let account_transactions = account_transactions.into_iter().collect::<Vec<_>>();
+    // Deleted accounts no longer have a row for their account transactions to reference.
+    let account_transactions = account_transactions
+        .into_iter()
+        .filter(|account_transaction| {
+            !delete_on_spend || !in_accounts.contains(&account_transaction.hash)
+        })
+        .collect::<Vec<_>>();
     for chunk in account_transactions.chunks(MAX_SQL_INSERTS) {
         persist_account_transactions(txn, chunk).await?;
     }
@@ -192,6 +370,285 @@ async fn persist_state_tree_history(
     Ok(())
 }
 
+#[derive(FromQueryResult)]
+struct SpentAccountModel {
+    hash: Vec<u8>,
+    slot_spent: Option<i64>,
+}
+
+/// State of the re-indexed accounts of a slot that was persisted by later slots, captured by
+/// [`delete_slot_state`] and restored by [`restore_slot_state`] once the slot is persisted again.
+pub struct RetainedSlotState {
+    spent_accounts: HashMap<Option<u64>, Vec<Hash>>,
+    account_transactions: Vec<AccountTransaction>,
+}
+
+/// Removes the state persisted for a slot so that the slot can be indexed again. All the accounts
+/// created in the slot are removed along with their token balances, so that the re-indexed slot
+/// persists them again from the block. For the accounts in `reindexed_accounts`, whether later
+/// slots spent them and the transactions of later slots that reference them are returned, to be
+/// restored with [`restore_slot_state`]. Spends performed in the slot are not reverted, but
+/// re-indexing the slot spends the same accounts again. Accounts that were deleted on spend cannot
+/// be restored, so they are indexed again as unspent.
+pub async fn delete_slot_state(
+    txn: &DatabaseTransaction,
+    slot: u64,
+    reindexed_accounts: &[Hash],
+) -> Result<RetainedSlotState, IngesterError> {
+    let reindexed_accounts = reindexed_accounts
+        .iter()
+        .map(|hash| hash.to_vec())
+        .collect::<Vec<_>>();
+    let mut spent_accounts = HashMap::<Option<u64>, Vec<Hash>>::new();
+    let mut account_transactions = Vec::new();
+    for chunk in reindexed_accounts.chunks(MAX_SQL_INSERTS) {
+        let spent_models = accounts::Entity::find()
+            .select_only()
+            .column(accounts::Column::Hash)
+            .column(accounts::Column::SlotSpent)
+            .filter(accounts::Column::SlotCreated.eq(slot as i64))
+            .filter(accounts::Column::Spent.eq(true))
+            .filter(accounts::Column::Hash.is_in(chunk.to_vec()))
+            .into_model::<SpentAccountModel>()
+            .all(txn)
+            .await?;
+        for model in spent_models {
+            let hash = Hash::try_from(model.hash).map_err(|e| {
+                IngesterError::DatabaseError(format!("Invalid account hash: {}", e))
+            })?;
+            spent_accounts
+                .entry(model.slot_spent.map(|slot_spent| slot_spent as u64))
+                .or_default()
+                .push(hash);
+        }
+
+        let transactions_in_slot = transactions::Entity::find()
+            .select_only()
+            .column(transactions::Column::Signature)
+            .filter(transactions::Column::Slot.eq(slot as i64))
+            .into_query();
+        let account_transaction_models = account_transactions::Entity::find()
+            .filter(account_transactions::Column::Hash.is_in(chunk.to_vec()))
+            .filter(account_transactions::Column::Signature.not_in_subquery(transactions_in_slot))
+            .all(txn)
+            .await?;
+        for model in account_transaction_models {
+            let hash = Hash::try_from(model.hash).map_err(|e| {
+                IngesterError::DatabaseError(format!("Invalid account hash: {}", e))
+            })?;
+            let signature = Signature::try_from(model.signature.as_slice()).map_err(|e| {
+                IngesterError::DatabaseError(format!("Invalid transaction signature: {}", e))
+            })?;
+            account_transactions.push(AccountTransaction { hash, signature });
+        }
+    }
+
+    delete_slots_state(txn, slot, slot).await?;
+    Ok(RetainedSlotState {
+        spent_accounts,
+        account_transactions,
+    })
+}
+
+/// Restores the state of the re-indexed accounts of a slot captured by [`delete_slot_state`], after
+/// the slot was persisted again. The accounts spent by later slots are spent again, which also
+/// updates the balances of their owners.
+pub async fn restore_slot_state(
+    txn: &DatabaseTransaction,
+    retained_state: RetainedSlotState,
+) -> Result<(), IngesterError> {
+    for (slot_spent, hashes) in retained_state.spent_accounts {
+        for chunk in hashes.chunks(MAX_SQL_INSERTS) {
+            spend_input_accounts(txn, chunk, slot_spent).await?;
+        }
+    }
+    for chunk in retained_state.account_transactions.chunks(MAX_SQL_INSERTS) {
+        persist_account_transactions(txn, chunk).await?;
+    }
+    Ok(())
+}
+
+#[derive(FromQueryResult)]
+struct TreeSeqModel {
+    tree: Vec<u8>,
+    seq: i64,
+}
+
+/// Rolls back the state persisted for the slots from `from_slot` to `to_slot`, which were orphaned
+/// by a reorg. Accounts spent in these slots are marked as unspent again, the state tree updates
+/// of their transactions are reverted, and the accounts, transactions and block metadata persisted
+/// for them are removed. The slots must be the last indexed ones. Accounts that were deleted on
+/// spend and address tree updates cannot be rolled back.
+pub async fn rollback_slots(
+    txn: &DatabaseTransaction,
+    from_slot: u64,
+    to_slot: u64,
+) -> Result<(), IngesterError> {
+    let (from_slot, to_slot) = (from_slot as i64, to_slot as i64);
+    let db_backend = txn.get_database_backend();
+
+    debug!(
+        "Unspending accounts spent in slots {}-{}...",
+        from_slot, to_slot
+    );
+    // Token accounts are unspent first since they are identified by the slot their account was
+    // spent in.
+    let accounts_spent_in_slots = accounts::Entity::find()
+        .select_only()
+        .column(accounts::Column::Hash)
+        .filter(accounts::Column::SlotSpent.between(from_slot, to_slot))
+        .into_query();
+    let query = token_accounts::Entity::update_many()
+        .col_expr(token_accounts::Column::Spent, Expr::value(false))
+        .col_expr(
+            token_accounts::Column::PrevSpent,
+            Expr::col(token_accounts::Column::Spent).into(),
+        )
+        .filter(token_accounts::Column::Hash.in_subquery(accounts_spent_in_slots))
+        .build(db_backend);
+    execute_account_update_query_and_update_balances(
+        txn,
+        query,
+        AccountType::TokenAccount,
+        ModificationType::Unspend,
+    )
+    .await?;
+    let query = accounts::Entity::update_many()
+        .col_expr(accounts::Column::Spent, Expr::value(false))
+        .col_expr(accounts::Column::SlotSpent, Expr::value(None::<i64>))
+        .col_expr(
+            accounts::Column::PrevSpent,
+            Expr::col(accounts::Column::Spent).into(),
+        )
+        .filter(accounts::Column::SlotSpent.between(from_slot, to_slot))
+        .build(db_backend);
+    execute_account_update_query_and_update_balances(
+        txn,
+        query,
+        AccountType::Account,
+        ModificationType::Unspend,
+    )
+    .await?;
+
+    debug!(
+        "Rolling back state tree updates in slots {}-{}...",
+        from_slot, to_slot
+    );
+    let transactions_in_slots = transactions::Entity::find()
+        .select_only()
+        .column(transactions::Column::Signature)
+        .filter(transactions::Column::Slot.between(from_slot, to_slot))
+        .into_query();
+    let first_rolled_back_seqs = state_tree_histories::Entity::find()
+        .select_only()
+        .column(state_tree_histories::Column::Tree)
+        .column_as(Expr::col(state_tree_histories::Column::Seq).min(), "seq")
+        .filter(
+            state_tree_histories::Column::TransactionSignature.in_subquery(transactions_in_slots),
+        )
+        .group_by(state_tree_histories::Column::Tree)
+        .into_model::<TreeSeqModel>()
+        .all(txn)
+        .await?;
+    for TreeSeqModel { tree, seq } in first_rolled_back_seqs {
+        let tree = SerializablePubkey::try_from(tree)
+            .map_err(|e| IngesterError::DatabaseError(format!("Invalid tree: {}", e)))?;
+        rollback_tree_nodes(txn, tree, seq as u64, TREE_HEIGHT).await?;
+    }
+
+    delete_slots_state(txn, from_slot as u64, to_slot as u64).await
+}
+
+// Removes the accounts, transactions and block metadata persisted for the slots from `from_slot`
+// to `to_slot`.
+async fn delete_slots_state(
+    txn: &DatabaseTransaction,
+    from_slot: u64,
+    to_slot: u64,
+) -> Result<(), IngesterError> {
+    let (from_slot, to_slot) = (from_slot as i64, to_slot as i64);
+    let db_backend = txn.get_database_backend();
+
+    debug!(
+        "Deleting token accounts created in slots {}-{}...",
+        from_slot, to_slot
+    );
+    let accounts_created_in_slots = accounts::Entity::find()
+        .select_only()
+        .column(accounts::Column::Hash)
+        .filter(accounts::Column::SlotCreated.between(from_slot, to_slot))
+        .into_query();
+    let query = token_accounts::Entity::delete_many()
+        .filter(token_accounts::Column::Hash.in_subquery(accounts_created_in_slots))
+        .build(db_backend);
+    execute_account_update_query_and_update_balances(
+        txn,
+        query,
+        AccountType::TokenAccount,
+        ModificationType::Delete,
+    )
+    .await?;
+
+    debug!(
+        "Deleting accounts created in slots {}-{}...",
+        from_slot, to_slot
+    );
+    let query = accounts::Entity::delete_many()
+        .filter(accounts::Column::SlotCreated.between(from_slot, to_slot))
+        .build(db_backend);
+    execute_account_update_query_and_update_balances(
+        txn,
+        query,
+        AccountType::Account,
+        ModificationType::Delete,
+    )
+    .await?;
+
+    delete_slots_transactions(txn, from_slot as u64, to_slot as u64).await
+}
+
+// Removes the transactions and block metadata persisted for the slots from `from_slot` to
+// `to_slot`.
+async fn delete_slots_transactions(
+    txn: &DatabaseTransaction,
+    from_slot: u64,
+    to_slot: u64,
+) -> Result<(), IngesterError> {
+    let (from_slot, to_slot) = (from_slot as i64, to_slot as i64);
+
+    debug!(
+        "Deleting transactions in slots {}-{}...",
+        from_slot, to_slot
+    );
+    let transactions_in_slots = transactions::Entity::find()
+        .select_only()
+        .column(transactions::Column::Signature)
+        .filter(transactions::Column::Slot.between(from_slot, to_slot))
+        .into_query();
+    account_transactions::Entity::delete_many()
+        .filter(account_transactions::Column::Signature.in_subquery(transactions_in_slots))
+        .exec(txn)
+        .await?;
+    raw_events::Entity::delete_many()
+        .filter(raw_events::Column::Slot.between(from_slot, to_slot))
+        .exec(txn)
+        .await?;
+    account_mappings::Entity::delete_many()
+        .filter(account_mappings::Column::Slot.between(from_slot, to_slot))
+        .exec(txn)
+        .await?;
+    transactions::Entity::delete_many()
+        .filter(transactions::Column::Slot.between(from_slot, to_slot))
+        .exec(txn)
+        .await?;
+    blocks::Entity::delete_many()
+        .filter(blocks::Column::Slot.between(from_slot, to_slot))
+        .exec(txn)
+        .await?;
+
+    Ok(())
+}
+
 pub fn parse_token_data(account: &Account) -> Result<Option<TokenData>, IngesterError> {
     match account.data.clone() {
         Some(data) if account.owner.0 == COMPRESSED_TOKEN_PROGRAM => {
@@ -208,10 +665,15 @@ pub fn parse_token_data(account: &Account) -> Result<Option<TokenData>, Ingester
 async fn spend_input_accounts(
     txn: &DatabaseTransaction,
     in_accounts: &[Hash],
+    slot_spent: Option<u64>,
 ) -> Result<(), IngesterError> {
     // Perform the update operation on the identified records
     let query = accounts::Entity::update_many()
         .col_expr(accounts::Column::Spent, Expr::value(true))
+        .col_expr(
+            accounts::Column::SlotSpent,
+            Expr::value(slot_spent.map(|slot| slot as i64)),
+        )
         .col_expr(
             accounts::Column::PrevSpent,
             Expr::col(accounts::Column::Spent).into(),
@@ -262,6 +724,43 @@ async fn spend_input_accounts(
     Ok(())
 }
 
+async fn delete_input_accounts(
+    txn: &DatabaseTransaction,
+    in_accounts: &[Hash],
+) -> Result<(), IngesterError> {
+    let hashes = in_accounts
+        .iter()
+        .map(|account| account.to_vec())
+        .collect::<Vec<Vec<u8>>>();
+
+    // Token accounts are deleted first since deleting the accounts would otherwise cascade to
+    // them without updating the token balances.
+    debug!("Deleting spent token accounts...");
+    let query = token_accounts::Entity::delete_many()
+        .filter(token_accounts::Column::Hash.is_in(hashes.clone()))
+        .build(txn.get_database_backend());
+    execute_account_update_query_and_update_balances(
+        txn,
+        query,
+        AccountType::TokenAccount,
+        ModificationType::Delete,
+    )
+    .await?;
+
+    let query = accounts::Entity::delete_many()
+        .filter(accounts::Column::Hash.is_in(hashes))
+        .build(txn.get_database_backend());
+    execute_account_update_query_and_update_balances(
+        txn,
+        query,
+        AccountType::Account,
+        ModificationType::Delete,
+    )
+    .await?;
+
+    Ok(())
+}
+
 pub struct EnrichedTokenAccount {
     pub token_data: TokenData,
     pub hash: Hash,
@@ -277,6 +776,8 @@ enum AccountType {
 enum ModificationType {
     Append,
     Spend,
+    Unspend,
+    Delete,
 }
 
 pub fn bytes_to_sql_format(database_backend: DatabaseBackend, bytes: Vec<u8>) -> String {
@@ -315,7 +816,7 @@ async fn execute_account_update_query_and_update_balances(
     };
 
     query.sql = format!(
-        "{} RETURNING owner,prev_spent,{}{}",
+        "{} RETURNING owner,prev_spent,spent,{}{}",
         query.sql, balance_column, additional_columns
     );
     let result = txn.query_all(query.clone()).await.map_err(|e| {
@@ -325,15 +826,19 @@ async fn execute_account_update_query_and_update_balances(
         ))
     })?;
     let multiplier = Decimal::from(match &modification_type {
-        ModificationType::Append => 1,
-        ModificationType::Spend => -1,
+        ModificationType::Append | ModificationType::Unspend => 1,
+        ModificationType::Spend | ModificationType::Delete => -1,
     });
     let mut balance_modifications = HashMap::new();
     let db_backend = txn.get_database_backend();
     for row in result {
         let prev_spent: Option<bool> = row.try_get("", "prev_spent")?;
-        match (prev_spent, &modification_type) {
-            (_, ModificationType::Append) | (Some(false), ModificationType::Spend) => {
+        let spent: bool = row.try_get("", "spent")?;
+        match (prev_spent, spent, &modification_type) {
+            (_, _, ModificationType::Append)
+            | (Some(false), _, ModificationType::Spend)
+            | (Some(true), _, ModificationType::Unspend)
+            | (_, false, ModificationType::Delete) => {
                 let mut amount_of_interest = match db_backend {
                     DatabaseBackend::Postgres => row.try_get("", balance_column)?,
                     DatabaseBackend::Sqlite => {
@@ -385,11 +890,13 @@ async fn execute_account_update_query_and_update_balances(
 async fn append_output_accounts(
     txn: &DatabaseTransaction,
     out_accounts: &[Account],
+    config: &PersistConfig,
 ) -> Result<(), IngesterError> {
     let mut account_models = Vec::new();
     let mut token_accounts = Vec::new();
 
     for account in out_accounts {
+        let (data, data_truncated) = limit_account_data(account, config);
         account_models.push(accounts::ActiveModel {
             hash: Set(account.hash.to_vec()),
             address: Set(account.address.map(|x| x.to_bytes_vec())),
@@ -397,7 +904,7 @@ async fn append_output_accounts(
                 .data
                 .as_ref()
                 .map(|x| Decimal::from(x.discriminator.0))),
-            data: Set(account.data.as_ref().map(|x| x.data.clone().0)),
+            data: Set(data),
             data_hash: Set(account.data.as_ref().map(|x| x.data_hash.to_vec())),
             tree: Set(account.tree.to_bytes_vec()),
             leaf_index: Set(account.leaf_index.0 as i64),
@@ -407,6 +914,9 @@ async fn append_output_accounts(
             slot_created: Set(account.slot_created.0 as i64),
             seq: Set(account.seq.0 as i64),
             prev_spent: Set(None),
+            slot_spent: Set(None),
+            data_truncated: Set(data_truncated),
+            finalized: Set(false),
         });
 
         if let Some(token_data) = parse_token_data(account)? {
@@ -503,6 +1013,7 @@ pub fn compute_parent_hash(left: Vec<u8>, right: Vec<u8>) -> Result<Vec<u8>, Ing
 async fn persist_transactions(
     txn: &DatabaseTransaction,
     transactions: &[Transaction],
+    signature_dedupe_window: Option<u64>,
 ) -> Result<(), IngesterError> {
     let transaction_models = transactions
         .iter()
@@ -514,6 +1025,10 @@ async fn persist_transactions(
         })
         .collect::<Vec<_>>();
 
+    if let Some(signature_dedupe_window) = signature_dedupe_window {
+        rerecord_reappeared_transactions(txn, transactions, signature_dedupe_window).await?;
+    }
+
     if !transaction_models.is_empty() {
         // We first build the query and then execute it because SeaORM has a bug where it always throws
         // an error if we do not insert a record in an insert statement. However, in this case, it's
@@ -549,6 +1064,188 @@ async fn persist_transactions(
     Ok(())
 }
 
+/// Moves already recorded signatures that reappear after the dedupe window to the slot of their
+/// new appearance. Reappearances within the window are duplicates and are ignored by the insert.
+async fn rerecord_reappeared_transactions(
+    txn: &DatabaseTransaction,
+    transactions: &[Transaction],
+    signature_dedupe_window: u64,
+) -> Result<(), IngesterError> {
+    let recorded_slots = transactions::Entity::find()
+        .filter(
+            transactions::Column::Signature.is_in(
+                transactions
+                    .iter()
+                    .map(|transaction| Into::<[u8; 64]>::into(transaction.signature).to_vec()),
+            ),
+        )
+        .all(txn)
+        .await?
+        .into_iter()
+        .map(|model| (model.signature, model.slot as u64))
+        .collect::<HashMap<_, _>>();
+
+    let mut duplicates = 0;
+    for transaction in transactions {
+        let signature = Into::<[u8; 64]>::into(transaction.signature).to_vec();
+        let recorded_slot = match recorded_slots.get(&signature) {
+            Some(recorded_slot) => *recorded_slot,
+            None => continue,
+        };
+        if transaction.slot <= recorded_slot.saturating_add(signature_dedupe_window) {
+            duplicates += 1;
+            continue;
+        }
+        transactions::Entity::update_many()
+            .col_expr(
+                transactions::Column::Slot,
+                Expr::value(transaction.slot as i64),
+            )
+            .col_expr(
+                transactions::Column::UsesCompression,
+                Expr::value(transaction.uses_compression),
+            )
+            .col_expr(
+                transactions::Column::Error,
+                Expr::value(transaction.error.clone()),
+            )
+            .filter(transactions::Column::Signature.eq(signature))
+            .exec(txn)
+            .await?;
+    }
+    metric! {
+        statsd_count!("transactions.deduplicated", duplicates);
+    }
+    Ok(())
+}
+
+async fn persist_raw_events(
+    txn: &DatabaseTransaction,
+    raw_events: &[RawEvent],
+) -> Result<(), IngesterError> {
+    let raw_event_models = raw_events
+        .iter()
+        .map(|raw_event| raw_events::ActiveModel {
+            signature: Set(Into::<[u8; 64]>::into(raw_event.signature).to_vec()),
+            event_index: Set(raw_event.index as i32),
+            slot: Set(raw_event.slot as i64),
+            kind: Set(raw_event.kind.into()),
+            data: Set(raw_event.data.clone()),
+        })
+        .collect::<Vec<_>>();
+
+    if !raw_event_models.is_empty() {
+        // We first build the query and then execute it because SeaORM has a bug where it always throws
+        // an error if we do not insert a record in an insert statement. However, in this case, it's
+        // expected not to insert anything if the key already exists.
+        let query = raw_events::Entity::insert_many(raw_event_models)
+            .on_conflict(
+                OnConflict::columns([
+                    raw_events::Column::Signature,
+                    raw_events::Column::EventIndex,
+                ])
+                .do_nothing()
+                .to_owned(),
+            )
+            .build(txn.get_database_backend());
+        txn.execute(query).await?;
+    }
+
+    Ok(())
+}
+
+#[derive(FromQueryResult)]
+struct AccountIdentityModel {
+    hash: Vec<u8>,
+    address: Option<Vec<u8>>,
+    owner: Vec<u8>,
+}
+
+/// Derives which output of each event supersedes which of its inputs. An input is superseded by
+/// the output with the same address. Inputs without an address are superseded by the outputs of
+/// the same owner without an address, in the order in which the event lists them. Inputs that
+/// were never persisted cannot be mapped.
+async fn map_event_accounts(
+    txn: &DatabaseTransaction,
+    event_accounts: &[EventAccounts],
+) -> Result<Vec<account_mappings::ActiveModel>, IngesterError> {
+    let hashes = event_accounts
+        .iter()
+        .flat_map(|event| event.input_hashes.iter().chain(event.output_hashes.iter()))
+        .map(|hash| hash.to_vec())
+        .collect::<Vec<_>>();
+    let mut accounts_by_hash = HashMap::new();
+    for chunk in hashes.chunks(MAX_SQL_INSERTS) {
+        let models = accounts::Entity::find()
+            .select_only()
+            .column(accounts::Column::Hash)
+            .column(accounts::Column::Address)
+            .column(accounts::Column::Owner)
+            .filter(accounts::Column::Hash.is_in(chunk.to_vec()))
+            .into_model::<AccountIdentityModel>()
+            .all(txn)
+            .await?;
+        accounts_by_hash.extend(models.into_iter().map(|model| (model.hash.clone(), model)));
+    }
+
+    let mut account_mappings = Vec::new();
+    for event in event_accounts {
+        let outputs = event
+            .output_hashes
+            .iter()
+            .filter_map(|hash| accounts_by_hash.get(&hash.to_vec()))
+            .collect::<Vec<_>>();
+        let mut superseding = vec![false; outputs.len()];
+        for input in event.input_hashes.iter() {
+            let input = match accounts_by_hash.get(&input.to_vec()) {
+                Some(input) => input,
+                None => continue,
+            };
+            let position = (0..outputs.len()).find(|&i| {
+                !superseding[i]
+                    && match &input.address {
+                        Some(address) => outputs[i].address.as_ref() == Some(address),
+                        None => outputs[i].address.is_none() && outputs[i].owner == input.owner,
+                    }
+            });
+            if let Some(position) = position {
+                superseding[position] = true;
+                account_mappings.push(account_mappings::ActiveModel {
+                    signature: Set(Into::<[u8; 64]>::into(event.signature).to_vec()),
+                    input_hash: Set(input.hash.clone()),
+                    output_hash: Set(outputs[position].hash.clone()),
+                    slot: Set(event.slot as i64),
+                });
+            }
+        }
+    }
+    Ok(account_mappings)
+}
+
+async fn persist_account_mappings(
+    txn: &DatabaseTransaction,
+    account_mappings: &[account_mappings::ActiveModel],
+) -> Result<(), IngesterError> {
+    if !account_mappings.is_empty() {
+        // We first build the query and then execute it because SeaORM has a bug where it always throws
+        // an error if we do not insert a record in an insert statement. However, in this case, it's
+        // expected not to insert anything if the key already exists.
+        let query = account_mappings::Entity::insert_many(account_mappings.to_vec())
+            .on_conflict(
+                OnConflict::columns([
+                    account_mappings::Column::Signature,
+                    account_mappings::Column::InputHash,
+                ])
+                .do_nothing()
+                .to_owned(),
+            )
+            .build(txn.get_database_backend());
+        txn.execute(query).await?;
+    }
+
+    Ok(())
+}
+
 async fn persist_account_transactions(
     txn: &DatabaseTransaction,
     account_transactions: &[AccountTransaction],
@@ -1,7 +1,12 @@
-use std::{cmp::max, collections::HashMap};
+use std::{
+    cmp::max,
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
 
 use cadence_macros::statsd_count;
 use itertools::Itertools;
+use once_cell::sync::Lazy;
 use sea_orm::{
     sea_query::OnConflict, ColumnTrait, ConnectionTrait, DatabaseTransaction, DbErr, EntityTrait,
     QueryFilter, QueryTrait, Set, Statement, TransactionTrait, Value,
@@ -12,12 +17,12 @@ use utoipa::ToSchema;
 use crate::{
     api::error::PhotonApiError,
     common::typedefs::{account::Account, hash::Hash, serializable_pubkey::SerializablePubkey},
-    dao::generated::state_trees,
+    dao::generated::{accounts, state_tree_histories, state_trees},
     ingester::{error::IngesterError, parser::state_update::LeafNullification},
     metric,
 };
 
-use super::{compute_parent_hash, get_node_direct_ancestors};
+use super::{compute_parent_hash, get_node_direct_ancestors, MAX_SQL_INSERTS};
 
 #[derive(Clone, Debug)]
 pub struct LeafNode {
@@ -33,7 +38,7 @@ impl LeafNode {
     }
 }
 
-fn leaf_index_to_node_index(leaf_index: u32, tree_height: u32) -> i64 {
+pub(crate) fn leaf_index_to_node_index(leaf_index: u32, tree_height: u32) -> i64 {
     2_i64.pow(tree_height - 1) + leaf_index as i64
 }
 
@@ -170,9 +175,186 @@ pub async fn persist_leaf_nodes(
     txn.execute(query).await.map_err(|e| {
         IngesterError::DatabaseError(format!("Failed to persist path nodes: {}", e))
     })?;
+
+    let trees = leaf_nodes
+        .iter()
+        .map(|leaf_node| leaf_node.tree.to_bytes_vec())
+        .unique()
+        .collect::<Vec<_>>();
+    refresh_prewarmed_trees(txn, &trees).await?;
+    Ok(())
+}
+
+/// Restores a state tree to its state before the update with sequence number `seq`, by removing
+/// all updates with at least that sequence number. Each leaf they updated is restored to its
+/// latest earlier version, which is the account appended with that sequence number, or a
+/// nullified leaf if there is no such account. Leaves without an earlier version are reset to
+/// empty leaves, and the paths of all restored leaves are recomputed.
+pub async fn rollback_tree_nodes(
+    txn: &DatabaseTransaction,
+    tree: SerializablePubkey,
+    seq: u64,
+    tree_height: u32,
+) -> Result<(), IngesterError> {
+    let seq = seq as i64;
+    let leaf_indices = state_trees::Entity::find()
+        .filter(
+            state_trees::Column::Tree
+                .eq(tree.to_bytes_vec())
+                .and(state_trees::Column::Seq.gte(seq))
+                .and(state_trees::Column::Level.eq(0)),
+        )
+        .all(txn)
+        .await?
+        .into_iter()
+        .filter_map(|node| node.leaf_idx)
+        .collect::<Vec<_>>();
+    state_trees::Entity::delete_many()
+        .filter(
+            state_trees::Column::Tree
+                .eq(tree.to_bytes_vec())
+                .and(state_trees::Column::Seq.gte(seq)),
+        )
+        .exec(txn)
+        .await?;
+    state_tree_histories::Entity::delete_many()
+        .filter(
+            state_tree_histories::Column::Tree
+                .eq(tree.to_bytes_vec())
+                .and(state_tree_histories::Column::Seq.gte(seq)),
+        )
+        .exec(txn)
+        .await?;
+
+    let mut previous_seqs: HashMap<i64, i64> = HashMap::new();
+    let mut account_hashes: HashMap<(i64, i64), Vec<u8>> = HashMap::new();
+    for chunk in leaf_indices.chunks(MAX_SQL_INSERTS) {
+        let histories = state_tree_histories::Entity::find()
+            .filter(
+                state_tree_histories::Column::Tree
+                    .eq(tree.to_bytes_vec())
+                    .and(state_tree_histories::Column::LeafIdx.is_in(chunk.to_vec())),
+            )
+            .all(txn)
+            .await?;
+        for history in histories {
+            previous_seqs
+                .entry(history.leaf_idx)
+                .and_modify(|previous_seq| *previous_seq = max(*previous_seq, history.seq))
+                .or_insert(history.seq);
+        }
+        let accounts = accounts::Entity::find()
+            .filter(
+                accounts::Column::Tree
+                    .eq(tree.to_bytes_vec())
+                    .and(accounts::Column::LeafIndex.is_in(chunk.to_vec())),
+            )
+            .all(txn)
+            .await?;
+        for account in accounts {
+            account_hashes.insert((account.leaf_index, account.seq), account.hash);
+        }
+    }
+
+    let leaf_nodes = leaf_indices
+        .into_iter()
+        .map(|leaf_index| {
+            let (hash, seq) = match previous_seqs.get(&leaf_index) {
+                Some(&seq) => match account_hashes.remove(&(leaf_index, seq)) {
+                    Some(hash) => (
+                        Hash::try_from(hash).map_err(|e| {
+                            IngesterError::DatabaseError(format!("Invalid account hash: {}", e))
+                        })?,
+                        seq,
+                    ),
+                    None => (Hash::from(ZERO_BYTES[0]), seq),
+                },
+                None => (Hash::from(ZERO_BYTES[0]), 0),
+            };
+            Ok(LeafNode {
+                tree,
+                leaf_index: leaf_index as u32,
+                hash,
+                seq: seq as u32,
+            })
+        })
+        .collect::<Result<Vec<_>, IngesterError>>()?;
+    for chunk in leaf_nodes.chunks(MAX_SQL_INSERTS) {
+        persist_leaf_nodes(txn, chunk.to_vec(), tree_height).await?;
+    }
     Ok(())
 }
 
+/// Number of upper levels of a prewarmed tree, counting the root, whose nodes are kept in memory.
+/// These nodes are shared by the proofs of all leaves of the tree, so only the lower part of each
+/// proof path needs to be read from the database.
+pub const PREWARM_LEVELS: u32 = 10;
+
+type CachedNodes = Arc<HashMap<i64, state_trees::Model>>;
+
+// Maps each prewarmed tree to its cached upper nodes, keyed by node index. Trees that have not
+// been loaded yet map to `None`.
+static PREWARMED_TREES: Lazy<RwLock<HashMap<Vec<u8>, Option<CachedNodes>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Caches the upper nodes of `trees` in memory to speed up proof requests. The cache is refreshed
+/// whenever nodes of those trees are persisted.
+pub async fn prewarm_trees<T: ConnectionTrait>(
+    conn: &T,
+    trees: &[SerializablePubkey],
+) -> Result<(), DbErr> {
+    let trees = trees
+        .iter()
+        .map(|tree| tree.to_bytes_vec())
+        .collect::<Vec<_>>();
+    {
+        let mut prewarmed_trees = PREWARMED_TREES.write().unwrap();
+        for tree in trees.iter() {
+            prewarmed_trees.entry(tree.clone()).or_insert(None);
+        }
+    }
+    refresh_prewarmed_trees(conn, &trees).await
+}
+
+/// Reloads the cached upper nodes of those of `trees` that are prewarmed. Cached nodes are only
+/// used for proofs whose root in the database matches the cached root, so the cache may be
+/// refreshed from a transaction that has not been committed yet.
+async fn refresh_prewarmed_trees<T: ConnectionTrait>(
+    conn: &T,
+    trees: &[Vec<u8>],
+) -> Result<(), DbErr> {
+    let trees = {
+        let prewarmed_trees = PREWARMED_TREES.read().unwrap();
+        trees
+            .iter()
+            .filter(|tree| prewarmed_trees.contains_key(*tree))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    for tree in trees {
+        let nodes = state_trees::Entity::find()
+            .filter(
+                state_trees::Column::Tree
+                    .eq(tree.clone())
+                    .and(state_trees::Column::NodeIdx.lt(1_i64 << PREWARM_LEVELS)),
+            )
+            .all(conn)
+            .await?
+            .into_iter()
+            .map(|node| (node.node_idx, node))
+            .collect::<HashMap<_, _>>();
+        PREWARMED_TREES
+            .write()
+            .unwrap()
+            .insert(tree, Some(Arc::new(nodes)));
+    }
+    Ok(())
+}
+
+fn get_cached_nodes(tree: &[u8]) -> Option<CachedNodes> {
+    PREWARMED_TREES.read().unwrap().get(tree).cloned().flatten()
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(deny_unknown_fields, rename_all = "camelCase")]
 #[allow(non_snake_case)]
@@ -262,7 +444,7 @@ pub async fn get_multiple_compressed_leaf_proofs_from_full_leaf_info(
         })
         .collect::<HashMap<(Vec<u8>, i64), Vec<i64>>>();
 
-    let node_to_model = get_proof_nodes(
+    let node_to_model = get_proof_nodes_using_prewarmed_trees(
         txn,
         leaf_nodes_with_node_index
             .iter()
@@ -333,12 +515,33 @@ pub async fn get_multiple_compressed_leaf_proofs_from_full_leaf_info(
 }
 
 pub fn validate_proof(proof: &MerkleProofWithContext) -> Result<(), PhotonApiError> {
-    let leaf_index = proof.leafIndex;
-    let tree_height = (proof.proof.len() + 1) as u32;
+    let computed_root = compute_root(&proof.hash, &proof.proof, proof.leafIndex)?;
+
+    if computed_root != proof.root.to_vec() {
+        metric! {
+            statsd_count!("invalid_proof", 1);
+        }
+        return Err(PhotonApiError::UnexpectedError(format!(
+            "Computed root does not match the provided root. Proof; {:?}",
+            proof
+        )));
+    }
+
+    Ok(())
+}
+
+/// Computes the root of the tree from a leaf and the siblings along its path, ordered from the
+/// leaf level up.
+pub fn compute_root(
+    leaf: &Hash,
+    siblings: &[Hash],
+    leaf_index: u32,
+) -> Result<Vec<u8>, PhotonApiError> {
+    let tree_height = (siblings.len() + 1) as u32;
     let node_index = leaf_index_to_node_index(leaf_index, tree_height);
-    let mut computed_root = proof.hash.to_vec();
+    let mut computed_root = leaf.to_vec();
 
-    for (idx, node) in proof.proof.iter().enumerate() {
+    for (idx, node) in siblings.iter().enumerate() {
         let is_left = (node_index >> idx) & 1 == 0;
         computed_root = compute_parent_hash(
             if is_left {
@@ -360,17 +563,7 @@ pub fn validate_proof(proof: &MerkleProofWithContext) -> Result<(), PhotonApiErr
         })?;
     }
 
-    if computed_root != proof.root.to_vec() {
-        metric! {
-            statsd_count!("invalid_proof", 1);
-        }
-        return Err(PhotonApiError::UnexpectedError(format!(
-            "Computed root does not match the provided root. Proof; {:?}",
-            proof
-        )));
-    }
-
-    Ok(())
+    Ok(computed_root)
 }
 
 pub fn get_proof_path(index: i64, include_leaf: bool) -> Vec<i64> {
@@ -415,10 +608,83 @@ where
         .dedup()
         .collect::<Vec<(Vec<u8>, i64)>>();
 
+    get_nodes(txn_or_conn, all_required_node_indices).await
+}
+
+/// Same as [`get_proof_nodes`], except that the upper nodes of prewarmed trees are served from
+/// memory. They are only read from the database if the root of a tree changed since it was cached.
+async fn get_proof_nodes_using_prewarmed_trees<T>(
+    txn_or_conn: &T,
+    leaf_nodes_locations: Vec<(Vec<u8>, i64)>,
+    include_leafs: bool,
+) -> Result<HashMap<(Vec<u8>, i64), state_trees::Model>, DbErr>
+where
+    T: ConnectionTrait + TransactionTrait,
+{
+    let cached_trees = leaf_nodes_locations
+        .iter()
+        .map(|(tree, _)| tree.clone())
+        .unique()
+        .filter_map(|tree| get_cached_nodes(&tree).map(|nodes| (tree, nodes)))
+        .collect::<HashMap<_, _>>();
+    if cached_trees.is_empty() {
+        return get_proof_nodes(txn_or_conn, leaf_nodes_locations, include_leafs).await;
+    }
+
+    let is_cached_node = |tree: &Vec<u8>, idx: i64| {
+        idx > 1 && idx < 1 << PREWARM_LEVELS && cached_trees.contains_key(tree)
+    };
+    let (cached_node_locations, uncached_node_locations): (Vec<_>, Vec<_>) = leaf_nodes_locations
+        .iter()
+        .flat_map(|(tree, index)| {
+            get_proof_path(*index, include_leafs)
+                .into_iter()
+                .map(move |idx| (tree.clone(), idx))
+        })
+        .unique()
+        .partition(|(tree, idx)| is_cached_node(tree, *idx));
+
+    let mut nodes = get_nodes(txn_or_conn, uncached_node_locations).await?;
+    let mut stale_node_locations = vec![];
+    for (tree, idx) in cached_node_locations {
+        let cached_nodes = &cached_trees[&tree];
+        let root_is_current = match (cached_nodes.get(&1), nodes.get(&(tree.clone(), 1))) {
+            (Some(cached_root), Some(root)) => cached_root.hash == root.hash,
+            _ => false,
+        };
+        match (root_is_current, cached_nodes.get(&idx)) {
+            (true, Some(node)) => {
+                nodes.insert((tree, idx), node.clone());
+            }
+            // The tree has no node at this index, so the proof uses the zero hash of its level.
+            (true, None) => {}
+            (false, _) => stale_node_locations.push((tree, idx)),
+        }
+    }
+    if !stale_node_locations.is_empty() {
+        metric! {
+            statsd_count!("prewarmed_tree_cache_miss", 1);
+        }
+        nodes.extend(get_nodes(txn_or_conn, stale_node_locations).await?);
+    }
+    Ok(nodes)
+}
+
+async fn get_nodes<T>(
+    txn_or_conn: &T,
+    node_locations: Vec<(Vec<u8>, i64)>,
+) -> Result<HashMap<(Vec<u8>, i64), state_trees::Model>, DbErr>
+where
+    T: ConnectionTrait + TransactionTrait,
+{
+    if node_locations.is_empty() {
+        return Ok(HashMap::new());
+    }
+
     let mut params = Vec::new();
     let mut placeholders = Vec::new();
 
-    for (index, (tree, node_idx)) in all_required_node_indices.into_iter().enumerate() {
+    for (index, (tree, node_idx)) in node_locations.into_iter().enumerate() {
         let param_index = index * 2; // each pair contributes two parameters
         params.push(Value::from(tree));
         params.push(Value::from(node_idx));
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use cadence_macros::statsd_count;
+use log::{error, info};
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+    TransactionTrait,
+};
+
+use crate::{
+    common::typedefs::{hash::Hash, serializable_pubkey::SerializablePubkey},
+    dao::generated::{accounts, state_trees},
+    ingester::error::IngesterError,
+    metric,
+};
+
+use super::{
+    persisted_state_tree::{
+        compute_root, get_proof_nodes, get_proof_path, leaf_index_to_node_index, ZERO_BYTES,
+    },
+    TREE_HEIGHT,
+};
+
+// Number of accounts whose proofs are loaded from the database at once.
+const VERIFICATION_BATCH_SIZE: u64 = 1000;
+
+/// An unspent account whose proof does not reconstruct the stored root of its tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofInconsistency {
+    pub hash: Hash,
+    pub tree: SerializablePubkey,
+    pub leaf_index: u64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProofVerificationReport {
+    pub verified_accounts: u64,
+    pub inconsistencies: Vec<ProofInconsistency>,
+}
+
+/// Verifies that the proof of every unspent account reconstructs the stored root of its tree.
+/// Only a random `sample_rate` fraction of the accounts is verified, so that a rate of 1.0
+/// verifies all of them. Every account is read, so this is expensive on large databases.
+pub async fn verify_persisted_proofs(
+    db: &DatabaseConnection,
+    sample_rate: f64,
+) -> Result<ProofVerificationReport, IngesterError> {
+    let mut report = ProofVerificationReport::default();
+    let mut cursor: Option<Vec<u8>> = None;
+    loop {
+        let mut filter = accounts::Column::Spent.eq(false);
+        if let Some(cursor) = cursor {
+            filter = filter.and(accounts::Column::Hash.gt(cursor));
+        }
+        let page = accounts::Entity::find()
+            .filter(filter)
+            .order_by_asc(accounts::Column::Hash)
+            .limit(VERIFICATION_BATCH_SIZE)
+            .all(db)
+            .await?;
+        let sampled_accounts = page
+            .iter()
+            .filter(|_| sample_rate >= 1.0 || rand::random::<f64>() < sample_rate)
+            .collect::<Vec<_>>();
+
+        if !sampled_accounts.is_empty() {
+            let leaf_locations = sampled_accounts
+                .iter()
+                .map(|account| {
+                    (
+                        account.tree.clone(),
+                        leaf_index_to_node_index(account.leaf_index as u32, TREE_HEIGHT),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let txn = db.begin().await?;
+            let nodes = get_proof_nodes(&txn, leaf_locations, true).await?;
+            txn.commit().await?;
+
+            for account in sampled_accounts {
+                report.verified_accounts += 1;
+                if let Some(reason) = find_proof_inconsistency(account, &nodes) {
+                    report.inconsistencies.push(ProofInconsistency {
+                        hash: Hash::try_from(account.hash.clone()).unwrap_or_default(),
+                        tree: SerializablePubkey::try_from(account.tree.clone())
+                            .unwrap_or_default(),
+                        leaf_index: account.leaf_index as u64,
+                        reason,
+                    });
+                }
+            }
+        }
+
+        cursor = match page.last() {
+            Some(account) if page.len() as u64 == VERIFICATION_BATCH_SIZE => {
+                info!(
+                    "Verified the proofs of {} accounts...",
+                    report.verified_accounts
+                );
+                Some(account.hash.clone())
+            }
+            _ => break,
+        };
+    }
+
+    for inconsistency in report.inconsistencies.iter() {
+        error!(
+            "Inconsistent proof for account {} at leaf {} of tree {}: {}",
+            inconsistency.hash, inconsistency.leaf_index, inconsistency.tree, inconsistency.reason
+        );
+    }
+    metric! {
+        statsd_count!("proof_verification.verified_accounts", report.verified_accounts);
+        statsd_count!("proof_verification.inconsistencies", report.inconsistencies.len() as u64);
+    }
+    Ok(report)
+}
+
+// Returns why the proof of the account does not reconstruct the stored root of its tree, if it
+// does not.
+fn find_proof_inconsistency(
+    account: &accounts::Model,
+    nodes: &HashMap<(Vec<u8>, i64), state_trees::Model>,
+) -> Option<String> {
+    let tree = &account.tree;
+    let node_index = leaf_index_to_node_index(account.leaf_index as u32, TREE_HEIGHT);
+    match nodes.get(&(tree.clone(), node_index)) {
+        Some(leaf) if leaf.hash == account.hash => {}
+        _ => return Some("The leaf of the account does not match its hash".to_string()),
+    }
+    let root = match nodes.get(&(tree.clone(), 1)) {
+        Some(root) => &root.hash,
+        None => return Some("The tree of the account has no root".to_string()),
+    };
+
+    let mut path = get_proof_path(node_index, false);
+    // The path ends with the root.
+    path.pop();
+    let siblings = path
+        .iter()
+        .enumerate()
+        .map(|(level, idx)| {
+            let hash = nodes
+                .get(&(tree.clone(), *idx))
+                .map(|node| node.hash.clone())
+                .unwrap_or(ZERO_BYTES[level].to_vec());
+            Hash::try_from(hash).map_err(|e| e.to_string())
+        })
+        .collect::<Result<Vec<_>, _>>();
+    let computed_root = siblings.and_then(|siblings| {
+        let leaf = Hash::try_from(account.hash.clone()).map_err(|e| e.to_string())?;
+        compute_root(&leaf, &siblings, account.leaf_index as u32).map_err(|e| e.to_string())
+    });
+    match computed_root {
+        Ok(computed_root) if &computed_root == root => None,
+        Ok(_) => Some("The proof of the account does not reconstruct the stored root".to_string()),
+        Err(e) => Some(format!("Failed to compute the root: {}", e)),
+    }
+}
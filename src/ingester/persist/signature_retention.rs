@@ -0,0 +1,106 @@
+use std::{sync::Arc, time::Duration};
+
+use cadence_macros::statsd_count;
+use log::{error, info};
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QuerySelect, Statement,
+};
+use tokio::task::JoinHandle;
+
+use crate::{
+    dao::generated::transactions,
+    ingester::{
+        error::IngesterError,
+        indexer::{fetch_last_indexed_slot_with_infinite_retry, OptionalContextModel},
+    },
+    metric,
+};
+
+// Interval between prunings of the signature index.
+const SIGNATURE_PRUNING_INTERVAL: Duration = Duration::from_secs(60);
+
+// Number of slots whose transactions are pruned in a single statement, so that pruning a large
+// backlog does not lock all of its rows at once.
+const PRUNING_SLOT_BATCH_SIZE: i64 = 100;
+
+/// Deletes the transactions indexed more than `retention_slots` slots before the last indexed
+/// slot, along with the account transactions, raw events and account mappings that reference
+/// them. Transactions of unspent accounts are kept so that the history of live accounts remains
+/// queryable. Returns the number of deleted transactions.
+pub async fn prune_signatures(
+    db: &DatabaseConnection,
+    retention_slots: u64,
+) -> Result<u64, IngesterError> {
+    let last_indexed_slot = match fetch_last_indexed_slot_with_infinite_retry(db).await {
+        Some(last_indexed_slot) => last_indexed_slot as u64,
+        None => return Ok(0),
+    };
+    let oldest_retained_slot = last_indexed_slot.saturating_sub(retention_slots) as i64;
+    let mut pruned_signatures = 0;
+    let mut next_slot = 0;
+    // Batches move forward past the transactions that are kept, so that each transaction is only
+    // visited once.
+    while let Some(start_slot) =
+        fetch_first_transaction_slot(db, next_slot, oldest_retained_slot).await?
+    {
+        let end_slot = oldest_retained_slot.min(start_slot + PRUNING_SLOT_BATCH_SIZE);
+        let result = db
+            .execute(Statement::from_sql_and_values(
+                db.get_database_backend(),
+                "
+                DELETE FROM transactions
+                WHERE slot >= $1 AND slot < $2
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM account_transactions
+                    JOIN accounts ON accounts.hash = account_transactions.hash
+                    WHERE account_transactions.signature = transactions.signature
+                    AND accounts.spent = false
+                )
+                ",
+                vec![start_slot.into(), end_slot.into()],
+            ))
+            .await?;
+        pruned_signatures += result.rows_affected();
+        next_slot = end_slot;
+    }
+    metric! {
+        statsd_count!("signature_retention.pruned", pruned_signatures);
+    }
+    Ok(pruned_signatures)
+}
+
+// Returns the lowest slot from `from_slot` up to, but excluding, `to_slot` that has transactions.
+async fn fetch_first_transaction_slot(
+    db: &DatabaseConnection,
+    from_slot: i64,
+    to_slot: i64,
+) -> Result<Option<i64>, IngesterError> {
+    let context = transactions::Entity::find()
+        .select_only()
+        .column_as(Expr::col(transactions::Column::Slot).min(), "slot")
+        .filter(transactions::Column::Slot.gte(from_slot))
+        .filter(transactions::Column::Slot.lt(to_slot))
+        .into_model::<OptionalContextModel>()
+        .one(db)
+        .await?;
+    Ok(context.and_then(|context| context.slot))
+}
+
+/// Periodically prunes the signatures older than `retention_slots` slots in the background.
+pub fn continously_prune_signatures(
+    db: Arc<DatabaseConnection>,
+    retention_slots: u64,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match prune_signatures(db.as_ref(), retention_slots).await {
+                Ok(0) => {}
+                Ok(pruned_signatures) => info!("Pruned {} signatures", pruned_signatures),
+                Err(e) => error!("Failed to prune signatures: {}", e),
+            }
+            tokio::time::sleep(SIGNATURE_PRUNING_INTERVAL).await;
+        }
+    })
+}
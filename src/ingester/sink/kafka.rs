@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rdkafka::config::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+
+use super::MessageProducer;
+
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct KafkaProducer {
+    producer: FutureProducer,
+    topic: String,
+}
+
+impl KafkaProducer {
+    pub fn new(brokers: &str, topic: String) -> Result<Self, String> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set(
+                "message.timeout.ms",
+                MESSAGE_TIMEOUT.as_millis().to_string(),
+            )
+            .create()
+            .map_err(|e| format!("Failed to create Kafka producer: {}", e))?;
+        Ok(Self { producer, topic })
+    }
+}
+
+#[async_trait]
+impl MessageProducer for KafkaProducer {
+    async fn send(&self, key: &str, payload: &[u8]) -> Result<(), String> {
+        self.producer
+            .send(
+                FutureRecord::to(&self.topic).key(key).payload(payload),
+                Duration::from_secs(0),
+            )
+            .await
+            .map(|_| ())
+            .map_err(|(e, _)| e.to_string())
+    }
+}
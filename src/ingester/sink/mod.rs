@@ -0,0 +1,262 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash as _, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use cadence_macros::statsd_count;
+use log::{error, warn};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::common::typedefs::account::Account;
+use crate::common::typedefs::hash::Hash;
+use crate::metric;
+
+use super::parser::state_update::StateUpdate;
+
+#[cfg(feature = "kafka")]
+pub mod kafka;
+
+pub const DEFAULT_SINK_BUFFER_SIZE: usize = 10_000;
+const MAX_PUBLISH_ATTEMPTS: u32 = 5;
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(100);
+// Number of messages published concurrently. Messages are spread over this many lanes by key and
+// each lane publishes its messages one after the other, so that the messages of an account are
+// published in the order they were persisted.
+const PUBLISH_LANES: usize = 32;
+// Number of account changes buffered for each subscriber that has not received them yet.
+const ACCOUNT_CHANGES_CAPACITY: usize = 16;
+
+/// A change to a single account, published once the state update that contains it is persisted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum StateUpdateMessage {
+    AccountCreated { account: Account },
+    AccountSpent { hash: Hash },
+}
+
+/// A change to an account, sent to the in-process subscribers of the account once it is persisted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountChange {
+    pub hash: Hash,
+    pub message: StateUpdateMessage,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinkMessage {
+    /// Base58 encoded hash of the account the message is about.
+    pub key: String,
+    /// JSON encoded [`StateUpdateMessage`].
+    pub payload: Vec<u8>,
+}
+
+/// Publishes messages to an external system such as Kafka.
+#[async_trait]
+pub trait MessageProducer: Send + Sync {
+    async fn send(&self, key: &str, payload: &[u8]) -> Result<(), String>;
+}
+
+/// Splits a state update into one change per created or spent account.
+pub fn account_changes(state_update: &StateUpdate) -> Vec<AccountChange> {
+    let created = state_update.out_accounts.iter().map(|account| {
+        (
+            account.hash.clone(),
+            StateUpdateMessage::AccountCreated {
+                account: account.clone(),
+            },
+        )
+    });
+    let spent = state_update.in_accounts.iter().map(|hash| {
+        (
+            hash.clone(),
+            StateUpdateMessage::AccountSpent { hash: hash.clone() },
+        )
+    });
+    created
+        .chain(spent)
+        .map(|(hash, message)| AccountChange { hash, message })
+        .collect()
+}
+
+/// Splits a state update into one message per created or spent account.
+pub fn state_update_messages(state_update: &StateUpdate) -> Vec<SinkMessage> {
+    account_changes(state_update)
+        .into_iter()
+        .map(|change| SinkMessage {
+            key: change.hash.to_string(),
+            payload: serde_json::to_vec(&change.message).unwrap(),
+        })
+        .collect()
+}
+
+// Subscribers keyed by the hash of the account they are subscribed to, so that changes are only
+// queued for the subscribers of the changed account.
+type AccountChangeSubscribers = HashMap<Hash, Vec<mpsc::Sender<StateUpdateMessage>>>;
+
+static ACCOUNT_CHANGE_SUBSCRIBERS: Lazy<Mutex<AccountChangeSubscribers>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Receives the changes of a single account. Dropping it ends the subscription.
+pub struct AccountChangeReceiver {
+    hash: Hash,
+    receiver: mpsc::Receiver<StateUpdateMessage>,
+}
+
+impl AccountChangeReceiver {
+    /// Returns the next change of the account, or `None` once the subscription has ended because
+    /// the subscriber fell more than `ACCOUNT_CHANGES_CAPACITY` changes behind.
+    pub async fn recv(&mut self) -> Option<StateUpdateMessage> {
+        self.receiver.recv().await
+    }
+}
+
+impl Drop for AccountChangeReceiver {
+    fn drop(&mut self) {
+        self.receiver.close();
+        let mut subscribers = ACCOUNT_CHANGE_SUBSCRIBERS.lock().unwrap();
+        if let Some(senders) = subscribers.get_mut(&self.hash) {
+            senders.retain(|sender| !sender.is_closed());
+            if senders.is_empty() {
+                subscribers.remove(&self.hash);
+            }
+        }
+    }
+}
+
+/// Subscribes to the changes of the account with `hash` persisted from now on.
+pub fn subscribe_to_account_changes(hash: Hash) -> AccountChangeReceiver {
+    let (sender, receiver) = mpsc::channel(ACCOUNT_CHANGES_CAPACITY);
+    ACCOUNT_CHANGE_SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .entry(hash.clone())
+        .or_default()
+        .push(sender);
+    AccountChangeReceiver { hash, receiver }
+}
+
+pub fn has_account_change_subscribers() -> bool {
+    !ACCOUNT_CHANGE_SUBSCRIBERS.lock().unwrap().is_empty()
+}
+
+pub fn broadcast_account_changes(changes: Vec<AccountChange>) {
+    let mut subscribers = ACCOUNT_CHANGE_SUBSCRIBERS.lock().unwrap();
+    for change in changes {
+        let senders = match subscribers.get_mut(&change.hash) {
+            Some(senders) => senders,
+            None => continue,
+        };
+        // Subscribers that fall behind are dropped, which ends their subscription once they have
+        // received the changes queued so far, instead of having them silently miss changes.
+        senders.retain(|sender| match sender.try_send(change.message.clone()) {
+            Ok(()) => true,
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                warn!(
+                    "Ending subscription to account {} that fell behind",
+                    change.hash
+                );
+                false
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => false,
+        });
+        if senders.is_empty() {
+            subscribers.remove(&change.hash);
+        }
+    }
+}
+
+/// Forwards persisted state updates to a [`MessageProducer`] from background tasks. Messages are
+/// buffered in a bounded queue so that a slow or unavailable producer never blocks indexing;
+/// messages that do not fit into the queue are dropped. Messages with different keys are
+/// published concurrently, while messages with the same key are published in order.
+pub struct StateUpdateSink {
+    sender: mpsc::Sender<SinkMessage>,
+}
+
+impl StateUpdateSink {
+    pub fn new(producer: Arc<dyn MessageProducer>, buffer_size: usize) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<SinkMessage>(buffer_size);
+        let lane_buffer_size = (buffer_size / PUBLISH_LANES).max(1);
+        let lanes = (0..PUBLISH_LANES)
+            .map(|_| {
+                let (lane_sender, mut lane_receiver) =
+                    mpsc::channel::<SinkMessage>(lane_buffer_size);
+                let producer = producer.clone();
+                tokio::spawn(async move {
+                    while let Some(message) = lane_receiver.recv().await {
+                        publish_with_retries(producer.as_ref(), &message).await;
+                    }
+                });
+                lane_sender
+            })
+            .collect::<Vec<_>>();
+        tokio::spawn(async move {
+            while let Some(message) = receiver.recv().await {
+                let lane = &lanes[publish_lane(&message.key)];
+                if lane.send(message).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Self { sender }
+    }
+
+    pub fn publish(&self, messages: Vec<SinkMessage>) {
+        let mut dropped_messages = 0;
+        for message in messages {
+            if self.sender.try_send(message).is_err() {
+                dropped_messages += 1;
+            }
+        }
+        if dropped_messages > 0 {
+            warn!(
+                "State update sink buffer is full. Dropped {} messages",
+                dropped_messages
+            );
+            metric! {
+                statsd_count!("state_update_sink.dropped", dropped_messages);
+            }
+        }
+    }
+}
+
+// Returns the lane that publishes the messages with `key`.
+fn publish_lane(key: &str) -> usize {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() % PUBLISH_LANES as u64) as usize
+}
+
+async fn publish_with_retries(producer: &dyn MessageProducer, message: &SinkMessage) {
+    let mut delay = INITIAL_RETRY_DELAY;
+    for attempt in 1..=MAX_PUBLISH_ATTEMPTS {
+        match producer.send(&message.key, &message.payload).await {
+            Ok(()) => {
+                metric! {
+                    statsd_count!("state_update_sink.published", 1);
+                }
+                return;
+            }
+            Err(err) if attempt < MAX_PUBLISH_ATTEMPTS => {
+                warn!(
+                    "Failed to publish message for account {} (attempt {}): {}",
+                    message.key, attempt, err
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            Err(err) => {
+                error!(
+                    "Giving up on publishing message for account {}: {}",
+                    message.key, err
+                );
+                metric! {
+                    statsd_count!("state_update_sink.failed", 1);
+                }
+            }
+        }
+    }
+}
@@ -1,52 +1,95 @@
 use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
 
 use async_std::stream::StreamExt;
 use async_stream::stream;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use futures::pin_mut;
 use jsonrpsee::server::ServerHandle;
-use log::{error, info};
+use log::{error, info, warn};
+use photon_indexer::api::batch_limit::DEFAULT_MAX_BATCH_SIZE;
+use photon_indexer::api::method::get_indexer_health::HEALTH_CHECK_SLOT_DISTANCE;
 use photon_indexer::api::{self, api::PhotonApi};
 
+use photon_indexer::common::config::parse_args_with_config_file;
+use photon_indexer::common::prometheus::write_prometheus_metrics;
+use photon_indexer::common::tls::load_tls_config;
+use photon_indexer::common::typedefs::serializable_pubkey::SerializablePubkey;
 use photon_indexer::common::{
-    fetch_block_parent_slot, fetch_current_slot_with_infinite_retry, get_network_start_slot,
-    get_rpc_client, setup_logging, setup_metrics, setup_pg_pool, LoggingFormat,
+    get_rpc_client_with_commitment, setup_logging, setup_metrics, setup_pg_pool, setup_sqlite_pool,
+    Commitment, LoggingFormat, RetryBackoffConfig, UnknownNetworkStartSlot,
+    DEFAULT_DB_CONNECT_RETRIES, DEFAULT_RETRY_BASE_DELAY_MS, DEFAULT_RETRY_MAX_DELAY_MS,
 };
 
+use photon_indexer::export::{export, ExportFilter, ExportFormat, ExportTable};
+use photon_indexer::ingester::fetchers::grpc::{GrpcUnreachablePolicy, GrpcXToken};
 use photon_indexer::ingester::fetchers::BlockStreamConfig;
-use photon_indexer::ingester::indexer::{
-    fetch_last_indexed_slot_with_infinite_retry, index_block_stream,
+use photon_indexer::ingester::indexer::{index_block_stream, resolve_last_indexed_slot};
+use photon_indexer::ingester::parser::ParserConfig;
+use photon_indexer::ingester::persist::finalization::continously_track_finalization;
+use photon_indexer::ingester::persist::persisted_state_tree::prewarm_trees;
+use photon_indexer::ingester::persist::proof_verification::verify_persisted_proofs;
+use photon_indexer::ingester::persist::signature_retention::continously_prune_signatures;
+use photon_indexer::ingester::persist::{
+    OnSpend, OversizedAccountData, PersistConfig, MAX_SQL_INSERTS,
 };
+use photon_indexer::ingester::sink::StateUpdateSink;
+use photon_indexer::ingester::{reparse_raw_events, IngesterConfig};
 use photon_indexer::migration::{
+    backfill_columns, check_schema_version, dump_schema,
     sea_orm::{DatabaseBackend, DatabaseConnection, SqlxPostgresConnector, SqlxSqliteConnector},
     Migrator, MigratorTrait,
 };
 
 use photon_indexer::monitor::continously_monitor_photon;
+use photon_indexer::snapshot::diff::diff_snapshots;
 use photon_indexer::snapshot::{
-    get_snapshot_files_with_metadata, load_block_stream_from_directory_adapter, DirectoryAdapter,
+    download_snapshot, get_snapshot_files_with_metadata, load_block_stream_from_directory_adapter,
+    should_load_snapshot, DirectoryAdapter, SNAPSHOT_DOWNLOAD_ATTEMPTS,
 };
 use solana_client::nonblocking::rpc_client::RpcClient;
-use sqlx::{
-    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
-    SqlitePool,
-};
+use solana_sdk::pubkey::Pubkey;
+use sqlx::SqlitePool;
 use std::env::temp_dir;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio_rustls::rustls::ServerConfig;
 
 /// Photon: a compressed transaction Solana indexer
 #[derive(Parser, Debug)]
 #[command(version, about)]
 struct Args {
+    /// TOML file with default values for the arguments. Its keys are the names of the arguments
+    /// with underscores, e.g. `rpc_url`. Arguments passed on the command line take precedence
+    #[arg(long)]
+    config: Option<PathBuf>,
+
     /// Port to expose the local Photon API
     // We use a random default port to avoid conflicts with other services
     #[arg(short, long, default_value_t = 8784)]
     port: u16,
 
-    /// URL of the RPC server
+    /// URL of the RPC server. With a comma separated list of URLs, requests fail over to the next
+    /// URL on connection errors and rate limits
     #[arg(short, long, default_value = "http://127.0.0.1:8899")]
     rpc_url: String,
 
+    /// Commitment level of the slots and blocks fetched from the RPC server and the gRPC stream.
+    /// With `finalized`, blocks are never orphaned by a reorg
+    #[arg(long, value_enum, default_value_t = Commitment::Confirmed)]
+    commitment: Commitment,
+
+    /// Delay in milliseconds before retrying a failed RPC request. The delay doubles after each
+    /// consecutive failure of the request, with random jitter
+    #[arg(long, default_value_t = DEFAULT_RETRY_BASE_DELAY_MS)]
+    retry_base_delay_ms: u64,
+
+    /// Maximum delay in milliseconds between the retries of a failed RPC request
+    #[arg(long, default_value_t = DEFAULT_RETRY_MAX_DELAY_MS)]
+    retry_max_delay_ms: u64,
+
     /// DB URL to store indexing data. By default we use an in-memory SQLite database.
     #[arg(short, long)]
     db_url: Option<String>,
@@ -60,10 +103,21 @@ struct Args {
     #[arg(short, long)]
     start_slot: Option<String>,
 
+    /// Where to start indexing an empty database on networks other than devnet and mainnet,
+    /// whose start slot is not known to Photon
+    #[arg(long, value_enum, default_value_t = UnknownNetworkStartSlot::FirstAvailableBlock)]
+    unknown_network_start_slot: UnknownNetworkStartSlot,
+
     /// Max database connections to use in database pool
     #[arg(long, default_value_t = 10)]
     max_db_conn: u32,
 
+    /// Number of times connecting to the database is retried at startup before giving up, so
+    /// that Photon can start before its database is ready. The delay between attempts doubles
+    /// from 1 second up to 30 seconds
+    #[arg(long, default_value_t = DEFAULT_DB_CONNECT_RETRIES)]
+    db_connect_retries: u32,
+
     /// Logging format
     #[arg(short, long, default_value_t = LoggingFormat::Standard)]
     logging_format: LoggingFormat,
@@ -73,44 +127,301 @@ struct Args {
     #[arg(short, long)]
     max_concurrent_block_fetches: Option<usize>,
 
+    /// Max number of fetched block batches to buffer ahead of the indexer, so that fetching
+    /// continues while earlier blocks are being persisted.
+    #[arg(long, default_value_t = 10)]
+    fetch_ahead_window: usize,
+
     /// Light Prover url to use for verifying proofs
     #[arg(long, default_value = "http://127.0.0.1:3001")]
     prover_url: String,
 
+    /// Fail validity proof requests without connecting to the prover. Use this when no prover is
+    /// running
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    disable_proofs: bool,
+
+    /// Verify the validity proofs returned by the prover against the stored state and address tree
+    /// roots, and reject proofs whose state tree roots were evicted from the on-chain root history
+    /// while the prover was generating them
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    verify_proof_roots: bool,
+
+    /// Maximum number of hashes and addresses accepted by a single batch proof request
+    /// (getMultipleCompressedAccountProofs, getMultipleNewAddressProofs(V2) and getValidityProof).
+    /// Larger requests are rejected. Unlimited by default.
+    #[arg(long, default_value = None)]
+    max_proof_batch_size: Option<usize>,
+
+    /// Maximum number of calls in a JSON-RPC batch request. Larger batches are rejected
+    #[arg(long, default_value_t = DEFAULT_MAX_BATCH_SIZE)]
+    max_batch_size: usize,
+
+    /// Comma separated origins from which browsers may call the API, or `*` for any origin. An
+    /// empty value disables CORS. Browsers do not send credentials to servers allowing `*`
+    #[arg(long, value_delimiter = ',', default_value = "*")]
+    cors_allowed_origins: Vec<String>,
+
+    /// PEM certificate chain with which the API server is served over HTTPS. Requires --tls-key
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key of the certificate passed with --tls-cert
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Serve getCompressedAccount requests for accounts that are not indexed yet, for instance
+    /// because they were just created, from the RPC node. Such responses have their `source` set
+    /// to `rpc`.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    enable_rpc_fallback: bool,
+
+    /// Log API calls that take longer than this many milliseconds at warn level, along with the
+    /// name of the method. Disabled by default
+    #[arg(long, default_value = None)]
+    slow_query_threshold_ms: Option<u64>,
+
+    /// Report the indexer as behind in getIndexerHealth and getIndexerHealthStatus once it lags
+    /// the RPC node by more than this many slots
+    #[arg(long, default_value_t = HEALTH_CHECK_SLOT_DISTANCE as u64)]
+    max_slot_lag: u64,
+
     /// Snasphot directory
     #[arg(long, default_value = None)]
     snapshot_dir: Option<String>,
 
+    /// Snapshot to restore the state from before indexing starts, either a snapshot directory or
+    /// the URL of a snapshotter to download the snapshot from. Indexing then continues from the
+    /// end slot of the snapshot.
+    #[arg(long, default_value = None, conflicts_with = "snapshot_dir")]
+    snapshot_source: Option<String>,
+
     #[arg(short, long, default_value = None)]
     /// Yellowstone gRPC URL. If it's inputed, then the indexer will use gRPC to fetch new blocks
     /// instead of polling. It will still use RPC to fetch blocks if
     grpc_url: Option<String>,
 
+    /// Token sent in the `x-token` header to authenticate with the Yellowstone gRPC endpoint
+    #[arg(long, env = "GRPC_X_TOKEN", hide_env_values = true)]
+    grpc_x_token: Option<GrpcXToken>,
+
+    /// What to do when the gRPC endpoint cannot be reached when indexing starts: keep retrying to
+    /// connect while fetching blocks over RPC, or only fetch blocks over RPC from then on
+    #[arg(long, value_enum, default_value_t = GrpcUnreachablePolicy::Retry)]
+    grpc_unreachable_policy: GrpcUnreachablePolicy,
+
     /// Disable indexing
     #[arg(long, action = clap::ArgAction::SetTrue)]
     disable_indexing: bool,
 
+    /// Whether spent accounts are retained, marked as spent, or deleted when they are spent.
+    /// Deleting saves space but spent accounts and their signatures can no longer be queried.
+    #[arg(long, default_value_t = OnSpend::Retain)]
+    on_spend: OnSpend,
+
+    /// Prune the signatures indexed more than this many slots ago, except for the signatures of
+    /// unspent accounts. Pruned signatures can no longer be queried. Disabled by default
+    #[arg(long, default_value = None)]
+    signature_retention_slots: Option<u64>,
+
+    /// Maximum size in bytes of the data of indexed accounts. Accounts with larger data are
+    /// handled according to `--oversized-account-data`. No limit by default
+    #[arg(long)]
+    max_account_data_bytes: Option<u64>,
+
+    /// Whether accounts whose data exceeds `--max-account-data-bytes` are indexed without their
+    /// data or with their data truncated. Either way, they are flagged as having truncated data
+    #[arg(long, value_enum, default_value_t = OversizedAccountData::Reject)]
+    oversized_account_data: OversizedAccountData,
+
+    /// Persist the raw events emitted by compression transactions alongside the derived state, so
+    /// that they can be reparsed with the `reparse` subcommand after a parser upgrade
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    persist_raw_events: bool,
+
+    /// Number of slots within which a signature that is seen again, e.g. because of retries, is
+    /// treated as a duplicate of its first sighting. A signature seen again after the window, e.g.
+    /// because it was included in another block after a reorg, is recorded at its new slot. By
+    /// default, the first sighting is always kept.
+    #[arg(long, default_value = None)]
+    signature_dedupe_window: Option<u64>,
+
+    /// Number of output accounts inserted by a single statement. Larger batches take fewer
+    /// round-trips to the database for blocks with many outputs. Capped at 2000 to stay under the
+    /// parameter limit of SQLite
+    #[arg(long, default_value_t = MAX_SQL_INSERTS)]
+    account_insert_batch_size: usize,
+
+    /// Delay in seconds before retrying to index blocks when the database is out of space. The
+    /// delay doubles while the database stays out of space, up to
+    /// `--out-of-space-retry-max-delay-secs`
+    #[arg(long, default_value_t = 1)]
+    out_of_space_retry_base_delay_secs: u64,
+
+    /// Maximum delay in seconds between retries while the database is out of space
+    #[arg(long, default_value_t = 60)]
+    out_of_space_retry_max_delay_secs: u64,
+
+    /// Seconds to wait on shutdown for the blocks being indexed to be committed and for in-flight
+    /// API requests to finish, before they are aborted
+    #[arg(long, default_value_t = 30)]
+    shutdown_timeout_secs: u64,
+
+    /// Maximum number of instructions of an instruction group that are scanned for compression
+    /// events. Events emitted after the cap are ignored, so this only protects against
+    /// pathological transactions. By default, all instructions are scanned
+    #[arg(long, default_value = None)]
+    max_scanned_instructions_per_group: Option<usize>,
+
+    /// Fail parsing a whole transaction when one of its events is malformed, instead of logging
+    /// and skipping the event. Indexing stalls on such transactions until they are fixed
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    strict_parsing: bool,
+
+    /// Comma separated Kafka brokers to publish the created and spent accounts of each indexed
+    /// batch of blocks to. Requires Photon to be built with the `kafka` feature.
+    #[arg(long, requires = "kafka_topic")]
+    kafka_brokers: Option<String>,
+
+    /// Kafka topic to publish account changes to
+    #[arg(long, requires = "kafka_brokers")]
+    kafka_topic: Option<String>,
+
+    /// Comma separated state trees whose upper nodes are kept in memory, so that proof requests
+    /// for these trees only read the lower part of each proof path from the database
+    #[arg(long, value_delimiter = ',')]
+    prewarm_trees: Vec<Pubkey>,
+
+    /// Verify before starting that the proofs of unspent accounts reconstruct the stored roots of
+    /// their trees. This reads every account, so it is expensive on large databases
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    verify_on_startup: bool,
+
+    /// Fraction of the unspent accounts whose proofs are verified by `--verify-on-startup`
+    #[arg(long, default_value_t = 1.0)]
+    verify_on_startup_sample_rate: f64,
+
+    /// Refuse to start if `--verify-on-startup` finds inconsistent proofs
+    #[arg(long, action = clap::ArgAction::SetTrue, requires = "verify_on_startup")]
+    exit_on_inconsistent_proofs: bool,
+
     /// Disable API
     #[arg(long, action = clap::ArgAction::SetTrue)]
     disable_api: bool,
 
+    /// Expose admin methods, such as `reindexSlot`, on the API server. These methods mutate the
+    /// indexed state, so the API port should not be publicly reachable when this is enabled.
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    enable_admin_api: bool,
+
+    /// Start even if the database schema does not match the migrations known to this binary
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    skip_schema_version_check: bool,
+
     /// Metrics endpoint in the format `host:port`
     /// If provided, metrics will be sent to the specified statsd server.
     #[arg(long, default_value = None)]
     metrics_endpoint: Option<String>,
+
+    /// File to write the final values of all counters and gauges to in the Prometheus text format
+    /// when Photon is shut down with Ctrl-C. Useful to capture the results of benchmark runs.
+    #[arg(long)]
+    export_prometheus_on_shutdown: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the table definitions of the database and exit. Without a DB URL, this prints the
+    /// schema produced by running all migrations on a fresh database.
+    DumpSchema,
+    /// Derive the state of already indexed slots again from their persisted raw events using the
+    /// current parser and exit. Requires the slots to have been indexed with `--persist-raw-events`.
+    Reparse {
+        /// First slot to reparse
+        #[arg(long)]
+        from_slot: u64,
+    },
+    /// Fill columns added by recent migrations for rows indexed before those migrations ran and
+    /// exit. Safe to rerun; rows that already have a value are left untouched.
+    BackfillColumns,
+    /// Export the accounts or token accounts of the database for analytics and exit.
+    Export {
+        /// Format of the export
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        /// Table to export
+        #[arg(long, value_enum, default_value_t = ExportTable::Accounts)]
+        table: ExportTable,
+        /// Path of the file to write the export to
+        #[arg(long)]
+        out: PathBuf,
+        /// Only export accounts of this owner
+        #[arg(long)]
+        owner: Option<Pubkey>,
+        /// Only export token accounts of this mint
+        #[arg(long)]
+        mint: Option<Pubkey>,
+        /// Only export accounts created at or after this slot
+        #[arg(long)]
+        start_slot: Option<u64>,
+        /// Only export accounts created at or before this slot
+        #[arg(long)]
+        end_slot: Option<u64>,
+    },
+    /// Print the slot that indexing would start from and exit. The slot is resolved from the start
+    /// slot, the database, and the snapshot in the same way as when indexing starts.
+    ShowStartSlot,
+    /// Print the accounts added, spent and updated between two local snapshot directories as JSON
+    /// and exit. The second snapshot must continue the chain of the first one.
+    SnapshotDiff {
+        /// Directory of the base snapshot
+        a: String,
+        /// Directory of the later snapshot
+        b: String,
+    },
 }
 
 async fn start_api_server(
-    db: Arc<DatabaseConnection>,
-    rpc_client: Arc<RpcClient>,
-    prover_url: String,
+    api: PhotonApi,
     api_port: u16,
+    enable_admin_api: bool,
+    max_batch_size: usize,
+    cors_allowed_origins: &[String],
+    tls_config: Option<Arc<ServerConfig>>,
 ) -> ServerHandle {
-    let api = PhotonApi::new(db, rpc_client, prover_url);
-    api::rpc_server::run_server(api, api_port).await.unwrap()
+    api::rpc_server::run_server(
+        api,
+        api_port,
+        enable_admin_api,
+        max_batch_size,
+        cors_allowed_origins,
+        tls_config,
+    )
+    .await
+    .unwrap()
+}
+
+#[cfg(feature = "kafka")]
+fn setup_kafka_sink(kafka_brokers: &str, kafka_topic: String) -> StateUpdateSink {
+    use photon_indexer::ingester::sink::{kafka::KafkaProducer, DEFAULT_SINK_BUFFER_SIZE};
+
+    let producer = KafkaProducer::new(kafka_brokers, kafka_topic).unwrap();
+    StateUpdateSink::new(Arc::new(producer), DEFAULT_SINK_BUFFER_SIZE)
+}
+
+#[cfg(not(feature = "kafka"))]
+fn setup_kafka_sink(_kafka_brokers: &str, _kafka_topic: String) -> StateUpdateSink {
+    error!("Photon was built without Kafka support. Rebuild it with `--features kafka`.");
+    std::process::exit(1);
 }
 
-async fn setup_temporary_sqlite_database_pool(max_connections: u32) -> SqlitePool {
+async fn setup_temporary_sqlite_database_pool(
+    max_connections: u32,
+    connect_retries: u32,
+) -> Result<SqlitePool, sqlx::Error> {
     let dir = temp_dir();
     if !dir.exists() {
         std::fs::create_dir_all(&dir).unwrap();
@@ -123,66 +434,111 @@ async fn setup_temporary_sqlite_database_pool(max_connections: u32) -> SqlitePoo
     info!("Creating temporary SQLite database at: {:?}", path);
     File::create(&path).unwrap();
     let db_path = format!("sqlite:////{}", path.to_str().unwrap());
-    setup_sqlite_pool(&db_path, max_connections).await
-}
-
-async fn setup_sqlite_pool(db_url: &str, max_connections: u32) -> SqlitePool {
-    let options: SqliteConnectOptions = db_url.parse().unwrap();
-    SqlitePoolOptions::new()
-        .max_connections(max_connections)
-        .min_connections(1)
-        .connect_with(options)
-        .await
-        .unwrap()
+    setup_sqlite_pool(&db_path, max_connections, connect_retries).await
 }
 
-pub fn parse_db_type(db_url: &str) -> DatabaseBackend {
+pub fn parse_db_type(db_url: &str) -> Result<DatabaseBackend, sqlx::Error> {
     if db_url.starts_with("postgres://") {
-        DatabaseBackend::Postgres
+        Ok(DatabaseBackend::Postgres)
     } else if db_url.starts_with("sqlite://") {
-        DatabaseBackend::Sqlite
+        Ok(DatabaseBackend::Sqlite)
+    } else if db_url.starts_with("mysql://") {
+        // Persisting state updates relies on INSERT/UPDATE ... RETURNING and ON CONFLICT, which
+        // MySQL does not support.
+        Err(sqlx::Error::Configuration(
+            "MySQL is not supported. Use a Postgres or SQLite database instead".into(),
+        ))
     } else {
-        unimplemented!("Unsupported database type: {}", db_url)
+        Err(sqlx::Error::Configuration(
+            format!("Unsupported database type: {}", db_url).into(),
+        ))
     }
 }
 
 async fn setup_database_connection(
     db_url: Option<String>,
     max_connections: u32,
-) -> Arc<DatabaseConnection> {
-    Arc::new(match db_url {
+    connect_retries: u32,
+) -> Result<Arc<DatabaseConnection>, sqlx::Error> {
+    Ok(Arc::new(match db_url {
         Some(db_url) => {
-            let db_type = parse_db_type(&db_url);
+            let db_type = parse_db_type(&db_url)?;
             match db_type {
                 DatabaseBackend::Postgres => SqlxPostgresConnector::from_sqlx_postgres_pool(
-                    setup_pg_pool(&db_url, max_connections).await,
+                    setup_pg_pool(&db_url, max_connections, connect_retries).await?,
                 ),
                 DatabaseBackend::Sqlite => SqlxSqliteConnector::from_sqlx_sqlite_pool(
-                    setup_sqlite_pool(&db_url, max_connections).await,
+                    setup_sqlite_pool(&db_url, max_connections, connect_retries).await?,
                 ),
                 _ => unimplemented!("Unsupported database type: {}", db_url),
             }
         }
         None => SqlxSqliteConnector::from_sqlx_sqlite_pool(
-            setup_temporary_sqlite_database_pool(max_connections).await,
+            setup_temporary_sqlite_database_pool(max_connections, connect_retries).await?,
         ),
-    })
+    }))
 }
 
+fn is_snapshot_url(snapshot_source: &str) -> bool {
+    snapshot_source.starts_with("http://") || snapshot_source.starts_with("https://")
+}
+
+// Fetches the end slot of the snapshot served by the snapshotter at `snapshot_server_url`, if it
+// has any snapshot.
+async fn fetch_snapshot_end_slot(snapshot_server_url: &str) -> anyhow::Result<Option<u64>> {
+    let response = reqwest::get(format!(
+        "{}/slot",
+        snapshot_server_url.trim_end_matches('/')
+    ))
+    .await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let end_slot = response.error_for_status()?.text().await?;
+    Ok(Some(end_slot.trim().parse::<u64>()?))
+}
+
+// Downloads the snapshot served by the snapshotter at `snapshot_server_url` into a temporary
+// directory and returns the directory. An interrupted download is resumed by the next run.
+async fn download_snapshot_source(snapshot_server_url: &str) -> anyhow::Result<String> {
+    let snapshot_dir = temp_dir().join("photon_snapshot_source");
+    if snapshot_dir.exists() {
+        std::fs::remove_dir_all(&snapshot_dir)?;
+    }
+    let snapshot_dir = snapshot_dir.to_str().unwrap().to_string();
+    let download_dir = temp_dir().join("photon_snapshot_download");
+    info!("Downloading snapshot from {}...", snapshot_server_url);
+    let directory_adapter = DirectoryAdapter::from_local_directory(snapshot_dir.clone());
+    download_snapshot(
+        snapshot_server_url,
+        download_dir.to_str().unwrap(),
+        SNAPSHOT_DOWNLOAD_ATTEMPTS,
+        &directory_adapter,
+    )
+    .await?;
+    Ok(snapshot_dir)
+}
+
+/// Indexes new blocks until `shutdown` fires. The blocks being indexed when it fires are still
+/// committed, so that stopping the indexer does not leave partially indexed blocks behind.
 fn continously_index_new_blocks(
     block_stream_config: BlockStreamConfig,
     db: Arc<DatabaseConnection>,
     rpc_client: Arc<RpcClient>,
     last_indexed_slot: u64,
+    ingester_config: IngesterConfig,
+    shutdown: oneshot::Receiver<()>,
 ) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
-        let block_stream = block_stream_config.load_block_stream();
+        let block_stream =
+            futures::StreamExt::take_until(block_stream_config.load_block_stream(), shutdown);
         index_block_stream(
             block_stream,
             db,
             rpc_client.clone(),
             last_indexed_slot,
             None,
+            &ingester_config,
         )
         .await;
     })
@@ -190,18 +546,223 @@ fn continously_index_new_blocks(
 
 #[tokio::main]
 async fn main() {
-    let args = Args::parse();
+    let args: Args = parse_args_with_config_file();
     setup_logging(args.logging_format);
-    setup_metrics(args.metrics_endpoint);
+    if let Some(config) = &args.config {
+        info!("Loaded arguments from config file {}", config.display());
+    }
+    setup_metrics(
+        args.metrics_endpoint,
+        args.export_prometheus_on_shutdown.is_some(),
+    );
+    let retry_backoff = RetryBackoffConfig {
+        base_delay_ms: args.retry_base_delay_ms,
+        max_delay_ms: args.retry_max_delay_ms,
+    };
+    let state_update_sink = match (&args.kafka_brokers, &args.kafka_topic) {
+        (Some(kafka_brokers), Some(kafka_topic)) => {
+            info!("Publishing state updates to Kafka topic {}", kafka_topic);
+            Some(Arc::new(setup_kafka_sink(
+                kafka_brokers,
+                kafka_topic.clone(),
+            )))
+        }
+        _ => None,
+    };
+    let ingester_config = IngesterConfig {
+        parser: ParserConfig {
+            max_scanned_instructions_per_group: args.max_scanned_instructions_per_group,
+            strict_parsing: args.strict_parsing,
+        },
+        persist: PersistConfig {
+            on_spend: args.on_spend,
+            persist_raw_events: args.persist_raw_events,
+            signature_dedupe_window: args.signature_dedupe_window,
+            account_insert_batch_size: args.account_insert_batch_size,
+            max_account_data_bytes: args.max_account_data_bytes,
+            oversized_account_data: args.oversized_account_data,
+        },
+        out_of_space_retry_base_delay: Duration::from_secs(args.out_of_space_retry_base_delay_secs),
+        out_of_space_retry_max_delay: Duration::from_secs(args.out_of_space_retry_max_delay_secs),
+        state_update_sink,
+    };
+    if let Some(Command::SnapshotDiff { a, b }) = &args.command {
+        let base = Arc::new(DirectoryAdapter::from_local_directory(a.clone()));
+        let snapshot = Arc::new(DirectoryAdapter::from_local_directory(b.clone()));
+        match diff_snapshots(base, snapshot, &ingester_config.parser).await {
+            Ok(diff) => println!("{}", serde_json::to_string_pretty(&diff).unwrap()),
+            Err(err) => {
+                error!("Failed to diff snapshots: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    // The TLS files are loaded before anything else starts, so that a bad certificate fails the
+    // startup instead of the API server.
+    let tls_config = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert_path), Some(key_path)) => match load_tls_config(cert_path, key_path) {
+            Ok(tls_config) => Some(tls_config),
+            Err(err) => {
+                error!("Failed to load the TLS certificate and key: {}", err);
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
 
-    let db_conn = setup_database_connection(args.db_url.clone(), args.max_db_conn).await;
+    let db_conn = match setup_database_connection(
+        args.db_url.clone(),
+        args.max_db_conn,
+        args.db_connect_retries,
+    )
+    .await
+    {
+        Ok(db_conn) => db_conn,
+        Err(err) => {
+            error!("Failed to connect to the database: {}", err);
+            std::process::exit(1);
+        }
+    };
     if args.db_url.is_none() {
         info!("Running migrations...");
         Migrator::up(db_conn.as_ref(), None).await.unwrap();
     }
+    if let Some(Command::DumpSchema) = args.command {
+        println!("{}", dump_schema(db_conn.as_ref()).await.unwrap());
+        return;
+    }
+    if !args.skip_schema_version_check {
+        if let Err(err) = check_schema_version(db_conn.as_ref()).await {
+            error!("Refusing to start: {}", err);
+            std::process::exit(1);
+        }
+    }
+    if let Some(Command::Reparse { from_slot }) = args.command {
+        info!("Reparsing raw events from slot {}...", from_slot);
+        match reparse_raw_events(db_conn.as_ref(), from_slot, &ingester_config).await {
+            Ok(reparsed_slots) => info!("Reparsed {} slots", reparsed_slots),
+            Err(err) => {
+                error!("Failed to reparse raw events: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(Command::BackfillColumns) = args.command {
+        info!("Backfilling columns...");
+        match backfill_columns(db_conn.as_ref()).await {
+            Ok(backfilled_rows) => info!("Backfilled {} rows", backfilled_rows),
+            Err(err) => {
+                error!("Failed to backfill columns: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(Command::Export {
+        format,
+        table,
+        out,
+        owner,
+        mint,
+        start_slot,
+        end_slot,
+    }) = &args.command
+    {
+        let filter = ExportFilter {
+            owner: owner.map(SerializablePubkey::from),
+            mint: mint.map(SerializablePubkey::from),
+            start_slot: *start_slot,
+            end_slot: *end_slot,
+        };
+        info!("Exporting {:?} to {:?}...", table, out);
+        let mut writer = BufWriter::new(File::create(out).unwrap());
+        match export(db_conn.as_ref(), *format, *table, &filter, &mut writer).await {
+            Ok(exported_rows) => info!("Exported {} rows", exported_rows),
+            Err(err) => {
+                error!("Failed to export: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(Command::ShowStartSlot) = args.command {
+        let rpc_client = get_rpc_client_with_commitment(&args.rpc_url, args.commitment);
+        let last_indexed_slot = resolve_last_indexed_slot(
+            db_conn.as_ref(),
+            &rpc_client,
+            args.start_slot.as_deref(),
+            args.unknown_network_start_slot,
+            retry_backoff,
+        )
+        .await;
+        let snapshot_end_slot = match args.snapshot_source.as_ref().or(args.snapshot_dir.as_ref()) {
+            Some(snapshot_source) if is_snapshot_url(snapshot_source) => {
+                fetch_snapshot_end_slot(snapshot_source).await
+            }
+            Some(snapshot_dir) => get_snapshot_files_with_metadata(
+                &DirectoryAdapter::from_local_directory(snapshot_dir.clone()),
+            )
+            .await
+            .map(|snapshot_files| snapshot_files.last().map(|file| file.end_slot)),
+            None => Ok(None),
+        };
+        let snapshot_end_slot = match snapshot_end_slot {
+            Ok(snapshot_end_slot) => snapshot_end_slot.filter(|snapshot_end_slot| {
+                should_load_snapshot(
+                    *snapshot_end_slot,
+                    last_indexed_slot,
+                    args.snapshot_offset.unwrap_or(0),
+                )
+            }),
+            Err(err) => {
+                error!("Failed to read the end slot of the snapshot: {}", err);
+                std::process::exit(1);
+            }
+        };
+        println!("{}", snapshot_end_slot.unwrap_or(last_indexed_slot) + 1);
+        return;
+    }
+    if !args.prewarm_trees.is_empty() {
+        info!("Prewarming {} trees...", args.prewarm_trees.len());
+        let trees = args
+            .prewarm_trees
+            .iter()
+            .map(|tree| SerializablePubkey::from(*tree))
+            .collect::<Vec<_>>();
+        if let Err(err) = prewarm_trees(db_conn.as_ref(), &trees).await {
+            error!("Failed to prewarm trees: {}", err);
+        }
+    }
+    if args.verify_on_startup {
+        info!("Verifying persisted proofs...");
+        let inconsistent =
+            match verify_persisted_proofs(db_conn.as_ref(), args.verify_on_startup_sample_rate)
+                .await
+            {
+                Ok(report) => {
+                    info!(
+                        "Verified the proofs of {} accounts. Found {} inconsistencies",
+                        report.verified_accounts,
+                        report.inconsistencies.len()
+                    );
+                    !report.inconsistencies.is_empty()
+                }
+                Err(err) => {
+                    error!("Failed to verify persisted proofs: {}", err);
+                    true
+                }
+            };
+        if inconsistent && args.exit_on_inconsistent_proofs {
+            error!("Refusing to start since persisted proofs could not be verified");
+            std::process::exit(1);
+        }
+    }
     let is_rpc_node_local = args.rpc_url.contains("127.0.0.1");
-    let rpc_client = get_rpc_client(&args.rpc_url);
+    let rpc_client = get_rpc_client_with_commitment(&args.rpc_url, args.commitment);
 
+    let (indexer_shutdown_sender, indexer_shutdown_receiver) = oneshot::channel();
     let (indexer_handle, monitor_handle) = match args.disable_indexing {
         true => {
             info!("Indexing is disabled");
@@ -210,26 +771,31 @@ async fn main() {
         false => {
             info!("Starting indexer...");
 
-            let last_indexed_slot = match args.start_slot {
-                        Some(start_slot) => match start_slot.as_str() {
-                            "latest" => fetch_current_slot_with_infinite_retry(&rpc_client).await,
-                            _ => {
-                                fetch_block_parent_slot(&rpc_client, start_slot.parse::<u64>().unwrap())
-                                    .await
-                            }
-                        },
-                        None => fetch_last_indexed_slot_with_infinite_retry(db_conn.as_ref())
-                            .await
-                            .unwrap_or(
-                                get_network_start_slot(&rpc_client)
-                                    .await
-                                    .try_into()
-                                    .unwrap(),
-                            )
-                            .try_into()
-                            .unwrap(),
-                    };
-            if let Some(snapshot_dir) = args.snapshot_dir {
+            let mut last_indexed_slot = resolve_last_indexed_slot(
+                db_conn.as_ref(),
+                &rpc_client,
+                args.start_slot.as_deref(),
+                args.unknown_network_start_slot,
+                retry_backoff,
+            )
+            .await;
+            let snapshot_dir = match args.snapshot_source {
+                Some(snapshot_source) if is_snapshot_url(&snapshot_source) => {
+                    match download_snapshot_source(&snapshot_source).await {
+                        Ok(snapshot_dir) => Some(snapshot_dir),
+                        Err(e) => {
+                            error!(
+                                "Failed to download snapshot from {}: {}",
+                                snapshot_source, e
+                            );
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                Some(snapshot_source) => Some(snapshot_source),
+                None => args.snapshot_dir,
+            };
+            if let Some(snapshot_dir) = snapshot_dir {
                 let directory_adapter = Arc::new(DirectoryAdapter::from_local_directory(snapshot_dir));
                 let snapshot_files = get_snapshot_files_with_metadata(&directory_adapter)
                     .await
@@ -237,10 +803,11 @@ async fn main() {
                 if !snapshot_files.is_empty() {
                     info!("Detected snapshot files. Loading snapshot...");
                     let last_slot = snapshot_files.last().unwrap().end_slot;
-                    // Compute the snapshot offset, if the snapshot is not more recent than this offset then don't fetch the snapshot
-                    let snapshot_offset = last_slot - args.snapshot_offset.unwrap_or(0);
-
-                    if snapshot_offset >= last_indexed_slot {
+                    if should_load_snapshot(
+                        last_slot,
+                        last_indexed_slot,
+                        args.snapshot_offset.unwrap_or(0),
+                    ) {
                         info!("Snapshot is newer than the last indexed slot. Loading snapshot...");
 
                         let block_stream =
@@ -260,8 +827,10 @@ async fn main() {
                             rpc_client.clone(),
                             last_stream_indexed_slot,
                             Some(last_slot),
+                            &ingester_config,
                         )
                         .await;
+                        last_indexed_slot = last_slot;
                     } else {
                         info!("Snapshot is already indexed. Skipping...");
                     }
@@ -287,6 +856,11 @@ async fn main() {
                 max_concurrent_block_fetches,
                 last_indexed_slot,
                 geyser_url: args.grpc_url,
+                grpc_x_token: args.grpc_x_token,
+                grpc_unreachable_policy: args.grpc_unreachable_policy,
+                fetch_ahead_window: args.fetch_ahead_window,
+                commitment: args.commitment,
+                retry_backoff,
             };
 
             (
@@ -295,6 +869,8 @@ async fn main() {
                     db_conn.clone(),
                     rpc_client.clone(),
                     last_indexed_slot,
+                    ingester_config.clone(),
+                    indexer_shutdown_receiver,
                 )),
                 Some(continously_monitor_photon(
                     db_conn.clone(),
@@ -303,6 +879,11 @@ async fn main() {
             )
         }
     };
+    let signature_retention_handle = args
+        .signature_retention_slots
+        .map(|retention_slots| continously_prune_signatures(db_conn.clone(), retention_slots));
+    let finalization_handle = (!args.disable_indexing)
+        .then(|| continously_track_finalization(db_conn.clone(), rpc_client.clone()));
 
     info!("Starting API server with port {}...", args.port);
     let api_handler = if args.disable_api {
@@ -310,27 +891,55 @@ async fn main() {
     } else {
         Some(
             start_api_server(
-                db_conn.clone(),
-                rpc_client.clone(),
-                args.prover_url,
+                PhotonApi::new(db_conn.clone(), rpc_client.clone(), args.prover_url)
+                    .with_proof_root_verification(args.verify_proof_roots)
+                    .with_proofs_disabled(args.disable_proofs)
+                    .with_max_proof_batch_size(args.max_proof_batch_size)
+                    .with_rpc_fallback(args.enable_rpc_fallback)
+                    .with_slow_query_threshold(
+                        args.slow_query_threshold_ms.map(Duration::from_millis),
+                    )
+                    .with_max_slot_lag(args.max_slot_lag)
+                    .with_ingester_config(ingester_config)
+                    .with_retry_backoff(retry_backoff)
+                    .with_commitment(args.commitment),
                 args.port,
+                args.enable_admin_api,
+                args.max_batch_size,
+                &args.cors_allowed_origins,
+                tls_config,
             )
             .await,
         )
     };
 
+    let shutdown_timeout = Duration::from_secs(args.shutdown_timeout_secs);
     match tokio::signal::ctrl_c().await {
         Ok(()) => {
-            if let Some(indexer_handle) = indexer_handle {
+            if let Some(mut indexer_handle) = indexer_handle {
                 info!("Shutting down indexer...");
-                indexer_handle.abort();
-                indexer_handle
+                // The indexer stops fetching blocks, but the blocks being indexed are committed.
+                let _ = indexer_shutdown_sender.send(());
+                if tokio::time::timeout(shutdown_timeout, &mut indexer_handle)
                     .await
-                    .expect_err("Indexer should have been aborted");
+                    .is_err()
+                {
+                    warn!("Indexer did not stop within the shutdown timeout. Aborting it...");
+                    indexer_handle.abort();
+                }
             }
-            if let Some(api_handler) = &api_handler {
+            if let Some(api_handler) = api_handler {
                 info!("Shutting down API server...");
                 api_handler.stop().unwrap();
+                // We need to wait for the API server to stop to ensure that all clean up is done
+                if tokio::time::timeout(shutdown_timeout, api_handler.stopped())
+                    .await
+                    .is_err()
+                {
+                    warn!(
+                        "API server did not finish in-flight requests within the shutdown timeout"
+                    );
+                }
             }
 
             if let Some(monitor_handle) = monitor_handle {
@@ -340,13 +949,32 @@ async fn main() {
                     .await
                     .expect_err("Monitor should have been aborted");
             }
+
+            if let Some(signature_retention_handle) = signature_retention_handle {
+                info!("Shutting down signature pruning...");
+                signature_retention_handle.abort();
+                signature_retention_handle
+                    .await
+                    .expect_err("Signature pruning should have been aborted");
+            }
+
+            if let Some(finalization_handle) = finalization_handle {
+                info!("Shutting down finalization tracking...");
+                finalization_handle.abort();
+                finalization_handle
+                    .await
+                    .expect_err("Finalization tracking should have been aborted");
+            }
+
+            if let Some(path) = &args.export_prometheus_on_shutdown {
+                info!("Writing metrics to {:?}...", path);
+                if let Err(err) = write_prometheus_metrics(path) {
+                    error!("Failed to write metrics: {}", err);
+                }
+            }
         }
         Err(err) => {
             error!("Unable to listen for shutdown signal: {}", err);
         }
     }
-    // We need to wait for the API server to stop to ensure that all clean up is done
-    if let Some(api_handler) = api_handler {
-        tokio::spawn(api_handler.stopped());
-    }
 }
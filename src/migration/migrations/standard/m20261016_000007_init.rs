@@ -0,0 +1,77 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, DatabaseBackend, Statement};
+
+use crate::migration::model::table::Accounts;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+async fn execute_sql<'a>(manager: &SchemaManager<'_>, sql: &str) -> Result<(), DbErr> {
+    manager
+        .get_connection()
+        .execute(Statement::from_string(
+            manager.get_database_backend(),
+            sql.to_string(),
+        ))
+        .await?;
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Accounts::Table)
+                    .add_column(ColumnDef::new(Accounts::SlotSpent).big_integer().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        if manager.get_database_backend() == DatabaseBackend::Postgres {
+            // Create index concurrently for Postgres
+            execute_sql(
+                manager,
+                "CREATE INDEX CONCURRENTLY IF NOT EXISTS accounts_slot_spent_idx ON accounts (slot_spent, hash);",
+            )
+            .await?;
+        } else {
+            // For other databases, create index normally
+            manager
+                .create_index(
+                    Index::create()
+                        .name("accounts_slot_spent_idx")
+                        .table(Accounts::Table)
+                        .col(Accounts::SlotSpent)
+                        .col(Accounts::Hash)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("accounts_slot_spent_idx")
+                    .table(Accounts::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Accounts::Table)
+                    .drop_column(Accounts::SlotSpent)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
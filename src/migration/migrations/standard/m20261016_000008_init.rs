@@ -0,0 +1,59 @@
+use sea_orm_migration::prelude::*;
+
+use super::super::super::model::table::{RawEvents, Transactions};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RawEvents::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(RawEvents::Signature).binary().not_null())
+                    .col(ColumnDef::new(RawEvents::EventIndex).integer().not_null())
+                    .col(ColumnDef::new(RawEvents::Slot).big_integer().not_null())
+                    .col(ColumnDef::new(RawEvents::Kind).small_integer().not_null())
+                    .col(ColumnDef::new(RawEvents::Data).binary().not_null())
+                    .primary_key(
+                        Index::create()
+                            .name("pk_raw_events")
+                            .col(RawEvents::Signature)
+                            .col(RawEvents::EventIndex),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("raw_events_signature_fk")
+                            .from(RawEvents::Table, RawEvents::Signature)
+                            .to(Transactions::Table, Transactions::Signature)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("raw_events_slot_signature_event_index_idx")
+                    .table(RawEvents::Table)
+                    .col(RawEvents::Slot)
+                    .col(RawEvents::Signature)
+                    .col(RawEvents::EventIndex)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RawEvents::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::DatabaseBackend;
+
+use crate::migration::{get_state_tree_partitions, repartition_state_trees};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Partitioning of the state tree nodes is opt-in, since rebuilding the table is expensive on
+/// large databases. Set STATE_TREE_PARTITIONS to the number of partitions to enable it.
+fn state_tree_partitions() -> Result<Option<u32>, DbErr> {
+    match std::env::var("STATE_TREE_PARTITIONS") {
+        Ok(partitions) => partitions.parse::<u32>().map(Some).map_err(|_| {
+            DbErr::Custom(format!(
+                "Invalid STATE_TREE_PARTITIONS value: {}",
+                partitions
+            ))
+        }),
+        Err(_) => Ok(None),
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if let Some(partitions) = state_tree_partitions()? {
+            if manager.get_database_backend() != DatabaseBackend::Postgres {
+                return Err(DbErr::Custom(
+                    "STATE_TREE_PARTITIONS is only supported on Postgres".to_string(),
+                ));
+            }
+            repartition_state_trees(manager.get_connection(), Some(partitions)).await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() == DatabaseBackend::Postgres
+            && get_state_tree_partitions(manager.get_connection())
+                .await?
+                .is_some()
+        {
+            repartition_state_trees(manager.get_connection(), None).await?;
+        }
+        Ok(())
+    }
+}
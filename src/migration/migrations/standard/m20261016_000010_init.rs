@@ -0,0 +1,75 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, DatabaseBackend, Statement};
+
+use crate::migration::model::table::Accounts;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+async fn execute_sql<'a>(manager: &SchemaManager<'_>, sql: &str) -> Result<(), DbErr> {
+    manager
+        .get_connection()
+        .execute(Statement::from_string(
+            manager.get_database_backend(),
+            sql.to_string(),
+        ))
+        .await?;
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() == DatabaseBackend::Postgres {
+            // Create indexes concurrently for Postgres
+            execute_sql(
+                manager,
+                "CREATE INDEX CONCURRENTLY IF NOT EXISTS accounts_lamports_hash_idx ON accounts (lamports, hash);",
+            )
+            .await?;
+            execute_sql(
+                manager,
+                "CREATE INDEX CONCURRENTLY IF NOT EXISTS accounts_owner_lamports_hash_idx ON accounts (owner, lamports, hash);",
+            )
+            .await?;
+        } else {
+            // For other databases, create indexes normally
+            manager
+                .create_index(
+                    Index::create()
+                        .name("accounts_lamports_hash_idx")
+                        .table(Accounts::Table)
+                        .col(Accounts::Lamports)
+                        .col(Accounts::Hash)
+                        .to_owned(),
+                )
+                .await?;
+            manager
+                .create_index(
+                    Index::create()
+                        .name("accounts_owner_lamports_hash_idx")
+                        .table(Accounts::Table)
+                        .col(Accounts::Owner)
+                        .col(Accounts::Lamports)
+                        .col(Accounts::Hash)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for index in [
+            "accounts_lamports_hash_idx",
+            "accounts_owner_lamports_hash_idx",
+        ] {
+            manager
+                .drop_index(Index::drop().name(index).table(Accounts::Table).to_owned())
+                .await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+use sea_orm_migration::prelude::*;
+
+use super::super::super::model::table::{AccountMappings, Transactions};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AccountMappings::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AccountMappings::Signature)
+                            .binary()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(AccountMappings::InputHash)
+                            .binary()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(AccountMappings::OutputHash)
+                            .binary()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(AccountMappings::Slot)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .name("pk_account_mappings")
+                            .col(AccountMappings::Signature)
+                            .col(AccountMappings::InputHash),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("account_mappings_signature_fk")
+                            .from(AccountMappings::Table, AccountMappings::Signature)
+                            .to(Transactions::Table, Transactions::Signature)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("account_mappings_slot_idx")
+                    .table(AccountMappings::Table)
+                    .col(AccountMappings::Slot)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AccountMappings::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, Statement};
+
+use crate::migration::model::table::StateTrees;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // The index is not created concurrently on Postgres since state_trees may be partitioned,
+        // in which case partitioning it in an earlier migration already created the index.
+        manager
+            .get_connection()
+            .execute(Statement::from_string(
+                manager.get_database_backend(),
+                "CREATE INDEX IF NOT EXISTS state_trees_tree_seq_node_idx_idx \
+                ON state_trees (tree, seq, node_idx);"
+                    .to_string(),
+            ))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("state_trees_tree_seq_node_idx_idx")
+                    .table(StateTrees::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, DatabaseBackend, Statement};
+
+use crate::migration::model::table::Accounts;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Serves listing the accounts of an owner by slot. Listing them by lamports is served by
+        // accounts_owner_lamports_hash_idx.
+        if manager.get_database_backend() == DatabaseBackend::Postgres {
+            manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    manager.get_database_backend(),
+                    "CREATE INDEX CONCURRENTLY IF NOT EXISTS accounts_owner_slot_created_hash_idx \
+                    ON accounts (owner, slot_created, hash);"
+                        .to_string(),
+                ))
+                .await?;
+        } else {
+            manager
+                .create_index(
+                    Index::create()
+                        .name("accounts_owner_slot_created_hash_idx")
+                        .table(Accounts::Table)
+                        .col(Accounts::Owner)
+                        .col(Accounts::SlotCreated)
+                        .col(Accounts::Hash)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("accounts_owner_slot_created_hash_idx")
+                    .table(Accounts::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
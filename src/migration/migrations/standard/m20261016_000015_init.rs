@@ -0,0 +1,50 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, DatabaseBackend, Statement};
+
+use crate::migration::model::table::Accounts;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Serves scanning the unspent accounts of a tree.
+        if manager.get_database_backend() == DatabaseBackend::Postgres {
+            manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    manager.get_database_backend(),
+                    "CREATE INDEX CONCURRENTLY IF NOT EXISTS accounts_tree_hash_idx ON accounts (tree, hash);"
+                        .to_string(),
+                ))
+                .await?;
+        } else {
+            manager
+                .create_index(
+                    Index::create()
+                        .name("accounts_tree_hash_idx")
+                        .table(Accounts::Table)
+                        .col(Accounts::Tree)
+                        .col(Accounts::Hash)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("accounts_tree_hash_idx")
+                    .table(Accounts::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
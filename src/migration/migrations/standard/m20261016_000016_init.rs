@@ -0,0 +1,74 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, DatabaseBackend, Statement};
+
+use crate::migration::model::table::Accounts;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Accounts::Table)
+                    .add_column(
+                        ColumnDef::new(Accounts::Finalized)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Serves finding the accounts whose slot has been finalized since the last update.
+        if manager.get_database_backend() == DatabaseBackend::Postgres {
+            manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    manager.get_database_backend(),
+                    "CREATE INDEX CONCURRENTLY IF NOT EXISTS accounts_finalized_slot_created_idx \
+                    ON accounts (finalized, slot_created);"
+                        .to_string(),
+                ))
+                .await?;
+        } else {
+            manager
+                .create_index(
+                    Index::create()
+                        .name("accounts_finalized_slot_created_idx")
+                        .table(Accounts::Table)
+                        .col(Accounts::Finalized)
+                        .col(Accounts::SlotCreated)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("accounts_finalized_slot_created_idx")
+                    .table(Accounts::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Accounts::Table)
+                    .drop_column(Accounts::Finalized)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, DatabaseBackend, Statement};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+async fn execute_sql<'a>(manager: &SchemaManager<'_>, sql: &str) -> Result<(), DbErr> {
+    manager
+        .get_connection()
+        .execute(Statement::from_string(
+            manager.get_database_backend(),
+            sql.to_string(),
+        ))
+        .await?;
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Finding the accounts to finalize only needs the accounts that are not finalized yet,
+        // which are few compared to the finalized ones, so they are indexed on their own.
+        if manager.get_database_backend() == DatabaseBackend::Postgres {
+            execute_sql(
+                manager,
+                "CREATE INDEX CONCURRENTLY IF NOT EXISTS accounts_unfinalized_slot_created_idx \
+                ON accounts (slot_created) WHERE finalized = false;",
+            )
+            .await?;
+            execute_sql(
+                manager,
+                "DROP INDEX CONCURRENTLY IF EXISTS accounts_finalized_slot_created_idx;",
+            )
+            .await?;
+        } else {
+            execute_sql(
+                manager,
+                "CREATE INDEX IF NOT EXISTS accounts_unfinalized_slot_created_idx \
+                ON accounts (slot_created) WHERE finalized = false;",
+            )
+            .await?;
+            execute_sql(
+                manager,
+                "DROP INDEX IF EXISTS accounts_finalized_slot_created_idx;",
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        execute_sql(
+            manager,
+            "CREATE INDEX IF NOT EXISTS accounts_finalized_slot_created_idx \
+            ON accounts (finalized, slot_created);",
+        )
+        .await?;
+        execute_sql(
+            manager,
+            "DROP INDEX IF EXISTS accounts_unfinalized_slot_created_idx;",
+        )
+        .await?;
+
+        Ok(())
+    }
+}
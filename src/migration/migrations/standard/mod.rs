@@ -6,6 +6,17 @@ pub mod m20240624_000003_init;
 pub mod m20240807_000004_init;
 pub mod m20240914_000005_init;
 pub mod m20241008_000006_init;
+pub mod m20261016_000007_init;
+pub mod m20261016_000008_init;
+pub mod m20261016_000009_init;
+pub mod m20261016_000010_init;
+pub mod m20261016_000011_init;
+pub mod m20261016_000012_init;
+pub mod m20261016_000013_init;
+pub mod m20261016_000014_init;
+pub mod m20261016_000015_init;
+pub mod m20261016_000016_init;
+pub mod m20261016_000017_init;
 
 
 
@@ -18,5 +29,16 @@ pub fn get_standard_migrations() -> Vec<Box<dyn MigrationTrait>> {
         Box::new(m20240807_000004_init::Migration),
         Box::new(m20240914_000005_init::Migration),
         Box::new(m20241008_000006_init::Migration),
+        Box::new(m20261016_000007_init::Migration),
+        Box::new(m20261016_000008_init::Migration),
+        Box::new(m20261016_000009_init::Migration),
+        Box::new(m20261016_000010_init::Migration),
+        Box::new(m20261016_000011_init::Migration),
+        Box::new(m20261016_000012_init::Migration),
+        Box::new(m20261016_000013_init::Migration),
+        Box::new(m20261016_000014_init::Migration),
+        Box::new(m20261016_000015_init::Migration),
+        Box::new(m20261016_000016_init::Migration),
+        Box::new(m20261016_000017_init::Migration),
     ]
 }
@@ -1,5 +1,6 @@
 use migrations::{custom::get_custom_migrations, standard::get_standard_migrations};
 
+use sea_orm::{ConnectionTrait, DatabaseBackend, DatabaseConnection, DbErr, Statement};
 pub use sea_orm_migration::prelude::*;
 
 mod migrations;
@@ -23,4 +24,285 @@ impl MigratorTrait for MigractorWithCustomMigrations {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
         get_standard_migrations().into_iter().chain(get_custom_migrations()).collect()
     }
-}
\ No newline at end of file
+}
+
+/// Introspects the live database and returns its table definitions as SQL. Migration bookkeeping
+/// tables are omitted.
+pub async fn dump_schema(db: &DatabaseConnection) -> Result<String, DbErr> {
+    let db_backend = db.get_database_backend();
+    match db_backend {
+        DatabaseBackend::Sqlite => {
+            let rows = db
+                .query_all(Statement::from_string(
+                    db_backend,
+                    "SELECT sql FROM sqlite_master \
+                    WHERE sql IS NOT NULL AND name NOT LIKE 'seaql_%' AND name NOT LIKE 'sqlite_%' \
+                    ORDER BY type DESC, name"
+                        .to_string(),
+                ))
+                .await?;
+            let statements = rows
+                .into_iter()
+                .map(|row| {
+                    row.try_get::<String>("", "sql")
+                        .map(|sql| format!("{};", sql))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(statements.join("\n\n"))
+        }
+        DatabaseBackend::Postgres => {
+            let rows = db
+                .query_all(Statement::from_string(
+                    db_backend,
+                    "SELECT table_name::text AS table_name, column_name::text AS column_name, \
+                    data_type::text AS data_type, is_nullable::text AS is_nullable \
+                    FROM information_schema.columns \
+                    WHERE table_schema = 'public' AND table_name != 'seaql_migrations' \
+                    ORDER BY table_name, ordinal_position"
+                        .to_string(),
+                ))
+                .await?;
+            let mut tables: Vec<(String, Vec<String>)> = Vec::new();
+            for row in rows {
+                let table_name: String = row.try_get("", "table_name")?;
+                let column_name: String = row.try_get("", "column_name")?;
+                let data_type: String = row.try_get("", "data_type")?;
+                let is_nullable: String = row.try_get("", "is_nullable")?;
+                let column = match is_nullable.as_str() {
+                    "NO" => format!("    {} {} NOT NULL", column_name, data_type),
+                    _ => format!("    {} {}", column_name, data_type),
+                };
+                match tables.last_mut() {
+                    Some((name, columns)) if *name == table_name => columns.push(column),
+                    _ => tables.push((table_name, vec![column])),
+                }
+            }
+            Ok(tables
+                .into_iter()
+                .map(|(name, columns)| {
+                    format!("CREATE TABLE {} (\n{}\n);", name, columns.join(",\n"))
+                })
+                .collect::<Vec<_>>()
+                .join("\n\n"))
+        }
+        _ => Err(DbErr::Custom(format!(
+            "Unsupported database backend: {:?}",
+            db_backend
+        ))),
+    }
+}
+
+/// Compares the migrations applied to the database against the ones known to this binary. Fails
+/// if the database has migrations that this binary does not know about, which happens when it was
+/// migrated by a newer version of Photon, or if migrations required by this binary are missing.
+pub async fn check_schema_version(db: &DatabaseConnection) -> Result<(), DbErr> {
+    let db_backend = db.get_database_backend();
+    let migrations_table_query = match db_backend {
+        DatabaseBackend::Sqlite => {
+            "SELECT COUNT(*) AS count FROM sqlite_master \
+            WHERE type = 'table' AND name = 'seaql_migrations'"
+        }
+        DatabaseBackend::Postgres => {
+            "SELECT COUNT(*) AS count FROM information_schema.tables \
+            WHERE table_schema = 'public' AND table_name = 'seaql_migrations'"
+        }
+        _ => {
+            return Err(DbErr::Custom(format!(
+                "Unsupported database backend: {:?}",
+                db_backend
+            )))
+        }
+    };
+    let migrations_table_exists = db
+        .query_one(Statement::from_string(
+            db_backend,
+            migrations_table_query.to_string(),
+        ))
+        .await?
+        .map(|row| row.try_get::<i64>("", "count"))
+        .transpose()?
+        .unwrap_or_default()
+        > 0;
+    let applied_migrations = match migrations_table_exists {
+        true => db
+            .query_all(Statement::from_string(
+                db_backend,
+                "SELECT version FROM seaql_migrations ORDER BY version".to_string(),
+            ))
+            .await?
+            .into_iter()
+            .map(|row| row.try_get::<String>("", "version"))
+            .collect::<Result<Vec<_>, _>>()?,
+        false => vec![],
+    };
+
+    let known_migrations = MigractorWithCustomMigrations::migrations()
+        .iter()
+        .map(|migration| migration.name().to_string())
+        .collect::<Vec<_>>();
+    let unknown_migrations = applied_migrations
+        .iter()
+        .filter(|version| !known_migrations.contains(version))
+        .cloned()
+        .collect::<Vec<_>>();
+    if !unknown_migrations.is_empty() {
+        return Err(DbErr::Custom(format!(
+            "The database schema is newer than this version of Photon. Unknown migrations: {}. \
+            Upgrade Photon or restart with --skip-schema-version-check to ignore this check.",
+            unknown_migrations.join(", ")
+        )));
+    }
+
+    let missing_migrations = Migrator::migrations()
+        .iter()
+        .map(|migration| migration.name().to_string())
+        .filter(|version| !applied_migrations.contains(version))
+        .collect::<Vec<_>>();
+    if !missing_migrations.is_empty() {
+        return Err(DbErr::Custom(format!(
+            "The database schema is older than this version of Photon. Missing migrations: {}. \
+            Run the migrations or restart with --skip-schema-version-check to ignore this check.",
+            missing_migrations.join(", ")
+        )));
+    }
+
+    Ok(())
+}
+
+const BACKFILL_BATCH_SIZE: i64 = 1000;
+
+/// Fills columns that were added by later migrations for rows indexed before those migrations
+/// ran, so that existing databases do not need to be reindexed. Only rows where the column is
+/// still NULL are touched, which makes the backfill safe to interrupt and rerun. Returns the
+/// number of backfilled rows.
+///
+/// Currently this derives `accounts.slot_spent` of spent accounts from the slot of the latest
+/// transaction referencing the account, which is the transaction that spent it.
+pub async fn backfill_columns(db: &DatabaseConnection) -> Result<u64, DbErr> {
+    let db_backend = db.get_database_backend();
+    let mut cursor: Vec<u8> = vec![];
+    let mut backfilled_rows = 0;
+    loop {
+        // Batch by hash ranges to keep each update transaction short on large databases.
+        let last_hash = db
+            .query_all(Statement::from_sql_and_values(
+                db_backend,
+                "SELECT hash FROM accounts \
+                WHERE spent = true AND slot_spent IS NULL AND hash > $1 \
+                ORDER BY hash LIMIT $2",
+                vec![cursor.clone().into(), BACKFILL_BATCH_SIZE.into()],
+            ))
+            .await?
+            .last()
+            .map(|row| row.try_get::<Vec<u8>>("", "hash"))
+            .transpose()?;
+        let last_hash = match last_hash {
+            Some(last_hash) => last_hash,
+            None => break,
+        };
+
+        let result = db
+            .execute(Statement::from_sql_and_values(
+                db_backend,
+                "UPDATE accounts SET slot_spent = ( \
+                    SELECT MAX(transactions.slot) FROM account_transactions \
+                    JOIN transactions ON transactions.signature = account_transactions.signature \
+                    WHERE account_transactions.hash = accounts.hash \
+                ) \
+                WHERE spent = true AND slot_spent IS NULL AND hash > $1 AND hash <= $2 \
+                AND EXISTS ( \
+                    SELECT 1 FROM account_transactions \
+                    WHERE account_transactions.hash = accounts.hash \
+                )",
+                vec![cursor.into(), last_hash.clone().into()],
+            ))
+            .await?;
+        backfilled_rows += result.rows_affected();
+        cursor = last_hash;
+    }
+    Ok(backfilled_rows)
+}
+
+/// Rebuilds the `state_trees` table, which holds the nodes of all state trees, either as a table
+/// hash-partitioned by tree into `partitions` partitions or, if `partitions` is `None`, as a single
+/// table. Partitioning keeps the indexes of each partition small on databases with many large
+/// trees, since proof lookups and node upserts always filter on a single tree. Existing nodes are
+/// copied into the rebuilt table. Only supported on Postgres.
+pub async fn repartition_state_trees<C: ConnectionTrait>(
+    db: &C,
+    partitions: Option<u32>,
+) -> Result<(), DbErr> {
+    let db_backend = db.get_database_backend();
+    if db_backend != DatabaseBackend::Postgres {
+        return Err(DbErr::Custom(format!(
+            "State tree partitioning is not supported on {:?}",
+            db_backend
+        )));
+    }
+    if partitions == Some(0) {
+        return Err(DbErr::Custom(
+            "The number of state tree partitions must be positive".to_string(),
+        ));
+    }
+    if get_state_tree_partitions(db).await? == partitions {
+        return Ok(());
+    }
+
+    let mut statements = vec!["DROP TABLE IF EXISTS state_trees_new".to_string()];
+    match partitions {
+        Some(partitions) => {
+            statements.push(
+                "CREATE TABLE state_trees_new (LIKE state_trees INCLUDING DEFAULTS) \
+                PARTITION BY HASH (tree)"
+                    .to_string(),
+            );
+            // Partition names include the modulus so that they never clash with the partitions
+            // of the table that is being replaced.
+            for remainder in 0..partitions {
+                statements.push(format!(
+                    "CREATE TABLE state_trees_{partitions}_{remainder} PARTITION OF state_trees_new \
+                    FOR VALUES WITH (MODULUS {partitions}, REMAINDER {remainder})"
+                ));
+            }
+        }
+        None => statements
+            .push("CREATE TABLE state_trees_new (LIKE state_trees INCLUDING DEFAULTS)".to_string()),
+    }
+    statements.extend(
+        [
+            "INSERT INTO state_trees_new SELECT * FROM state_trees",
+            "DROP TABLE state_trees",
+            "ALTER TABLE state_trees_new RENAME TO state_trees",
+            "ALTER TABLE state_trees ADD CONSTRAINT pk_state_trees PRIMARY KEY (tree, node_idx)",
+            "CREATE UNIQUE INDEX state_trees_tree_leaf_idx ON state_trees (tree, leaf_idx)",
+            "CREATE INDEX state_trees_hash_idx ON state_trees (hash) WHERE level = 0",
+            "CREATE INDEX state_trees_tree_seq_node_idx_idx ON state_trees (tree, seq, node_idx)",
+        ]
+        .map(String::from),
+    );
+    for statement in statements {
+        db.execute(Statement::from_string(db_backend, statement))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Returns the number of partitions of the `state_trees` table, or `None` if it is not
+/// partitioned.
+pub async fn get_state_tree_partitions<C: ConnectionTrait>(db: &C) -> Result<Option<u32>, DbErr> {
+    let partitions = db
+        .query_one(Statement::from_string(
+            db.get_database_backend(),
+            "SELECT COUNT(*) AS count FROM pg_inherits \
+            WHERE inhparent = 'state_trees'::regclass"
+                .to_string(),
+        ))
+        .await?
+        .map(|row| row.try_get::<i64>("", "count"))
+        .transpose()?
+        .unwrap_or_default();
+    Ok(match partitions {
+        0 => None,
+        partitions => Some(partitions as u32),
+    })
+}
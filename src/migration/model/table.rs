@@ -25,6 +25,10 @@ pub enum Accounts {
     PrevSpent,
     Seq,
     SlotCreated,
+    SlotSpent,
+    Lamports,
+    DataTruncated,
+    Finalized,
 }
 
 #[derive(Copy, Clone, Iden)]
@@ -99,3 +103,22 @@ pub enum StateTreeHistories {
     TransactionSignature,
     LeafIdx,
 }
+
+#[derive(Copy, Clone, Iden)]
+pub enum RawEvents {
+    Table,
+    Signature,
+    EventIndex,
+    Slot,
+    Kind,
+    Data,
+}
+
+#[derive(Copy, Clone, Iden)]
+pub enum AccountMappings {
+    Table,
+    Signature,
+    InputHash,
+    OutputHash,
+    Slot,
+}
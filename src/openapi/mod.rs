@@ -1,10 +1,19 @@
 use std::collections::HashSet;
 
 use crate::api::api::PhotonApi;
+use crate::api::method::decode_compressed_account::DecodedAccount;
+use crate::api::method::get_compressed_account::AccountSource;
+use crate::api::method::get_compressed_account_and_proof_by_address::AccountWithProof;
+use crate::api::method::get_compressed_account_proof::MerkleProofPath;
+use crate::api::method::get_compressed_account_summary::AccountSummary;
+use crate::api::method::get_compressed_accounts_by_lamport_range::PaginatedLamportRangeAccountList;
+use crate::api::method::get_compressed_accounts_by_owner::AccountOrder;
 use crate::api::method::get_compressed_accounts_by_owner::DataSlice;
 use crate::api::method::get_compressed_accounts_by_owner::FilterSelector;
 use crate::api::method::get_compressed_accounts_by_owner::Memcmp;
 use crate::api::method::get_compressed_accounts_by_owner::PaginatedAccountList;
+use crate::api::method::get_compressed_accounts_by_owner_grouped_by_tree::PaginatedTreeAccountsList;
+use crate::api::method::get_compressed_accounts_by_owner_grouped_by_tree::TreeAccounts;
 use crate::api::method::get_compressed_mint_token_holders::OwnerBalance;
 use crate::api::method::get_compressed_mint_token_holders::OwnerBalanceList;
 use crate::api::method::get_compressed_mint_token_holders::OwnerBalancesResponse;
@@ -12,12 +21,25 @@ use crate::api::method::get_compressed_token_account_balance::TokenAccountBalanc
 use crate::api::method::get_compressed_token_balances_by_owner::TokenBalance;
 use crate::api::method::get_compressed_token_balances_by_owner::TokenBalanceList;
 use crate::api::method::get_compressed_token_balances_by_owner::TokenBalanceListV2;
+use crate::api::method::get_indexer_health::{IndexerHealth, IndexerHealthStatus};
+use crate::api::method::get_indexer_stats::IndexerStats;
+use crate::api::method::get_ingestion_status::IngestionStatus;
+use crate::api::method::get_instruction_validity_proof::InstructionValidityProof;
+use crate::api::method::get_latest_compressed_accounts::PaginatedLatestAccountList;
 use crate::api::method::get_multiple_compressed_accounts::AccountList;
 
 use crate::api::method::get_multiple_new_address_proofs::AddressListWithTrees;
 use crate::api::method::get_multiple_new_address_proofs::AddressWithTree;
 use crate::api::method::get_multiple_new_address_proofs::MerkleContextWithNewAddressProof;
+use crate::api::method::get_recent_parse_errors::ParseError;
+use crate::api::method::get_recently_spent_accounts::PaginatedSpentAccountList;
+use crate::api::method::get_recently_spent_accounts::SpentAccount;
+use crate::api::method::get_transaction_account_mapping::AccountMapping;
 use crate::api::method::get_transaction_with_compression_info::AccountWithOptionalTokenData;
+use crate::api::method::get_tree_nodes::PaginatedTreeNodeList;
+use crate::api::method::get_tree_nodes::TreeNode;
+use crate::api::method::get_tree_size::TreeSize;
+use crate::api::method::get_unspent_account_set::PaginatedUnspentAccountList;
 use crate::api::method::get_validity_proof::CompressedProof;
 use crate::api::method::get_validity_proof::CompressedProofWithContext;
 use crate::api::method::utils::Context;
@@ -29,6 +51,7 @@ use crate::api::method::utils::SignatureInfoListWithError;
 use crate::api::method::utils::SignatureInfoWithError;
 use crate::api::method::utils::TokenAcccount;
 use crate::api::method::utils::TokenAccountList;
+use crate::api::method::verify_proof::ProofVerification;
 use crate::common::typedefs::account::Account;
 use crate::common::typedefs::account::AccountData;
 use crate::common::typedefs::bs58_string::Base58String;
@@ -40,6 +63,8 @@ use crate::common::typedefs::token_data::AccountState;
 use crate::common::typedefs::token_data::TokenData;
 use crate::common::typedefs::unix_timestamp::UnixTimestamp;
 use crate::common::typedefs::unsigned_integer::UnsignedInteger;
+use crate::ingester::fetchers::status::BlockSource;
+use crate::ingester::fetchers::status::GrpcConnectionState;
 use crate::ingester::persist::persisted_state_tree::MerkleProofWithContext;
 use dirs;
 use utoipa::openapi::Components;
@@ -104,12 +129,38 @@ const JSON_CONTENT_TYPE: &str = "application/json";
     DataSlice,
     FilterSelector,
     Memcmp,
+    AccountOrder,
     AddressListWithTrees,
     AddressWithTree,
     OwnerBalance,
     OwnerBalanceList,
     OwnerBalancesResponse,
     TokenBalanceListV2,
+    IndexerStats,
+    DecodedAccount,
+    MerkleProofPath,
+    SpentAccount,
+    PaginatedSpentAccountList,
+    IngestionStatus,
+    BlockSource,
+    GrpcConnectionState,
+    PaginatedLatestAccountList,
+    TreeAccounts,
+    PaginatedTreeAccountsList,
+    PaginatedLamportRangeAccountList,
+    ProofVerification,
+    AccountMapping,
+    TreeSize,
+    InstructionValidityProof,
+    IndexerHealth,
+    IndexerHealthStatus,
+    AccountWithProof,
+    TreeNode,
+    PaginatedTreeNodeList,
+    ParseError,
+    PaginatedUnspentAccountList,
+    AccountSummary,
+    AccountSource,
 )))]
 struct ApiDoc;
 
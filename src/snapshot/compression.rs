@@ -0,0 +1,157 @@
+use std::io::{self, Write};
+
+use anyhow::Result;
+use async_stream::stream;
+use bytes::Bytes;
+use clap::ValueEnum;
+use flate2::write::{GzEncoder, MultiGzDecoder};
+use futures::{pin_mut, Stream, StreamExt};
+
+/// Compression of the snapshot files written by the snapshotter. Files are decompressed according
+/// to their extension when read, so a directory can mix compressed and uncompressed files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum SnapshotCompression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl SnapshotCompression {
+    /// Extension of the names of files compressed this way.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SnapshotCompression::None => "",
+            SnapshotCompression::Gzip => ".gz",
+            SnapshotCompression::Zstd => ".zst",
+        }
+    }
+
+    /// Splits the name of a file into its name without extension and its compression.
+    pub fn from_file_name(file_name: &str) -> (&str, SnapshotCompression) {
+        for compression in [SnapshotCompression::Gzip, SnapshotCompression::Zstd] {
+            if let Some(name) = file_name.strip_suffix(compression.extension()) {
+                return (name, compression);
+            }
+        }
+        (file_name, SnapshotCompression::None)
+    }
+
+    /// Token of this compression in the `Accept-Encoding` and `Content-Encoding` HTTP headers.
+    pub fn content_encoding(&self) -> Option<&'static str> {
+        match self {
+            SnapshotCompression::None => None,
+            SnapshotCompression::Gzip => Some("gzip"),
+            SnapshotCompression::Zstd => Some("zstd"),
+        }
+    }
+
+    pub fn compress(
+        &self,
+        byte_stream: impl Stream<Item = Result<Bytes>> + Send + 'static,
+    ) -> impl Stream<Item = Result<Bytes>> + Send + 'static {
+        let encoder: io::Result<Option<Box<dyn Transcoder>>> = match self {
+            SnapshotCompression::None => Ok(None),
+            SnapshotCompression::Gzip => Ok(Some(Box::new(GzEncoder::new(
+                Vec::new(),
+                flate2::Compression::default(),
+            )))),
+            SnapshotCompression::Zstd => {
+                zstd::stream::write::Encoder::new(Vec::new(), zstd::DEFAULT_COMPRESSION_LEVEL)
+                    .map(|encoder| Some(Box::new(encoder) as Box<dyn Transcoder>))
+            }
+        };
+        transcode(byte_stream, encoder)
+    }
+
+    pub fn decompress(
+        &self,
+        byte_stream: impl Stream<Item = Result<Bytes>> + Send + 'static,
+    ) -> impl Stream<Item = Result<Bytes>> + Send + 'static {
+        let decoder: io::Result<Option<Box<dyn Transcoder>>> = match self {
+            SnapshotCompression::None => Ok(None),
+            SnapshotCompression::Gzip => Ok(Some(Box::new(MultiGzDecoder::new(Vec::new())))),
+            SnapshotCompression::Zstd => zstd::stream::write::Decoder::new(Vec::new())
+                .map(|decoder| Some(Box::new(decoder) as Box<dyn Transcoder>)),
+        };
+        transcode(byte_stream, decoder)
+    }
+}
+
+// Compresses or decompresses the bytes written to it into an in-memory buffer, which is drained
+// after every write so that streams are transcoded in constant memory.
+trait Transcoder: Write + Send {
+    fn output(&mut self) -> &mut Vec<u8>;
+
+    fn finish(self: Box<Self>) -> io::Result<Vec<u8>>;
+}
+
+impl Transcoder for GzEncoder<Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<Vec<u8>> {
+        GzEncoder::finish(*self)
+    }
+}
+
+impl Transcoder for MultiGzDecoder<Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<Vec<u8>> {
+        MultiGzDecoder::finish(*self)
+    }
+}
+
+impl Transcoder for zstd::stream::write::Encoder<'static, Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<Vec<u8>> {
+        zstd::stream::write::Encoder::finish(*self)
+    }
+}
+
+impl Transcoder for zstd::stream::write::Decoder<'static, Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<Vec<u8>> {
+        self.flush()?;
+        Ok((*self).into_inner())
+    }
+}
+
+fn transcode(
+    byte_stream: impl Stream<Item = Result<Bytes>> + Send + 'static,
+    transcoder: io::Result<Option<Box<dyn Transcoder>>>,
+) -> impl Stream<Item = Result<Bytes>> + Send + 'static {
+    stream! {
+        pin_mut!(byte_stream);
+        let mut transcoder = match transcoder? {
+            Some(transcoder) => transcoder,
+            None => {
+                while let Some(bytes) = byte_stream.next().await {
+                    yield bytes;
+                }
+                return;
+            }
+        };
+        while let Some(bytes) = byte_stream.next().await {
+            transcoder.write_all(&bytes?)?;
+            let output = std::mem::take(transcoder.output());
+            if !output.is_empty() {
+                yield Ok(Bytes::from(output));
+            }
+        }
+        let output = transcoder.finish()?;
+        if !output.is_empty() {
+            yield Ok(Bytes::from(output));
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use futures::{pin_mut, StreamExt};
+use serde::Serialize;
+
+use crate::common::typedefs::hash::Hash;
+use crate::common::typedefs::serializable_pubkey::SerializablePubkey;
+use crate::ingester::parser::state_update::EventAccounts;
+use crate::ingester::parser::{parse_transaction, ParserConfig};
+
+use super::{
+    get_snapshot_files_with_metadata, load_block_stream_from_directory_adapter, DirectoryAdapter,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlotRange {
+    pub start_slot: u64,
+    pub end_slot: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountUpdate {
+    pub input_hash: Hash,
+    pub output_hash: Hash,
+}
+
+/// Net changes to the compressed accounts between the end of a base snapshot and the end of a
+/// later snapshot of the same chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotDiff {
+    pub base_slots: SlotRange,
+    /// Slots of the later snapshot after the end of the base snapshot. None if the later snapshot
+    /// does not end after the base snapshot.
+    pub diff_slots: Option<SlotRange>,
+    /// Accounts created in the diff slots that are unspent at their end, except for the accounts
+    /// that supersede an account of the base snapshot.
+    pub added_accounts: Vec<Hash>,
+    /// Accounts of the base snapshot spent in the diff slots without being superseded.
+    pub spent_accounts: Vec<Hash>,
+    /// Accounts of the base snapshot superseded in the diff slots, by an account with the same
+    /// address, or with the same owner for accounts without address, mapped to their latest
+    /// version.
+    pub updated_accounts: Vec<AccountUpdate>,
+}
+
+type AccountIdentity = (Option<SerializablePubkey>, SerializablePubkey);
+
+/// Compares the accounts of a snapshot with the accounts of a base snapshot. The snapshot must
+/// continue the chain of the base snapshot, i.e. start at most one slot after its end.
+pub async fn diff_snapshots(
+    base: Arc<DirectoryAdapter>,
+    snapshot: Arc<DirectoryAdapter>,
+    parser_config: &ParserConfig,
+) -> Result<SnapshotDiff> {
+    let base_slots = get_slot_range(base.as_ref()).await?;
+    let snapshot_slots = get_slot_range(snapshot.as_ref()).await?;
+    if snapshot_slots.start_slot > base_slots.end_slot + 1 {
+        return Err(anyhow!(
+            "Snapshot starting at slot {} does not continue the base snapshot ending at slot {}",
+            snapshot_slots.start_slot,
+            base_slots.end_slot
+        ));
+    }
+    let diff_slots = match snapshot_slots.end_slot > base_slots.end_slot {
+        true => Some(SlotRange {
+            start_slot: base_slots.end_slot + 1,
+            end_slot: snapshot_slots.end_slot,
+        }),
+        false => None,
+    };
+
+    let mut events = Vec::new();
+    let mut identities = HashMap::new();
+    let mut created_accounts = Vec::new();
+    let block_stream = load_block_stream_from_directory_adapter(snapshot).await;
+    pin_mut!(block_stream);
+    while let Some(blocks) = block_stream.next().await {
+        for block in blocks {
+            let slot = block.metadata.slot;
+            if slot <= base_slots.end_slot {
+                continue;
+            }
+            for transaction in block.transactions.iter() {
+                let state_update = parse_transaction(transaction, slot, parser_config)?;
+                for account in state_update.out_accounts {
+                    created_accounts.push(account.hash.clone());
+                    identities.insert(account.hash, (account.address, account.owner));
+                }
+                events.extend(state_update.event_accounts);
+            }
+        }
+    }
+
+    // The identities of the spent accounts of the base snapshot are only found in the blocks in
+    // which they were created.
+    let mut base_inputs = events
+        .iter()
+        .flat_map(|event| event.input_hashes.iter())
+        .filter(|hash| !identities.contains_key(*hash))
+        .cloned()
+        .collect::<HashSet<_>>();
+    let block_stream = load_block_stream_from_directory_adapter(base).await;
+    pin_mut!(block_stream);
+    while let Some(blocks) = block_stream.next().await {
+        if base_inputs.is_empty() {
+            break;
+        }
+        for block in blocks {
+            for transaction in block.transactions.iter() {
+                let state_update =
+                    parse_transaction(transaction, block.metadata.slot, parser_config)?;
+                for account in state_update.out_accounts {
+                    if base_inputs.remove(&account.hash) {
+                        identities.insert(account.hash, (account.address, account.owner));
+                    }
+                }
+            }
+        }
+    }
+
+    let (spent, superseded_by) = map_superseding_accounts(&events, &identities);
+    let mut added_accounts = Vec::new();
+    let mut spent_accounts = Vec::new();
+    let mut updated_accounts = Vec::new();
+    let mut latest_versions = HashSet::new();
+    let created = created_accounts.iter().collect::<HashSet<_>>();
+    for hash in events.iter().flat_map(|event| event.input_hashes.iter()) {
+        if created.contains(hash) {
+            continue;
+        }
+        let mut latest_version = hash;
+        while let Some(output) = superseded_by.get(latest_version) {
+            latest_version = output;
+        }
+        match spent.contains(latest_version) {
+            true => spent_accounts.push(hash.clone()),
+            false => {
+                latest_versions.insert(latest_version.clone());
+                updated_accounts.push(AccountUpdate {
+                    input_hash: hash.clone(),
+                    output_hash: latest_version.clone(),
+                });
+            }
+        }
+    }
+    for hash in created_accounts {
+        if !spent.contains(&hash) && !latest_versions.contains(&hash) {
+            added_accounts.push(hash);
+        }
+    }
+
+    Ok(SnapshotDiff {
+        base_slots,
+        diff_slots,
+        added_accounts,
+        spent_accounts,
+        updated_accounts,
+    })
+}
+
+async fn get_slot_range(directory_adapter: &DirectoryAdapter) -> Result<SlotRange> {
+    let snapshot_files = get_snapshot_files_with_metadata(directory_adapter).await?;
+    match (snapshot_files.first(), snapshot_files.last()) {
+        (Some(first), Some(last)) => Ok(SlotRange {
+            start_slot: first.start_slot,
+            end_slot: last.end_slot,
+        }),
+        _ => Err(anyhow!("No snapshot files found")),
+    }
+}
+
+// Returns the spent accounts and the output superseding each input, matched the same way as the
+// account mappings persisted by the indexer.
+fn map_superseding_accounts(
+    events: &[EventAccounts],
+    identities: &HashMap<Hash, AccountIdentity>,
+) -> (HashSet<Hash>, HashMap<Hash, Hash>) {
+    let mut spent = HashSet::new();
+    let mut superseded_by = HashMap::new();
+    for event in events {
+        let mut superseding = vec![false; event.output_hashes.len()];
+        for input in event.input_hashes.iter() {
+            spent.insert(input.clone());
+            let (address, owner) = match identities.get(input) {
+                Some(identity) => identity,
+                None => continue,
+            };
+            let position = (0..event.output_hashes.len()).find(|&i| {
+                !superseding[i]
+                    && match identities.get(&event.output_hashes[i]) {
+                        Some((output_address, output_owner)) => match address {
+                            Some(_) => output_address == address,
+                            None => output_address.is_none() && output_owner == owner,
+                        },
+                        None => false,
+                    }
+            });
+            if let Some(position) = position {
+                superseding[position] = true;
+                superseded_by.insert(input.clone(), event.output_hashes[position].clone());
+            }
+        }
+    }
+    (spent, superseded_by)
+}
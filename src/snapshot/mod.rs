@@ -1,11 +1,13 @@
 use std::{
+    collections::HashSet,
     env::temp_dir,
     fs::{self, File, OpenOptions},
-    io::{BufReader, Error, ErrorKind, Read, Write},
-    path::PathBuf,
+    io::{Error, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
     pin::Pin,
-    sync::Arc,
+    sync::{Arc, Mutex},
     task::Poll,
+    time::Duration,
 };
 
 pub use crate::common::{
@@ -16,25 +18,82 @@ use crate::ingester::{
     parser::ACCOUNT_COMPRESSION_PROGRAM_ID,
     typedefs::block_info::{BlockInfo, Instruction, TransactionInfo},
 };
+use crate::metric;
 use anyhow::{anyhow, Context as AnyhowContext, Result};
 use async_stream::stream;
-use bytes::{BufMut, Bytes};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use cadence_macros::{statsd_count, statsd_gauge};
 use futures::stream::StreamExt;
 use futures::{pin_mut, stream, Stream};
-use log::info;
+use log::{error, info, warn};
 use s3::creds::Credentials;
 use s3::region::Region;
 use s3::{bucket::Bucket, BucketConfiguration};
 use s3_utils::multipart_upload::put_object_stream_custom;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use tokio::io::{AsyncRead, ReadBuf};
+use tokio::sync::mpsc;
+pub mod compression;
+pub mod diff;
 pub mod s3_utils;
 
+use compression::SnapshotCompression;
+
 pub const MEGABYTE: usize = 1024 * 1024;
 pub const CHUNK_SIZE: usize = 100 * 1024 * 1024;
+/// Default size of the chunks in which snapshots are streamed to clients.
+pub const DEFAULT_DOWNLOAD_CHUNK_SIZE: usize = 64 * 1024;
 // Up to 50 MB
 pub const TRANSACTIONS_TO_ACCUMULATE: usize = 5000;
 
 const SNAPSHOT_VERSION: u8 = 1;
+/// Extension of the sidecar files holding the SHA-256 digests of the snapshot files, in the
+/// format of `sha256sum`.
+pub const CHECKSUM_EXTENSION: &str = ".sha256";
+/// Name of the file in a dead-letter directory that records the snapshot files that could not be
+/// written, one JSON encoded [`DeadLetter`] per line.
+pub const DEAD_LETTER_RECORD_FILE: &str = "dead-letters.jsonl";
+/// Default number of snapshot writes that can be queued while another write is in progress.
+/// Snapshot files are written in the background, so that blocks keep being ingested while a file
+/// is uploaded, and the snapshotter only stops consuming blocks once this many writes are queued.
+pub const DEFAULT_MAX_PENDING_SNAPSHOT_WRITES: usize = 16;
+
+/// How often failed writes of snapshot files are retried, and where files are kept once all
+/// attempts have failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadRetryPolicy {
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled after every further attempt.
+    pub initial_delay: Duration,
+    /// Local directory that files are dead-lettered to for manual upload. Without one, the write
+    /// fails once all attempts have failed.
+    pub dead_letter_dir: Option<String>,
+}
+
+impl Default for UploadRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_delay: Duration::from_secs(1),
+            dead_letter_dir: None,
+        }
+    }
+}
+
+/// A snapshot file that could not be written to the snapshot directory and was stored in the
+/// dead-letter directory instead.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeadLetter {
+    /// Name of the file, which is the same in the dead-letter directory.
+    pub file: String,
+    /// Number of failed attempts to write the file, which is 0 for files held back because an
+    /// earlier file was dead-lettered.
+    pub attempts: u32,
+    /// Error of the last attempt.
+    pub error: String,
+}
 
 pub struct R2DirectoryAdapter {
     pub r2_bucket: Bucket,
@@ -111,20 +170,32 @@ impl R2DirectoryAdapter {
     async fn read_file(
         arc_self: Arc<Self>,
         path: String,
+        offset: u64,
     ) -> impl Stream<Item = Result<Bytes>> + std::marker::Send + 'static {
         stream! {
             let r2_directory_adapter = arc_self.clone();
             let mut result = r2_directory_adapter.r2_bucket.get_object_stream(path.clone()).await.with_context(|| format!("Failed to read file: {:?}", path))?;
             let stream = result.bytes();
 
+            // The bytes before the offset are downloaded but not yielded.
+            let mut skipped = 0;
             while let Some(byte) = stream.next().await {
-                let byte = byte.with_context(|| "Failed to read byte from file").unwrap();
+                let mut byte = byte.with_context(|| "Failed to read byte from file").unwrap();
+                if skipped < offset {
+                    let skip = std::cmp::min(offset - skipped, byte.len() as u64);
+                    byte.advance(skip as usize);
+                    skipped += skip;
+                    if byte.is_empty() {
+                        continue;
+                    }
+                }
                 yield Ok(byte);
             }
         }
     }
 
-    async fn list_files(&self) -> Result<Vec<String>> {
+    /// Lists the names of the files along with their sizes in bytes.
+    async fn list_files(&self) -> Result<Vec<(String, u64)>> {
         let results = self
             .r2_bucket
             .list(self.r2_prefix.clone(), None)
@@ -134,7 +205,7 @@ impl R2DirectoryAdapter {
         let mut files = Vec::new();
         for result in results {
             for object in result.contents {
-                files.push(object.key);
+                files.push((object.key, object.size));
             }
         }
         Ok(files)
@@ -172,26 +243,32 @@ pub struct FileSystemDirectoryApapter {
 }
 
 impl FileSystemDirectoryApapter {
-    async fn read_file(&self, path: String) -> impl Stream<Item = Result<Bytes>> + Send {
+    async fn read_file(
+        &self,
+        path: String,
+        offset: u64,
+    ) -> impl Stream<Item = Result<Bytes>> + Send {
         let path = format!("{}/{}", self.snapshot_dir, path);
-        let file = OpenOptions::new().read(true).open(path).unwrap();
-        let bytes = BufReader::new(file).bytes();
+        let mut file = OpenOptions::new().read(true).open(path).unwrap();
         stream! {
-            let mut byte_chunk = vec![];
-            for byte in bytes.into_iter() {
-                byte_chunk.push(byte.with_context(|| "Failed to read byte from file")?);
-                if byte_chunk.len() == CHUNK_SIZE {
-                    yield Ok(Bytes::from(byte_chunk.clone()));
-                    byte_chunk.clear();
+            file.seek(SeekFrom::Start(offset))
+                .with_context(|| "Failed to seek in file")?;
+            loop {
+                let mut byte_chunk = Vec::new();
+                let read = (&mut file)
+                    .take(CHUNK_SIZE as u64)
+                    .read_to_end(&mut byte_chunk)
+                    .with_context(|| "Failed to read chunk from file")?;
+                if read == 0 {
+                    break;
                 }
-            }
-            if !byte_chunk.is_empty() {
                 yield Ok(Bytes::from(byte_chunk));
             }
         }
     }
 
-    async fn list_files(&self) -> Result<Vec<String>> {
+    /// Lists the names of the files along with their sizes in bytes.
+    async fn list_files(&self) -> Result<Vec<(String, u64)>> {
         if !PathBuf::new().join(&self.snapshot_dir).exists() {
             return Ok(Vec::new());
         }
@@ -201,7 +278,7 @@ impl FileSystemDirectoryApapter {
         for file in files {
             let file = file?;
             let file_name = file.file_name().into_string().unwrap();
-            file_names.push(file_name);
+            file_names.push((file_name, file.metadata()?.len()));
         }
         Ok(file_names)
     }
@@ -218,16 +295,19 @@ impl FileSystemDirectoryApapter {
         path: String,
         bytes: impl Stream<Item = Result<Bytes>>,
     ) -> Result<()> {
-        let (mut temp_file, temp_path) = create_temp_snapshot_file(&self.snapshot_dir);
+        let (mut temp_file, temp_path) = create_temp_snapshot_file(&self.snapshot_dir)?;
         pin_mut!(bytes);
         while let Some(byte) = bytes.next().await {
             let byte = byte?;
-            temp_file.write_all(&byte).unwrap();
+            temp_file
+                .write_all(&byte)
+                .with_context(|| format!("Failed to write file: {:?}", temp_path))?;
         }
 
         // Create snapshot directory if it doesn't exist
         if !PathBuf::new().join(&self.snapshot_dir).exists() {
-            fs::create_dir_all(&self.snapshot_dir).unwrap();
+            fs::create_dir_all(&self.snapshot_dir)
+                .with_context(|| format!("Failed to create directory: {:?}", self.snapshot_dir))?;
         }
         let path = format!("{}/{}", self.snapshot_dir, path);
         fs::rename(temp_path.clone(), path.clone())
@@ -242,6 +322,8 @@ impl FileSystemDirectoryApapter {
 pub struct DirectoryAdapter {
     filesystem_directory_adapter: Option<Arc<FileSystemDirectoryApapter>>,
     r2_directory_adapter: Option<Arc<R2DirectoryAdapter>>,
+    compression: SnapshotCompression,
+    upload_retry_policy: UploadRetryPolicy,
 }
 
 impl DirectoryAdapter {
@@ -258,9 +340,23 @@ impl DirectoryAdapter {
         Self {
             filesystem_directory_adapter: filesystem_directory_adapter.map(Arc::new),
             r2_directory_adapter: r2_directory_adapter.map(Arc::new),
+            compression: SnapshotCompression::None,
+            upload_retry_policy: UploadRetryPolicy::default(),
         }
     }
 
+    /// Compresses the files written from now on. Files are always decompressed when read.
+    pub fn with_compression(mut self, compression: SnapshotCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Retries failed writes of the snapshot files written by the snapshotter.
+    pub fn with_upload_retry_policy(mut self, upload_retry_policy: UploadRetryPolicy) -> Self {
+        self.upload_retry_policy = upload_retry_policy;
+        self
+    }
+
     pub fn from_local_directory(snapshot_dir: String) -> Self {
         Self::new(Some(FileSystemDirectoryApapter { snapshot_dir }), None)
     }
@@ -294,19 +390,29 @@ impl DirectoryAdapter {
         )
     }
 
-    /// Reads the contents of a file at the given path
+    /// Reads the decompressed contents of a file at the given path
     async fn read_file(&self, path: String) -> impl Stream<Item = Result<Bytes>> + 'static {
+        let (_, compression) = SnapshotCompression::from_file_name(&path);
+        compression.decompress(self.read_raw_file(path, 0).await)
+    }
+
+    /// Reads the contents of a file at the given path as they are stored, starting at `offset`
+    async fn read_raw_file(
+        &self,
+        path: String,
+        offset: u64,
+    ) -> impl Stream<Item = Result<Bytes>> + 'static {
         let file_system_directory_adapter = self.filesystem_directory_adapter.clone();
         let r2_directory_adapter = self.r2_directory_adapter.clone();
         stream! {
             if let Some(filesystem_directory_adapter) = file_system_directory_adapter {
-                let stream = filesystem_directory_adapter.read_file(path).await;
+                let stream = filesystem_directory_adapter.read_file(path, offset).await;
                 pin_mut!(stream);
                 while let Some(byte) = stream.next().await {
                     yield byte;
                 }
             } else if let Some(r2_directory_adapter) = r2_directory_adapter {
-                let stream = R2DirectoryAdapter::read_file(r2_directory_adapter, path).await;
+                let stream = R2DirectoryAdapter::read_file(r2_directory_adapter, path, offset).await;
                 pin_mut!(stream);
                 while let Some(byte) = stream.next().await {
                     yield byte;
@@ -317,8 +423,8 @@ impl DirectoryAdapter {
         }
     }
 
-    /// Writes data to a file at the given path
-    async fn list_files(&self) -> Result<Vec<String>> {
+    /// Lists the names of the files along with their sizes in bytes
+    async fn list_files(&self) -> Result<Vec<(String, u64)>> {
         if let Some(filesystem_directory_adapter) = &self.filesystem_directory_adapter {
             filesystem_directory_adapter.list_files().await
         } else if let Some(r2_directory_adapter) = &self.r2_directory_adapter {
@@ -328,8 +434,23 @@ impl DirectoryAdapter {
         }
     }
 
-    /// Deletes the file at the given path
+    /// Deletes the file at the given path, along with its checksum file if it has one
     pub async fn delete_file(&self, path: String) -> Result<()> {
+        if !path.ends_with(CHECKSUM_EXTENSION) {
+            let checksum_path = format!("{}{}", path, CHECKSUM_EXTENSION);
+            let has_checksum = self
+                .list_files()
+                .await?
+                .iter()
+                .any(|(file, _)| *file == checksum_path);
+            if has_checksum {
+                self.delete_raw_file(checksum_path).await?;
+            }
+        }
+        self.delete_raw_file(path).await
+    }
+
+    async fn delete_raw_file(&self, path: String) -> Result<()> {
         if let Some(filesystem_directory_adapter) = &self.filesystem_directory_adapter {
             filesystem_directory_adapter.delete_file(path).await
         } else if let Some(r2_directory_adapter) = &self.r2_directory_adapter {
@@ -339,11 +460,117 @@ impl DirectoryAdapter {
         }
     }
 
-    /// Write file to the given path
+    /// Write file to the given path, followed by the extension of the compression of the adapter.
+    /// The SHA-256 digest of the stored bytes is written to a checksum file next to it.
     async fn write_file(
         &self,
         path: String,
         bytes: impl Stream<Item = Result<Bytes>> + std::marker::Send + 'static,
+    ) -> Result<()> {
+        let path = format!("{}{}", path, self.compression.extension());
+        let hasher = Arc::new(Mutex::new(Sha256::new()));
+        let bytes = {
+            let hasher = hasher.clone();
+            self.compression.compress(bytes).map(move |bytes| {
+                if let Ok(bytes) = &bytes {
+                    hasher.lock().unwrap().update(bytes);
+                }
+                bytes
+            })
+        };
+        self.write_raw_file(path.clone(), bytes).await?;
+
+        let digest = hex::encode(hasher.lock().unwrap().clone().finalize());
+        let checksum = format!("{}  {}\n", digest, path);
+        self.write_raw_file(
+            format!("{}{}", path, CHECKSUM_EXTENSION),
+            stream::iter(vec![Ok(Bytes::from(checksum))]),
+        )
+        .await
+    }
+
+    /// Writes the bytes to a file like `write_file`, retrying failed writes according to the upload
+    /// retry policy. Once all attempts have failed, the file is written to the dead-letter
+    /// directory and recorded there, and its dead letter is returned.
+    pub async fn write_file_with_retries(
+        &self,
+        path: String,
+        bytes: Bytes,
+    ) -> Result<Option<DeadLetter>> {
+        let max_attempts = self.upload_retry_policy.max_attempts.max(1);
+        let mut delay = self.upload_retry_policy.initial_delay;
+        let mut attempt = 1;
+        let err = loop {
+            let byte_stream = stream::iter(vec![Ok(bytes.clone())]);
+            match self.write_file(path.clone(), byte_stream).await {
+                Ok(()) => return Ok(None),
+                Err(err) if attempt < max_attempts => {
+                    warn!(
+                        "Failed to write snapshot file {} (attempt {}): {:?}",
+                        path, attempt, err
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(err) => break err,
+            }
+        };
+        if self.upload_retry_policy.dead_letter_dir.is_none() {
+            return Err(err);
+        }
+        self.dead_letter_file(path, bytes, attempt, format!("{:?}", err))
+            .await
+            .map(Some)
+    }
+
+    /// Writes the bytes to the dead-letter directory of the upload retry policy instead of the
+    /// snapshot directory, and records the file there with the given number of failed attempts
+    /// and error.
+    pub async fn dead_letter_file(
+        &self,
+        path: String,
+        bytes: Bytes,
+        attempts: u32,
+        error: String,
+    ) -> Result<DeadLetter> {
+        let dead_letter_dir = self
+            .upload_retry_policy
+            .dead_letter_dir
+            .as_ref()
+            .ok_or_else(|| anyhow!("No dead-letter directory configured"))?;
+        error!(
+            "Writing snapshot file {} to {} instead of the snapshot directory after {} attempts: \
+             {}",
+            path, dead_letter_dir, attempts, error
+        );
+        DirectoryAdapter::from_local_directory(dead_letter_dir.clone())
+            .with_compression(self.compression)
+            .write_file(path.clone(), stream::iter(vec![Ok(bytes)]))
+            .await?;
+        let dead_letter = DeadLetter {
+            file: format!("{}{}", path, self.compression.extension()),
+            attempts,
+            error,
+        };
+        let record_path = PathBuf::from(dead_letter_dir).join(DEAD_LETTER_RECORD_FILE);
+        let mut record_file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&record_path)
+            .with_context(|| format!("Failed to open file: {:?}", record_path))?;
+        writeln!(record_file, "{}", serde_json::to_string(&dead_letter)?)
+            .with_context(|| format!("Failed to write file: {:?}", record_path))?;
+        metric! {
+            statsd_count!("snapshot_upload.dead_lettered", 1);
+        }
+        Ok(dead_letter)
+    }
+
+    async fn write_raw_file(
+        &self,
+        path: String,
+        bytes: impl Stream<Item = Result<Bytes>> + std::marker::Send + 'static,
     ) -> Result<()> {
         if let Some(filesystem_directory_adapter) = &self.filesystem_directory_adapter {
             filesystem_directory_adapter.write_file(path, bytes).await
@@ -381,17 +608,36 @@ pub struct SnapshotFileWithSlots {
     pub file: String,
     pub start_slot: u64,
     pub end_slot: u64,
+    pub compression: SnapshotCompression,
+    /// Size of the file in bytes, as stored.
+    pub size: u64,
+    /// Total size of the files up to and including this one, so that the file spans the bytes
+    /// from `cumulative_size - size` up to `cumulative_size` of the concatenated files.
+    pub cumulative_size: u64,
+    /// Whether the file has a checksum file. Files written before checksums were introduced
+    /// have none.
+    pub has_checksum: bool,
 }
 
 pub async fn get_snapshot_files_with_metadata(
     directory_adapter: &DirectoryAdapter,
 ) -> anyhow::Result<Vec<SnapshotFileWithSlots>> {
-    let snapshot_files = directory_adapter.list_files().await?;
+    let (checksum_files, snapshot_files): (Vec<_>, Vec<_>) = directory_adapter
+        .list_files()
+        .await?
+        .into_iter()
+        .partition(|(file, _)| file.ends_with(CHECKSUM_EXTENSION));
+    let checksum_files = checksum_files
+        .into_iter()
+        .map(|(file, _)| file)
+        .collect::<HashSet<_>>();
     let mut snapshot_files_with_slots = Vec::new();
 
-    for file in snapshot_files {
+    for (file, size) in snapshot_files {
+        let has_checksum = checksum_files.contains(&format!("{}{}", file, CHECKSUM_EXTENSION));
         // Make this return an error if file name is not in the expected format
-        let parts: Vec<&str> = file.split('-').collect();
+        let (name, compression) = SnapshotCompression::from_file_name(&file);
+        let parts: Vec<&str> = name.split('-').collect();
         if parts.len() == 3 {
             let start_slot = parts[1].parse::<u64>()?;
             let end_slot = parts[2].parse::<u64>()?;
@@ -399,27 +645,123 @@ pub async fn get_snapshot_files_with_metadata(
                 file,
                 start_slot,
                 end_slot,
+                compression,
+                size,
+                cumulative_size: 0,
+                has_checksum,
             });
         }
     }
     snapshot_files_with_slots.sort_by_key(|file| file.start_slot);
+    let mut cumulative_size = 0;
+    for file in snapshot_files_with_slots.iter_mut() {
+        cumulative_size += file.size;
+        file.cumulative_size = cumulative_size;
+    }
     Ok(snapshot_files_with_slots)
 }
 
-fn create_temp_snapshot_file(dir: &str) -> (File, PathBuf) {
+/// Reads the hex encoded SHA-256 digest of a snapshot file as stored from its checksum file, if
+/// it has one.
+pub async fn read_snapshot_file_checksum(
+    directory_adapter: &DirectoryAdapter,
+    snapshot_file: &SnapshotFileWithSlots,
+) -> Result<Option<String>> {
+    if !snapshot_file.has_checksum {
+        return Ok(None);
+    }
+    let path = format!("{}{}", snapshot_file.file, CHECKSUM_EXTENSION);
+    read_checksum_file(directory_adapter, path).await.map(Some)
+}
+
+// Reads the digest from a checksum file in the format of `sha256sum`.
+async fn read_checksum_file(directory_adapter: &DirectoryAdapter, path: String) -> Result<String> {
+    let byte_stream = directory_adapter.read_raw_file(path.clone(), 0).await;
+    pin_mut!(byte_stream);
+    let mut bytes = Vec::new();
+    while let Some(chunk) = byte_stream.next().await {
+        bytes.extend_from_slice(&chunk?);
+    }
+    String::from_utf8(bytes)
+        .ok()
+        .and_then(|checksum| checksum.split_whitespace().next().map(str::to_string))
+        .ok_or_else(|| anyhow!("Invalid checksum file: {}", path))
+}
+
+/// Recomputes the SHA-256 digests of the snapshot files and returns the files whose digest does
+/// not match their checksum file. Files without a checksum file cannot be verified and are
+/// skipped.
+pub async fn verify_snapshot_directory(
+    directory_adapter: &DirectoryAdapter,
+) -> Result<Vec<String>> {
+    let mut corrupt_files = Vec::new();
+    for snapshot_file in get_snapshot_files_with_metadata(directory_adapter).await? {
+        let expected_sha256 =
+            match read_snapshot_file_checksum(directory_adapter, &snapshot_file).await? {
+                Some(sha256) => sha256,
+                None => {
+                    warn!("Snapshot file {} has no checksum file", snapshot_file.file);
+                    continue;
+                }
+            };
+        let sha256 = compute_snapshot_file_sha256(directory_adapter, &snapshot_file.file).await?;
+        if sha256 != expected_sha256 {
+            error!(
+                "Snapshot file {} is corrupt: expected SHA-256 {}, computed {}",
+                snapshot_file.file, expected_sha256, sha256
+            );
+            corrupt_files.push(snapshot_file.file);
+        }
+    }
+    Ok(corrupt_files)
+}
+
+/// Computes the hex encoded SHA-256 digest of a snapshot file as it is stored.
+pub async fn compute_snapshot_file_sha256(
+    directory_adapter: &DirectoryAdapter,
+    file: &str,
+) -> Result<String> {
+    let byte_stream = directory_adapter.read_raw_file(file.to_string(), 0).await;
+    pin_mut!(byte_stream);
+    let mut hasher = Sha256::new();
+    while let Some(bytes) = byte_stream.next().await {
+        hasher.update(bytes?);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn create_temp_snapshot_file(dir: &str) -> Result<(File, PathBuf)> {
     let temp_dir = temp_dir();
     // Create a subdirectory for the snapshot files
     let temp_dir = temp_dir.join(dir);
     if !temp_dir.exists() {
-        fs::create_dir(&temp_dir).unwrap();
+        fs::create_dir(&temp_dir)
+            .with_context(|| format!("Failed to create directory: {:?}", temp_dir))?;
     }
     let random_number = rand::random::<u64>();
     let temp_file_path = temp_dir.join(format!("temp-snapshot-{}", random_number));
     if temp_file_path.exists() {
-        fs::remove_file(&temp_file_path).unwrap();
+        fs::remove_file(&temp_file_path)
+            .with_context(|| format!("Failed to delete file: {:?}", temp_file_path))?;
     }
-    let temp_file = File::create(&temp_file_path).unwrap();
-    (temp_file, temp_file_path)
+    let temp_file = File::create(&temp_file_path)
+        .with_context(|| format!("Failed to create file: {:?}", temp_file_path))?;
+    Ok((temp_file, temp_file_path))
+}
+
+/// Reads the dead letters recorded in a dead-letter directory.
+pub fn read_dead_letters(dead_letter_dir: &str) -> Result<Vec<DeadLetter>> {
+    let record_path = PathBuf::from(dead_letter_dir).join(DEAD_LETTER_RECORD_FILE);
+    if !record_path.exists() {
+        return Ok(Vec::new());
+    }
+    let records = fs::read_to_string(&record_path)
+        .with_context(|| format!("Failed to read file: {:?}", record_path))?;
+    records
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(|e| anyhow!(e)))
+        .collect()
 }
 
 async fn merge_snapshots(directory_adapter: Arc<DirectoryAdapter>) {
@@ -444,11 +786,23 @@ async fn merge_snapshots(directory_adapter: Arc<DirectoryAdapter>) {
     }
 }
 
+/// Whether a snapshot ending at `snapshot_end_slot` should be loaded before indexing resumes after
+/// `last_indexed_slot`. Snapshots that are less than `snapshot_offset` slots newer than the last
+/// indexed slot are not worth loading.
+pub fn should_load_snapshot(
+    snapshot_end_slot: u64,
+    last_indexed_slot: u64,
+    snapshot_offset: u64,
+) -> bool {
+    snapshot_end_slot.saturating_sub(snapshot_offset) >= last_indexed_slot
+}
+
 pub async fn update_snapshot(
     directory_adapter: Arc<DirectoryAdapter>,
     block_stream_config: BlockStreamConfig,
     full_snapshot_interval_slots: u64,
     incremental_snapshot_interval_slots: u64,
+    max_pending_writes: usize,
 ) {
     // Convert stream to iterator
     let block_stream = block_stream_config.load_block_stream();
@@ -458,6 +812,7 @@ pub async fn update_snapshot(
         block_stream_config.last_indexed_slot,
         incremental_snapshot_interval_slots,
         full_snapshot_interval_slots,
+        max_pending_writes,
     )
     .await;
 }
@@ -468,6 +823,7 @@ pub async fn update_snapshot_helper(
     last_indexed_slot: u64,
     incremental_snapshot_interval_slots: u64,
     full_snapshot_interval_slots: u64,
+    max_pending_writes: usize,
 ) {
     let snapshot_files = get_snapshot_files_with_metadata(directory_adapter.as_ref())
         .await
@@ -483,6 +839,7 @@ pub async fn update_snapshot_helper(
         .unwrap_or(last_indexed_slot);
 
     let mut byte_buffer = Vec::new();
+    let (sender, writer) = spawn_snapshot_writer(directory_adapter.clone(), max_pending_writes);
 
     pin_mut!(blocks_stream);
     while let Some(blocks) = blocks_stream.next().await {
@@ -508,24 +865,118 @@ pub async fn update_snapshot_helper(
             byte_buffer.extend(block_bytes);
 
             if write_incremental_snapshot {
-                let snapshot_file_path = format!("snapshot-{}-{}", last_snapshot_slot + 1, slot);
-                info!("Writing snapshot file: {}", snapshot_file_path);
-                let byte_buffer_clone = byte_buffer.clone();
-                let byte_stream = stream! {
-                    yield Ok(Bytes::from(byte_buffer_clone));
-                };
-                directory_adapter
-                    .as_ref()
-                    .write_file(snapshot_file_path, byte_stream)
+                let file = format!("snapshot-{}-{}", last_snapshot_slot + 1, slot);
+                let bytes = Bytes::from(std::mem::take(&mut byte_buffer));
+                if sender
+                    .send(SnapshotWrite::Incremental { file, bytes })
                     .await
-                    .unwrap();
-                byte_buffer.clear();
+                    .is_err()
+                {
+                    break;
+                }
                 last_snapshot_slot = slot;
             }
             if write_full_snapshot {
-                merge_snapshots(directory_adapter.clone()).await;
+                if sender.send(SnapshotWrite::Full).await.is_err() {
+                    break;
+                }
                 last_full_snapshot_slot = slot;
             }
+            let pending_writes = (sender.max_capacity() - sender.capacity()) as u64;
+            metric! {
+                statsd_gauge!("snapshot_pending_writes", pending_writes);
+            }
+        }
+    }
+    // Waits for the queued writes, and resumes the panic of the writer if a write failed.
+    drop(sender);
+    if let Err(e) = writer.await {
+        std::panic::resume_unwind(e.into_panic());
+    }
+}
+
+enum SnapshotWrite {
+    Incremental {
+        file: String,
+        bytes: Bytes,
+    },
+    /// Merges the snapshot files written so far into a full snapshot.
+    Full,
+}
+
+// Writes snapshot files from a background task in the order in which they are queued, so that
+// merges only include the files queued before them.
+//
+// Once a file is dead-lettered, the snapshot directory would have a slot gap if later files were
+// still written to it, and merging would make the gap permanent. Later files are therefore
+// dead-lettered as well and merges are skipped, until all dead-lettered files have been restored
+// to the snapshot directory.
+fn spawn_snapshot_writer(
+    directory_adapter: Arc<DirectoryAdapter>,
+    max_pending_writes: usize,
+) -> (mpsc::Sender<SnapshotWrite>, tokio::task::JoinHandle<()>) {
+    let (sender, mut receiver) = mpsc::channel(max_pending_writes.max(1));
+    let writer = tokio::spawn(async move {
+        let mut dead_lettered_files = Vec::new();
+        while let Some(snapshot_write) = receiver.recv().await {
+            if !dead_lettered_files.is_empty()
+                && are_files_restored(directory_adapter.as_ref(), &dead_lettered_files).await
+            {
+                info!("Dead-lettered snapshot files were restored, resuming snapshot writes");
+                dead_lettered_files.clear();
+            }
+            match snapshot_write {
+                SnapshotWrite::Incremental { file, bytes } if dead_lettered_files.is_empty() => {
+                    info!("Writing snapshot file: {}", file);
+                    if let Some(dead_letter) = directory_adapter
+                        .write_file_with_retries(file, bytes)
+                        .await
+                        .unwrap()
+                    {
+                        dead_lettered_files.push(dead_letter.file);
+                    }
+                }
+                SnapshotWrite::Incremental { file, bytes } => {
+                    let dead_letter = directory_adapter
+                        .dead_letter_file(
+                            file,
+                            bytes,
+                            0,
+                            "Held back until earlier dead-lettered files are restored".to_string(),
+                        )
+                        .await
+                        .unwrap();
+                    dead_lettered_files.push(dead_letter.file);
+                }
+                SnapshotWrite::Full if dead_lettered_files.is_empty() => {
+                    merge_snapshots(directory_adapter.clone()).await
+                }
+                SnapshotWrite::Full => {
+                    warn!(
+                        "Skipping merge of snapshot files until {} dead-lettered files are restored",
+                        dead_lettered_files.len()
+                    );
+                }
+            }
+        }
+    });
+    (sender, writer)
+}
+
+// Whether all of `files` are present in the directory. Failing to list the directory counts as
+// the files not being restored yet.
+async fn are_files_restored(directory_adapter: &DirectoryAdapter, files: &[String]) -> bool {
+    match directory_adapter.list_files().await {
+        Ok(present_files) => {
+            let present_files = present_files
+                .into_iter()
+                .map(|(file, _)| file)
+                .collect::<HashSet<_>>();
+            files.iter().all(|file| present_files.contains(file))
+        }
+        Err(e) => {
+            warn!("Failed to list snapshot files: {:?}", e);
+            false
         }
     }
 }
@@ -564,6 +1015,202 @@ pub async fn load_byte_stream_from_directory_adapter(
     }
 }
 
+/// Loads the snapshot from `directory_adapter` as a stream of chunks of `chunk_size` bytes, except
+/// for the last chunk, which can be smaller.
+pub async fn load_chunked_byte_stream_from_directory_adapter(
+    directory_adapter: Arc<DirectoryAdapter>,
+    chunk_size: usize,
+) -> impl Stream<Item = Result<Bytes>> + 'static {
+    let byte_stream = load_byte_stream_from_directory_adapter(directory_adapter).await;
+    chunk_byte_stream(byte_stream, chunk_size)
+}
+
+/// Returns the compression of the snapshot files if all of them are compressed the same way.
+pub async fn get_snapshot_compression(
+    directory_adapter: &DirectoryAdapter,
+) -> Result<Option<SnapshotCompression>> {
+    let snapshot_files = get_snapshot_files_with_metadata(directory_adapter).await?;
+    let compression = snapshot_files.first().map(|file| file.compression);
+    Ok(compression.filter(|compression| {
+        snapshot_files
+            .iter()
+            .all(|file| file.compression == *compression)
+    }))
+}
+
+/// Loads the snapshot from `directory_adapter` without decompressing its files, which must all be
+/// compressed with `compression`. The snapshot header is compressed the same way, so that the
+/// stream decompresses to the same bytes as [`load_byte_stream_from_directory_adapter`].
+pub async fn load_compressed_byte_stream_from_directory_adapter(
+    directory_adapter: Arc<DirectoryAdapter>,
+    compression: SnapshotCompression,
+) -> impl Stream<Item = Result<Bytes>> + 'static {
+    stream! {
+        let snapshot_files =
+            get_snapshot_files_with_metadata(directory_adapter.as_ref()).await.context("Failed to retrieve snapshot files")?;
+        if snapshot_files.is_empty() {
+            yield Err(anyhow!("No snapshot files found"));
+            return;
+        }
+        if let Some(file) = snapshot_files.iter().find(|file| file.compression != compression) {
+            yield Err(anyhow!("Snapshot file {} is not compressed with {:?}", file.file, compression));
+            return;
+        }
+
+        yield compressed_snapshot_header(&snapshot_files, compression).await;
+
+        for snapshot_file in snapshot_files {
+            let byte_stream = directory_adapter.read_raw_file(snapshot_file.file.clone(), 0).await;
+            pin_mut!(byte_stream);
+            while let Some(bytes) = byte_stream.next().await {
+                yield bytes;
+            }
+        }
+    }
+}
+
+// Returns the header of a snapshot of `snapshot_files`, compressed with `compression`.
+async fn compressed_snapshot_header(
+    snapshot_files: &[SnapshotFileWithSlots],
+    compression: SnapshotCompression,
+) -> Result<Bytes> {
+    let mut header = vec![SNAPSHOT_VERSION];
+    header.extend(snapshot_files.first().unwrap().start_slot.to_le_bytes());
+    header.extend(snapshot_files.last().unwrap().end_slot.to_le_bytes());
+    let header = compression.compress(stream::iter(vec![Ok(Bytes::from(header))]));
+    pin_mut!(header);
+    let mut compressed_header = BytesMut::new();
+    while let Some(bytes) = header.next().await {
+        compressed_header.extend_from_slice(&bytes?);
+    }
+    Ok(compressed_header.freeze())
+}
+
+/// Returns the size in bytes of the stream returned by
+/// [`load_compressed_byte_stream_from_directory_adapter`] for `compression`. With
+/// [`SnapshotCompression::None`], this is also the size of the uncompressed snapshot.
+pub async fn get_compressed_snapshot_size(
+    directory_adapter: &DirectoryAdapter,
+    compression: SnapshotCompression,
+) -> Result<u64> {
+    let snapshot_files = get_snapshot_files_with_metadata(directory_adapter).await?;
+    if snapshot_files.is_empty() {
+        return Err(anyhow!("No snapshot files found"));
+    }
+    let header = compressed_snapshot_header(&snapshot_files, compression).await?;
+    Ok(header.len() as u64 + snapshot_files.last().unwrap().cumulative_size)
+}
+
+/// Returns a strong `ETag` of the snapshot served with `content_encoding`, derived from the names
+/// and sizes of the snapshot files. Snapshot files are never modified once they are written, so
+/// the tag changes whenever the served bytes do, e.g. when snapshots are merged.
+pub async fn get_snapshot_etag(
+    directory_adapter: &DirectoryAdapter,
+    content_encoding: Option<&str>,
+) -> Result<String> {
+    let snapshot_files = get_snapshot_files_with_metadata(directory_adapter).await?;
+    let mut hasher = Sha256::new();
+    hasher.update(content_encoding.unwrap_or("identity"));
+    for snapshot_file in snapshot_files {
+        hasher.update(format!("\n{} {}", snapshot_file.file, snapshot_file.size));
+    }
+    Ok(format!("\"{}\"", hex::encode(&hasher.finalize()[..16])))
+}
+
+/// Loads the bytes from `start` up to, but excluding, `end` of the stream returned by
+/// [`load_compressed_byte_stream_from_directory_adapter`] for `compression`. Only the files that
+/// overlap the range are read.
+pub async fn load_compressed_byte_range_from_directory_adapter(
+    directory_adapter: Arc<DirectoryAdapter>,
+    compression: SnapshotCompression,
+    start: u64,
+    end: u64,
+) -> impl Stream<Item = Result<Bytes>> + 'static {
+    stream! {
+        let snapshot_files =
+            get_snapshot_files_with_metadata(directory_adapter.as_ref()).await.context("Failed to retrieve snapshot files")?;
+        if snapshot_files.is_empty() {
+            yield Err(anyhow!("No snapshot files found"));
+            return;
+        }
+        if let Some(file) = snapshot_files.iter().find(|file| file.compression != compression) {
+            yield Err(anyhow!("Snapshot file {} is not compressed with {:?}", file.file, compression));
+            return;
+        }
+
+        let header = compressed_snapshot_header(&snapshot_files, compression).await?;
+        let header_len = header.len() as u64;
+        if start < header_len {
+            yield Ok(header.slice(start as usize..std::cmp::min(end, header_len) as usize));
+        }
+
+        for snapshot_file in snapshot_files {
+            let file_start = header_len + snapshot_file.cumulative_size - snapshot_file.size;
+            let file_end = header_len + snapshot_file.cumulative_size;
+            if file_end <= start {
+                continue;
+            }
+            if file_start >= end {
+                break;
+            }
+            let offset = start.saturating_sub(file_start);
+            let mut remaining = std::cmp::min(end, file_end) - file_start - offset;
+            let byte_stream = directory_adapter.read_raw_file(snapshot_file.file.clone(), offset).await;
+            pin_mut!(byte_stream);
+            while let Some(bytes) = byte_stream.next().await {
+                let mut bytes = bytes?;
+                if bytes.len() as u64 >= remaining {
+                    yield Ok(bytes.split_to(remaining as usize));
+                    break;
+                }
+                remaining -= bytes.len() as u64;
+                yield Ok(bytes);
+            }
+        }
+    }
+}
+
+/// Regroups the bytes of `byte_stream` into chunks of `chunk_size` bytes, except for the last
+/// chunk, which can be smaller. On error, the bytes read so far are yielded before the error and
+/// the stream ends.
+pub fn chunk_byte_stream(
+    byte_stream: impl Stream<Item = Result<Bytes>> + 'static,
+    chunk_size: usize,
+) -> impl Stream<Item = Result<Bytes>> + 'static {
+    let chunk_size = chunk_size.max(1);
+    stream! {
+        pin_mut!(byte_stream);
+        let mut chunk = BytesMut::with_capacity(chunk_size);
+        while let Some(bytes) = byte_stream.next().await {
+            let mut bytes = match bytes {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    if !chunk.is_empty() {
+                        yield Ok(chunk.split().freeze());
+                    }
+                    yield Err(e);
+                    return;
+                }
+            };
+            while !bytes.is_empty() {
+                if chunk.is_empty() && bytes.len() >= chunk_size {
+                    // Whole chunks are yielded without copying them.
+                    yield Ok(bytes.split_to(chunk_size));
+                    continue;
+                }
+                let len = std::cmp::min(chunk_size - chunk.len(), bytes.len());
+                chunk.extend_from_slice(&bytes.split_to(len));
+                if chunk.len() == chunk_size {
+                    yield Ok(chunk.split().freeze());
+                }
+            }
+        }
+        if !chunk.is_empty() {
+            yield Ok(chunk.freeze());
+        }
+    }
+}
+
 pub async fn load_block_stream_from_directory_adapter(
     directory_adapter: Arc<DirectoryAdapter>,
 ) -> impl Stream<Item = Vec<BlockInfo>> {
@@ -681,3 +1328,279 @@ pub async fn create_snapshot_from_byte_stream(
     info!("Snapshot downloaded successfully to {:?}", snapshot_name);
     Ok(())
 }
+
+/// Number of attempts at downloading a snapshot from a snapshotter before giving up.
+pub const SNAPSHOT_DOWNLOAD_ATTEMPTS: u32 = 5;
+
+// Name of the file a snapshot is downloaded to, followed by the extension of the compression it
+// is served with.
+const SNAPSHOT_DOWNLOAD_FILE: &str = "snapshot.download";
+
+/// Downloads the snapshot served by the snapshotter at `snapshot_server_url` and creates it in
+/// `directory_adapter`. See [`download_snapshot_file`] for how interrupted downloads are resumed.
+pub async fn download_snapshot(
+    snapshot_server_url: &str,
+    download_dir: &str,
+    max_attempts: u32,
+    directory_adapter: &DirectoryAdapter,
+) -> Result<()> {
+    let (path, compression) =
+        download_snapshot_file(snapshot_server_url, download_dir, max_attempts).await?;
+    let mut file = File::open(&path).with_context(|| format!("Failed to open file: {:?}", path))?;
+    let byte_stream = stream! {
+        loop {
+            let mut byte_chunk = Vec::new();
+            let read = (&mut file)
+                .take(CHUNK_SIZE as u64)
+                .read_to_end(&mut byte_chunk)
+                .with_context(|| "Failed to read chunk from file")?;
+            if read == 0 {
+                break;
+            }
+            yield Ok(Bytes::from(byte_chunk));
+        }
+    };
+    create_snapshot_from_byte_stream(compression.decompress(byte_stream), directory_adapter)
+        .await?;
+    remove_snapshot_download(&path)
+}
+
+/// Downloads the snapshot served by the snapshotter at `snapshot_server_url` into `download_dir`,
+/// returning the downloaded file along with its compression. The bytes received are kept in the
+/// file, so that a download interrupted during this or a previous run is resumed from where it
+/// stopped with a range request instead of being restarted. The `ETag` and size of the snapshot
+/// are recorded next to the file, and the download is restarted if the snapshotter serves a
+/// different snapshot by the time it is resumed. Snapshots are requested as stored,
+/// since the snapshotter only serves ranges of compressed snapshots without decompressing them.
+pub async fn download_snapshot_file(
+    snapshot_server_url: &str,
+    download_dir: &str,
+    max_attempts: u32,
+) -> Result<(PathBuf, SnapshotCompression)> {
+    let download_dir = PathBuf::from(download_dir);
+    fs::create_dir_all(&download_dir)
+        .with_context(|| format!("Failed to create directory: {:?}", download_dir))?;
+    let url = format!("{}/download", snapshot_server_url.trim_end_matches('/'));
+    let client = reqwest::Client::new();
+    let mut delay = Duration::from_secs(1);
+    let mut attempt = 1;
+    loop {
+        match download_snapshot_file_attempt(&client, &url, &download_dir).await {
+            Ok(download) => return Ok(download),
+            Err(e) if attempt < max_attempts => {
+                warn!(
+                    "Failed to download snapshot (attempt {}). Resuming in {:?}: {:?}",
+                    attempt, delay, e
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => {
+                return Err(e.context(format!(
+                    "Failed to download snapshot after {} attempts",
+                    attempt
+                )))
+            }
+        }
+    }
+}
+
+async fn download_snapshot_file_attempt(
+    client: &reqwest::Client,
+    url: &str,
+    download_dir: &Path,
+) -> Result<(PathBuf, SnapshotCompression)> {
+    // Partial downloads are only resumed if it can be checked that the snapshotter still serves
+    // the same snapshot.
+    let partial_download = match find_snapshot_download(download_dir)? {
+        Some((path, compression)) => match read_download_validator(&path)? {
+            Some(validator) => Some((path, compression, validator)),
+            None => {
+                remove_snapshot_download(&path)?;
+                None
+            }
+        },
+        None => None,
+    };
+    let received = match &partial_download {
+        Some((path, _, _)) => fs::metadata(path)?.len(),
+        None => 0,
+    };
+    let accept_encoding = [SnapshotCompression::Gzip, SnapshotCompression::Zstd]
+        .iter()
+        .filter_map(|compression| compression.content_encoding())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut request = client
+        .get(url)
+        .header(reqwest::header::ACCEPT_ENCODING, accept_encoding);
+    if let Some((_, _, validator)) = partial_download.as_ref().filter(|_| received > 0) {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", received));
+        // The snapshotter serves the whole snapshot instead of the range if it has changed.
+        if let Some(etag) = &validator.etag {
+            request = request.header(reqwest::header::IF_RANGE, etag);
+        }
+    }
+    let response = request.send().await?;
+    let content_range = response
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)
+        .and_then(|content_range| content_range.to_str().ok())
+        .and_then(parse_content_range);
+    let etag = response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(str::to_string);
+    // Whether the response is for the snapshot that the partial download was started for.
+    let matches_validator = |validator: &DownloadValidator| {
+        let total_size = content_range.map(|(_, size)| size);
+        (validator.etag.is_none() || validator.etag == etag)
+            && (validator.size.is_none() || validator.size == total_size)
+    };
+
+    if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        if let Some((path, compression, validator)) = partial_download {
+            // The previous attempt was only interrupted after receiving the whole snapshot.
+            if content_range == Some((None, received)) && matches_validator(&validator) {
+                return Ok((path, compression));
+            }
+            remove_snapshot_download(&path)?;
+        }
+        return Err(anyhow!(
+            "The partially downloaded snapshot does not match the served snapshot"
+        ));
+    }
+    let response = response.error_for_status()?;
+    let compression = match response.headers().get(reqwest::header::CONTENT_ENCODING) {
+        Some(encoding) => [SnapshotCompression::Gzip, SnapshotCompression::Zstd]
+            .into_iter()
+            .find(|compression| compression.content_encoding() == encoding.to_str().ok())
+            .ok_or_else(|| anyhow!("Unsupported content encoding: {:?}", encoding))?,
+        None => SnapshotCompression::None,
+    };
+    let path = download_dir.join(format!(
+        "{}{}",
+        SNAPSHOT_DOWNLOAD_FILE,
+        compression.extension()
+    ));
+    let resumes_partial_download = response.status() == reqwest::StatusCode::PARTIAL_CONTENT
+        && matches!(&partial_download, Some((partial_path, partial_compression, validator))
+            if *partial_path == path
+                && *partial_compression == compression
+                && matches_validator(validator))
+        && matches!(content_range, Some((Some(first), _)) if first == received);
+    let mut file = match resumes_partial_download {
+        true => {
+            info!("Resuming snapshot download after {} bytes...", received);
+            OpenOptions::new().append(true).open(&path)?
+        }
+        false => {
+            if let Some((partial_path, _, _)) = partial_download {
+                remove_snapshot_download(&partial_path)?;
+            }
+            if response.status() == reqwest::StatusCode::PARTIAL_CONTENT {
+                return Err(anyhow!("Received an unexpected range of the snapshot"));
+            }
+            let file = File::create(&path)
+                .with_context(|| format!("Failed to create file: {:?}", path))?;
+            let validator = DownloadValidator {
+                etag,
+                size: response.content_length(),
+            };
+            write_download_validator(&path, &validator)?;
+            file
+        }
+    };
+
+    let content_length = response.content_length();
+    let mut written = 0;
+    let mut byte_stream = response.bytes_stream();
+    while let Some(bytes) = byte_stream.next().await {
+        let bytes = bytes?;
+        file.write_all(&bytes)?;
+        written += bytes.len() as u64;
+    }
+    file.flush()?;
+    if content_length.is_some_and(|content_length| content_length != written) {
+        return Err(anyhow!("The snapshot download ended early"));
+    }
+    Ok((path, compression))
+}
+
+// Extension of the file next to a partially downloaded snapshot that records which snapshot was
+// being downloaded, so that the download is only resumed while the same snapshot is served.
+const DOWNLOAD_VALIDATOR_EXTENSION: &str = ".validator";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct DownloadValidator {
+    /// `ETag` of the snapshot, if the snapshotter sent one.
+    etag: Option<String>,
+    /// Size of the whole snapshot in bytes, if the snapshotter sent it.
+    size: Option<u64>,
+}
+
+fn download_validator_path(path: &Path) -> PathBuf {
+    let mut validator_path = path.as_os_str().to_owned();
+    validator_path.push(DOWNLOAD_VALIDATOR_EXTENSION);
+    PathBuf::from(validator_path)
+}
+
+fn read_download_validator(path: &Path) -> Result<Option<DownloadValidator>> {
+    let validator_path = download_validator_path(path);
+    if !validator_path.exists() {
+        return Ok(None);
+    }
+    let validator = fs::read_to_string(&validator_path)
+        .with_context(|| format!("Failed to read file: {:?}", validator_path))?;
+    // A validator that can't be parsed, e.g. because a write of it was interrupted, is ignored.
+    Ok(serde_json::from_str(&validator).ok())
+}
+
+fn write_download_validator(path: &Path, validator: &DownloadValidator) -> Result<()> {
+    let validator_path = download_validator_path(path);
+    fs::write(&validator_path, serde_json::to_vec(validator)?)
+        .with_context(|| format!("Failed to write file: {:?}", validator_path))
+}
+
+// Deletes a partially downloaded snapshot along with its validator.
+fn remove_snapshot_download(path: &Path) -> Result<()> {
+    fs::remove_file(path).with_context(|| format!("Failed to delete file: {:?}", path))?;
+    let validator_path = download_validator_path(path);
+    if validator_path.exists() {
+        fs::remove_file(&validator_path)
+            .with_context(|| format!("Failed to delete file: {:?}", validator_path))?;
+    }
+    Ok(())
+}
+
+// Returns the partially downloaded snapshot in `download_dir`, if any, along with its compression.
+fn find_snapshot_download(download_dir: &Path) -> Result<Option<(PathBuf, SnapshotCompression)>> {
+    for entry in fs::read_dir(download_dir)? {
+        let path = entry?.path();
+        let file_name = match path.file_name().and_then(|file_name| file_name.to_str()) {
+            Some(file_name) => file_name.to_string(),
+            None => continue,
+        };
+        let (name, compression) = SnapshotCompression::from_file_name(&file_name);
+        if name == SNAPSHOT_DOWNLOAD_FILE {
+            return Ok(Some((path, compression)));
+        }
+    }
+    Ok(None)
+}
+
+// Parses a `Content-Range` header into the first offset of the range, which is missing for
+// unsatisfiable ranges, and the size of the whole snapshot.
+fn parse_content_range(content_range: &str) -> Option<(Option<u64>, u64)> {
+    let (range, size) = content_range
+        .trim()
+        .strip_prefix("bytes ")?
+        .split_once('/')?;
+    let first = match range {
+        "*" => None,
+        range => Some(range.split_once('-')?.0.parse().ok()?),
+    };
+    Some((first, size.parse().ok()?))
+}
@@ -1,40 +1,87 @@
 use clap::Parser;
 use futures::StreamExt;
 use log::{error, info};
+use once_cell::sync::Lazy;
+use photon_indexer::common::config::parse_args_with_config_file;
+use photon_indexer::common::tls::{load_tls_config, serve_tls};
 use photon_indexer::common::{
     fetch_block_parent_slot, fetch_current_slot_with_infinite_retry, get_network_start_slot,
-    get_rpc_client, setup_logging, setup_metrics, LoggingFormat,
+    get_rpc_client_with_commitment, setup_logging, setup_metrics, Commitment, LoggingFormat,
+    RetryBackoffConfig, UnknownNetworkStartSlot, DEFAULT_RETRY_BASE_DELAY_MS,
+    DEFAULT_RETRY_MAX_DELAY_MS,
 };
+use photon_indexer::ingester::fetchers::grpc::{GrpcUnreachablePolicy, GrpcXToken};
 use photon_indexer::ingester::fetchers::BlockStreamConfig;
+use photon_indexer::snapshot::compression::SnapshotCompression;
 use photon_indexer::snapshot::{
-    get_snapshot_files_with_metadata, load_byte_stream_from_directory_adapter, DirectoryAdapter,
+    chunk_byte_stream, compute_snapshot_file_sha256, get_compressed_snapshot_size,
+    get_snapshot_compression, get_snapshot_etag, get_snapshot_files_with_metadata,
+    load_byte_stream_from_directory_adapter, load_compressed_byte_range_from_directory_adapter,
+    load_compressed_byte_stream_from_directory_adapter, read_snapshot_file_checksum,
+    verify_snapshot_directory, DirectoryAdapter, UploadRetryPolicy, DEFAULT_DOWNLOAD_CHUNK_SIZE,
+    DEFAULT_MAX_PENDING_SNAPSHOT_WRITES,
 };
+use serde::Serialize;
+use std::collections::HashMap;
 use std::future::pending;
 use std::io;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 
+use futures::Stream;
+use hyper::header::{
+    ACCEPT_ENCODING, ACCEPT_RANGES, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_RANGE, ETAG,
+    IF_RANGE, RANGE,
+};
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Request, Response, Server, StatusCode};
 use std::convert::Infallible;
+use tokio_rustls::rustls::ServerConfig;
 use tower::ServiceBuilder;
 
 /// Photon Snapshotter: a utility to create snapshots of Photon's state at regular intervals.
 #[derive(Parser, Debug)]
 #[command(version, about)]
 struct Args {
+    /// TOML file with default values for the arguments. Its keys are the names of the arguments
+    /// with underscores, e.g. `rpc_url`. Arguments passed on the command line take precedence
+    #[arg(long)]
+    config: Option<PathBuf>,
+
     /// Port to expose the local snapshotter API
     #[arg(short, long, default_value_t = 8825)]
     port: u16,
 
-    /// URL of the RPC server
+    /// URL of the RPC server. With a comma separated list of URLs, requests fail over to the next
+    /// URL on connection errors and rate limits
     #[arg(short, long, default_value = "http://127.0.0.1:8899")]
     rpc_url: String,
 
+    /// Commitment level of the slots and blocks fetched from the RPC server and the gRPC stream.
+    /// With `finalized`, blocks are never orphaned by a reorg
+    #[arg(long, value_enum, default_value_t = Commitment::Confirmed)]
+    commitment: Commitment,
+
+    /// Delay in milliseconds before retrying a failed RPC request. The delay doubles after each
+    /// consecutive failure of the request, with random jitter
+    #[arg(long, default_value_t = DEFAULT_RETRY_BASE_DELAY_MS)]
+    retry_base_delay_ms: u64,
+
+    /// Maximum delay in milliseconds between the retries of a failed RPC request
+    #[arg(long, default_value_t = DEFAULT_RETRY_MAX_DELAY_MS)]
+    retry_max_delay_ms: u64,
+
     /// The start slot to begin indexing from. If "latest", the latest slot is used.
     #[arg(short, long)]
     start_slot: Option<String>,
 
+    /// Where to start on networks other than devnet and mainnet, whose start slot is not known to
+    /// Photon
+    #[arg(long, value_enum, default_value_t = UnknownNetworkStartSlot::FirstAvailableBlock)]
+    unknown_network_start_slot: UnknownNetworkStartSlot,
+
     /// Logging format
     #[arg(short, long, default_value_t = LoggingFormat::Standard)]
     logging_format: LoggingFormat,
@@ -43,6 +90,15 @@ struct Args {
     #[arg(short, long)]
     max_concurrent_block_fetches: Option<usize>,
 
+    /// Max number of fetched block batches to buffer ahead of the snapshotter
+    #[arg(long, default_value_t = 10)]
+    fetch_ahead_window: usize,
+
+    /// Max number of snapshot files queued to be written while another file is being written.
+    /// Blocks keep being ingested until the queue is full
+    #[arg(long, default_value_t = DEFAULT_MAX_PENDING_SNAPSHOT_WRITES)]
+    max_pending_snapshot_writes: usize,
+
     /// Snapshot directory
     #[arg(long)]
     snapshot_dir: Option<String>,
@@ -68,6 +124,15 @@ struct Args {
     #[arg(short, long, default_value = None)]
     grpc_url: Option<String>,
 
+    /// Token sent in the `x-token` header to authenticate with the Yellowstone gRPC endpoint
+    #[arg(long, env = "GRPC_X_TOKEN", hide_env_values = true)]
+    grpc_x_token: Option<GrpcXToken>,
+
+    /// What to do when the gRPC endpoint cannot be reached on start: keep retrying to connect
+    /// while fetching blocks over RPC, or only fetch blocks over RPC from then on
+    #[arg(long, value_enum, default_value_t = GrpcUnreachablePolicy::Retry)]
+    grpc_unreachable_policy: GrpcUnreachablePolicy,
+
     /// Metrics endpoint in the format `host:port`
     #[arg(long, default_value = None)]
     metrics_endpoint: Option<String>,
@@ -79,6 +144,39 @@ struct Args {
     /// Disable api server
     #[arg(long, default_value_t = false)]
     disable_api: bool,
+
+    /// Size in bytes of the chunks in which snapshots are streamed to clients
+    #[arg(long, default_value_t = DEFAULT_DOWNLOAD_CHUNK_SIZE)]
+    download_chunk_size: usize,
+
+    /// PEM certificate chain with which the API server is served over HTTPS. Requires --tls-key
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key of the certificate passed with --tls-cert
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Compression of the snapshot files written from now on. Existing files are read regardless
+    /// of their compression
+    #[arg(long, value_enum, default_value_t = SnapshotCompression::None)]
+    compression: SnapshotCompression,
+
+    /// Verify the SHA-256 checksums of the snapshot files on start and refuse to start if any file
+    /// is corrupt
+    #[arg(long, default_value_t = false)]
+    verify_on_start: bool,
+
+    /// Number of attempts made to write a snapshot file before giving up on it
+    #[arg(long, default_value_t = 5)]
+    upload_attempts: u32,
+
+    /// Local directory that snapshot files are written to, for manual upload, once all attempts
+    /// to write them have failed. Later snapshot files are written there as well, and merges are
+    /// skipped, until the dead-lettered files are uploaded. Without one, the snapshotter stops on
+    /// such failures
+    #[arg(long)]
+    dead_letter_dir: Option<String>,
 }
 
 async fn continously_run_snapshotter(
@@ -86,6 +184,7 @@ async fn continously_run_snapshotter(
     block_stream_config: BlockStreamConfig,
     full_snapshot_interval_slots: u64,
     incremental_snapshot_interval_slots: u64,
+    max_pending_snapshot_writes: usize,
 ) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
         photon_indexer::snapshot::update_snapshot(
@@ -93,27 +192,168 @@ async fn continously_run_snapshotter(
             block_stream_config,
             incremental_snapshot_interval_slots,
             full_snapshot_interval_slots,
+            max_pending_snapshot_writes,
         )
         .await;
     })
 }
 
+fn accepts_encoding(accept_encoding: &str, encoding: &str) -> bool {
+    accept_encoding.split(',').any(|accepted| {
+        let mut parts = accepted.split(';').map(str::trim);
+        parts.next() == Some(encoding) && !parts.any(|parameter| parameter == "q=0")
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ByteRange {
+    /// The whole snapshot is served, since there is no range or it is not supported.
+    Full,
+    /// The bytes from the first up to and including the last offset are served.
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+// Parses a `Range` header for a snapshot of `size` bytes. Only single byte ranges are supported;
+// other ranges are ignored, as allowed by RFC 9110.
+fn parse_byte_range(range: &str, size: u64) -> ByteRange {
+    let (first, last) = match range
+        .trim()
+        .strip_prefix("bytes=")
+        .filter(|range| !range.contains(','))
+        .and_then(|range| range.split_once('-'))
+    {
+        Some((first, last)) => (first.trim(), last.trim()),
+        None => return ByteRange::Full,
+    };
+    match (first.parse::<u64>(), last.parse::<u64>()) {
+        // A suffix range of the last bytes of the snapshot.
+        (Err(_), Ok(suffix)) if first.is_empty() => match suffix {
+            0 => ByteRange::Unsatisfiable,
+            suffix => ByteRange::Partial(size.saturating_sub(suffix), size - 1),
+        },
+        (Ok(first), Err(_)) if last.is_empty() => match first < size {
+            true => ByteRange::Partial(first, size - 1),
+            false => ByteRange::Unsatisfiable,
+        },
+        (Ok(first), Ok(last)) if first <= last => match first < size {
+            true => ByteRange::Partial(first, std::cmp::min(last, size - 1)),
+            false => ByteRange::Unsatisfiable,
+        },
+        _ => ByteRange::Full,
+    }
+}
+
 async fn stream_bytes(
     directory_adapter: Arc<DirectoryAdapter>,
+    chunk_size: usize,
+    accept_encoding: Option<String>,
+    range: Option<String>,
+    if_range: Option<String>,
 ) -> Result<Response<Body>, hyper::http::Error> {
-    let byte_stream = load_byte_stream_from_directory_adapter(directory_adapter).await;
+    let compression = get_snapshot_compression(directory_adapter.as_ref())
+        .await
+        .ok()
+        .flatten();
+    // Clients that accept the compression of the snapshot files get them as they are stored,
+    // instead of having the snapshotter decompress them.
+    let content_encoding = compression.and_then(|compression| {
+        compression
+            .content_encoding()
+            .filter(|encoding| {
+                accept_encoding
+                    .as_deref()
+                    .is_some_and(|accept_encoding| accepts_encoding(accept_encoding, encoding))
+            })
+            .map(|encoding| (compression, encoding))
+    });
+    // Offsets can only be mapped to the snapshot files when they are served as stored.
+    let stored_compression = match content_encoding {
+        Some((compression, _)) => Some(compression),
+        None => compression.filter(|compression| *compression == SnapshotCompression::None),
+    };
+    let size = match stored_compression {
+        Some(compression) => get_compressed_snapshot_size(directory_adapter.as_ref(), compression)
+            .await
+            .ok(),
+        None => None,
+    };
+    let etag = get_snapshot_etag(
+        directory_adapter.as_ref(),
+        content_encoding.map(|(_, encoding)| encoding),
+    )
+    .await
+    .ok();
+    // A range is only served if the snapshot is still the one the client started downloading, as
+    // identified by the `If-Range` header. Otherwise the whole snapshot is served.
+    let range = range.filter(|_| {
+        if_range
+            .as_ref()
+            .map_or(true, |if_range| Some(if_range) == etag.as_ref())
+    });
+    let byte_range = match (size, range) {
+        (Some(size), Some(range)) => parse_byte_range(&range, size),
+        _ => ByteRange::Full,
+    };
+
+    let byte_stream: Pin<Box<dyn Stream<Item = anyhow::Result<bytes::Bytes>> + Send>> =
+        match (byte_range, stored_compression) {
+            (ByteRange::Unsatisfiable, _) => {
+                return Response::builder()
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(
+                        CONTENT_RANGE,
+                        format!("bytes */{}", size.unwrap_or_default()),
+                    )
+                    .body(Body::empty());
+            }
+            (ByteRange::Partial(first, last), Some(compression)) => Box::pin(
+                load_compressed_byte_range_from_directory_adapter(
+                    directory_adapter,
+                    compression,
+                    first,
+                    last + 1,
+                )
+                .await,
+            ),
+            _ => match content_encoding {
+                Some((compression, _)) => Box::pin(
+                    load_compressed_byte_stream_from_directory_adapter(
+                        directory_adapter,
+                        compression,
+                    )
+                    .await,
+                ),
+                None => Box::pin(load_byte_stream_from_directory_adapter(directory_adapter).await),
+            },
+        };
     info!("Finished loading byte stream");
-    let byte_stream = byte_stream.map(|bytes| {
+    let byte_stream = chunk_byte_stream(byte_stream, chunk_size).map(|bytes| {
         bytes.map_err(|e| {
             error!("Error reading byte: {:?}", e);
             io::Error::new(io::ErrorKind::Other, "Stream Error")
         })
     });
 
-    Response::builder()
-        .status(StatusCode::OK)
-        .header("Content-Type", "application/octet-stream")
-        .body(Body::wrap_stream(byte_stream))
+    let mut response = Response::builder().header("Content-Type", "application/octet-stream");
+    response = match (byte_range, size) {
+        (ByteRange::Partial(first, last), Some(size)) => response
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(CONTENT_RANGE, format!("bytes {}-{}/{}", first, last, size))
+            .header(CONTENT_LENGTH, last - first + 1),
+        (_, Some(size)) => response.status(StatusCode::OK).header(CONTENT_LENGTH, size),
+        _ => response.status(StatusCode::OK),
+    };
+    if size.is_some() {
+        response = response.header(ACCEPT_RANGES, "bytes");
+    }
+    if let Some(etag) = etag {
+        response = response.header(ETAG, etag);
+    }
+    if let Some((_, encoding)) = content_encoding {
+        response = response.header(CONTENT_ENCODING, encoding);
+    }
+    response.body(Body::wrap_stream(byte_stream))
 }
 
 async fn fetch_slot(
@@ -142,12 +382,119 @@ async fn fetch_slot(
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum SnapshotKind {
+    Full,
+    Incremental,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct ManifestEntry {
+    file: String,
+    start_slot: u64,
+    end_slot: u64,
+    kind: SnapshotKind,
+    /// Size of the file in bytes, as stored.
+    size: u64,
+    /// Hex encoded SHA-256 digest of the file, as stored.
+    sha256: String,
+}
+
+// Digests of the snapshot files keyed by file name and size. Files are never modified once they
+// are written, so the digest of each file only has to be read or computed once.
+static SNAPSHOT_FILE_DIGESTS: Lazy<Mutex<HashMap<(String, u64), String>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+async fn fetch_manifest(
+    directory_adapter: Arc<DirectoryAdapter>,
+) -> Result<Response<hyper::Body>, hyper::http::Error> {
+    let manifest = async {
+        let snapshot_files = get_snapshot_files_with_metadata(directory_adapter.as_ref()).await?;
+        let mut manifest = Vec::with_capacity(snapshot_files.len());
+        for (index, snapshot_file) in snapshot_files.into_iter().enumerate() {
+            let key = (snapshot_file.file.clone(), snapshot_file.size);
+            let cached_digest = SNAPSHOT_FILE_DIGESTS.lock().unwrap().get(&key).cloned();
+            let sha256 = match cached_digest {
+                Some(sha256) => sha256,
+                None => {
+                    // The digest recorded when the file was written is preferred, so that clients
+                    // can verify the file against it.
+                    let recorded_digest =
+                        read_snapshot_file_checksum(directory_adapter.as_ref(), &snapshot_file)
+                            .await?;
+                    let sha256 = match recorded_digest {
+                        Some(sha256) => sha256,
+                        None => {
+                            compute_snapshot_file_sha256(
+                                directory_adapter.as_ref(),
+                                &snapshot_file.file,
+                            )
+                            .await?
+                        }
+                    };
+                    SNAPSHOT_FILE_DIGESTS
+                        .lock()
+                        .unwrap()
+                        .insert(key, sha256.clone());
+                    sha256
+                }
+            };
+            // Incremental snapshots are merged into the first file, which holds the full snapshot.
+            let kind = match index {
+                0 => SnapshotKind::Full,
+                _ => SnapshotKind::Incremental,
+            };
+            manifest.push(ManifestEntry {
+                file: snapshot_file.file,
+                start_slot: snapshot_file.start_slot,
+                end_slot: snapshot_file.end_slot,
+                kind,
+                size: snapshot_file.size,
+                sha256,
+            });
+        }
+        anyhow::Ok(manifest)
+    };
+
+    match manifest.await {
+        Ok(manifest) => Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/json")
+            .body(Body::from(serde_json::to_vec(&manifest).unwrap())),
+        Err(e) => {
+            error!("Error building snapshot manifest: {:?}", e);
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from("Internal Server Error"))
+        }
+    }
+}
+
 async fn handle_request(
     req: Request<Body>,
     directory_adapter: Arc<DirectoryAdapter>,
+    download_chunk_size: usize,
 ) -> Result<Response<Body>, hyper::http::Error> {
     match req.uri().path() {
-        "/download" => match stream_bytes(directory_adapter).await {
+        "/download" => match stream_bytes(
+            directory_adapter,
+            download_chunk_size,
+            req.headers()
+                .get(ACCEPT_ENCODING)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+            req.headers()
+                .get(RANGE)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+            req.headers()
+                .get(IF_RANGE)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+        )
+        .await
+        {
             Ok(response) => Ok(response),
             Err(e) => {
                 error!("Error creating stream: {:?}", e);
@@ -160,6 +507,7 @@ async fn handle_request(
             .status(StatusCode::OK)
             .body(Body::from("OK")),
         "/slot" => fetch_slot(directory_adapter).await,
+        "/manifest" => fetch_manifest(directory_adapter).await,
         _ => Response::builder()
             .status(StatusCode::NOT_FOUND)
             .body(Body::from("404 Not Found")),
@@ -172,8 +520,16 @@ async fn handle_request(
 async fn create_server(
     port: u16,
     directory_adapter: Arc<DirectoryAdapter>,
+    download_chunk_size: usize,
+    tls_config: Option<Arc<ServerConfig>>,
 ) -> tokio::task::JoinHandle<()> {
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    // With TLS, the server only listens on localhost and a TLS endpoint on the public port
+    // forwards the decrypted connections to it.
+    let server_addr = match tls_config {
+        Some(_) => SocketAddr::from(([127, 0, 0, 1], 0)),
+        None => addr,
+    };
 
     // Spawn the server task
     tokio::spawn(async move {
@@ -182,41 +538,100 @@ async fn create_server(
             let directory_adapter = directory_adapter.clone();
             async move {
                 Ok::<_, Infallible>(layer.service(service_fn(move |req| {
-                    handle_request(req, directory_adapter.clone())
+                    handle_request(req, directory_adapter.clone(), download_chunk_size)
                 })))
             }
         });
 
-        let server = Server::bind(&addr).serve(make_svc);
-        info!("Listening on http://{}", addr);
+        let server = Server::bind(&server_addr).serve(make_svc);
+        let tls_handle = match tls_config {
+            Some(tls_config) => match serve_tls(addr, server.local_addr(), tls_config).await {
+                Ok(tls_handle) => {
+                    info!("Listening on https://{}", addr);
+                    Some(tls_handle)
+                }
+                Err(e) => {
+                    error!("Failed to listen on {}: {}", addr, e);
+                    return;
+                }
+            },
+            None => {
+                info!("Listening on http://{}", addr);
+                None
+            }
+        };
 
         if let Err(e) = server.await {
             error!("Server error: {}", e);
         }
+        if let Some(tls_handle) = tls_handle {
+            tls_handle.abort();
+        }
     })
 }
 
 #[tokio::main]
 async fn main() {
-    let args = Args::parse();
+    let args: Args = parse_args_with_config_file();
     setup_logging(args.logging_format);
-    setup_metrics(args.metrics_endpoint);
+    if let Some(config) = &args.config {
+        info!("Loaded arguments from config file {}", config.display());
+    }
+    setup_metrics(args.metrics_endpoint, false);
+    let retry_backoff = RetryBackoffConfig {
+        base_delay_ms: args.retry_base_delay_ms,
+        max_delay_ms: args.retry_max_delay_ms,
+    };
+    let tls_config = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert_path), Some(key_path)) => match load_tls_config(cert_path, key_path) {
+            Ok(tls_config) => Some(tls_config),
+            Err(err) => {
+                error!("Failed to load the TLS certificate and key: {}", err);
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
 
-    let rpc_client = get_rpc_client(&args.rpc_url);
+    let rpc_client = get_rpc_client_with_commitment(&args.rpc_url, args.commitment);
 
     let directory_adapter = match (args.snapshot_dir.clone(), args.r2_bucket.clone()) {
-        (Some(snapshot_dir), None) => {
-            Arc::new(DirectoryAdapter::from_local_directory(snapshot_dir))
-        }
-        (None, Some(r2_bucket)) => Arc::new(
+        (Some(snapshot_dir), None) => DirectoryAdapter::from_local_directory(snapshot_dir),
+        (None, Some(r2_bucket)) => {
             DirectoryAdapter::from_r2_bucket_and_prefix_and_env(r2_bucket, args.r2_prefix.clone())
-                .await,
-        ),
+                .await
+        }
         _ => {
             error!("Either snapshot_dir or r2_bucket must be provided");
             return;
         }
     };
+    let directory_adapter = Arc::new(
+        directory_adapter
+            .with_compression(args.compression)
+            .with_upload_retry_policy(UploadRetryPolicy {
+                max_attempts: args.upload_attempts,
+                dead_letter_dir: args.dead_letter_dir.clone(),
+                ..Default::default()
+            }),
+    );
+    if args.verify_on_start {
+        info!("Verifying snapshot checksums...");
+        match verify_snapshot_directory(directory_adapter.as_ref()).await {
+            Ok(corrupt_files) if corrupt_files.is_empty() => info!("Snapshot checksums verified"),
+            Ok(corrupt_files) => {
+                error!(
+                    "Refusing to start with corrupt snapshot files: {:?}",
+                    corrupt_files
+                );
+                std::process::exit(1);
+            }
+            Err(e) => {
+                error!("Failed to verify snapshot checksums: {:?}", e);
+                std::process::exit(1);
+            }
+        }
+    }
     let snapshotter_handle = if args.disable_snapshot_generation {
         None
     } else {
@@ -241,7 +656,12 @@ async fn main() {
             }
             None => {
                 if snapshot_files.is_empty() {
-                    get_network_start_slot(&rpc_client).await
+                    get_network_start_slot(
+                        &rpc_client,
+                        args.unknown_network_start_slot,
+                        retry_backoff,
+                    )
+                    .await
                 } else {
                     snapshot_files.last().unwrap().end_slot
                 }
@@ -256,9 +676,15 @@ async fn main() {
                     max_concurrent_block_fetches: args.max_concurrent_block_fetches.unwrap_or(20),
                     last_indexed_slot,
                     geyser_url: args.grpc_url.clone(),
+                    grpc_x_token: args.grpc_x_token.clone(),
+                    grpc_unreachable_policy: args.grpc_unreachable_policy,
+                    fetch_ahead_window: args.fetch_ahead_window,
+                    commitment: args.commitment,
+                    retry_backoff,
                 },
                 args.incremental_snapshot_interval_slots,
                 args.snapshot_interval_slots,
+                args.max_pending_snapshot_writes,
             )
             .await,
         )
@@ -266,7 +692,15 @@ async fn main() {
     let server_handle = if args.disable_api {
         None
     } else {
-        Some(create_server(args.port, directory_adapter.clone()).await)
+        Some(
+            create_server(
+                args.port,
+                directory_adapter.clone(),
+                args.download_chunk_size,
+                tls_config,
+            )
+            .await,
+        )
     };
 
     // Use `tokio::select!` to handle both the shutdown signal and task completions
@@ -25,7 +25,9 @@ async fn main() {
 
     let args = Args::parse();
     let max_connections = 1;
-    let db = setup_pg_connection(&args.db_url, max_connections).await;
+    let db = setup_pg_connection(&args.db_url, max_connections)
+        .await
+        .expect("Failed to connect to the database");
     let tree_address = SerializablePubkey::from(Pubkey::from_str(&args.tree_address).unwrap());
     info!("Validating tree {:?}", tree_address);
 
@@ -7,7 +7,7 @@ use photon_indexer::api::method::get_multiple_new_address_proofs::AddressList;
 use photon_indexer::api::method::get_transaction_with_compression_info::get_transaction_helper;
 use photon_indexer::api::method::get_validity_proof::CompressedProof;
 use photon_indexer::common::typedefs::serializable_pubkey::SerializablePubkey;
-use photon_indexer::ingester::index_block;
+use photon_indexer::ingester::{index_block, reindex_block, IngesterConfig};
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::pubkey::Pubkey;
 
@@ -21,12 +21,15 @@ use photon_indexer::api::method::{
     get_multiple_compressed_account_proofs::HashList, get_validity_proof::GetValidityProofRequest,
 };
 use photon_indexer::common::typedefs::hash::Hash;
-use photon_indexer::dao::generated::blocks;
+use photon_indexer::dao::generated::{accounts, blocks, owner_balances, transactions};
+use photon_indexer::ingester::parser::ParserConfig;
 use photon_indexer::ingester::typedefs::block_info::{BlockInfo, BlockMetadata};
-use sea_orm::EntityTrait;
+use sea_orm::sea_query::Expr;
 use sea_orm::QueryFilter;
 use sea_orm::{ColumnTrait, DatabaseConnection};
+use sea_orm::{EntityTrait, PaginatorTrait, QueryOrder};
 use serial_test::serial;
+use sqlx::types::Decimal;
 use std::str::FromStr;
 
 use futures::StreamExt;
@@ -65,18 +68,7 @@ fn all_indexing_methodologies(
         for index_transactions_individually in [true, false] {
             for txs in txs_permutations.clone() {
                 reset_tables(db_conn.as_ref()).await.unwrap();
-                // HACK: We index a block so that API methods can fetch the current slot.
-                index_block(
-                    db_conn.as_ref(),
-                    &BlockInfo {
-                        metadata: BlockMetadata {
-                    slot: 0,
-                    ..Default::default()
-                        },
-                        ..Default::default()
-                    },
-                ).await
-                .unwrap();
+                index_empty_block(db_conn.as_ref()).await;
 
                 if index_transactions_individually {
                     for tx in txs {
@@ -227,7 +219,7 @@ async fn test_e2e_mint_and_transfer_transactions(
                 cached_fetch_transaction(&setup.name, setup.client.clone(), txn_signature).await;
             let txn_signature = SerializableSignature(Signature::from_str(txn_signature).unwrap());
             // Test get transaction
-            let parsed_transaction: photon_indexer::api::method::get_transaction_with_compression_info::GetTransactionResponse = get_transaction_helper(&setup.db_conn, txn_signature, txn).await.unwrap();
+            let parsed_transaction: photon_indexer::api::method::get_transaction_with_compression_info::GetTransactionResponse = get_transaction_helper(&setup.db_conn, txn_signature, txn, &ParserConfig::default()).await.unwrap();
             assert_json_snapshot!(
                 format!("{}-{}-transaction", name.clone(), txn_name),
                 parsed_transaction
@@ -453,7 +445,9 @@ async fn test_index_block_metadata(
 
     let slot = 254170887;
     let block = cached_fetch_block(&setup.name, setup.client.clone(), slot).await;
-    index_block(&setup.db_conn, &block).await.unwrap();
+    index_block(&setup.db_conn, &block, &IngesterConfig::default())
+        .await
+        .unwrap();
     let filter = blocks::Column::Slot.eq(block.metadata.slot);
 
     let block_model = blocks::Entity::find()
@@ -479,15 +473,159 @@ async fn test_index_block_metadata(
     assert_eq!(block_model.block_time, 1710441678);
 
     // Verify that we don't get an error if we try to index the same block again
-    index_block(&setup.db_conn, &block).await.unwrap();
+    index_block(&setup.db_conn, &block, &IngesterConfig::default())
+        .await
+        .unwrap();
     assert_eq!(setup.api.get_indexer_slot().await.unwrap().0, slot);
 
     // Verify that get_indexer_slot() gets updated a new block is indexed.
     let block = cached_fetch_block(&setup.name, setup.client.clone(), slot + 1).await;
-    index_block(&setup.db_conn, &block).await.unwrap();
+    index_block(&setup.db_conn, &block, &IngesterConfig::default())
+        .await
+        .unwrap();
     assert_eq!(setup.api.get_indexer_slot().await.unwrap().0, slot + 1);
 }
 
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_reindex_block(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    let name = trim_test_name(function_name!());
+    let setup = setup_with_options(
+        name.clone(),
+        TestSetupOptions {
+            network: Network::Mainnet,
+            db_backend,
+        },
+    )
+    .await;
+
+    // Reuse the cached block from the block metadata test.
+    let slot = 254170887;
+    let block = cached_fetch_block("index_block_metadata", setup.client.clone(), slot).await;
+    index_block(&setup.db_conn, &block, &IngesterConfig::default())
+        .await
+        .unwrap();
+
+    let count_transactions = || async {
+        transactions::Entity::find()
+            .filter(transactions::Column::Slot.eq(slot as i64))
+            .count(setup.db_conn.as_ref())
+            .await
+            .unwrap()
+    };
+    let expected_transactions = count_transactions().await;
+
+    // Simulate a mis-indexed slot by dropping its persisted data.
+    transactions::Entity::delete_many()
+        .filter(transactions::Column::Slot.eq(slot as i64))
+        .exec(setup.db_conn.as_ref())
+        .await
+        .unwrap();
+    blocks::Entity::delete_many()
+        .filter(blocks::Column::Slot.eq(slot as i64))
+        .exec(setup.db_conn.as_ref())
+        .await
+        .unwrap();
+    assert_eq!(count_transactions().await, 0);
+
+    reindex_block(&setup.db_conn, &block, &IngesterConfig::default())
+        .await
+        .unwrap();
+    assert_eq!(count_transactions().await, expected_transactions);
+    assert_eq!(setup.api.get_indexer_slot().await.unwrap().0, slot);
+
+    // Index a block that creates accounts and a later block that spends them.
+    let mut blocks = Vec::new();
+    for (block_slot, tx) in [
+        (
+            slot + 1,
+            "5NLdbqznXqmTPTN8JBLquriDggb9qaRszVGLSvt6t5esy2Q8Z1iqAuXF4qoLK7HM6oGLySUNUkzhnSocwArpAqmV",
+        ),
+        (
+            slot + 2,
+            "4TFBPyvatWgjTdNesfaTo3YkbP2spvGmgZgLn6CvTeqRZSi1ZuPCkK7fLaDbPKskMSF4Azge6QPvtZt9VUV7KBF8",
+        ),
+    ] {
+        let tx = cached_fetch_transaction("lamport_transfers", setup.client.clone(), tx).await;
+        let block = BlockInfo {
+            metadata: BlockMetadata {
+                slot: block_slot,
+                parent_slot: block_slot - 1,
+                ..Default::default()
+            },
+            transactions: vec![tx.try_into().unwrap()],
+        };
+        index_block(&setup.db_conn, &block, &IngesterConfig::default()).await.unwrap();
+        blocks.push(block);
+    }
+
+    let persisted_state = || async {
+        let accounts = accounts::Entity::find()
+            .order_by_asc(accounts::Column::Hash)
+            .all(setup.db_conn.as_ref())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|account| {
+                (
+                    account.hash,
+                    account.slot_created,
+                    account.spent,
+                    account.slot_spent,
+                    account.lamports,
+                    account.data,
+                )
+            })
+            .collect::<Vec<_>>();
+        let owner_balances = owner_balances::Entity::find()
+            .order_by_asc(owner_balances::Column::Owner)
+            .all(setup.db_conn.as_ref())
+            .await
+            .unwrap();
+        (accounts, owner_balances)
+    };
+    let (accounts_before, owner_balances_before) = persisted_state().await;
+    assert!(accounts_before
+        .iter()
+        .any(
+            |(_, slot_created, spent, slot_spent, _, _)| *slot_created == (slot + 1) as i64
+                && *spent
+                && *slot_spent == Some((slot + 2) as i64)
+        ));
+
+    // Accounts spent by a later slot stay spent, and balances are unchanged.
+    reindex_block(&setup.db_conn, &blocks[0], &IngesterConfig::default())
+        .await
+        .unwrap();
+    let (accounts_after, owner_balances_after) = persisted_state().await;
+    assert_eq!(accounts_after, accounts_before);
+    assert_eq!(owner_balances_after, owner_balances_before);
+
+    // Simulate a mis-indexed account of the slot by corrupting its persisted row.
+    accounts::Entity::update_many()
+        .col_expr(accounts::Column::Lamports, Expr::value(Decimal::from(1)))
+        .col_expr(accounts::Column::Data, Expr::value(Some(vec![1u8, 2, 3])))
+        .filter(accounts::Column::SlotCreated.eq((slot + 1) as i64))
+        .filter(accounts::Column::Spent.eq(true))
+        .exec(setup.db_conn.as_ref())
+        .await
+        .unwrap();
+    let (accounts_corrupted, _) = persisted_state().await;
+    assert_ne!(accounts_corrupted, accounts_before);
+
+    // Re-indexing the slot restores the account from the block, and it stays spent.
+    reindex_block(&setup.db_conn, &blocks[0], &IngesterConfig::default())
+        .await
+        .unwrap();
+    let (accounts_after, owner_balances_after) = persisted_state().await;
+    assert_eq!(accounts_after, accounts_before);
+    assert_eq!(owner_balances_after, owner_balances_before);
+}
+
 #[named]
 #[rstest]
 #[tokio::test]
@@ -507,7 +645,9 @@ async fn test_get_latest_non_voting_signatures(
 
     let slot = 270893658;
     let block = cached_fetch_block(&setup.name, setup.client.clone(), slot).await;
-    index_block(&setup.db_conn, &block).await.unwrap();
+    index_block(&setup.db_conn, &block, &IngesterConfig::default())
+        .await
+        .unwrap();
     let all_nonvoting_transactions = setup
         .api
         .get_latest_non_voting_signatures(GetLatestSignaturesRequest {
@@ -542,7 +682,9 @@ async fn test_get_latest_non_voting_signatures_with_failures(
 
     let slot = 279620356;
     let block = cached_fetch_block(&setup.name, setup.client.clone(), slot).await;
-    index_block(&setup.db_conn, &block).await.unwrap();
+    index_block(&setup.db_conn, &block, &IngesterConfig::default())
+        .await
+        .unwrap();
     let all_nonvoting_transactions = setup
         .api
         .get_latest_non_voting_signatures(GetLatestSignaturesRequest {
@@ -646,3 +788,40 @@ async fn test_nullfiier_and_address_queue_transactions(
         assert_json_snapshot!(format!("{}-proof-address", name.clone()), proof_v2);
     }
 }
+
+#[tokio::test]
+#[serial]
+async fn test_parallel_parsing_matches_sequential_parsing() {
+    use photon_indexer::ingester::parser::state_update::StateUpdate;
+    use photon_indexer::ingester::parser::{parse_transaction, parse_transactions};
+    use photon_indexer::ingester::typedefs::block_info::TransactionInfo;
+
+    // The transactions are cached, so the RPC client is never called.
+    let rpc_client = Arc::new(RpcClient::new("http://127.0.0.1:8899".to_string()));
+    let mut transactions: Vec<TransactionInfo> = Vec::new();
+    for tx in [
+        "5NLdbqznXqmTPTN8JBLquriDggb9qaRszVGLSvt6t5esy2Q8Z1iqAuXF4qoLK7HM6oGLySUNUkzhnSocwArpAqmV",
+        "4TFBPyvatWgjTdNesfaTo3YkbP2spvGmgZgLn6CvTeqRZSi1ZuPCkK7fLaDbPKskMSF4Azge6QPvtZt9VUV7KBF8",
+        "QBrbAZFq12LCbnv5dByn8vB8Znam4ieGQVzybapgPL5LCa9KHfuYZKV6Nah6UGsa6FUptmT6tSpexWZDrbp82iP",
+    ] {
+        let tx = cached_fetch_transaction("lamport_transfers", rpc_client.clone(), tx).await;
+        transactions.push(tx.try_into().unwrap());
+    }
+    // Repeat the transactions so that the block is large enough to be parsed on several threads.
+    let transactions = transactions.repeat(100);
+
+    let config = ParserConfig::default();
+    let sequential = transactions
+        .iter()
+        .map(|tx| parse_transaction(tx, 1, &config).unwrap())
+        .collect::<Vec<_>>();
+    let parallel = parse_transactions(&transactions, 1, &config)
+        .into_iter()
+        .map(Result::unwrap)
+        .collect::<Vec<_>>();
+    assert_eq!(parallel, sequential);
+    assert_eq!(
+        StateUpdate::merge_updates(parallel),
+        StateUpdate::merge_updates(sequential)
+    );
+}
@@ -15,6 +15,7 @@ use photon_indexer::api::method::utils::{
     GetCompressedTokenAccountsByOwner,
 };
 use photon_indexer::common::typedefs::bs58_string::Base58String;
+use photon_indexer::common::UnknownNetworkStartSlot;
 use photon_indexer::ingester::persist::persisted_indexed_merkle_tree::{
     get_exclusion_range_with_proof, update_indexed_tree_leaves, validate_tree,
 };
@@ -31,12 +32,13 @@ use photon_indexer::common::typedefs::account::Account;
 use photon_indexer::common::typedefs::bs64_string::Base64String;
 use photon_indexer::common::typedefs::{hash::Hash, serializable_pubkey::SerializablePubkey};
 use photon_indexer::dao::generated::accounts;
-use photon_indexer::ingester::index_block;
-use photon_indexer::ingester::parser::state_update::StateUpdate;
+use photon_indexer::ingester::parser::{state_update::StateUpdate, ParserConfig};
 use photon_indexer::ingester::persist::persisted_state_tree::{persist_leaf_nodes, LeafNode};
 use photon_indexer::ingester::persist::{
-    compute_parent_hash, persist_token_accounts, EnrichedTokenAccount,
+    compute_parent_hash, persist_state_update, persist_token_accounts, EnrichedTokenAccount,
+    OnSpend, PersistConfig,
 };
+use photon_indexer::ingester::{index_block, IngesterConfig};
 
 use photon_indexer::ingester::typedefs::block_info::{BlockInfo, BlockMetadata};
 use sea_orm::{EntityTrait, Set};
@@ -69,19 +71,7 @@ async fn test_persist_state_update_basic(
     let name = trim_test_name(function_name!());
     let setup = setup(name, db_backend).await;
 
-    // HACK: We index a block so that API methods can fetch the current slot.
-    index_block(
-        &setup.db_conn,
-        &BlockInfo {
-            metadata: BlockMetadata {
-                slot: 0,
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-    )
-    .await
-    .unwrap();
+    index_empty_block(&setup.db_conn).await;
 
     let mut state_update = StateUpdate::new();
     let account = Account {
@@ -98,13 +88,59 @@ async fn test_persist_state_update_basic(
         leaf_index: UnsignedInteger(0),
         seq: UnsignedInteger(0),
         slot_created: UnsignedInteger(0),
+        data_truncated: false,
+    };
+
+    // Lamport-only accounts carry no data.
+    let lamport_only_account = Account {
+        hash: Hash::new_unique(),
+        address: None,
+        data: None,
+        owner: account.owner,
+        lamports: UnsignedInteger(500),
+        tree: account.tree,
+        leaf_index: UnsignedInteger(1),
+        seq: UnsignedInteger(1),
+        slot_created: UnsignedInteger(0),
+        data_truncated: false,
     };
 
     state_update.out_accounts.push(account.clone());
+    state_update.out_accounts.push(lamport_only_account.clone());
     persist_state_update_using_connection(&setup.db_conn, state_update)
         .await
         .unwrap();
 
+    let res = setup
+        .api
+        .get_compressed_account(CompressedAccountRequest {
+            address: None,
+            hash: Some(lamport_only_account.hash.clone()),
+        })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(res, Some(lamport_only_account.clone()));
+
+    for (has_data, mut expected_accounts) in [
+        (None, vec![account.clone(), lamport_only_account.clone()]),
+        (Some(true), vec![account.clone()]),
+        (Some(false), vec![lamport_only_account.clone()]),
+    ] {
+        expected_accounts.sort_by_key(|account| account.hash.to_vec());
+        let res = setup
+            .api
+            .get_compressed_accounts_by_owner(GetCompressedAccountsByOwnerRequest {
+                owner: account.owner,
+                hasData: has_data,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .value;
+        assert_eq!(res.items, expected_accounts);
+    }
+
     let request = CompressedAccountRequest {
         address: None,
         hash: Some(account.hash.clone()),
@@ -150,19 +186,7 @@ async fn test_multiple_accounts(
     let name = trim_test_name(function_name!());
     let setup = setup(name, db_backend).await;
 
-    // HACK: We index a block so that API methods can fetch the current slot.
-    index_block(
-        &setup.db_conn,
-        &BlockInfo {
-            metadata: BlockMetadata {
-                slot: 0,
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-    )
-    .await
-    .unwrap();
+    index_empty_block(&setup.db_conn).await;
 
     let owner1 = SerializablePubkey::new_unique();
     let owner2 = SerializablePubkey::new_unique();
@@ -183,6 +207,7 @@ async fn test_multiple_accounts(
             leaf_index: UnsignedInteger(10),
             seq: UnsignedInteger(1),
             slot_created: UnsignedInteger(0),
+            data_truncated: false,
         },
         Account {
             hash: Hash::new_unique(),
@@ -198,6 +223,7 @@ async fn test_multiple_accounts(
             leaf_index: UnsignedInteger(11),
             seq: UnsignedInteger(2),
             slot_created: UnsignedInteger(0),
+            data_truncated: false,
         },
         Account {
             hash: Hash::new_unique(),
@@ -213,6 +239,7 @@ async fn test_multiple_accounts(
             leaf_index: UnsignedInteger(13),
             seq: UnsignedInteger(3),
             slot_created: UnsignedInteger(1),
+            data_truncated: false,
         },
         Account {
             hash: Hash::new_unique(),
@@ -228,6 +255,7 @@ async fn test_multiple_accounts(
             leaf_index: UnsignedInteger(23),
             seq: UnsignedInteger(1),
             slot_created: UnsignedInteger(0),
+            data_truncated: false,
         },
     ];
     state_update.out_accounts = accounts.clone();
@@ -316,6 +344,25 @@ async fn test_multiple_accounts(
         &mut res.items.iter().map(|x| x.clone().unwrap()).collect(),
         &mut accounts_of_interest,
     );
+
+    // Accounts that do not exist are returned as null instead of failing the whole batch.
+    let mut hashes = accounts[..3]
+        .iter()
+        .map(|x| x.hash.clone())
+        .collect::<Vec<_>>();
+    hashes.push(Hash::new_unique());
+    let res = setup
+        .api
+        .get_multiple_compressed_accounts(GetMultipleCompressedAccountsRequest {
+            addresses: None,
+            hashes: Some(hashes),
+        })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(res.items.len(), 4);
+    assert_eq!(res.items[..3].iter().flatten().count(), 3);
+    assert_eq!(res.items[3], None);
 }
 
 #[named]
@@ -339,19 +386,7 @@ async fn test_persist_token_data(
     let delegate1 = SerializablePubkey::new_unique();
     let delegate2 = SerializablePubkey::new_unique();
 
-    // HACK: We index a block so that API methods can fetch the current slot.
-    index_block(
-        &setup.db_conn,
-        &BlockInfo {
-            metadata: BlockMetadata {
-                slot: 0,
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-    )
-    .await
-    .unwrap();
+    index_empty_block(&setup.db_conn).await;
 
     let token_data1 = TokenData {
         mint: mint1,
@@ -497,8 +532,48 @@ async fn test_persist_token_data(
         .await
         .unwrap()
         .value;
+    assert_eq!(res.items.len(), 1);
     verify_response_matches_input_token_data(res.clone(), owner_tlv);
 
+    // The mint filter also applies to paginated requests.
+    for owner in [owner1, owner2, owner3] {
+        let res = setup
+            .api
+            .get_compressed_token_accounts_by_owner(GetCompressedTokenAccountsByOwner {
+                owner,
+                mint: Some(mint1),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .value;
+        let mut paginated_res = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = setup
+                .api
+                .get_compressed_token_accounts_by_owner(GetCompressedTokenAccountsByOwner {
+                    owner,
+                    mint: Some(mint1),
+                    cursor: cursor.clone(),
+                    limit: Some(Limit::new(1).unwrap()),
+                    ..Default::default()
+                })
+                .await
+                .unwrap()
+                .value;
+            paginated_res.extend(page.items);
+            cursor = page.cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert!(paginated_res
+            .iter()
+            .all(|token_account| token_account.token_data.mint == mint1));
+        assert_eq!(paginated_res, res.items);
+    }
+
     for owner in [owner2] {
         let owner_tlv = all_token_data
             .iter()
@@ -886,19 +961,7 @@ async fn test_get_multiple_new_address_proofs(
     let name = trim_test_name(function_name!());
     let setup = setup(name.clone(), db_backend).await;
 
-    // HACK: We index a block so that API methods can fetch the current slot.
-    index_block(
-        &setup.db_conn,
-        &BlockInfo {
-            metadata: BlockMetadata {
-                slot: 0,
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-    )
-    .await
-    .unwrap();
+    index_empty_block(&setup.db_conn).await;
 
     let addresses = vec![
         SerializablePubkey::try_from("Fi6AXBGuGs7DRXP428hwhJJfTpJ4BVZD8DiUcX1cj35W").unwrap(),
@@ -928,19 +991,7 @@ async fn test_get_multiple_new_address_proofs_interop(
     let name = trim_test_name(function_name!());
     let setup = setup(name.clone(), db_backend).await;
 
-    // HACK: We index a block so that API methods can fetch the current slot.
-    index_block(
-        &setup.db_conn,
-        &BlockInfo {
-            metadata: BlockMetadata {
-                slot: 0,
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-    )
-    .await
-    .unwrap();
+    index_empty_block(&setup.db_conn).await;
 
     let addresses = vec![SerializablePubkey::try_from(vec![
         0, 2, 3, 4, 5, 6, 7, 8, 9, 10, 42, 42, 42, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
@@ -954,6 +1005,7 @@ async fn test_get_multiple_new_address_proofs_interop(
     let mut validity_proof = get_validity_proof(
         &setup.db_conn,
         &setup.prover_url,
+        false,
         GetValidityProofRequest {
             newAddresses: addresses.clone(),
             newAddressesWithTrees: vec![],
@@ -987,6 +1039,7 @@ async fn test_get_multiple_new_address_proofs_interop(
     let mut validity_proof_v2 = get_validity_proof(
         &setup.db_conn,
         &setup.prover_url,
+        false,
         GetValidityProofRequest {
             newAddressesWithTrees: addresses_with_trees.clone(),
             hashes: vec![],
@@ -1005,24 +1058,126 @@ async fn test_get_multiple_new_address_proofs_interop(
 #[rstest]
 #[tokio::test]
 #[serial]
-#[ignore]
-async fn load_test(#[values(DatabaseBackend::Postgres)] db_backend: DatabaseBackend) {
+async fn test_get_new_address_proof(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::get_multiple_new_address_proofs::{
+        ADDRESS_TREE_ADDRESS, ADDRESS_TREE_HEIGHT,
+    };
+    use photon_indexer::api::method::get_new_address_proof::{
+        get_new_address_proof, GetNewAddressProofRequest,
+    };
+
     let name = trim_test_name(function_name!());
     let setup = setup(name.clone(), db_backend).await;
 
-    // HACK: We index a block so that API methods can fetch the current slot.
-    index_block(
+    index_empty_block(&setup.db_conn).await;
+
+    let tree = SerializablePubkey::from(ADDRESS_TREE_ADDRESS);
+    let existing_address = SerializablePubkey::try_from(vec![1; 32]).unwrap();
+    let new_address = SerializablePubkey::try_from(vec![2; 32]).unwrap();
+
+    let txn = setup.db_conn.as_ref().begin().await.unwrap();
+    multi_append(
+        &txn,
+        vec![existing_address.to_bytes_vec()],
+        tree.to_bytes_vec(),
+        ADDRESS_TREE_HEIGHT,
+    )
+    .await
+    .unwrap();
+    txn.commit().await.unwrap();
+
+    let proof = get_new_address_proof(
         &setup.db_conn,
-        &BlockInfo {
-            metadata: BlockMetadata {
-                slot: 0,
-                ..Default::default()
-            },
-            ..Default::default()
+        GetNewAddressProofRequest {
+            address: new_address,
+            tree: None,
         },
     )
     .await
-    .unwrap();
+    .unwrap()
+    .value;
+    assert_eq!(proof.address, new_address);
+    assert_eq!(proof.merkleTree, tree);
+    assert_eq!(proof.lowerRangeAddress, existing_address);
+    assert!(proof.higherRangeAddress.to_bytes_vec() > new_address.to_bytes_vec());
+    assert_eq!(proof.proof.len(), ADDRESS_TREE_HEIGHT as usize - 1);
+
+    let result = get_new_address_proof(
+        &setup.db_conn,
+        GetNewAddressProofRequest {
+            address: existing_address,
+            tree: Some(tree),
+        },
+    )
+    .await;
+    assert!(result.is_err());
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_max_proof_batch_size(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::error::PhotonApiError;
+    use photon_indexer::api::method::get_multiple_compressed_account_proofs::HashList;
+    use photon_indexer::api::method::get_multiple_new_address_proofs::AddressList;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name.clone(), db_backend).await;
+    let max_proof_batch_size = 2;
+    let api = setup
+        .api
+        .with_max_proof_batch_size(Some(max_proof_batch_size));
+
+    index_empty_block(&setup.db_conn).await;
+
+    let addresses: Vec<SerializablePubkey> = (0..max_proof_batch_size + 1)
+        .map(|i| SerializablePubkey::try_from(vec![i as u8 + 1; 32]).unwrap())
+        .collect();
+
+    let proofs = api
+        .get_multiple_new_address_proofs(AddressList(addresses[..max_proof_batch_size].to_vec()))
+        .await
+        .unwrap();
+    assert_eq!(proofs.value.len(), max_proof_batch_size);
+
+    let result = api
+        .get_multiple_new_address_proofs(AddressList(addresses.clone()))
+        .await;
+    assert!(matches!(result, Err(PhotonApiError::ValidationError(_))));
+
+    let hashes = (0..max_proof_batch_size + 1)
+        .map(|_| Hash::new_unique())
+        .collect::<Vec<_>>();
+    let result = api
+        .get_multiple_compressed_account_proofs(HashList(hashes.clone()))
+        .await;
+    assert!(matches!(result, Err(PhotonApiError::ValidationError(_))));
+
+    let result = api
+        .get_validity_proof(GetValidityProofRequest {
+            hashes: hashes[..1].to_vec(),
+            newAddresses: addresses[..max_proof_batch_size].to_vec(),
+            newAddressesWithTrees: vec![],
+        })
+        .await;
+    assert!(matches!(result, Err(PhotonApiError::ValidationError(_))));
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+#[ignore]
+async fn load_test(#[values(DatabaseBackend::Postgres)] db_backend: DatabaseBackend) {
+    let name = trim_test_name(function_name!());
+    let setup = setup(name.clone(), db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
 
     let mut state_update = StateUpdate::default();
 
@@ -1043,6 +1198,7 @@ async fn load_test(#[values(DatabaseBackend::Postgres)] db_backend: DatabaseBack
             leaf_index: UnsignedInteger(leaf_index),
             seq: UnsignedInteger(0),
             slot_created: UnsignedInteger(0),
+            data_truncated: false,
         }
     }
 
@@ -1073,19 +1229,7 @@ async fn test_persisted_state_trees_bug_with_latter_smaller_seq_values(
     let name = trim_test_name(function_name!());
 
     let setup = setup(name, db_backend).await;
-    // HACK: We index a block so that API methods can fetch the current slot.
-    index_block(
-        &setup.db_conn,
-        &BlockInfo {
-            metadata: BlockMetadata {
-                slot: 0,
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-    )
-    .await
-    .unwrap();
+    index_empty_block(&setup.db_conn).await;
     let tree =
         SerializablePubkey::try_from("C83cpRN6oaafjNgMQJvaYgAz592EP5wunKvbokeTKPLn").unwrap();
 
@@ -1162,19 +1306,7 @@ async fn test_gpa_filters(
     let name = trim_test_name(function_name!());
     let setup = setup(name, db_backend).await;
 
-    // HACK: We index a block so that API methods can fetch the current slot.
-    index_block(
-        &setup.db_conn,
-        &BlockInfo {
-            metadata: BlockMetadata {
-                slot: 0,
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-    )
-    .await
-    .unwrap();
+    index_empty_block(&setup.db_conn).await;
 
     let owner1 = SerializablePubkey::new_unique();
     let mut state_update = StateUpdate::default();
@@ -1193,6 +1325,7 @@ async fn test_gpa_filters(
         leaf_index: UnsignedInteger(10),
         seq: UnsignedInteger(1),
         slot_created: UnsignedInteger(0),
+        data_truncated: false,
     }];
     state_update.out_accounts = accounts.clone();
     persist_state_update_using_connection(&setup.db_conn, state_update)
@@ -1490,3 +1623,5587 @@ async fn test_update_indexed_merkle_tree(
         assert_eq!(tree_model.seq, 1 as i64);
     }
 }
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_dump_schema(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::migration::dump_schema;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    let schema = dump_schema(setup.db_conn.as_ref()).await.unwrap();
+    assert!(
+        schema.contains("CREATE TABLE accounts") || schema.contains("CREATE TABLE \"accounts\"")
+    );
+    for column in ["hash", "owner", "lamports", "spent"] {
+        assert!(
+            schema.contains(column),
+            "Schema should contain column {}",
+            column
+        );
+    }
+    assert!(!schema.contains("seaql_migrations"));
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_reparse_raw_events(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::dao::generated::raw_events;
+    use photon_indexer::ingester::parser::indexer_events::{
+        CompressedAccount, MerkleTreeSequenceNumber, OutputCompressedAccountWithPackedContext,
+        PublicTransactionEvent,
+    };
+    use photon_indexer::ingester::parser::state_update::{RawEvent, RawEventKind, Transaction};
+    use photon_indexer::ingester::reparse_raw_events;
+    use sea_orm::PaginatorTrait;
+    use solana_sdk::signature::Signature;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name.clone(), db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let tree = Pubkey::new_unique();
+    let hash = Hash::new_unique();
+    let event = PublicTransactionEvent {
+        output_compressed_account_hashes: vec![hash.0],
+        output_compressed_accounts: vec![OutputCompressedAccountWithPackedContext {
+            compressed_account: CompressedAccount {
+                owner: Pubkey::new_unique(),
+                lamports: 1000,
+                address: None,
+                data: None,
+            },
+            merkle_tree_index: 0,
+        }],
+        output_leaf_indices: vec![0],
+        sequence_numbers: vec![MerkleTreeSequenceNumber {
+            pubkey: tree,
+            seq: 0,
+        }],
+        pubkey_array: vec![tree],
+        ..Default::default()
+    };
+
+    // Simulate a transaction indexed by an older parser that ignored the event.
+    let signature = Signature::new_unique();
+    let state_update = StateUpdate {
+        transactions: HashSet::from([Transaction {
+            signature,
+            slot: 0,
+            uses_compression: true,
+            error: None,
+        }]),
+        raw_events: vec![RawEvent {
+            signature,
+            slot: 0,
+            index: 0,
+            kind: RawEventKind::PublicTransaction,
+            data: to_vec(&event).unwrap(),
+        }],
+        ..Default::default()
+    };
+    let persist_config = PersistConfig {
+        persist_raw_events: true,
+        ..Default::default()
+    };
+    let txn = setup.db_conn.begin().await.unwrap();
+    persist_state_update(&txn, state_update, &persist_config)
+        .await
+        .unwrap();
+    txn.commit().await.unwrap();
+
+    let raw_event_count = raw_events::Entity::find()
+        .count(setup.db_conn.as_ref())
+        .await
+        .unwrap();
+    assert_eq!(raw_event_count, 1);
+    let account = accounts::Entity::find()
+        .filter(accounts::Column::Hash.eq(hash.to_vec()))
+        .one(setup.db_conn.as_ref())
+        .await
+        .unwrap();
+    assert!(account.is_none());
+
+    let reparsed_slots = reparse_raw_events(&setup.db_conn, 0, &IngesterConfig::default())
+        .await
+        .unwrap();
+    assert_eq!(reparsed_slots, 1);
+    let account = accounts::Entity::find()
+        .filter(accounts::Column::Hash.eq(hash.to_vec()))
+        .one(setup.db_conn.as_ref())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(account.lamports, Decimal::from(1000));
+    assert_eq!(account.tree, tree.to_bytes().to_vec());
+
+    let reparsed_slots = reparse_raw_events(&setup.db_conn, 1, &IngesterConfig::default())
+        .await
+        .unwrap();
+    assert_eq!(reparsed_slots, 0);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_exclude_native_token_accounts(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::utils::NATIVE_MINT;
+    use std::str::FromStr;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let compressed_token_program =
+        Pubkey::from_str("cTokenmWW8bLPjZEBAUgYy3zKxQZW6VKi7bqNFEVv3m").unwrap();
+    let owner = SerializablePubkey::new_unique();
+    let delegate = SerializablePubkey::new_unique();
+    let native_mint = SerializablePubkey::from(NATIVE_MINT);
+    let other_mint = SerializablePubkey::new_unique();
+    let tree = SerializablePubkey::new_unique();
+
+    let mut state_update = StateUpdate::new();
+    for (leaf_index, mint) in [native_mint, other_mint].into_iter().enumerate() {
+        let token_data = TokenData {
+            mint,
+            owner,
+            amount: UnsignedInteger(100),
+            delegate: Some(delegate),
+            state: AccountState::initialized,
+            tlv: None,
+        };
+        state_update.out_accounts.push(Account {
+            hash: Hash::new_unique(),
+            address: None,
+            data: Some(AccountData {
+                discriminator: UnsignedInteger(2),
+                data: Base64String(to_vec(&token_data).unwrap()),
+                data_hash: Hash::new_unique(),
+            }),
+            owner: compressed_token_program.into(),
+            lamports: UnsignedInteger(0),
+            tree,
+            leaf_index: UnsignedInteger(leaf_index as u64),
+            seq: UnsignedInteger(leaf_index as u64),
+            slot_created: UnsignedInteger(0),
+            data_truncated: false,
+        });
+    }
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let all_accounts = setup
+        .api
+        .get_compressed_token_accounts_by_owner(GetCompressedTokenAccountsByOwner {
+            owner,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(all_accounts.items.len(), 2);
+
+    let non_native_accounts = setup
+        .api
+        .get_compressed_token_accounts_by_owner(GetCompressedTokenAccountsByOwner {
+            owner,
+            exclude_native: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(non_native_accounts.items.len(), 1);
+    assert_eq!(non_native_accounts.items[0].token_data.mint, other_mint);
+
+    let all_delegated_accounts = setup
+        .api
+        .get_compressed_token_accounts_by_delegate(GetCompressedTokenAccountsByDelegate {
+            delegate,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(all_delegated_accounts.items.len(), 2);
+
+    let non_native_delegated_accounts = setup
+        .api
+        .get_compressed_token_accounts_by_delegate(GetCompressedTokenAccountsByDelegate {
+            delegate,
+            exclude_native: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(non_native_delegated_accounts.items.len(), 1);
+    assert_eq!(
+        non_native_delegated_accounts.items[0].token_data.mint,
+        other_mint
+    );
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_indexer_stats(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    for slot in [5, 10] {
+        index_block(
+            &setup.db_conn,
+            &BlockInfo {
+                metadata: BlockMetadata {
+                    slot,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            &IngesterConfig::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    let tree = SerializablePubkey::new_unique();
+    let tree_accounts = (0..3)
+        .map(|i| Account {
+            hash: Hash::new_unique(),
+            address: None,
+            data: None,
+            owner: SerializablePubkey::new_unique(),
+            lamports: UnsignedInteger(10),
+            tree,
+            leaf_index: UnsignedInteger(i),
+            seq: UnsignedInteger(i),
+            slot_created: UnsignedInteger(5),
+            data_truncated: false,
+        })
+        .collect::<Vec<_>>();
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts = tree_accounts.clone();
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let mut state_update = StateUpdate::new();
+    state_update
+        .in_accounts
+        .insert(tree_accounts[0].hash.clone());
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let stats = setup.api.get_indexer_stats().await.unwrap().value;
+    assert_eq!(stats.unspent_accounts.0, 2);
+    assert_eq!(stats.spent_accounts.0, 1);
+    assert_eq!(stats.state_trees.0, 1);
+    assert_eq!(stats.address_trees.0, 0);
+    assert!(stats.state_tree_nodes.0 > 0);
+    assert_eq!(stats.first_indexed_slot.0, 5);
+    assert_eq!(stats.last_indexed_slot.0, 10);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_verify_proof_roots_are_current(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::error::PhotonApiError;
+    use photon_indexer::api::method::get_validity_proof::{
+        verify_proof_roots_are_current, STATE_TREE_QUEUE_SIZE,
+    };
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+    let tree = SerializablePubkey::new_unique();
+    let tree_height = 5;
+
+    let leaf_node = LeafNode {
+        tree,
+        leaf_index: 0,
+        hash: Hash::new_unique(),
+        seq: 0,
+    };
+    let txn = setup.db_conn.as_ref().begin().await.unwrap();
+    persist_leaf_nodes(&txn, vec![leaf_node.clone()], tree_height)
+        .await
+        .unwrap();
+    txn.commit().await.unwrap();
+
+    let txn = setup.db_conn.as_ref().begin().await.unwrap();
+    let proofs = get_multiple_compressed_leaf_proofs(&txn, vec![leaf_node.hash.clone()])
+        .await
+        .unwrap();
+    txn.commit().await.unwrap();
+    verify_proof_roots_are_current(&setup.db_conn, &proofs)
+        .await
+        .unwrap();
+
+    // Advance the tree far enough for the proof's root to be evicted from the root history.
+    let txn = setup.db_conn.as_ref().begin().await.unwrap();
+    persist_leaf_nodes(
+        &txn,
+        vec![LeafNode {
+            tree,
+            leaf_index: 1,
+            hash: Hash::new_unique(),
+            seq: STATE_TREE_QUEUE_SIZE as u32,
+        }],
+        tree_height,
+    )
+    .await
+    .unwrap();
+    txn.commit().await.unwrap();
+
+    let err = verify_proof_roots_are_current(&setup.db_conn, &proofs)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, PhotonApiError::InvalidProof(_)));
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_decode_compressed_account(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use byteorder::{ByteOrder, LittleEndian};
+    use photon_indexer::api::error::PhotonApiError;
+    use photon_indexer::api::method::decode_compressed_account::DecodeCompressedAccountRequest;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let person = Person {
+        name: "Alice".to_string(),
+        age: 20,
+    };
+    let discriminator =
+        LittleEndian::read_u64(&solana_sdk::hash::hash(b"account:Person").to_bytes()[..8]);
+    let account = Account {
+        hash: Hash::new_unique(),
+        address: None,
+        data: Some(AccountData {
+            discriminator: UnsignedInteger(discriminator),
+            data: Base64String(to_vec(&person).unwrap()),
+            data_hash: Hash::new_unique(),
+        }),
+        owner: SerializablePubkey::new_unique(),
+        lamports: UnsignedInteger(1000),
+        tree: SerializablePubkey::new_unique(),
+        leaf_index: UnsignedInteger(0),
+        seq: UnsignedInteger(0),
+        slot_created: UnsignedInteger(0),
+        data_truncated: false,
+    };
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts.push(account.clone());
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let idl = serde_json::json!({
+        "accounts": [
+            {
+                "name": "Other",
+                "type": {"kind": "struct", "fields": [{"name": "value", "type": "u8"}]}
+            },
+            {
+                "name": "Person",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        {"name": "name", "type": "string"},
+                        {"name": "age", "type": "u64"}
+                    ]
+                }
+            }
+        ]
+    });
+
+    let decoded = setup
+        .api
+        .decode_compressed_account(DecodeCompressedAccountRequest {
+            hash: account.hash.clone(),
+            idl: idl.clone(),
+            account_type: None,
+        })
+        .await
+        .unwrap()
+        .value
+        .unwrap();
+    assert_eq!(decoded.account_type, "Person");
+    assert_eq!(
+        decoded.fields,
+        serde_json::json!({"name": "Alice", "age": "20"})
+    );
+
+    // Decoding with a layout that does not fit the data is rejected.
+    let res = setup
+        .api
+        .decode_compressed_account(DecodeCompressedAccountRequest {
+            hash: account.hash.clone(),
+            idl: serde_json::json!({
+                "accounts": [{
+                    "name": "Person",
+                    "type": {"kind": "struct", "fields": [{"name": "key", "type": "publicKey"}]}
+                }]
+            }),
+            account_type: Some("Person".to_string()),
+        })
+        .await;
+    assert!(res.is_err());
+
+    // Nested arrays of types that consume no data are rejected instead of decoding an exponential
+    // number of values.
+    let mut bomb = serde_json::json!({"defined": "Empty"});
+    for _ in 0..6 {
+        bomb = serde_json::json!({"array": [bomb, 16]});
+    }
+    let err = setup
+        .api
+        .decode_compressed_account(DecodeCompressedAccountRequest {
+            hash: account.hash.clone(),
+            idl: serde_json::json!({
+                "accounts": [{
+                    "name": "Person",
+                    "type": {"kind": "struct", "fields": [{"name": "bomb", "type": bomb}]}
+                }],
+                "types": [{"name": "Empty", "type": {"kind": "struct", "fields": []}}]
+            }),
+            account_type: Some("Person".to_string()),
+        })
+        .await
+        .unwrap_err();
+    assert!(matches!(err, PhotonApiError::ValidationError(_)));
+
+    let missing = setup
+        .api
+        .decode_compressed_account(DecodeCompressedAccountRequest {
+            hash: Hash::new_unique(),
+            idl,
+            account_type: None,
+        })
+        .await
+        .unwrap();
+    assert_eq!(missing.value, None);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_on_spend(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+    #[values(OnSpend::Retain, OnSpend::Delete)] on_spend: OnSpend,
+) {
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let owner = SerializablePubkey::new_unique();
+    let account = Account {
+        hash: Hash::new_unique(),
+        address: None,
+        data: None,
+        owner,
+        lamports: UnsignedInteger(1000),
+        tree: SerializablePubkey::new_unique(),
+        leaf_index: UnsignedInteger(0),
+        seq: UnsignedInteger(0),
+        slot_created: UnsignedInteger(0),
+        data_truncated: false,
+    };
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts.push(account.clone());
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let persist_config = PersistConfig {
+        on_spend,
+        ..Default::default()
+    };
+    let mut state_update = StateUpdate::new();
+    state_update.in_accounts.insert(account.hash.clone());
+    let txn = setup.db_conn.begin().await.unwrap();
+    persist_state_update(&txn, state_update, &persist_config)
+        .await
+        .unwrap();
+    txn.commit().await.unwrap();
+
+    let row = accounts::Entity::find()
+        .filter(accounts::Column::Hash.eq(account.hash.to_vec()))
+        .one(setup.db_conn.as_ref())
+        .await
+        .unwrap();
+    match on_spend {
+        OnSpend::Retain => assert!(row.unwrap().spent),
+        OnSpend::Delete => assert!(row.is_none()),
+    }
+    let balance = setup
+        .api
+        .get_compressed_balance_by_owner(GetCompressedBalanceByOwnerRequest { owner })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(balance.0, 0);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_oversized_account_data(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+    #[values(OversizedAccountData::Reject, OversizedAccountData::Truncate)]
+    oversized_account_data: OversizedAccountData,
+) {
+    use photon_indexer::ingester::persist::OversizedAccountData;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let max_account_data_bytes = 100;
+    let data = vec![7; 500];
+    let account = Account {
+        hash: Hash::new_unique(),
+        address: None,
+        data: Some(AccountData {
+            discriminator: UnsignedInteger(1),
+            data: Base64String(data.clone()),
+            data_hash: Hash::new_unique(),
+        }),
+        owner: SerializablePubkey::new_unique(),
+        lamports: UnsignedInteger(1000),
+        tree: SerializablePubkey::new_unique(),
+        leaf_index: UnsignedInteger(0),
+        seq: UnsignedInteger(0),
+        slot_created: UnsignedInteger(0),
+        data_truncated: false,
+    };
+    let persist_config = PersistConfig {
+        max_account_data_bytes: Some(max_account_data_bytes),
+        oversized_account_data,
+        ..Default::default()
+    };
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts.push(account.clone());
+    let txn = setup.db_conn.begin().await.unwrap();
+    persist_state_update(&txn, state_update, &persist_config)
+        .await
+        .unwrap();
+    txn.commit().await.unwrap();
+
+    let row = accounts::Entity::find()
+        .filter(accounts::Column::Hash.eq(account.hash.to_vec()))
+        .one(setup.db_conn.as_ref())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(row.data_truncated);
+    let expected_data = match oversized_account_data {
+        OversizedAccountData::Reject => None,
+        OversizedAccountData::Truncate => Some(data[..max_account_data_bytes as usize].to_vec()),
+    };
+    assert_eq!(row.data, expected_data);
+
+    let stored_account = setup
+        .api
+        .get_compressed_account(CompressedAccountRequest {
+            address: None,
+            hash: Some(account.hash.clone()),
+        })
+        .await
+        .unwrap()
+        .value
+        .unwrap();
+    assert_eq!(stored_account.hash, account.hash);
+    assert!(stored_account.data_truncated);
+    assert_eq!(stored_account.data.map(|data| data.data.0), expected_data);
+
+    let owner_accounts = setup
+        .api
+        .get_compressed_accounts_by_owner(GetCompressedAccountsByOwnerRequest {
+            owner: account.owner,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .value
+        .items;
+    assert_eq!(owner_accounts.len(), 1);
+    assert!(owner_accounts[0].data_truncated);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_compressed_account_proof_path(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::error::PhotonApiError;
+    use photon_indexer::api::method::utils::HashRequest;
+    use photon_indexer::ingester::persist::TREE_HEIGHT;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let leaf_index = 0b1011_0110;
+    let account = Account {
+        hash: Hash::new_unique(),
+        address: None,
+        data: None,
+        owner: SerializablePubkey::new_unique(),
+        lamports: UnsignedInteger(1000),
+        tree: SerializablePubkey::new_unique(),
+        leaf_index: UnsignedInteger(leaf_index),
+        seq: UnsignedInteger(0),
+        slot_created: UnsignedInteger(0),
+        data_truncated: false,
+    };
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts.push(account.clone());
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let request = HashRequest {
+        hash: account.hash.clone(),
+    };
+    let proof = setup
+        .api
+        .get_compressed_account_proof(request.clone())
+        .await
+        .unwrap()
+        .value;
+    let path = setup
+        .api
+        .get_compressed_account_proof_path(request)
+        .await
+        .unwrap()
+        .value;
+
+    // The proof holds one sibling per level below the root.
+    assert_eq!(proof.proof.len(), TREE_HEIGHT as usize - 1);
+    assert_eq!(path.leaf_index, leaf_index as u32);
+    assert_eq!(path.siblings, proof.proof);
+    assert_eq!(path.root, proof.root);
+    assert_eq!(path.path_directions.len(), path.siblings.len());
+    for (level, direction) in path.path_directions.iter().enumerate() {
+        assert_eq!(*direction, (leaf_index >> level) & 1 == 1);
+    }
+    let leaf_node_index = (1 << path.siblings.len()) + leaf_index;
+    for (level, sibling_index) in path.sibling_indices.iter().enumerate() {
+        assert_eq!(*sibling_index, (leaf_node_index >> level) ^ 1);
+    }
+
+    let result = setup
+        .api
+        .get_compressed_account_proof(HashRequest {
+            hash: Hash::new_unique(),
+        })
+        .await;
+    assert!(matches!(result, Err(PhotonApiError::RecordNotFound(_))));
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_check_schema_version(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::migration::check_schema_version;
+    use sea_orm::{ConnectionTrait, Statement};
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+    let db = setup.db_conn.as_ref();
+
+    check_schema_version(db).await.unwrap();
+
+    // Simulate a database that was migrated by a newer binary.
+    db.execute(Statement::from_string(
+        db_backend,
+        "INSERT INTO seaql_migrations (version, applied_at) VALUES ('m20991231_000001_init', 0)"
+            .to_string(),
+    ))
+    .await
+    .unwrap();
+    let err = check_schema_version(db).await.unwrap_err();
+    assert!(err.to_string().contains("m20991231_000001_init"));
+
+    // Simulate a database that is missing a migration the binary relies on.
+    db.execute(Statement::from_string(
+        db_backend,
+        "DELETE FROM seaql_migrations WHERE version IN \
+        ('m20991231_000001_init', 'm20241008_000006_init')"
+            .to_string(),
+    ))
+    .await
+    .unwrap();
+    let err = check_schema_version(db).await.unwrap_err();
+    assert!(err.to_string().contains("m20241008_000006_init"));
+}
+
+#[tokio::test]
+async fn test_fetch_ahead_window() {
+    use futures::{pin_mut, StreamExt};
+    use photon_indexer::ingester::fetchers::fetch_ahead;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let window = 3;
+    let number_of_batches = 20;
+    let produced = Arc::new(AtomicUsize::new(0));
+    let producer_count = produced.clone();
+    let block_stream = futures::stream::iter(0..number_of_batches).map(move |slot| {
+        producer_count.fetch_add(1, Ordering::SeqCst);
+        vec![BlockInfo {
+            metadata: BlockMetadata {
+                slot,
+                ..Default::default()
+            },
+            ..Default::default()
+        }]
+    });
+
+    let buffered_stream = fetch_ahead(block_stream, window);
+    pin_mut!(buffered_stream);
+    let mut consumed = 0;
+    while let Some(blocks) = buffered_stream.next().await {
+        assert_eq!(blocks[0].metadata.slot, consumed);
+        consumed += 1;
+        // Simulate slow persistence so that the producer can fill the buffer.
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let buffered = produced.load(Ordering::SeqCst) - consumed as usize;
+        // The producer holds one extra batch while it waits for space in the buffer.
+        assert!(buffered <= window + 1);
+        if consumed == 1 {
+            assert!(buffered >= window);
+        }
+    }
+    assert_eq!(consumed, number_of_batches);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_recently_spent_accounts(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::get_recently_spent_accounts::GetRecentlySpentAccountsRequest;
+    use photon_indexer::ingester::parser::state_update::{AccountTransaction, Transaction};
+    use solana_sdk::signature::Signature;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    for slot in 0..4 {
+        index_block(
+            &setup.db_conn,
+            &BlockInfo {
+                metadata: BlockMetadata {
+                    slot,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            &IngesterConfig::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    let tree = SerializablePubkey::new_unique();
+    let accounts = (0..3)
+        .map(|i| Account {
+            hash: Hash::new_unique(),
+            address: None,
+            data: None,
+            owner: SerializablePubkey::new_unique(),
+            lamports: UnsignedInteger(10),
+            tree,
+            leaf_index: UnsignedInteger(i),
+            seq: UnsignedInteger(i),
+            slot_created: UnsignedInteger(0),
+            data_truncated: false,
+        })
+        .collect::<Vec<_>>();
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts = accounts.clone();
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    // Spend account i in slot i + 1.
+    for (i, account) in accounts.iter().enumerate() {
+        let signature = Signature::new_unique();
+        let mut state_update = StateUpdate::new();
+        state_update.in_accounts.insert(account.hash.clone());
+        state_update.transactions.insert(Transaction {
+            signature,
+            slot: i as u64 + 1,
+            uses_compression: true,
+            error: None,
+        });
+        state_update
+            .account_transactions
+            .insert(AccountTransaction {
+                hash: account.hash.clone(),
+                signature,
+            });
+        persist_state_update_using_connection(&setup.db_conn, state_update)
+            .await
+            .unwrap();
+    }
+
+    let spent = setup
+        .api
+        .get_recently_spent_accounts(GetRecentlySpentAccountsRequest {
+            since_slot: UnsignedInteger(2),
+            cursor: None,
+            limit: None,
+        })
+        .await
+        .unwrap()
+        .value;
+    let spent_hashes = spent
+        .items
+        .iter()
+        .map(|item| item.hash.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        spent_hashes,
+        vec![accounts[1].hash.clone(), accounts[2].hash.clone()]
+    );
+    assert_eq!(spent.items[0].slot_spent.0, 2);
+    assert_eq!(spent.items[1].slot_spent.0, 3);
+    assert_eq!(spent.cursor, None);
+
+    let first_page = setup
+        .api
+        .get_recently_spent_accounts(GetRecentlySpentAccountsRequest {
+            since_slot: UnsignedInteger(0),
+            cursor: None,
+            limit: Some(Limit::new(2).unwrap()),
+        })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(first_page.items.len(), 2);
+    assert_eq!(first_page.items[0].hash, accounts[0].hash);
+    let second_page = setup
+        .api
+        .get_recently_spent_accounts(GetRecentlySpentAccountsRequest {
+            since_slot: UnsignedInteger(0),
+            cursor: first_page.cursor,
+            limit: Some(Limit::new(2).unwrap()),
+        })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(second_page.items.len(), 1);
+    assert_eq!(second_page.items[0].hash, accounts[2].hash);
+    assert_eq!(second_page.cursor, None);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_latest_compressed_accounts(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::get_latest_compressed_accounts::GetLatestCompressedAccountsRequest;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    // Two accounts per slot in slots 0 to 2.
+    let tree = SerializablePubkey::new_unique();
+    let accounts = (0..6)
+        .map(|i| Account {
+            hash: Hash::new_unique(),
+            address: None,
+            data: None,
+            owner: SerializablePubkey::new_unique(),
+            lamports: UnsignedInteger(10),
+            tree,
+            leaf_index: UnsignedInteger(i),
+            seq: UnsignedInteger(i),
+            slot_created: UnsignedInteger(i / 2),
+            data_truncated: false,
+        })
+        .collect::<Vec<_>>();
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts = accounts.clone();
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    // Spent accounts are excluded.
+    let mut state_update = StateUpdate::new();
+    state_update.in_accounts.insert(accounts[4].hash.clone());
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let latest = setup
+        .api
+        .get_latest_compressed_accounts(GetLatestCompressedAccountsRequest::default())
+        .await
+        .unwrap()
+        .value;
+    let expected_hashes = [5, 3, 2, 1, 0]
+        .iter()
+        .map(|i| accounts[*i].hash.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        latest
+            .items
+            .iter()
+            .map(|account| account.hash.clone())
+            .collect::<Vec<_>>(),
+        expected_hashes
+    );
+    assert_eq!(latest.cursor, None);
+
+    let mut paginated_hashes = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = setup
+            .api
+            .get_latest_compressed_accounts(GetLatestCompressedAccountsRequest {
+                cursor,
+                limit: Some(Limit::new(2).unwrap()),
+            })
+            .await
+            .unwrap()
+            .value;
+        paginated_hashes.extend(page.items.into_iter().map(|account| account.hash));
+        cursor = page.cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(paginated_hashes, expected_hashes);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_backfill_columns(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::ingester::parser::state_update::{AccountTransaction, Transaction};
+    use photon_indexer::migration::backfill_columns;
+    use sea_orm::sea_query::Expr;
+    use solana_sdk::signature::Signature;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    for slot in 0..4 {
+        index_block(
+            &setup.db_conn,
+            &BlockInfo {
+                metadata: BlockMetadata {
+                    slot,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            &IngesterConfig::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    let tree = SerializablePubkey::new_unique();
+    let accounts = (0..3)
+        .map(|i| Account {
+            hash: Hash::new_unique(),
+            address: None,
+            data: None,
+            owner: SerializablePubkey::new_unique(),
+            lamports: UnsignedInteger(10),
+            tree,
+            leaf_index: UnsignedInteger(i),
+            seq: UnsignedInteger(i),
+            slot_created: UnsignedInteger(0),
+            data_truncated: false,
+        })
+        .collect::<Vec<_>>();
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts = accounts.clone();
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    // Spend the first two accounts in slots 2 and 3 and leave the last one unspent.
+    for (i, account) in accounts.iter().take(2).enumerate() {
+        let signature = Signature::new_unique();
+        let mut state_update = StateUpdate::new();
+        state_update.in_accounts.insert(account.hash.clone());
+        state_update.transactions.insert(Transaction {
+            signature,
+            slot: i as u64 + 2,
+            uses_compression: true,
+            error: None,
+        });
+        state_update
+            .account_transactions
+            .insert(AccountTransaction {
+                hash: account.hash.clone(),
+                signature,
+            });
+        persist_state_update_using_connection(&setup.db_conn, state_update)
+            .await
+            .unwrap();
+    }
+
+    // Simulate rows that were indexed before the slot_spent column existed.
+    accounts::Entity::update_many()
+        .col_expr(
+            accounts::Column::SlotSpent,
+            Expr::value(Option::<i64>::None),
+        )
+        .exec(setup.db_conn.as_ref())
+        .await
+        .unwrap();
+
+    assert_eq!(backfill_columns(setup.db_conn.as_ref()).await.unwrap(), 2);
+
+    for (account, expected_slot_spent) in accounts.iter().zip([Some(2), Some(3), None]) {
+        let model = accounts::Entity::find()
+            .filter(accounts::Column::Hash.eq(account.hash.to_vec()))
+            .one(setup.db_conn.as_ref())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(model.slot_spent, expected_slot_spent);
+    }
+
+    // Rerunning the backfill is a no-op.
+    assert_eq!(backfill_columns(setup.db_conn.as_ref()).await.unwrap(), 0);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_signature_dedupe_window(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::dao::generated::transactions;
+    use photon_indexer::ingester::parser::state_update::Transaction;
+    use solana_sdk::signature::Signature;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let signature = Signature::new_unique();
+    let persist_config = PersistConfig {
+        signature_dedupe_window: Some(10),
+        ..Default::default()
+    };
+    // Slot 15 is within the window of the first sighting and is a duplicate. Slot 30 is outside
+    // of it, so the signature is recorded at its new slot.
+    for (slot, expected_slot) in [(5, 5), (15, 5), (30, 30)] {
+        let mut state_update = StateUpdate::new();
+        state_update.transactions.insert(Transaction {
+            signature,
+            slot,
+            uses_compression: true,
+            error: None,
+        });
+        let txn = setup.db_conn.begin().await.unwrap();
+        persist_state_update(&txn, state_update, &persist_config)
+            .await
+            .unwrap();
+        txn.commit().await.unwrap();
+
+        let transaction = transactions::Entity::find()
+            .filter(transactions::Column::Signature.eq(signature.as_ref().to_vec()))
+            .one(setup.db_conn.as_ref())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(transaction.slot, expected_slot);
+    }
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_export_accounts_csv(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::export::{
+        export, ExportFilter, ExportFormat, ExportTable, ACCOUNTS_CSV_HEADER,
+    };
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    let owner = SerializablePubkey::new_unique();
+    let tree = SerializablePubkey::new_unique();
+    let accounts = (0..4)
+        .map(|i| Account {
+            hash: Hash::new_unique(),
+            address: Some(SerializablePubkey::new_unique()),
+            data: Some(AccountData {
+                discriminator: UnsignedInteger(i),
+                data: Base64String(vec![i as u8; 3]),
+                data_hash: Hash::new_unique(),
+            }),
+            owner: match i {
+                3 => SerializablePubkey::new_unique(),
+                _ => owner,
+            },
+            lamports: UnsignedInteger(1000 + i),
+            tree,
+            leaf_index: UnsignedInteger(i),
+            seq: UnsignedInteger(i),
+            slot_created: UnsignedInteger(i),
+            data_truncated: false,
+        })
+        .collect::<Vec<_>>();
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts = accounts.clone();
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let filter = ExportFilter {
+        owner: Some(owner),
+        start_slot: Some(1),
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    let exported_rows = export(
+        setup.db_conn.as_ref(),
+        ExportFormat::Csv,
+        ExportTable::Accounts,
+        &filter,
+        &mut out,
+    )
+    .await
+    .unwrap();
+    assert_eq!(exported_rows, 2);
+
+    let mut expected_accounts = accounts[1..3].to_vec();
+    expected_accounts.sort_by_key(|account| account.hash.to_vec());
+    let mut expected_lines = vec![ACCOUNTS_CSV_HEADER.to_string()];
+    for account in expected_accounts {
+        let data = account.data.unwrap();
+        #[allow(deprecated)]
+        let encoded_data = base64::encode(&data.data.0);
+        expected_lines.push(format!(
+            "{},{},{},{},{},{},{},false,,{},{},{},{}",
+            account.hash,
+            account.address.unwrap(),
+            account.owner,
+            account.tree,
+            account.leaf_index.0,
+            account.seq.0,
+            account.slot_created.0,
+            account.lamports.0,
+            data.discriminator.0,
+            data.data_hash,
+            encoded_data,
+        ));
+    }
+    assert_eq!(
+        String::from_utf8(out).unwrap().lines().collect::<Vec<_>>(),
+        expected_lines
+    );
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_event_leaf_indices_propagate_to_proofs(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::utils::HashRequest;
+    use photon_indexer::ingester::parser::indexer_events::{
+        CompressedAccount, MerkleTreeSequenceNumber, OutputCompressedAccountWithPackedContext,
+        PublicTransactionEvent,
+    };
+    use photon_indexer::ingester::parser::parse_raw_event;
+    use photon_indexer::ingester::parser::state_update::{RawEvent, RawEventKind};
+    use solana_sdk::signature::Signature;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let tree = Pubkey::new_unique();
+    let hashes = [Hash::new_unique(), Hash::new_unique()];
+    let leaf_indices = vec![7, 42];
+    let output_account = OutputCompressedAccountWithPackedContext {
+        compressed_account: CompressedAccount {
+            owner: Pubkey::new_unique(),
+            lamports: 1000,
+            address: None,
+            data: None,
+        },
+        merkle_tree_index: 0,
+    };
+    let mut event = PublicTransactionEvent {
+        output_compressed_account_hashes: hashes.iter().map(|hash| hash.0).collect(),
+        output_compressed_accounts: vec![output_account.clone(), output_account],
+        output_leaf_indices: leaf_indices.clone(),
+        sequence_numbers: vec![MerkleTreeSequenceNumber {
+            pubkey: tree,
+            seq: 0,
+        }],
+        pubkey_array: vec![tree],
+        ..Default::default()
+    };
+    let raw_event = |event: &PublicTransactionEvent| RawEvent {
+        signature: Signature::new_unique(),
+        slot: 0,
+        index: 0,
+        kind: RawEventKind::PublicTransaction,
+        data: to_vec(event).unwrap(),
+    };
+
+    let state_update = parse_raw_event(&raw_event(&event), &ParserConfig::default()).unwrap();
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+    for (hash, leaf_index) in hashes.iter().zip(leaf_indices) {
+        let proof = setup
+            .api
+            .get_compressed_account_proof(HashRequest { hash: hash.clone() })
+            .await
+            .unwrap()
+            .value;
+        assert_eq!(proof.leafIndex, leaf_index);
+    }
+
+    // Accounts without a leaf index cannot be proven, so the event is skipped, or rejected with
+    // strict parsing.
+    event.output_leaf_indices.pop();
+    assert_eq!(
+        parse_raw_event(&raw_event(&event), &ParserConfig::default()).unwrap(),
+        StateUpdate::new()
+    );
+    let strict_config = ParserConfig {
+        strict_parsing: true,
+        ..Default::default()
+    };
+    assert!(parse_raw_event(&raw_event(&event), &strict_config).is_err());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[serial]
+async fn test_sqlite_concurrent_reads_and_writes() {
+    use photon_indexer::common::{setup_sqlite_pool, DEFAULT_DB_CONNECT_RETRIES};
+    use photon_indexer::migration::{MigractorWithCustomMigrations, MigratorTrait};
+    use sea_orm::{PaginatorTrait, SqlxSqliteConnector};
+    use std::sync::Arc;
+
+    let path = std::env::temp_dir().join("photon_test_sqlite_concurrent_reads_and_writes.db");
+    if path.exists() {
+        std::fs::remove_file(&path).unwrap();
+    }
+    std::fs::File::create(&path).unwrap();
+    let db_url = format!("sqlite:////{}", path.to_str().unwrap());
+    let db = Arc::new(SqlxSqliteConnector::from_sqlx_sqlite_pool(
+        setup_sqlite_pool(&db_url, 10, DEFAULT_DB_CONNECT_RETRIES)
+            .await
+            .unwrap(),
+    ));
+    MigractorWithCustomMigrations::fresh(db.as_ref())
+        .await
+        .unwrap();
+
+    let number_of_writers = 4;
+    let writes_per_writer = 25;
+    let writers = (0..number_of_writers).map(|_| {
+        let db = db.clone();
+        tokio::spawn(async move {
+            for _ in 0..writes_per_writer {
+                let mut state_update = StateUpdate::new();
+                state_update.out_accounts.push(Account {
+                    hash: Hash::new_unique(),
+                    address: None,
+                    data: None,
+                    owner: SerializablePubkey::new_unique(),
+                    lamports: UnsignedInteger(10),
+                    tree: SerializablePubkey::new_unique(),
+                    leaf_index: UnsignedInteger(0),
+                    seq: UnsignedInteger(0),
+                    slot_created: UnsignedInteger(0),
+                    data_truncated: false,
+                });
+                persist_state_update_using_connection(db.as_ref(), state_update)
+                    .await
+                    .unwrap();
+            }
+        })
+    });
+    let readers = (0..4).map(|_| {
+        let db = db.clone();
+        tokio::spawn(async move {
+            for _ in 0..50 {
+                accounts::Entity::find().count(db.as_ref()).await.unwrap();
+            }
+        })
+    });
+    for handle in writers.chain(readers).collect::<Vec<_>>() {
+        handle.await.unwrap();
+    }
+
+    let count = accounts::Entity::find().count(db.as_ref()).await.unwrap();
+    assert_eq!(count, number_of_writers * writes_per_writer);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_ingestion_status(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+    #[values(None, Some("http://127.0.0.1:10000".to_string()))] geyser_url: Option<String>,
+) {
+    use photon_indexer::ingester::fetchers::status::{
+        record_rpc_fetch_error, BlockSource, GrpcConnectionState,
+    };
+    use photon_indexer::ingester::fetchers::BlockStreamConfig;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let block_stream_config = BlockStreamConfig {
+        rpc_client: setup.client.clone(),
+        geyser_url: geyser_url.clone(),
+        grpc_x_token: None,
+        grpc_unreachable_policy: Default::default(),
+        max_concurrent_block_fetches: 1,
+        last_indexed_slot: 0,
+        fetch_ahead_window: 1,
+        commitment: Default::default(),
+        retry_backoff: Default::default(),
+    };
+    block_stream_config.init_ingestion_status();
+
+    let status = setup.api.get_ingestion_status().await.unwrap().value;
+    match geyser_url {
+        Some(_) => {
+            assert_eq!(status.source, BlockSource::Grpc);
+            assert_eq!(
+                status.grpc_connection_state,
+                GrpcConnectionState::Connecting
+            );
+        }
+        None => {
+            assert_eq!(status.source, BlockSource::Rpc);
+            assert_eq!(
+                status.grpc_connection_state,
+                GrpcConnectionState::NotConfigured
+            );
+        }
+    }
+    assert_eq!(status.last_block_received_at, None);
+    assert_eq!(status.recent_rpc_fetch_errors, UnsignedInteger(0));
+
+    record_rpc_fetch_error();
+    let status = setup.api.get_ingestion_status().await.unwrap().value;
+    assert_eq!(status.recent_rpc_fetch_errors, UnsignedInteger(1));
+    assert_eq!(status.recent_grpc_errors, UnsignedInteger(0));
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_compressed_account_rpc_fallback(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::api::PhotonApi;
+    use photon_indexer::api::method::get_compressed_account::AccountSource;
+    use solana_client::mock_sender::Mocks;
+    use solana_client::nonblocking::rpc_client::RpcClient;
+    use solana_client::rpc_request::RpcRequest;
+    use std::sync::Arc;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let account = Account {
+        hash: Hash::new_unique(),
+        owner: SerializablePubkey::new_unique(),
+        lamports: UnsignedInteger(1000),
+        tree: SerializablePubkey::new_unique(),
+        slot_created: UnsignedInteger(1),
+        data_truncated: false,
+        ..Default::default()
+    };
+    let request = CompressedAccountRequest {
+        hash: Some(account.hash.clone()),
+        address: None,
+    };
+
+    let response = setup
+        .api
+        .get_compressed_account(request.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.value, None);
+    assert_eq!(response.source, None);
+
+    let mut mocks = Mocks::new();
+    mocks.insert(
+        RpcRequest::Custom {
+            method: "getCompressedAccount",
+        },
+        serde_json::json!({
+            "context": { "slot": 1 },
+            "value": account,
+        }),
+    );
+    let rpc_client = Arc::new(RpcClient::new_mock_with_mocks(
+        "succeeds".to_string(),
+        mocks,
+    ));
+    let api = PhotonApi::new(setup.db_conn.clone(), rpc_client, setup.prover_url.clone())
+        .with_rpc_fallback(true);
+
+    let response = api.get_compressed_account(request).await.unwrap();
+    assert_eq!(response.value, Some(account));
+    assert_eq!(response.source, Some(AccountSource::Rpc));
+    assert_eq!(response.context.slot, 1);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_unknown_network_start_slot(
+    #[values(
+        UnknownNetworkStartSlot::FirstAvailableBlock,
+        UnknownNetworkStartSlot::Latest,
+        UnknownNetworkStartSlot::Genesis
+    )]
+    unknown_network_start_slot: UnknownNetworkStartSlot,
+) {
+    use photon_indexer::common::{get_network_start_slot, RetryBackoffConfig};
+    use solana_client::mock_sender::Mocks;
+    use solana_client::nonblocking::rpc_client::RpcClient;
+    use solana_client::rpc_request::RpcRequest;
+
+    let mut mocks = Mocks::new();
+    mocks.insert(
+        RpcRequest::GetGenesisHash,
+        serde_json::json!(Hash::new_unique().to_string()),
+    );
+    mocks.insert(RpcRequest::GetFirstAvailableBlock, serde_json::json!(1000));
+    mocks.insert(RpcRequest::GetSlot, serde_json::json!(5000));
+    let rpc_client = RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks);
+
+    let start_slot = get_network_start_slot(
+        &rpc_client,
+        unknown_network_start_slot,
+        RetryBackoffConfig::default(),
+    )
+    .await;
+    let expected_start_slot = match unknown_network_start_slot {
+        UnknownNetworkStartSlot::FirstAvailableBlock => 999,
+        UnknownNetworkStartSlot::Latest => 5000,
+        UnknownNetworkStartSlot::Genesis => 0,
+    };
+    assert_eq!(start_slot, expected_start_slot);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_state_update_sink(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::ingester::index_block_batch;
+    use photon_indexer::ingester::parser::indexer_events::{
+        CompressedAccount, MerkleTreeSequenceNumber, OutputCompressedAccountWithPackedContext,
+        PublicTransactionEvent,
+    };
+    use photon_indexer::ingester::parser::ACCOUNT_COMPRESSION_PROGRAM_ID;
+    use photon_indexer::ingester::sink::{MessageProducer, StateUpdateSink};
+    use photon_indexer::ingester::typedefs::block_info::{
+        Instruction, InstructionGroup, TransactionInfo,
+    };
+    use solana_sdk::signature::Signature;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    // Fails the first send to exercise retries.
+    #[derive(Default)]
+    struct RecordingProducer {
+        attempts: Mutex<usize>,
+        messages: Mutex<Vec<(String, serde_json::Value)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl MessageProducer for RecordingProducer {
+        async fn send(&self, key: &str, payload: &[u8]) -> Result<(), String> {
+            let mut attempts = self.attempts.lock().unwrap();
+            *attempts += 1;
+            if *attempts == 1 {
+                return Err("Broker unavailable".to_string());
+            }
+            self.messages
+                .lock()
+                .unwrap()
+                .push((key.to_string(), serde_json::from_slice(payload).unwrap()));
+            Ok(())
+        }
+    }
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    let tree = Pubkey::new_unique();
+    let hash = Hash::new_unique();
+    let event = PublicTransactionEvent {
+        output_compressed_account_hashes: vec![hash.0],
+        output_compressed_accounts: vec![OutputCompressedAccountWithPackedContext {
+            compressed_account: CompressedAccount {
+                owner: Pubkey::new_unique(),
+                lamports: 1000,
+                address: None,
+                data: None,
+            },
+            merkle_tree_index: 0,
+        }],
+        output_leaf_indices: vec![0],
+        sequence_numbers: vec![MerkleTreeSequenceNumber {
+            pubkey: tree,
+            seq: 0,
+        }],
+        pubkey_array: vec![tree],
+        ..Default::default()
+    };
+    let instruction = |program_id: Pubkey, data: Vec<u8>| Instruction {
+        program_id,
+        data,
+        accounts: vec![],
+    };
+    let block = BlockInfo {
+        metadata: BlockMetadata {
+            slot: 0,
+            ..Default::default()
+        },
+        transactions: vec![TransactionInfo {
+            instruction_groups: vec![InstructionGroup {
+                outer_instruction: instruction(ACCOUNT_COMPRESSION_PROGRAM_ID, vec![]),
+                inner_instructions: vec![
+                    instruction(
+                        Pubkey::from_str("11111111111111111111111111111111").unwrap(),
+                        vec![],
+                    ),
+                    instruction(
+                        Pubkey::from_str("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV").unwrap(),
+                        to_vec(&event).unwrap(),
+                    ),
+                ],
+            }],
+            signature: Signature::new_unique(),
+            error: None,
+        }],
+    };
+
+    let producer = Arc::new(RecordingProducer::default());
+    let config = IngesterConfig {
+        state_update_sink: Some(Arc::new(StateUpdateSink::new(producer.clone(), 10))),
+        ..Default::default()
+    };
+    index_block_batch(&setup.db_conn, &vec![block], &config)
+        .await
+        .unwrap();
+
+    for _ in 0..50 {
+        if !producer.messages.lock().unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let messages = producer.messages.lock().unwrap().clone();
+    assert_eq!(messages.len(), 1);
+    let (key, payload) = &messages[0];
+    assert_eq!(key, &hash.to_string());
+    assert_eq!(payload["type"], "accountCreated");
+    assert_eq!(payload["account"]["hash"], hash.to_string());
+    assert_eq!(payload["account"]["lamports"], 1000);
+    assert_eq!(*producer.attempts.lock().unwrap(), 2);
+}
+
+#[named]
+#[tokio::test]
+#[serial]
+async fn test_partitioned_state_trees() {
+    use photon_indexer::api::method::utils::HashRequest;
+    use photon_indexer::migration::{get_state_tree_partitions, repartition_state_trees};
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, DatabaseBackend::Postgres).await;
+
+    repartition_state_trees(setup.db_conn.as_ref(), Some(4))
+        .await
+        .unwrap();
+    assert_eq!(
+        get_state_tree_partitions(setup.db_conn.as_ref())
+            .await
+            .unwrap(),
+        Some(4)
+    );
+
+    index_empty_block(&setup.db_conn).await;
+
+    let trees = (0..3)
+        .map(|_| SerializablePubkey::new_unique())
+        .collect::<Vec<_>>();
+    let mut state_update = StateUpdate::new();
+    for tree in trees.iter() {
+        for leaf_index in 0..2 {
+            state_update.out_accounts.push(Account {
+                hash: Hash::new_unique(),
+                address: None,
+                data: None,
+                owner: SerializablePubkey::new_unique(),
+                lamports: UnsignedInteger(1000),
+                tree: *tree,
+                leaf_index: UnsignedInteger(leaf_index),
+                seq: UnsignedInteger(leaf_index),
+                slot_created: UnsignedInteger(0),
+                data_truncated: false,
+            });
+        }
+    }
+    persist_state_update_using_connection(&setup.db_conn, state_update.clone())
+        .await
+        .unwrap();
+
+    let mut proofs = vec![];
+    for account in state_update.out_accounts.iter() {
+        let proof = setup
+            .api
+            .get_compressed_account_proof(HashRequest {
+                hash: account.hash.clone(),
+            })
+            .await
+            .unwrap()
+            .value;
+        assert_eq!(proof.merkleTree, account.tree);
+        assert_eq!(proof.leafIndex, account.leaf_index.0 as u32);
+        proofs.push(proof);
+    }
+    for tree in trees.iter() {
+        validate_tree(&setup.db_conn, *tree).await;
+    }
+
+    // Nodes written while the table was partitioned survive reverting to a single table.
+    repartition_state_trees(setup.db_conn.as_ref(), None)
+        .await
+        .unwrap();
+    assert_eq!(
+        get_state_tree_partitions(setup.db_conn.as_ref())
+            .await
+            .unwrap(),
+        None
+    );
+    for (account, proof) in state_update.out_accounts.iter().zip(proofs) {
+        let reverted_proof = setup
+            .api
+            .get_compressed_account_proof(HashRequest {
+                hash: account.hash.clone(),
+            })
+            .await
+            .unwrap()
+            .value;
+        assert_eq!(reverted_proof, proof);
+    }
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_prewarm_trees(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::utils::HashRequest;
+    use photon_indexer::ingester::persist::persisted_state_tree::{prewarm_trees, PREWARM_LEVELS};
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let tree = SerializablePubkey::new_unique();
+    let accounts = (0..2)
+        .map(|leaf_index| Account {
+            hash: Hash::new_unique(),
+            address: None,
+            data: None,
+            owner: SerializablePubkey::new_unique(),
+            lamports: UnsignedInteger(1000),
+            tree,
+            leaf_index: UnsignedInteger(leaf_index),
+            seq: UnsignedInteger(leaf_index),
+            slot_created: UnsignedInteger(0),
+            data_truncated: false,
+        })
+        .collect::<Vec<_>>();
+
+    // The first account is indexed before startup and the second one after, so that both the
+    // initial warmup and the refresh after an update are exercised.
+    for (i, account) in accounts.iter().enumerate() {
+        if i == 1 {
+            prewarm_trees(setup.db_conn.as_ref(), &[tree])
+                .await
+                .unwrap();
+        }
+        let mut state_update = StateUpdate::new();
+        state_update.out_accounts.push(account.clone());
+        persist_state_update_using_connection(&setup.db_conn, state_update)
+            .await
+            .unwrap();
+    }
+
+    let mut proofs = vec![];
+    for account in accounts.iter() {
+        let proof = setup
+            .api
+            .get_compressed_account_proof(HashRequest {
+                hash: account.hash.clone(),
+            })
+            .await
+            .unwrap()
+            .value;
+        proofs.push(proof);
+    }
+
+    // Remove the cached nodes below the root from the database. Proofs can only still be served
+    // if these nodes come from memory.
+    state_trees::Entity::delete_many()
+        .filter(state_trees::Column::Tree.eq(tree.to_bytes_vec()))
+        .filter(state_trees::Column::NodeIdx.gt(1))
+        .filter(state_trees::Column::NodeIdx.lt(1_i64 << PREWARM_LEVELS))
+        .exec(setup.db_conn.as_ref())
+        .await
+        .unwrap();
+    for (account, proof) in accounts.iter().zip(proofs) {
+        let prewarmed_proof = setup
+            .api
+            .get_compressed_account_proof(HashRequest {
+                hash: account.hash.clone(),
+            })
+            .await
+            .unwrap()
+            .value;
+        assert_eq!(prewarmed_proof, proof);
+    }
+}
+
+#[tokio::test]
+async fn test_parse_compression_instruction_at_group_end() {
+    use photon_indexer::ingester::parser::indexer_events::{
+        CompressedAccount, MerkleTreeEvent, MerkleTreeSequenceNumber, NullifierEvent,
+        OutputCompressedAccountWithPackedContext, PublicTransactionEvent,
+    };
+    use photon_indexer::ingester::parser::{parse_transaction, ACCOUNT_COMPRESSION_PROGRAM_ID};
+    use photon_indexer::ingester::typedefs::block_info::{
+        Instruction, InstructionGroup, TransactionInfo,
+    };
+    use solana_sdk::signature::Signature;
+    use std::str::FromStr;
+
+    let system_program = Pubkey::from_str("11111111111111111111111111111111").unwrap();
+    let noop_program = Pubkey::from_str("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV").unwrap();
+    let instruction = |program_id: Pubkey, data: Vec<u8>| Instruction {
+        program_id,
+        data,
+        accounts: vec![],
+    };
+    let transaction = |inner_instructions: Vec<Instruction>| TransactionInfo {
+        instruction_groups: vec![InstructionGroup {
+            outer_instruction: instruction(Pubkey::new_unique(), vec![]),
+            inner_instructions,
+        }],
+        signature: Signature::new_unique(),
+        error: None,
+    };
+
+    let tree = Pubkey::new_unique();
+    let hash = Hash::new_unique();
+    let event = PublicTransactionEvent {
+        output_compressed_account_hashes: vec![hash.0],
+        output_compressed_accounts: vec![OutputCompressedAccountWithPackedContext {
+            compressed_account: CompressedAccount {
+                owner: Pubkey::new_unique(),
+                lamports: 1000,
+                address: None,
+                data: None,
+            },
+            merkle_tree_index: 0,
+        }],
+        output_leaf_indices: vec![0],
+        sequence_numbers: vec![MerkleTreeSequenceNumber {
+            pubkey: tree,
+            seq: 0,
+        }],
+        pubkey_array: vec![tree],
+        ..Default::default()
+    };
+    let nullifier_event = MerkleTreeEvent::V2(NullifierEvent {
+        id: tree.to_bytes(),
+        nullified_leaves_indices: vec![0],
+        seq: 1,
+    });
+
+    // The transaction event is emitted by the last two instructions of the group.
+    let state_update = parse_transaction(
+        &transaction(vec![
+            instruction(ACCOUNT_COMPRESSION_PROGRAM_ID, vec![]),
+            instruction(system_program, vec![]),
+            instruction(noop_program, to_vec(&event).unwrap()),
+        ]),
+        0,
+        &ParserConfig::default(),
+    )
+    .unwrap();
+    assert_eq!(state_update.out_accounts.len(), 1);
+    assert_eq!(state_update.out_accounts[0].hash, hash);
+
+    // The nullifier event is emitted by the last instruction of the group.
+    let state_update = parse_transaction(
+        &transaction(vec![
+            instruction(ACCOUNT_COMPRESSION_PROGRAM_ID, vec![]),
+            instruction(noop_program, to_vec(&nullifier_event).unwrap()),
+        ]),
+        0,
+        &ParserConfig::default(),
+    )
+    .unwrap();
+    assert_eq!(state_update.leaf_nullifications.len(), 1);
+
+    // A compression instruction that ends the group emits no event.
+    let state_update = parse_transaction(
+        &transaction(vec![instruction(ACCOUNT_COMPRESSION_PROGRAM_ID, vec![])]),
+        0,
+        &ParserConfig::default(),
+    )
+    .unwrap();
+    assert!(state_update.out_accounts.is_empty());
+    assert!(state_update.leaf_nullifications.is_empty());
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_compressed_accounts_by_owner_grouped_by_tree(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::get_compressed_accounts_by_owner_grouped_by_tree::GetCompressedAccountsByOwnerGroupedByTreeRequest;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let owner = SerializablePubkey::new_unique();
+    let mut trees = vec![
+        SerializablePubkey::new_unique(),
+        SerializablePubkey::new_unique(),
+    ];
+    trees.sort_by_key(|tree| tree.to_bytes_vec());
+    let mut state_update = StateUpdate::new();
+    for (tree, number_of_accounts) in trees.iter().zip([3, 2]) {
+        for leaf_index in 0..number_of_accounts {
+            state_update.out_accounts.push(Account {
+                hash: Hash::new_unique(),
+                address: None,
+                data: None,
+                owner,
+                lamports: UnsignedInteger(1000),
+                tree: *tree,
+                leaf_index: UnsignedInteger(leaf_index),
+                seq: UnsignedInteger(leaf_index),
+                slot_created: UnsignedInteger(0),
+                data_truncated: false,
+            });
+        }
+    }
+    // Accounts of other owners are not returned.
+    state_update.out_accounts.push(Account {
+        hash: Hash::new_unique(),
+        address: None,
+        data: None,
+        owner: SerializablePubkey::new_unique(),
+        lamports: UnsignedInteger(1000),
+        tree: trees[0],
+        leaf_index: UnsignedInteger(3),
+        seq: UnsignedInteger(3),
+        slot_created: UnsignedInteger(0),
+        data_truncated: false,
+    });
+    persist_state_update_using_connection(&setup.db_conn, state_update.clone())
+        .await
+        .unwrap();
+
+    let groups = setup
+        .api
+        .get_compressed_accounts_by_owner_grouped_by_tree(
+            GetCompressedAccountsByOwnerGroupedByTreeRequest {
+                owner,
+                cursor: None,
+                limit: None,
+            },
+        )
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(groups.cursor, None);
+    assert_eq!(
+        groups
+            .items
+            .iter()
+            .map(|group| group.tree)
+            .collect::<Vec<_>>(),
+        trees
+    );
+    for group in groups.items.iter() {
+        let mut expected_hashes = state_update
+            .out_accounts
+            .iter()
+            .filter(|account| account.owner == owner && account.tree == group.tree)
+            .map(|account| account.hash.clone())
+            .collect::<Vec<_>>();
+        expected_hashes.sort_by_key(|hash| hash.to_vec());
+        let hashes = group
+            .accounts
+            .iter()
+            .map(|account| account.hash.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(hashes, expected_hashes);
+    }
+
+    // Paginating splits the accounts of the first tree across two pages.
+    let mut cursor = None;
+    let mut paginated_hashes = vec![];
+    loop {
+        let page = setup
+            .api
+            .get_compressed_accounts_by_owner_grouped_by_tree(
+                GetCompressedAccountsByOwnerGroupedByTreeRequest {
+                    owner,
+                    cursor,
+                    limit: Some(Limit::new(2).unwrap()),
+                },
+            )
+            .await
+            .unwrap()
+            .value;
+        for group in page.items {
+            paginated_hashes.extend(group.accounts.into_iter().map(|account| account.hash));
+        }
+        cursor = page.cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    let hashes = groups
+        .items
+        .into_iter()
+        .flat_map(|group| group.accounts.into_iter().map(|account| account.hash))
+        .collect::<Vec<_>>();
+    assert_eq!(paginated_hashes, hashes);
+}
+
+// SQLite stores balances as 64-bit integers, so only Postgres can hold sums that overflow a u64.
+#[named]
+#[tokio::test]
+#[serial]
+async fn test_balance_overflow() {
+    use photon_indexer::api::error::PhotonApiError;
+    use photon_indexer::dao::generated::token_owner_balances;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, DatabaseBackend::Postgres).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let owner = SerializablePubkey::new_unique();
+    let mut state_update = StateUpdate::new();
+    for leaf_index in 0..2 {
+        state_update.out_accounts.push(Account {
+            hash: Hash::new_unique(),
+            address: None,
+            data: None,
+            owner,
+            lamports: UnsignedInteger(u64::MAX),
+            tree: SerializablePubkey::new_unique(),
+            leaf_index: UnsignedInteger(leaf_index),
+            seq: UnsignedInteger(0),
+            slot_created: UnsignedInteger(0),
+            data_truncated: false,
+        });
+    }
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let mint = SerializablePubkey::new_unique();
+    token_owner_balances::Entity::insert(token_owner_balances::ActiveModel {
+        owner: Set(owner.to_bytes_vec()),
+        mint: Set(mint.to_bytes_vec()),
+        amount: Set(Decimal::from(u64::MAX) * Decimal::from(2)),
+    })
+    .exec(setup.db_conn.as_ref())
+    .await
+    .unwrap();
+
+    let overflow =
+        PhotonApiError::BalanceOverflow((Decimal::from(u64::MAX) * Decimal::from(2)).to_string());
+    let result = setup
+        .api
+        .get_compressed_balance_by_owner(GetCompressedBalanceByOwnerRequest { owner })
+        .await;
+    assert_eq!(result.unwrap_err(), overflow);
+    let result = setup
+        .api
+        .get_compressed_token_balances_by_owner(GetCompressedTokenBalancesByOwnerRequest {
+            owner,
+            ..Default::default()
+        })
+        .await;
+    assert_eq!(result.unwrap_err(), overflow);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_export_prometheus_metrics(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::common::prometheus::write_prometheus_metrics;
+    use photon_indexer::common::setup_metrics;
+    use photon_indexer::ingester::index_block_batch;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    setup_metrics(None, true);
+    let blocks = (0..3)
+        .map(|slot| BlockInfo {
+            metadata: BlockMetadata {
+                slot,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+    index_block_batch(&setup.db_conn, &blocks, &IngesterConfig::default())
+        .await
+        .unwrap();
+
+    let path = std::env::temp_dir().join("photon_test_export_prometheus_metrics.prom");
+    write_prometheus_metrics(&path).unwrap();
+    let metrics = std::fs::read_to_string(&path).unwrap();
+    assert!(metrics.contains("# TYPE photon_blocks_indexed counter\n"));
+    assert!(metrics.contains("# TYPE photon_transactions_parsed counter\n"));
+    let blocks_indexed = metrics
+        .lines()
+        .find(|line| line.starts_with("photon_blocks_indexed{env="))
+        .and_then(|line| line.rsplit_once(' '))
+        .map(|(_, value)| value.parse::<u64>().unwrap())
+        .unwrap();
+    assert!(blocks_indexed >= 3);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_compressed_accounts_by_lamport_range(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::get_compressed_accounts_by_lamport_range::GetCompressedAccountsByLamportRangeRequest;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let owners = [
+        SerializablePubkey::new_unique(),
+        SerializablePubkey::new_unique(),
+    ];
+    let tree = SerializablePubkey::new_unique();
+    let mut state_update = StateUpdate::new();
+    for (i, lamports) in [0, 5, 10, 10, 50, 100, 1000].into_iter().enumerate() {
+        state_update.out_accounts.push(Account {
+            hash: Hash::new_unique(),
+            address: None,
+            data: None,
+            owner: owners[i % 2],
+            lamports: UnsignedInteger(lamports),
+            tree,
+            leaf_index: UnsignedInteger(i as u64),
+            seq: UnsignedInteger(i as u64),
+            slot_created: UnsignedInteger(0),
+            data_truncated: false,
+        });
+    }
+    persist_state_update_using_connection(&setup.db_conn, state_update.clone())
+        .await
+        .unwrap();
+
+    let expected_hashes = |owner: Option<SerializablePubkey>| {
+        let mut accounts = state_update
+            .out_accounts
+            .iter()
+            .filter(|account| (5..=100).contains(&account.lamports.0))
+            .filter(|account| owner.is_none() || owner == Some(account.owner))
+            .collect::<Vec<_>>();
+        accounts.sort_by_key(|account| (account.lamports.0, account.hash.to_vec()));
+        accounts
+            .into_iter()
+            .map(|account| account.hash.clone())
+            .collect::<Vec<_>>()
+    };
+    for owner in [None, Some(owners[0]), Some(owners[1])] {
+        // Paginate with a small limit to exercise the cursor, which includes accounts with equal
+        // lamports.
+        let mut cursor = None;
+        let mut hashes = vec![];
+        loop {
+            let page = setup
+                .api
+                .get_compressed_accounts_by_lamport_range(
+                    GetCompressedAccountsByLamportRangeRequest {
+                        owner,
+                        min_lamports: UnsignedInteger(5),
+                        max_lamports: UnsignedInteger(100),
+                        cursor,
+                        limit: Some(Limit::new(2).unwrap()),
+                    },
+                )
+                .await
+                .unwrap()
+                .value;
+            hashes.extend(page.items.into_iter().map(|account| account.hash));
+            cursor = page.cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(hashes, expected_hashes(owner));
+    }
+
+    let result = setup
+        .api
+        .get_compressed_accounts_by_lamport_range(GetCompressedAccountsByLamportRangeRequest {
+            min_lamports: UnsignedInteger(100),
+            max_lamports: UnsignedInteger(5),
+            ..Default::default()
+        })
+        .await;
+    assert!(result.is_err());
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_verify_proof(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::utils::HashRequest;
+    use photon_indexer::api::method::verify_proof::VerifyProofRequest;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let tree = SerializablePubkey::new_unique();
+    let accounts = (0..2)
+        .map(|leaf_index| Account {
+            hash: Hash::new_unique(),
+            address: None,
+            data: None,
+            owner: SerializablePubkey::new_unique(),
+            lamports: UnsignedInteger(1000),
+            tree,
+            leaf_index: UnsignedInteger(leaf_index),
+            seq: UnsignedInteger(leaf_index),
+            slot_created: UnsignedInteger(0),
+            data_truncated: false,
+        })
+        .collect::<Vec<_>>();
+
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts.push(accounts[0].clone());
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+    let proof = setup
+        .api
+        .get_compressed_account_proof(HashRequest {
+            hash: accounts[0].hash.clone(),
+        })
+        .await
+        .unwrap()
+        .value;
+    let request = VerifyProofRequest {
+        leaf: proof.hash.clone(),
+        siblings: proof.proof.clone(),
+        leaf_index: UnsignedInteger(proof.leafIndex as u64),
+        tree,
+    };
+
+    let verification = setup.api.verify_proof(request.clone()).await.unwrap().value;
+    assert!(verification.valid);
+    assert_eq!(verification.root, proof.root);
+    assert_eq!(verification.root_seq.0, proof.rootSeq);
+
+    // Appending another leaf changes the root, so the cached proof becomes stale.
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts.push(accounts[1].clone());
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+    let verification = setup.api.verify_proof(request).await.unwrap().value;
+    assert!(!verification.valid);
+    assert_ne!(verification.root, proof.root);
+    assert_eq!(verification.root_seq.0, 1);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_verify_persisted_proofs(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::ingester::persist::proof_verification::verify_persisted_proofs;
+    use sea_orm::sea_query::Expr;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    let tree = SerializablePubkey::new_unique();
+    let accounts = [0, 2, 4]
+        .into_iter()
+        .map(|leaf_index| Account {
+            hash: Hash::new_unique(),
+            address: None,
+            data: None,
+            owner: SerializablePubkey::new_unique(),
+            lamports: UnsignedInteger(10),
+            tree,
+            leaf_index: UnsignedInteger(leaf_index),
+            seq: UnsignedInteger(leaf_index),
+            slot_created: UnsignedInteger(0),
+            data_truncated: false,
+        })
+        .collect::<Vec<_>>();
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts = accounts.clone();
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let report = verify_persisted_proofs(setup.db_conn.as_ref(), 1.0)
+        .await
+        .unwrap();
+    assert_eq!(report.verified_accounts, 3);
+    assert!(report.inconsistencies.is_empty());
+
+    let report = verify_persisted_proofs(setup.db_conn.as_ref(), 0.0)
+        .await
+        .unwrap();
+    assert_eq!(report.verified_accounts, 0);
+
+    // Corrupt the parent of leaves 2 and 3, which is only part of the proof of the first account.
+    let corrupted_node_idx = (2_i64.pow(26) + 2) / 2;
+    state_trees::Entity::update_many()
+        .col_expr(
+            state_trees::Column::Hash,
+            Expr::value(Hash::new_unique().to_vec()),
+        )
+        .filter(state_trees::Column::Tree.eq(tree.to_bytes_vec()))
+        .filter(state_trees::Column::NodeIdx.eq(corrupted_node_idx))
+        .exec(setup.db_conn.as_ref())
+        .await
+        .unwrap();
+
+    let report = verify_persisted_proofs(setup.db_conn.as_ref(), 1.0)
+        .await
+        .unwrap();
+    assert_eq!(report.verified_accounts, 3);
+    assert_eq!(report.inconsistencies.len(), 1);
+    assert_eq!(report.inconsistencies[0].hash, accounts[0].hash);
+    assert_eq!(report.inconsistencies[0].leaf_index, 0);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_transaction_account_mapping(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::get_transaction_account_mapping::{
+        AccountMapping, GetTransactionAccountMappingRequest,
+    };
+    use photon_indexer::common::typedefs::serializable_signature::SerializableSignature;
+    use photon_indexer::ingester::parser::indexer_events::{
+        CompressedAccount, MerkleTreeSequenceNumber, OutputCompressedAccountWithPackedContext,
+        PublicTransactionEvent,
+    };
+    use photon_indexer::ingester::parser::parse_raw_events;
+    use photon_indexer::ingester::parser::state_update::{RawEvent, RawEventKind};
+    use solana_sdk::signature::Signature;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let tree = Pubkey::new_unique();
+    let address = Pubkey::new_unique().to_bytes();
+    let owner = Pubkey::new_unique();
+    let output_account =
+        |owner: Pubkey, address: Option<[u8; 32]>| OutputCompressedAccountWithPackedContext {
+            compressed_account: CompressedAccount {
+                owner,
+                lamports: 1000,
+                address,
+                data: None,
+            },
+            merkle_tree_index: 0,
+        };
+    let index_event = |slot: u64, event: PublicTransactionEvent| {
+        let signature = Signature::new_unique();
+        let raw_event = RawEvent {
+            signature,
+            slot,
+            index: 0,
+            kind: RawEventKind::PublicTransaction,
+            data: to_vec(&event).unwrap(),
+        };
+        (signature, parse_raw_events(vec![raw_event]).unwrap())
+    };
+
+    // The first transaction creates an account with an address and one without.
+    let (addressed_input, unaddressed_input) = (Hash::new_unique(), Hash::new_unique());
+    let (_, state_update) = index_event(
+        0,
+        PublicTransactionEvent {
+            output_compressed_account_hashes: vec![addressed_input.0, unaddressed_input.0],
+            output_compressed_accounts: vec![
+                output_account(owner, Some(address)),
+                output_account(owner, None),
+            ],
+            output_leaf_indices: vec![0, 1],
+            sequence_numbers: vec![MerkleTreeSequenceNumber {
+                pubkey: tree,
+                seq: 0,
+            }],
+            pubkey_array: vec![tree],
+            ..Default::default()
+        },
+    );
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    // The second transaction spends both accounts, recreates them and creates an unrelated one.
+    let (new_account, unaddressed_output, addressed_output) =
+        (Hash::new_unique(), Hash::new_unique(), Hash::new_unique());
+    let (signature, state_update) = index_event(
+        1,
+        PublicTransactionEvent {
+            input_compressed_account_hashes: vec![addressed_input.0, unaddressed_input.0],
+            output_compressed_account_hashes: vec![
+                new_account.0,
+                unaddressed_output.0,
+                addressed_output.0,
+            ],
+            output_compressed_accounts: vec![
+                output_account(Pubkey::new_unique(), None),
+                output_account(owner, None),
+                output_account(owner, Some(address)),
+            ],
+            output_leaf_indices: vec![2, 3, 4],
+            sequence_numbers: vec![MerkleTreeSequenceNumber {
+                pubkey: tree,
+                seq: 2,
+            }],
+            pubkey_array: vec![tree],
+            ..Default::default()
+        },
+    );
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let mut expected_mapping = vec![
+        AccountMapping {
+            input_hash: addressed_input,
+            output_hash: addressed_output,
+        },
+        AccountMapping {
+            input_hash: unaddressed_input,
+            output_hash: unaddressed_output,
+        },
+    ];
+    expected_mapping.sort_by_key(|mapping| mapping.input_hash.to_vec());
+    let mapping = setup
+        .api
+        .get_transaction_account_mapping(GetTransactionAccountMappingRequest {
+            signature: SerializableSignature(signature),
+        })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(mapping, expected_mapping);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_tree_size(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::get_tree_size::{GetTreeSizeRequest, TreeSize};
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let tree = SerializablePubkey::new_unique();
+    let mut state_update = StateUpdate::default();
+    state_update.out_accounts = [0, 1, 5]
+        .into_iter()
+        .map(|leaf_index| Account {
+            hash: Hash::new_unique(),
+            address: None,
+            data: None,
+            owner: SerializablePubkey::new_unique(),
+            lamports: UnsignedInteger(1000),
+            tree,
+            leaf_index: UnsignedInteger(leaf_index),
+            seq: UnsignedInteger(leaf_index + 1),
+            slot_created: UnsignedInteger(0),
+            data_truncated: false,
+        })
+        .collect();
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let tree_size = setup
+        .api
+        .get_tree_size(GetTreeSizeRequest { tree })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(
+        tree_size,
+        TreeSize {
+            leaves: UnsignedInteger(3),
+            capacity: Some(UnsignedInteger(1 << 26)),
+        }
+    );
+
+    // Trees that are not indexed have no leaves and an unknown capacity.
+    let tree_size = setup
+        .api
+        .get_tree_size(GetTreeSizeRequest {
+            tree: SerializablePubkey::new_unique(),
+        })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(tree_size, TreeSize::default());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_unreachable_grpc_endpoint() {
+    use futures::{pin_mut, StreamExt};
+    use photon_indexer::common::Commitment;
+    use photon_indexer::ingester::fetchers::grpc::{
+        get_grpc_block_stream, GrpcUnreachablePolicy, GRPC_STARTUP_CONNECT_ATTEMPTS,
+    };
+    use photon_indexer::ingester::fetchers::status::{
+        get_ingestion_status_snapshot, GrpcConnectionState,
+    };
+    use std::time::Duration;
+
+    // Nothing listens on this port, so every attempt to connect fails.
+    let endpoint = "http://127.0.0.1:1".to_string();
+
+    // Falling back to RPC ends the gRPC stream once the endpoint is considered unreachable.
+    let grpc_stream = get_grpc_block_stream(
+        endpoint.clone(),
+        None,
+        GrpcUnreachablePolicy::Fallback,
+        Commitment::Confirmed,
+    );
+    pin_mut!(grpc_stream);
+    let block = tokio::time::timeout(Duration::from_secs(60), grpc_stream.next())
+        .await
+        .unwrap();
+    assert!(block.is_none());
+    assert_eq!(
+        get_ingestion_status_snapshot().grpc_connection_state,
+        GrpcConnectionState::Disconnected
+    );
+
+    // Retrying keeps the gRPC stream alive past the attempts made on startup.
+    let grpc_stream = get_grpc_block_stream(
+        endpoint,
+        None,
+        GrpcUnreachablePolicy::Retry,
+        Commitment::Confirmed,
+    );
+    pin_mut!(grpc_stream);
+    let timeout = Duration::from_secs(GRPC_STARTUP_CONNECT_ATTEMPTS as u64 + 2);
+    assert!(tokio::time::timeout(timeout, grpc_stream.next())
+        .await
+        .is_err());
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_resolve_start_slot(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::common::RetryBackoffConfig;
+    use photon_indexer::ingester::indexer::resolve_last_indexed_slot;
+    use photon_indexer::snapshot::should_load_snapshot;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_block(
+        &setup.db_conn,
+        &BlockInfo {
+            metadata: BlockMetadata {
+                slot: 10,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        &IngesterConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    // Without a start slot, indexing resumes after the last slot in the database.
+    let last_indexed_slot = resolve_last_indexed_slot(
+        &setup.db_conn,
+        &setup.client,
+        None,
+        UnknownNetworkStartSlot::Genesis,
+        RetryBackoffConfig::default(),
+    )
+    .await;
+    assert_eq!(last_indexed_slot, 10);
+
+    // (snapshot end slot, snapshot offset, expected start slot)
+    let scenarios = [
+        (None, 0, 11),
+        (Some(100), 0, 101),
+        (Some(100), 50, 101),
+        (Some(12), 5, 11),
+        (Some(8), 0, 11),
+    ];
+    for (snapshot_end_slot, snapshot_offset, expected_start_slot) in scenarios {
+        let start_slot = snapshot_end_slot
+            .filter(|end_slot| should_load_snapshot(*end_slot, last_indexed_slot, snapshot_offset))
+            .unwrap_or(last_indexed_slot)
+            + 1;
+        assert_eq!(start_slot, expected_start_slot);
+    }
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_instruction_validity_proof(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+    use photon_indexer::api::method::get_instruction_validity_proof::get_instruction_validity_proof;
+    use photon_indexer::api::method::get_multiple_new_address_proofs::{
+        get_multiple_new_address_proofs, AddressList,
+    };
+    use photon_indexer::api::method::get_validity_proof::STATE_TREE_QUEUE_SIZE;
+    use std::convert::Infallible;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    // A prover that always returns the same uncompressed proof.
+    let prover_response =
+        r#"{"ar":["0x1","0x2"],"bs":[["0x3","0x4"],["0x5","0x6"]],"krs":["0x7","0x8"]}"#;
+    let make_service = make_service_fn(move |_| async move {
+        Ok::<_, Infallible>(service_fn(move |_: Request<Body>| async move {
+            Ok::<_, Infallible>(Response::new(Body::from(prover_response)))
+        }))
+    });
+    let prover = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+    let prover_url = format!("http://{}", prover.local_addr());
+    tokio::spawn(prover);
+
+    let addresses = vec![SerializablePubkey::new_unique()];
+    let proof = get_instruction_validity_proof(
+        &setup.db_conn,
+        &prover_url,
+        false,
+        GetValidityProofRequest {
+            hashes: vec![],
+            newAddresses: addresses.clone(),
+            newAddressesWithTrees: vec![],
+        },
+    )
+    .await
+    .unwrap()
+    .value;
+
+    // Only the x coordinates are kept, with the sign of the y coordinates in their highest bit.
+    let mut expected_a = [0; 32];
+    expected_a[0] = 1 << 7;
+    expected_a[31] = 1;
+    let mut expected_b = [0; 64];
+    expected_b[31] = 3;
+    expected_b[63] = 4;
+    let mut expected_c = [0; 32];
+    expected_c[31] = 7;
+    assert_eq!(proof.compressedProof.a, expected_a);
+    assert_eq!(proof.compressedProof.b, expected_b);
+    assert_eq!(proof.compressedProof.c, expected_c);
+
+    let address_proofs = get_multiple_new_address_proofs(&setup.db_conn, AddressList(addresses))
+        .await
+        .unwrap()
+        .value;
+    let expected_root_indices = address_proofs
+        .iter()
+        .map(|address_proof| (address_proof.rootSeq % STATE_TREE_QUEUE_SIZE) as u16)
+        .collect::<Vec<_>>();
+    assert_eq!(proof.rootIndices, expected_root_indices);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_slow_query_log(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::api::PhotonApi;
+    use sea_orm::{ConnectionTrait, Statement};
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    // Collects the logs emitted by the API.
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    let logs = CapturedLogs::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let api = PhotonApi::new(
+        setup.db_conn.clone(),
+        setup.client.clone(),
+        setup.prover_url.clone(),
+    )
+    .with_slow_query_threshold(Some(Duration::from_millis(100)));
+    let query = || {
+        setup.db_conn.execute(Statement::from_string(
+            setup.db_conn.get_database_backend(),
+            "SELECT 1".to_string(),
+        ))
+    };
+
+    api.log_if_slow("fastQuery", query()).await.unwrap();
+    api.log_if_slow("slowQuery", async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        query().await
+    })
+    .await
+    .unwrap();
+
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    assert!(logs.contains("Slow query: slowQuery took"));
+    assert!(!logs.contains("fastQuery"));
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_compression_signatures_for_account(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::utils::HashRequest;
+    use photon_indexer::common::typedefs::serializable_signature::SerializableSignature;
+    use photon_indexer::ingester::parser::state_update::{AccountTransaction, Transaction};
+    use solana_sdk::signature::Signature;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    for slot in 0..3 {
+        index_block(
+            &setup.db_conn,
+            &BlockInfo {
+                metadata: BlockMetadata {
+                    slot,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            &IngesterConfig::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    let account = Account {
+        hash: Hash::new_unique(),
+        address: None,
+        data: None,
+        owner: SerializablePubkey::new_unique(),
+        lamports: UnsignedInteger(10),
+        tree: SerializablePubkey::new_unique(),
+        leaf_index: UnsignedInteger(0),
+        seq: UnsignedInteger(0),
+        slot_created: UnsignedInteger(1),
+        data_truncated: false,
+    };
+    // The account is created in slot 1 and spent in slot 2.
+    let signatures = [Signature::new_unique(), Signature::new_unique()];
+    for (i, signature) in signatures.iter().enumerate() {
+        let mut state_update = StateUpdate::new();
+        match i {
+            0 => state_update.out_accounts.push(account.clone()),
+            _ => {
+                state_update.in_accounts.insert(account.hash.clone());
+            }
+        }
+        state_update.transactions.insert(Transaction {
+            signature: *signature,
+            slot: i as u64 + 1,
+            uses_compression: true,
+            error: None,
+        });
+        state_update
+            .account_transactions
+            .insert(AccountTransaction {
+                hash: account.hash.clone(),
+                signature: *signature,
+            });
+        persist_state_update_using_connection(&setup.db_conn, state_update)
+            .await
+            .unwrap();
+    }
+
+    let items = setup
+        .api
+        .get_compression_signatures_for_account(HashRequest {
+            hash: account.hash.clone(),
+        })
+        .await
+        .unwrap()
+        .value
+        .items;
+    let history = items
+        .iter()
+        .map(|item| (item.signature.clone(), item.slot.0))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        history,
+        vec![
+            (SerializableSignature(signatures[1]), 2),
+            (SerializableSignature(signatures[0]), 1),
+        ]
+    );
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_prune_signatures(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::dao::generated::transactions;
+    use photon_indexer::ingester::parser::state_update::{AccountTransaction, Transaction};
+    use photon_indexer::ingester::persist::signature_retention::prune_signatures;
+    use solana_sdk::signature::Signature;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    for slot in 0..=10 {
+        index_block(
+            &setup.db_conn,
+            &BlockInfo {
+                metadata: BlockMetadata {
+                    slot,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            &IngesterConfig::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    let tree = SerializablePubkey::new_unique();
+    let new_account = |leaf_index| Account {
+        hash: Hash::new_unique(),
+        address: None,
+        data: None,
+        owner: SerializablePubkey::new_unique(),
+        lamports: UnsignedInteger(10),
+        tree,
+        leaf_index: UnsignedInteger(leaf_index),
+        seq: UnsignedInteger(leaf_index),
+        slot_created: UnsignedInteger(1),
+        data_truncated: false,
+    };
+    let live_account = new_account(0);
+    let spent_account = new_account(1);
+    let old_signature = Signature::new_unique();
+    let live_account_signature = Signature::new_unique();
+    let spent_account_signatures = [Signature::new_unique(), Signature::new_unique()];
+    let recent_signature = Signature::new_unique();
+
+    let mut state_updates = Vec::new();
+    for (signature, slot) in [(old_signature, 1), (recent_signature, 9)] {
+        let mut state_update = StateUpdate::new();
+        state_update.transactions.insert(Transaction {
+            signature,
+            slot,
+            uses_compression: true,
+            error: None,
+        });
+        state_updates.push(state_update);
+    }
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts = vec![live_account.clone(), spent_account.clone()];
+    for (account, signature) in [
+        (&live_account, live_account_signature),
+        (&spent_account, spent_account_signatures[0]),
+    ] {
+        state_update.transactions.insert(Transaction {
+            signature,
+            slot: 1,
+            uses_compression: true,
+            error: None,
+        });
+        state_update
+            .account_transactions
+            .insert(AccountTransaction {
+                hash: account.hash.clone(),
+                signature,
+            });
+    }
+    state_updates.push(state_update);
+    let mut state_update = StateUpdate::new();
+    state_update.in_accounts.insert(spent_account.hash.clone());
+    state_update.transactions.insert(Transaction {
+        signature: spent_account_signatures[1],
+        slot: 2,
+        uses_compression: true,
+        error: None,
+    });
+    state_update
+        .account_transactions
+        .insert(AccountTransaction {
+            hash: spent_account.hash.clone(),
+            signature: spent_account_signatures[1],
+        });
+    state_updates.push(state_update);
+    for state_update in state_updates {
+        persist_state_update_using_connection(&setup.db_conn, state_update)
+            .await
+            .unwrap();
+    }
+
+    // Only the signatures older than slot 5 that do not belong to the unspent account are pruned.
+    assert_eq!(prune_signatures(&setup.db_conn, 5).await.unwrap(), 3);
+    let mut remaining_signatures = transactions::Entity::find()
+        .all(setup.db_conn.as_ref())
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|transaction| transaction.signature)
+        .collect::<Vec<_>>();
+    remaining_signatures.sort();
+    let mut expected_signatures = [live_account_signature, recent_signature]
+        .iter()
+        .map(|signature| signature.as_ref().to_vec())
+        .collect::<Vec<_>>();
+    expected_signatures.sort();
+    assert_eq!(remaining_signatures, expected_signatures);
+
+    // Pruning is idempotent.
+    assert_eq!(prune_signatures(&setup.db_conn, 5).await.unwrap(), 0);
+
+    // Signatures spread over more slots than fit in a batch are pruned over several batches.
+    let distant_signature = Signature::new_unique();
+    for slot in [200, 1_000] {
+        index_block(
+            &setup.db_conn,
+            &BlockInfo {
+                metadata: BlockMetadata {
+                    slot,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            &IngesterConfig::default(),
+        )
+        .await
+        .unwrap();
+    }
+    let mut state_update = StateUpdate::new();
+    state_update.transactions.insert(Transaction {
+        signature: distant_signature,
+        slot: 200,
+        uses_compression: true,
+        error: None,
+    });
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+    assert_eq!(prune_signatures(&setup.db_conn, 5).await.unwrap(), 2);
+    let remaining_signatures = transactions::Entity::find()
+        .all(setup.db_conn.as_ref())
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|transaction| transaction.signature)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        remaining_signatures,
+        vec![live_account_signature.as_ref().to_vec()]
+    );
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_latest_compression_signatures(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::utils::GetLatestSignaturesRequest;
+    use photon_indexer::common::typedefs::serializable_signature::SerializableSignature;
+    use photon_indexer::ingester::parser::state_update::Transaction;
+    use solana_sdk::signature::Signature;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    for slot in 0..4 {
+        index_block(
+            &setup.db_conn,
+            &BlockInfo {
+                metadata: BlockMetadata {
+                    slot,
+                    block_time: slot as i64 * 100,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            &IngesterConfig::default(),
+        )
+        .await
+        .unwrap();
+    }
+
+    // One compression transaction in each of slots 1 to 3, and a transaction without compression
+    // in the last slot.
+    let signatures = (0..3).map(|_| Signature::new_unique()).collect::<Vec<_>>();
+    for (i, signature) in signatures.iter().enumerate() {
+        let mut state_update = StateUpdate::new();
+        state_update.transactions.insert(Transaction {
+            signature: *signature,
+            slot: i as u64 + 1,
+            uses_compression: true,
+            error: None,
+        });
+        persist_state_update_using_connection(&setup.db_conn, state_update)
+            .await
+            .unwrap();
+    }
+    let mut state_update = StateUpdate::new();
+    state_update.transactions.insert(Transaction {
+        signature: Signature::new_unique(),
+        slot: 3,
+        uses_compression: false,
+        error: None,
+    });
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let expected_history = signatures
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(i, signature)| {
+            (
+                SerializableSignature(*signature),
+                i as u64 + 1,
+                i as u64 + 1,
+            )
+        })
+        .collect::<Vec<_>>();
+    let latest = setup
+        .api
+        .get_latest_compression_signatures(GetLatestSignaturesRequest::default())
+        .await
+        .unwrap()
+        .value;
+    let history = latest
+        .items
+        .iter()
+        .map(|item| (item.signature.clone(), item.slot.0, item.block_time.0 / 100))
+        .collect::<Vec<_>>();
+    assert_eq!(history, expected_history);
+    assert_eq!(latest.cursor, None);
+
+    let mut paginated_signatures = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = setup
+            .api
+            .get_latest_compression_signatures(GetLatestSignaturesRequest {
+                cursor,
+                limit: Some(Limit::new(1).unwrap()),
+            })
+            .await
+            .unwrap()
+            .value;
+        paginated_signatures.extend(page.items.into_iter().map(|item| item.signature));
+        cursor = page.cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(
+        paginated_signatures,
+        expected_history
+            .into_iter()
+            .map(|(signature, _, _)| signature)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_indexer_health_status(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+    use photon_indexer::api::error::PhotonApiError;
+    use photon_indexer::api::method::get_indexer_health::{
+        get_indexer_health, get_indexer_health_status, IndexerHealth, IndexerHealthStatus,
+    };
+    use solana_client::nonblocking::rpc_client::RpcClient;
+    use std::convert::Infallible;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_block(
+        &setup.db_conn,
+        &BlockInfo {
+            metadata: BlockMetadata {
+                slot: 90,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        &IngesterConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    // An RPC node that is always at slot 100.
+    let rpc_response = r#"{"jsonrpc":"2.0","result":100,"id":1}"#;
+    let make_service = make_service_fn(move |_| async move {
+        Ok::<_, Infallible>(service_fn(move |_: Request<Body>| async move {
+            Ok::<_, Infallible>(Response::new(Body::from(rpc_response)))
+        }))
+    });
+    let rpc = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+    let rpc_client = RpcClient::new(format!("http://{}", rpc.local_addr()));
+    tokio::spawn(rpc);
+
+    let health = get_indexer_health_status(&setup.db_conn, &rpc_client, 10)
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(
+        health,
+        IndexerHealth {
+            last_indexed_slot: UnsignedInteger(90),
+            rpc_slot: UnsignedInteger(100),
+            slot_lag: UnsignedInteger(10),
+            status: IndexerHealthStatus::Healthy,
+        }
+    );
+    assert_eq!(
+        get_indexer_health(&setup.db_conn, &rpc_client, 10)
+            .await
+            .unwrap(),
+        "ok"
+    );
+
+    let health = get_indexer_health_status(&setup.db_conn, &rpc_client, 9)
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(health.slot_lag, UnsignedInteger(10));
+    assert_eq!(health.status, IndexerHealthStatus::Behind);
+    assert!(matches!(
+        get_indexer_health(&setup.db_conn, &rpc_client, 9).await,
+        Err(PhotonApiError::StaleSlot(10))
+    ));
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_compressed_account_and_proof_by_address(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::error::PhotonApiError;
+    use photon_indexer::api::method::get_compressed_account_and_proof_by_address::GetCompressedAccountAndProofByAddressRequest;
+    use photon_indexer::api::method::utils::HashRequest;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let address = SerializablePubkey::new_unique();
+    let tree = SerializablePubkey::new_unique();
+    let accounts = (0..2)
+        .map(|i| Account {
+            hash: Hash::new_unique(),
+            address: Some(address),
+            data: None,
+            owner: SerializablePubkey::new_unique(),
+            lamports: UnsignedInteger(1000),
+            tree,
+            leaf_index: UnsignedInteger(i),
+            seq: UnsignedInteger(i),
+            slot_created: UnsignedInteger(0),
+            data_truncated: false,
+        })
+        .collect::<Vec<_>>();
+
+    // The second account replaces the first one at the same address.
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts.push(accounts[0].clone());
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+    let mut state_update = StateUpdate::new();
+    state_update.in_accounts.insert(accounts[0].hash.clone());
+    state_update.out_accounts.push(accounts[1].clone());
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let account_with_proof = setup
+        .api
+        .get_compressed_account_and_proof_by_address(GetCompressedAccountAndProofByAddressRequest {
+            address,
+        })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(account_with_proof.account, accounts[1]);
+    let proof = setup
+        .api
+        .get_compressed_account_proof(HashRequest {
+            hash: accounts[1].hash.clone(),
+        })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(account_with_proof.proof, proof);
+
+    let result = setup
+        .api
+        .get_compressed_account_and_proof_by_address(GetCompressedAccountAndProofByAddressRequest {
+            address: SerializablePubkey::new_unique(),
+        })
+        .await;
+    assert!(matches!(result, Err(PhotonApiError::RecordNotFound(_))));
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_proof_methods_without_prover(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::api::PhotonApi;
+    use photon_indexer::api::error::PhotonApiError;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let request = GetValidityProofRequest {
+        hashes: vec![],
+        newAddresses: vec![SerializablePubkey::new_unique()],
+        newAddressesWithTrees: vec![],
+    };
+
+    let api = PhotonApi::new(
+        setup.db_conn.clone(),
+        setup.client.clone(),
+        setup.prover_url.clone(),
+    )
+    .with_proofs_disabled(true);
+    let result = api.get_validity_proof(request.clone()).await;
+    assert!(matches!(
+        result,
+        Err(PhotonApiError::ProverNotConfigured(_))
+    ));
+    let result = api.get_instruction_validity_proof(request.clone()).await;
+    assert!(matches!(
+        result,
+        Err(PhotonApiError::ProverNotConfigured(_))
+    ));
+    // The rest of the API keeps working.
+    assert_eq!(api.get_indexer_slot().await.unwrap(), UnsignedInteger(0));
+
+    // Nothing listens on the discard port.
+    let api = PhotonApi::new(
+        setup.db_conn.clone(),
+        setup.client.clone(),
+        "http://127.0.0.1:9".to_string(),
+    );
+    let result = api.get_validity_proof(request).await;
+    assert!(matches!(
+        result,
+        Err(PhotonApiError::ProverNotConfigured(_))
+    ));
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_indexer_slot(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    // Nothing has been indexed yet.
+    assert_eq!(
+        setup.api.get_indexer_slot().await.unwrap(),
+        UnsignedInteger(0)
+    );
+
+    let slot = 123;
+    index_block(
+        &setup.db_conn,
+        &BlockInfo {
+            metadata: BlockMetadata {
+                slot,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        &IngesterConfig::default(),
+    )
+    .await
+    .unwrap();
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts.push(Account {
+        hash: Hash::new_unique(),
+        address: None,
+        data: None,
+        owner: SerializablePubkey::new_unique(),
+        lamports: UnsignedInteger(1000),
+        tree: SerializablePubkey::new_unique(),
+        leaf_index: UnsignedInteger(0),
+        seq: UnsignedInteger(0),
+        slot_created: UnsignedInteger(slot),
+        data_truncated: false,
+    });
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        setup.api.get_indexer_slot().await.unwrap(),
+        UnsignedInteger(slot)
+    );
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_persist_busy_block_transactions(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::dao::generated::transactions;
+    use photon_indexer::ingester::parser::state_update::Transaction;
+    use photon_indexer::ingester::persist::MAX_SQL_INSERTS;
+    use sea_orm::{PaginatorTrait, QueryOrder};
+    use solana_sdk::signature::Signature;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    // More transactions than fit into a single insert.
+    let slot = 10;
+    let mut signatures = (0..MAX_SQL_INSERTS + 1)
+        .map(|_| Signature::new_unique())
+        .collect::<Vec<_>>();
+    let block_transactions = signatures
+        .iter()
+        .map(|signature| Transaction {
+            signature: *signature,
+            slot,
+            uses_compression: true,
+            error: None,
+        })
+        .collect::<HashSet<_>>();
+
+    // Replaying the block, for instance after a restart, must not duplicate its signatures.
+    for _ in 0..2 {
+        let mut state_update = StateUpdate::new();
+        state_update.transactions = block_transactions.clone();
+        persist_state_update_using_connection(&setup.db_conn, state_update)
+            .await
+            .unwrap();
+
+        let count = transactions::Entity::find()
+            .count(setup.db_conn.as_ref())
+            .await
+            .unwrap();
+        assert_eq!(count, signatures.len() as u64);
+    }
+
+    let persisted_signatures = transactions::Entity::find()
+        .order_by_asc(transactions::Column::Signature)
+        .all(setup.db_conn.as_ref())
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|transaction| {
+            assert_eq!(transaction.slot, slot as i64);
+            Signature::try_from(transaction.signature).unwrap()
+        })
+        .collect::<Vec<_>>();
+    signatures.sort_by_key(|signature| signature.as_ref().to_vec());
+    assert_eq!(persisted_signatures, signatures);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_validity_proof_prover_request(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server, StatusCode};
+    use photon_indexer::api::error::PhotonApiError;
+    use photon_indexer::ingester::persist::TREE_HEIGHT;
+    use std::convert::Infallible;
+    use std::sync::{Arc, Mutex};
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let account = Account {
+        hash: Hash::new_unique(),
+        address: None,
+        data: None,
+        owner: SerializablePubkey::new_unique(),
+        lamports: UnsignedInteger(1000),
+        tree: SerializablePubkey::new_unique(),
+        leaf_index: UnsignedInteger(0),
+        seq: UnsignedInteger(0),
+        slot_created: UnsignedInteger(0),
+        data_truncated: false,
+    };
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts.push(account.clone());
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    // A prover that records the request bodies it receives and responds with `status` and the
+    // same uncompressed proof.
+    let prover_response =
+        r#"{"ar":["0x1","0x2"],"bs":[["0x3","0x4"],["0x5","0x6"]],"krs":["0x7","0x8"]}"#;
+    let start_prover = |status: StatusCode| {
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let recorded_bodies = bodies.clone();
+        let make_service = make_service_fn(move |_| {
+            let bodies = recorded_bodies.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let bodies = bodies.clone();
+                    async move {
+                        let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                        bodies.lock().unwrap().push(body.to_vec());
+                        let mut response = Response::new(Body::from(prover_response));
+                        *response.status_mut() = status;
+                        Ok::<_, Infallible>(response)
+                    }
+                }))
+            }
+        });
+        let prover = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+        let prover_url = format!("http://{}", prover.local_addr());
+        tokio::spawn(prover);
+        (prover_url, bodies)
+    };
+
+    let request = GetValidityProofRequest {
+        hashes: vec![account.hash.clone()],
+        newAddresses: vec![SerializablePubkey::new_unique()],
+        newAddressesWithTrees: vec![],
+    };
+
+    // All inputs are submitted to the prover in a single request.
+    let (prover_url, bodies) = start_prover(StatusCode::OK);
+    get_validity_proof(&setup.db_conn, &prover_url, false, request.clone())
+        .await
+        .unwrap();
+    let bodies = bodies.lock().unwrap().clone();
+    assert_eq!(bodies.len(), 1);
+    let body: serde_json::Value = serde_json::from_slice(&bodies[0]).unwrap();
+    let inclusion_inputs = body["input-compressed-accounts"].as_array().unwrap();
+    assert_eq!(inclusion_inputs.len(), 1);
+    assert_eq!(
+        inclusion_inputs[0]["leaf"],
+        format!("0x{}", hex::encode(account.hash.to_vec()))
+    );
+    assert_eq!(inclusion_inputs[0]["pathIndex"], 0);
+    assert_eq!(
+        inclusion_inputs[0]["pathElements"]
+            .as_array()
+            .unwrap()
+            .len(),
+        TREE_HEIGHT as usize - 1
+    );
+    let non_inclusion_inputs = body["new-addresses"].as_array().unwrap();
+    assert_eq!(non_inclusion_inputs.len(), 1);
+    assert_eq!(
+        non_inclusion_inputs[0]["value"],
+        format!("0x{}", hex::encode(request.newAddresses[0].to_bytes_vec()))
+    );
+    for key in [
+        "root",
+        "pathIndex",
+        "pathElements",
+        "leafLowerRangeValue",
+        "leafHigherRangeValue",
+        "nextIndex",
+    ] {
+        assert!(non_inclusion_inputs[0].get(key).is_some());
+    }
+
+    let (prover_url, _) = start_prover(StatusCode::INTERNAL_SERVER_ERROR);
+    let result = get_validity_proof(&setup.db_conn, &prover_url, false, request).await;
+    assert!(matches!(result, Err(PhotonApiError::ProverError(_))));
+}
+
+#[tokio::test]
+async fn test_max_scanned_instructions_per_group() {
+    use photon_indexer::ingester::parser::indexer_events::{
+        CompressedAccount, MerkleTreeSequenceNumber, OutputCompressedAccountWithPackedContext,
+        PublicTransactionEvent,
+    };
+    use photon_indexer::ingester::parser::{parse_transaction, ACCOUNT_COMPRESSION_PROGRAM_ID};
+    use photon_indexer::ingester::typedefs::block_info::{
+        Instruction, InstructionGroup, TransactionInfo,
+    };
+    use solana_sdk::signature::Signature;
+    use std::str::FromStr;
+
+    let system_program = Pubkey::from_str("11111111111111111111111111111111").unwrap();
+    let noop_program = Pubkey::from_str("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV").unwrap();
+    let instruction = |program_id: Pubkey, data: Vec<u8>| Instruction {
+        program_id,
+        data,
+        accounts: vec![],
+    };
+    let transaction = |inner_instructions: Vec<Instruction>| TransactionInfo {
+        instruction_groups: vec![InstructionGroup {
+            outer_instruction: instruction(Pubkey::new_unique(), vec![]),
+            inner_instructions,
+        }],
+        signature: Signature::new_unique(),
+        error: None,
+    };
+
+    let tree = Pubkey::new_unique();
+    let event = PublicTransactionEvent {
+        output_compressed_account_hashes: vec![Hash::new_unique().0],
+        output_compressed_accounts: vec![OutputCompressedAccountWithPackedContext {
+            compressed_account: CompressedAccount {
+                owner: Pubkey::new_unique(),
+                lamports: 1000,
+                address: None,
+                data: None,
+            },
+            merkle_tree_index: 0,
+        }],
+        output_leaf_indices: vec![0],
+        sequence_numbers: vec![MerkleTreeSequenceNumber {
+            pubkey: tree,
+            seq: 0,
+        }],
+        pubkey_array: vec![tree],
+        ..Default::default()
+    };
+    let event_instructions = vec![
+        instruction(ACCOUNT_COMPRESSION_PROGRAM_ID, vec![]),
+        instruction(system_program, vec![]),
+        instruction(noop_program, to_vec(&event).unwrap()),
+    ];
+    let filler_instructions = (0..10_000)
+        .map(|_| instruction(system_program, vec![]))
+        .collect::<Vec<_>>();
+    let event_first = transaction(
+        event_instructions
+            .iter()
+            .chain(filler_instructions.iter())
+            .cloned()
+            .collect(),
+    );
+    let event_last = transaction(
+        filler_instructions
+            .iter()
+            .chain(event_instructions.iter())
+            .cloned()
+            .collect(),
+    );
+
+    let capped_config = ParserConfig {
+        max_scanned_instructions_per_group: Some(100),
+        ..Default::default()
+    };
+    // Events within the cap are parsed no matter how many instructions follow them.
+    let state_update = parse_transaction(&event_first, 0, &capped_config).unwrap();
+    assert_eq!(state_update.out_accounts.len(), 1);
+    // Events after the cap are ignored.
+    let state_update = parse_transaction(&event_last, 0, &capped_config).unwrap();
+    assert!(state_update.out_accounts.is_empty());
+
+    let state_update = parse_transaction(&event_last, 0, &ParserConfig::default()).unwrap();
+    assert_eq!(state_update.out_accounts.len(), 1);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_tree_nodes(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::get_tree_nodes::GetTreeNodesRequest;
+    use photon_indexer::ingester::persist::TREE_HEIGHT;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    // Each update appends one leaf and updates the path from the leaf to the root.
+    let tree = SerializablePubkey::new_unique();
+    for i in 0..3 {
+        let mut state_update = StateUpdate::new();
+        state_update.out_accounts.push(Account {
+            hash: Hash::new_unique(),
+            address: None,
+            data: None,
+            owner: SerializablePubkey::new_unique(),
+            lamports: UnsignedInteger(1000),
+            tree,
+            leaf_index: UnsignedInteger(i),
+            seq: UnsignedInteger(i + 1),
+            slot_created: UnsignedInteger(0),
+            data_truncated: false,
+        });
+        persist_state_update_using_connection(&setup.db_conn, state_update)
+            .await
+            .unwrap();
+    }
+
+    let all_nodes = setup
+        .api
+        .get_tree_nodes(GetTreeNodesRequest {
+            tree,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(all_nodes.cursor, None);
+    // The first update added a full path, and the other two updated the paths of their leaves.
+    assert_eq!(all_nodes.items.len(), TREE_HEIGHT as usize + 3);
+    let expected_nodes = all_nodes
+        .items
+        .iter()
+        .filter(|node| node.seq.0 > 1)
+        .cloned()
+        .collect::<Vec<_>>();
+    assert!(expected_nodes.len() < all_nodes.items.len());
+
+    // Syncing from an intermediate sequence number only returns the nodes updated since.
+    let mut synced_nodes = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = setup
+            .api
+            .get_tree_nodes(GetTreeNodesRequest {
+                tree,
+                from_seq: Some(UnsignedInteger(1)),
+                cursor,
+                limit: Some(Limit::new(5).unwrap()),
+            })
+            .await
+            .unwrap()
+            .value;
+        synced_nodes.extend(page.items);
+        cursor = page.cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(synced_nodes, expected_nodes);
+    assert!(synced_nodes
+        .iter()
+        .all(|node| node.leaf_index != Some(UnsignedInteger(0))));
+
+    let nodes = setup
+        .api
+        .get_tree_nodes(GetTreeNodesRequest {
+            tree,
+            from_seq: Some(UnsignedInteger(3)),
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .value;
+    assert!(nodes.items.is_empty());
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_rollback_orphaned_slots(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use futures::stream;
+    use photon_indexer::dao::generated::{blocks, transactions};
+    use photon_indexer::ingester::indexer::index_block_stream;
+    use photon_indexer::ingester::parser::state_update::{
+        AccountTransaction, LeafNullification, Transaction,
+    };
+    use solana_sdk::signature::Signature;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let tree = SerializablePubkey::new_unique();
+    let owner = SerializablePubkey::new_unique();
+    let new_account = |leaf_index: u64, lamports: u64, slot: u64| Account {
+        hash: Hash::new_unique(),
+        address: None,
+        data: None,
+        owner,
+        lamports: UnsignedInteger(lamports),
+        tree,
+        leaf_index: UnsignedInteger(leaf_index),
+        seq: UnsignedInteger(leaf_index + 1),
+        slot_created: UnsignedInteger(slot),
+        data_truncated: false,
+    };
+
+    // An account is created in slot 1, and spent in slot 2 by a transaction that creates another
+    // account.
+    let account = new_account(0, 1000, 1);
+    let signature = Signature::new_unique();
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts.push(account.clone());
+    state_update.transactions.insert(Transaction {
+        signature,
+        slot: 1,
+        uses_compression: true,
+        error: None,
+    });
+    state_update
+        .account_transactions
+        .insert(AccountTransaction {
+            hash: account.hash.clone(),
+            signature,
+        });
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+    let root = state_trees::Entity::find()
+        .filter(state_trees::Column::Tree.eq(tree.to_bytes_vec()))
+        .filter(state_trees::Column::NodeIdx.eq(1))
+        .one(setup.db_conn.as_ref())
+        .await
+        .unwrap()
+        .unwrap()
+        .hash;
+
+    let orphaned_account = new_account(1, 500, 2);
+    let orphaned_signature = Signature::new_unique();
+    let mut state_update = StateUpdate::new();
+    state_update.in_accounts.insert(account.hash.clone());
+    state_update.out_accounts.push(orphaned_account.clone());
+    state_update.leaf_nullifications.insert(LeafNullification {
+        tree: tree.0,
+        leaf_index: 0,
+        seq: 3,
+        signature: orphaned_signature,
+    });
+    state_update.transactions.insert(Transaction {
+        signature: orphaned_signature,
+        slot: 2,
+        uses_compression: true,
+        error: None,
+    });
+    for hash in [&account.hash, &orphaned_account.hash] {
+        state_update
+            .account_transactions
+            .insert(AccountTransaction {
+                hash: hash.clone(),
+                signature: orphaned_signature,
+            });
+    }
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    // Slot 2 is orphaned by a block in slot 3 whose parent is slot 1.
+    let block = |slot, parent_slot| BlockInfo {
+        metadata: BlockMetadata {
+            slot,
+            parent_slot,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let block_stream = stream::iter(vec![vec![block(1, 0), block(2, 1)], vec![block(3, 1)]]);
+    index_block_stream(
+        block_stream,
+        setup.db_conn.clone(),
+        setup.client.clone(),
+        0,
+        Some(3),
+        &IngesterConfig::default(),
+    )
+    .await;
+
+    let mut slots = blocks::Entity::find()
+        .all(setup.db_conn.as_ref())
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|block| block.slot)
+        .collect::<Vec<_>>();
+    slots.sort();
+    assert_eq!(slots, vec![0, 1, 3]);
+    let signatures = transactions::Entity::find()
+        .all(setup.db_conn.as_ref())
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|transaction| transaction.signature)
+        .collect::<Vec<_>>();
+    assert_eq!(signatures, vec![signature.as_ref().to_vec()]);
+
+    // The account spent in the orphaned slot is unspent, and the account created in it is gone.
+    let accounts = accounts::Entity::find()
+        .all(setup.db_conn.as_ref())
+        .await
+        .unwrap();
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0].hash, account.hash.to_vec());
+    assert!(!accounts[0].spent);
+    assert_eq!(accounts[0].slot_spent, None);
+    let balance = setup
+        .api
+        .get_compressed_balance_by_owner(GetCompressedBalanceByOwnerRequest { owner })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(balance.0, 1000);
+
+    // The state tree is restored to its state before the orphaned slot.
+    let rolled_back_root = state_trees::Entity::find()
+        .filter(state_trees::Column::Tree.eq(tree.to_bytes_vec()))
+        .filter(state_trees::Column::NodeIdx.eq(1))
+        .one(setup.db_conn.as_ref())
+        .await
+        .unwrap()
+        .unwrap()
+        .hash;
+    assert_eq!(rolled_back_root, root);
+    let proof = get_multiple_compressed_leaf_proofs(
+        &setup.db_conn.begin().await.unwrap(),
+        vec![account.hash.clone()],
+    )
+    .await
+    .unwrap();
+    assert_eq!(proof[0].root.to_vec(), root);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_compressed_accounts_by_owner_order(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::get_compressed_accounts_by_owner::AccountOrder;
+    use std::cmp::Reverse;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let owner = SerializablePubkey::new_unique();
+    let tree = SerializablePubkey::new_unique();
+    // Two accounts share a slot and two share a balance, so ties are broken by hash.
+    let accounts = [(1, 300), (3, 100), (3, 200), (2, 300)]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (slot, lamports))| Account {
+            hash: Hash::new_unique(),
+            address: None,
+            data: None,
+            owner,
+            lamports: UnsignedInteger(lamports),
+            tree,
+            leaf_index: UnsignedInteger(i as u64),
+            seq: UnsignedInteger(i as u64),
+            slot_created: UnsignedInteger(slot),
+            data_truncated: false,
+        })
+        .collect::<Vec<_>>();
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts.extend(accounts.clone());
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    for order in [
+        AccountOrder::HashAsc,
+        AccountOrder::SlotDesc,
+        AccountOrder::LamportsDesc,
+    ] {
+        let mut expected_accounts = accounts.clone();
+        match order {
+            AccountOrder::HashAsc => expected_accounts.sort_by_key(|account| account.hash.to_vec()),
+            AccountOrder::SlotDesc => expected_accounts
+                .sort_by_key(|account| Reverse((account.slot_created.0, account.hash.to_vec()))),
+            AccountOrder::LamportsDesc => expected_accounts
+                .sort_by_key(|account| Reverse((account.lamports.0, account.hash.to_vec()))),
+        }
+
+        // Pages of a single account check that the cursor continues in the same order.
+        let mut paginated_accounts = Vec::new();
+        let mut cursor = None;
+        loop {
+            let res = setup
+                .api
+                .get_compressed_accounts_by_owner(GetCompressedAccountsByOwnerRequest {
+                    owner,
+                    cursor,
+                    limit: Some(Limit::new(1).unwrap()),
+                    orderBy: order,
+                    ..Default::default()
+                })
+                .await
+                .unwrap()
+                .value;
+            paginated_accounts.extend(res.items);
+            cursor = res.cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(paginated_accounts, expected_accounts, "{:?}", order);
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_parse_multiple_events_per_instruction_group() {
+    use photon_indexer::ingester::parser::indexer_events::{
+        CompressedAccount, MerkleTreeSequenceNumber, OutputCompressedAccountWithPackedContext,
+        PublicTransactionEvent,
+    };
+    use photon_indexer::ingester::parser::{parse_transaction, ACCOUNT_COMPRESSION_PROGRAM_ID};
+    use photon_indexer::ingester::typedefs::block_info::{
+        Instruction, InstructionGroup, TransactionInfo,
+    };
+    use solana_sdk::signature::Signature;
+    use std::str::FromStr;
+
+    let system_program = Pubkey::from_str("11111111111111111111111111111111").unwrap();
+    let noop_program = Pubkey::from_str("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV").unwrap();
+    let instruction = |program_id: Pubkey, data: Vec<u8>| Instruction {
+        program_id,
+        data,
+        accounts: vec![],
+    };
+    let event = |hash: Hash| {
+        let tree = Pubkey::new_unique();
+        PublicTransactionEvent {
+            output_compressed_account_hashes: vec![hash.0],
+            output_compressed_accounts: vec![OutputCompressedAccountWithPackedContext {
+                compressed_account: CompressedAccount {
+                    owner: Pubkey::new_unique(),
+                    lamports: 1000,
+                    address: None,
+                    data: None,
+                },
+                merkle_tree_index: 0,
+            }],
+            output_leaf_indices: vec![0],
+            sequence_numbers: vec![MerkleTreeSequenceNumber {
+                pubkey: tree,
+                seq: 0,
+            }],
+            pubkey_array: vec![tree],
+            ..Default::default()
+        }
+    };
+
+    // Batched CPIs emit an event after each account compression instruction of the group.
+    let hashes = vec![Hash::new_unique(), Hash::new_unique()];
+    let inner_instructions = hashes
+        .iter()
+        .flat_map(|hash| {
+            vec![
+                instruction(ACCOUNT_COMPRESSION_PROGRAM_ID, vec![]),
+                instruction(system_program, vec![]),
+                instruction(noop_program, to_vec(&event(hash.clone())).unwrap()),
+            ]
+        })
+        .collect();
+    let transaction = TransactionInfo {
+        instruction_groups: vec![InstructionGroup {
+            outer_instruction: instruction(Pubkey::new_unique(), vec![]),
+            inner_instructions,
+        }],
+        signature: Signature::new_unique(),
+        error: None,
+    };
+
+    let state_update = parse_transaction(&transaction, 0, &ParserConfig::default()).unwrap();
+    let out_accounts = state_update
+        .out_accounts
+        .iter()
+        .map(|account| account.hash.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(out_accounts, hashes);
+    assert_eq!(state_update.event_accounts.len(), 2);
+    assert_eq!(
+        state_update
+            .raw_events
+            .iter()
+            .map(|raw_event| raw_event.index)
+            .collect::<Vec<_>>(),
+        vec![0, 1]
+    );
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_recent_parse_errors(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::get_recent_parse_errors::GetRecentParseErrorsRequest;
+    use photon_indexer::common::typedefs::serializable_signature::SerializableSignature;
+    use photon_indexer::ingester::parser::{parse_transaction, ACCOUNT_COMPRESSION_PROGRAM_ID};
+    use photon_indexer::ingester::typedefs::block_info::{
+        Instruction, InstructionGroup, TransactionInfo,
+    };
+    use solana_sdk::signature::Signature;
+    use std::str::FromStr;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let system_program = Pubkey::from_str("11111111111111111111111111111111").unwrap();
+    let noop_program = Pubkey::from_str("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV").unwrap();
+    let instruction = |program_id: Pubkey, data: Vec<u8>| Instruction {
+        program_id,
+        data,
+        accounts: vec![],
+    };
+    // The noop instructions carry bytes that are not a valid event.
+    let malformed_transaction = |data_length: usize| TransactionInfo {
+        instruction_groups: vec![InstructionGroup {
+            outer_instruction: instruction(Pubkey::new_unique(), vec![]),
+            inner_instructions: vec![
+                instruction(ACCOUNT_COMPRESSION_PROGRAM_ID, vec![]),
+                instruction(system_program, vec![]),
+                instruction(noop_program, vec![1; data_length]),
+            ],
+        }],
+        signature: Signature::new_unique(),
+        error: None,
+    };
+    let transactions = [malformed_transaction(3), malformed_transaction(5)];
+    for (slot, transaction) in transactions.iter().enumerate() {
+        assert!(parse_transaction(transaction, slot as u64 + 1, &ParserConfig::default()).is_err());
+    }
+
+    let parse_errors = setup
+        .api
+        .get_recent_parse_errors(GetRecentParseErrorsRequest {
+            limit: Some(Limit::new(2).unwrap()),
+        })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(
+        parse_errors
+            .iter()
+            .map(|parse_error| (
+                parse_error.signature.clone(),
+                parse_error.slot.0,
+                parse_error.data_length.map(|length| length.0)
+            ))
+            .collect::<Vec<_>>(),
+        vec![
+            (SerializableSignature(transactions[1].signature), 2, Some(5)),
+            (SerializableSignature(transactions[0].signature), 1, Some(3)),
+        ]
+    );
+    assert!(parse_errors
+        .iter()
+        .all(|parse_error| !parse_error.error.is_empty()));
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_skip_malformed_event(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::ingester::parser::indexer_events::{
+        CompressedAccount, MerkleTreeSequenceNumber, OutputCompressedAccountWithPackedContext,
+        PublicTransactionEvent,
+    };
+    use photon_indexer::ingester::parser::{parse_transaction, ACCOUNT_COMPRESSION_PROGRAM_ID};
+    use photon_indexer::ingester::typedefs::block_info::{
+        Instruction, InstructionGroup, TransactionInfo,
+    };
+    use solana_sdk::signature::Signature;
+    use std::str::FromStr;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let system_program = Pubkey::from_str("11111111111111111111111111111111").unwrap();
+    let noop_program = Pubkey::from_str("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV").unwrap();
+    let instruction = |program_id: Pubkey, data: Vec<u8>| Instruction {
+        program_id,
+        data,
+        accounts: vec![],
+    };
+    let tree = Pubkey::new_unique();
+    let event = |hash: &Hash, leaf_index: u32| PublicTransactionEvent {
+        output_compressed_account_hashes: vec![hash.0],
+        output_compressed_accounts: vec![OutputCompressedAccountWithPackedContext {
+            compressed_account: CompressedAccount {
+                owner: Pubkey::new_unique(),
+                lamports: 1000,
+                address: None,
+                data: None,
+            },
+            merkle_tree_index: 0,
+        }],
+        output_leaf_indices: vec![leaf_index],
+        sequence_numbers: vec![MerkleTreeSequenceNumber {
+            pubkey: tree,
+            seq: leaf_index as u64,
+        }],
+        pubkey_array: vec![tree],
+        ..Default::default()
+    };
+    let (valid_hash, malformed_hash) = (Hash::new_unique(), Hash::new_unique());
+    // The output account of the malformed event has no leaf index.
+    let mut malformed_event = event(&malformed_hash, 1);
+    malformed_event.output_leaf_indices.clear();
+    let inner_instructions = [event(&valid_hash, 0), malformed_event]
+        .iter()
+        .flat_map(|event| {
+            vec![
+                instruction(ACCOUNT_COMPRESSION_PROGRAM_ID, vec![]),
+                instruction(system_program, vec![]),
+                instruction(noop_program, to_vec(event).unwrap()),
+            ]
+        })
+        .collect();
+    let transaction = TransactionInfo {
+        instruction_groups: vec![InstructionGroup {
+            outer_instruction: instruction(Pubkey::new_unique(), vec![]),
+            inner_instructions,
+        }],
+        signature: Signature::new_unique(),
+        error: None,
+    };
+
+    let state_update = parse_transaction(&transaction, 1, &ParserConfig::default()).unwrap();
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+    for (hash, persisted) in [(valid_hash, true), (malformed_hash, false)] {
+        let account = setup
+            .api
+            .get_compressed_account(CompressedAccountRequest {
+                address: None,
+                hash: Some(hash),
+            })
+            .await
+            .unwrap()
+            .value;
+        assert_eq!(account.is_some(), persisted);
+    }
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_create_and_spend_account_in_same_block(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::ingester::parser::indexer_events::{
+        CompressedAccount, MerkleTreeSequenceNumber, OutputCompressedAccountWithPackedContext,
+        PublicTransactionEvent,
+    };
+    use photon_indexer::ingester::parser::ACCOUNT_COMPRESSION_PROGRAM_ID;
+    use photon_indexer::ingester::typedefs::block_info::{
+        Instruction, InstructionGroup, TransactionInfo,
+    };
+    use solana_sdk::signature::Signature;
+    use std::str::FromStr;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let system_program = Pubkey::from_str("11111111111111111111111111111111").unwrap();
+    let noop_program = Pubkey::from_str("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV").unwrap();
+    let instruction = |program_id: Pubkey, data: Vec<u8>| Instruction {
+        program_id,
+        data,
+        accounts: vec![],
+    };
+    let transaction = |event: PublicTransactionEvent| TransactionInfo {
+        instruction_groups: vec![InstructionGroup {
+            outer_instruction: instruction(Pubkey::new_unique(), vec![]),
+            inner_instructions: vec![
+                instruction(ACCOUNT_COMPRESSION_PROGRAM_ID, vec![]),
+                instruction(system_program, vec![]),
+                instruction(noop_program, to_vec(&event).unwrap()),
+            ],
+        }],
+        signature: Signature::new_unique(),
+        error: None,
+    };
+    let tree = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let event =
+        |inputs: Vec<Hash>, output: &Hash, lamports: u64, leaf_index: u32| PublicTransactionEvent {
+            input_compressed_account_hashes: inputs.iter().map(|hash| hash.0).collect(),
+            output_compressed_account_hashes: vec![output.0],
+            output_compressed_accounts: vec![OutputCompressedAccountWithPackedContext {
+                compressed_account: CompressedAccount {
+                    owner,
+                    lamports,
+                    address: None,
+                    data: None,
+                },
+                merkle_tree_index: 0,
+            }],
+            output_leaf_indices: vec![leaf_index],
+            sequence_numbers: vec![MerkleTreeSequenceNumber {
+                pubkey: tree,
+                seq: leaf_index as u64,
+            }],
+            pubkey_array: vec![tree],
+            ..Default::default()
+        };
+
+    // The first transaction of the block creates an account that the second one spends.
+    let (created_and_spent, live) = (Hash::new_unique(), Hash::new_unique());
+    index_block(
+        &setup.db_conn,
+        &BlockInfo {
+            metadata: BlockMetadata {
+                slot: 1,
+                parent_slot: 0,
+                ..Default::default()
+            },
+            transactions: vec![
+                transaction(event(vec![], &created_and_spent, 1000, 0)),
+                transaction(event(vec![created_and_spent.clone()], &live, 600, 1)),
+            ],
+        },
+        &IngesterConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    let accounts = setup
+        .api
+        .get_compressed_accounts_by_owner(GetCompressedAccountsByOwnerRequest {
+            owner: SerializablePubkey(owner),
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(
+        accounts
+            .items
+            .iter()
+            .map(|account| account.hash.clone())
+            .collect::<Vec<_>>(),
+        vec![live]
+    );
+    let balance = setup
+        .api
+        .get_compressed_balance_by_owner(GetCompressedBalanceByOwnerRequest {
+            owner: SerializablePubkey(owner),
+        })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(balance.0, 600);
+}
+
+#[tokio::test]
+async fn test_retry_backoff() {
+    use photon_indexer::common::{RetryBackoff, RetryBackoffConfig};
+    use std::time::Duration;
+
+    let config = RetryBackoffConfig {
+        base_delay_ms: 100,
+        max_delay_ms: 1000,
+    };
+    let mut backoff = RetryBackoff::new(config);
+    for expected_delay_ms in [100, 200, 400, 800, 1000, 1000, 1000] {
+        let delay = backoff.next_delay();
+        assert!(delay >= Duration::from_millis(expected_delay_ms / 2));
+        assert!(delay <= Duration::from_millis(expected_delay_ms));
+    }
+
+    // A new request starts again from the base delay.
+    let delay = RetryBackoff::new(config).next_delay();
+    assert!(delay <= Duration::from_millis(100));
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_unspent_account_set(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::get_unspent_account_set::GetUnspentAccountSetRequest;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let trees = [
+        SerializablePubkey::new_unique(),
+        SerializablePubkey::new_unique(),
+    ];
+    let accounts = (0..8)
+        .map(|i| Account {
+            hash: Hash::new_unique(),
+            address: None,
+            data: None,
+            owner: SerializablePubkey::new_unique(),
+            lamports: UnsignedInteger(10),
+            tree: trees[i % 2],
+            leaf_index: UnsignedInteger(i as u64),
+            seq: UnsignedInteger(i as u64),
+            slot_created: UnsignedInteger(0),
+            data_truncated: false,
+        })
+        .collect::<Vec<_>>();
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts = accounts.clone();
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let spent = [1, 2, 5];
+    let mut state_update = StateUpdate::new();
+    for i in spent {
+        state_update.in_accounts.insert(accounts[i].hash.clone());
+    }
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let expected_hashes = |tree: Option<SerializablePubkey>| {
+        let mut hashes = accounts
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !spent.contains(i))
+            .filter(|(_, account)| tree.is_none() || tree == Some(account.tree))
+            .map(|(_, account)| account.hash.clone())
+            .collect::<Vec<_>>();
+        hashes.sort_by_key(|hash| hash.to_vec());
+        hashes
+    };
+    for tree in [None, Some(trees[0]), Some(trees[1])] {
+        let mut cursor = None;
+        let mut hashes = vec![];
+        loop {
+            let page = setup
+                .api
+                .get_unspent_account_set(GetUnspentAccountSetRequest {
+                    tree,
+                    cursor,
+                    limit: Some(Limit::new(2).unwrap()),
+                })
+                .await
+                .unwrap()
+                .value;
+            hashes.extend(page.items.into_iter().map(|account| account.hash));
+            cursor = page.cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(hashes, expected_hashes(tree));
+    }
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_finalized_accounts(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::get_compressed_accounts_by_owner::GetCompressedAccountsByOwnerRequest;
+    use photon_indexer::ingester::persist::finalization::mark_finalized_accounts;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let owner = SerializablePubkey::new_unique();
+    let account = Account {
+        hash: Hash::new_unique(),
+        address: None,
+        data: None,
+        owner,
+        lamports: UnsignedInteger(10),
+        tree: SerializablePubkey::new_unique(),
+        leaf_index: UnsignedInteger(0),
+        seq: UnsignedInteger(0),
+        slot_created: UnsignedInteger(5),
+        data_truncated: false,
+    };
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts.push(account.clone());
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let get_hashes = |finalized_only: bool| {
+        let api = &setup.api;
+        async move {
+            api.get_compressed_accounts_by_owner(GetCompressedAccountsByOwnerRequest {
+                owner,
+                finalizedOnly: finalized_only,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .value
+            .items
+            .into_iter()
+            .map(|account| account.hash)
+            .collect::<Vec<_>>()
+        }
+    };
+
+    // The account is only confirmed until its slot is finalized.
+    assert_eq!(get_hashes(false).await, vec![account.hash.clone()]);
+    assert_eq!(get_hashes(true).await, vec![]);
+    assert_eq!(mark_finalized_accounts(&setup.db_conn, 4).await.unwrap(), 0);
+    assert_eq!(get_hashes(true).await, vec![]);
+
+    assert_eq!(mark_finalized_accounts(&setup.db_conn, 5).await.unwrap(), 1);
+    assert_eq!(get_hashes(true).await, vec![account.hash.clone()]);
+    assert_eq!(get_hashes(false).await, vec![account.hash]);
+
+    // Accounts spread over more slots than fit in a batch are finalized over several batches.
+    let mut state_update = StateUpdate::new();
+    for slot in [10, 150, 1_000, 5_000] {
+        state_update.out_accounts.push(Account {
+            hash: Hash::new_unique(),
+            address: None,
+            data: None,
+            owner,
+            lamports: UnsignedInteger(10),
+            tree: SerializablePubkey::new_unique(),
+            leaf_index: UnsignedInteger(0),
+            seq: UnsignedInteger(0),
+            slot_created: UnsignedInteger(slot),
+            data_truncated: false,
+        });
+    }
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+    assert_eq!(
+        mark_finalized_accounts(&setup.db_conn, 1_000)
+            .await
+            .unwrap(),
+        3
+    );
+    assert_eq!(get_hashes(true).await.len(), 4);
+    assert_eq!(
+        mark_finalized_accounts(&setup.db_conn, 10_000)
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(get_hashes(true).await.len(), 5);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_account_insert_batches(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let persist_config = PersistConfig {
+        account_insert_batch_size: 2,
+        ..Default::default()
+    };
+    let owner = SerializablePubkey::new_unique();
+    let tree = SerializablePubkey::new_unique();
+    let accounts = (0..5)
+        .map(|i| Account {
+            hash: Hash::new_unique(),
+            address: None,
+            data: None,
+            owner,
+            lamports: UnsignedInteger(10),
+            tree,
+            leaf_index: UnsignedInteger(i),
+            seq: UnsignedInteger(i),
+            slot_created: UnsignedInteger(0),
+            data_truncated: false,
+        })
+        .collect::<Vec<_>>();
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts = accounts.clone();
+    state_update.in_accounts.insert(accounts[1].hash.clone());
+    let txn = setup.db_conn.begin().await.unwrap();
+    persist_state_update(&txn, state_update, &persist_config)
+        .await
+        .unwrap();
+    txn.commit().await.unwrap();
+
+    // Accounts delivered again conflict with the persisted ones, which keep their spent state.
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts = accounts.clone();
+    let txn = setup.db_conn.begin().await.unwrap();
+    persist_state_update(&txn, state_update, &persist_config)
+        .await
+        .unwrap();
+    txn.commit().await.unwrap();
+
+    let mut hashes = setup
+        .api
+        .get_compressed_accounts_by_owner(GetCompressedAccountsByOwnerRequest {
+            owner,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .value
+        .items
+        .into_iter()
+        .map(|account| account.hash)
+        .collect::<Vec<_>>();
+    hashes.sort_by_key(|hash| hash.to_vec());
+    let mut expected_hashes = accounts
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != 1)
+        .map(|(_, account)| account.hash.clone())
+        .collect::<Vec<_>>();
+    expected_hashes.sort_by_key(|hash| hash.to_vec());
+    assert_eq!(hashes, expected_hashes);
+
+    let balance = setup
+        .api
+        .get_compressed_balance_by_owner(GetCompressedBalanceByOwnerRequest { owner })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(balance.0, 40);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_compressed_account_summary(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::method::get_compressed_account_summary::AccountSummary;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let tree = SerializablePubkey::new_unique();
+    let account_with_data = Account {
+        hash: Hash::new_unique(),
+        address: Some(SerializablePubkey::new_unique()),
+        data: Some(AccountData {
+            discriminator: UnsignedInteger(1),
+            data: Base64String(vec![1; 500]),
+            data_hash: Hash::new_unique(),
+        }),
+        owner: SerializablePubkey::new_unique(),
+        lamports: UnsignedInteger(1000),
+        tree,
+        leaf_index: UnsignedInteger(0),
+        seq: UnsignedInteger(0),
+        slot_created: UnsignedInteger(0),
+        data_truncated: false,
+    };
+    let account_without_data = Account {
+        hash: Hash::new_unique(),
+        address: None,
+        data: None,
+        owner: SerializablePubkey::new_unique(),
+        lamports: UnsignedInteger(10),
+        tree,
+        leaf_index: UnsignedInteger(1),
+        seq: UnsignedInteger(1),
+        slot_created: UnsignedInteger(0),
+        data_truncated: false,
+    };
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts = vec![account_with_data.clone(), account_without_data.clone()];
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let expected_summary = AccountSummary {
+        hash: account_with_data.hash.clone(),
+        address: account_with_data.address,
+        owner: account_with_data.owner,
+        lamports: UnsignedInteger(1000),
+        data_size: UnsignedInteger(500),
+        data_hash: Some(account_with_data.data.unwrap().data_hash),
+    };
+    for request in [
+        CompressedAccountRequest {
+            hash: Some(account_with_data.hash.clone()),
+            address: None,
+        },
+        CompressedAccountRequest {
+            hash: None,
+            address: account_with_data.address,
+        },
+    ] {
+        let summary = setup
+            .api
+            .get_compressed_account_summary(request)
+            .await
+            .unwrap()
+            .value;
+        assert_eq!(summary, Some(expected_summary.clone()));
+    }
+
+    let summary = setup
+        .api
+        .get_compressed_account_summary(CompressedAccountRequest {
+            hash: Some(account_without_data.hash.clone()),
+            address: None,
+        })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(
+        summary,
+        Some(AccountSummary {
+            hash: account_without_data.hash,
+            address: None,
+            owner: account_without_data.owner,
+            lamports: UnsignedInteger(10),
+            data_size: UnsignedInteger(0),
+            data_hash: None,
+        })
+    );
+
+    let summary = setup
+        .api
+        .get_compressed_account_summary(CompressedAccountRequest {
+            hash: Some(Hash::new_unique()),
+            address: None,
+        })
+        .await
+        .unwrap()
+        .value;
+    assert_eq!(summary, None);
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_retry_when_database_is_out_of_space(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::dao::generated::blocks;
+    use photon_indexer::ingester::index_block_batch_with_infinite_retries;
+    use sea_orm::{ConnectionTrait, Statement};
+    use std::time::Duration;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+    let db = setup.db_conn.clone();
+
+    // Inserting blocks fails with the error the database returns when its disk is full.
+    let (create_statements, drop_statements) = match db_backend {
+        DatabaseBackend::Postgres => (
+            vec![
+                "CREATE OR REPLACE FUNCTION simulate_disk_full() RETURNS trigger AS $$ \
+                BEGIN RAISE EXCEPTION 'could not extend file: No space left on device' \
+                USING ERRCODE = 'disk_full'; END; $$ LANGUAGE plpgsql",
+                "CREATE TRIGGER simulate_disk_full BEFORE INSERT ON blocks \
+                FOR EACH ROW EXECUTE FUNCTION simulate_disk_full()",
+            ],
+            vec![
+                "DROP TRIGGER simulate_disk_full ON blocks",
+                "DROP FUNCTION simulate_disk_full",
+            ],
+        ),
+        _ => (
+            vec![
+                "CREATE TRIGGER simulate_disk_full BEFORE INSERT ON blocks \
+                BEGIN SELECT RAISE(ABORT, 'database or disk is full'); END",
+            ],
+            vec!["DROP TRIGGER simulate_disk_full"],
+        ),
+    };
+    for statement in create_statements {
+        db.execute(Statement::from_string(db_backend, statement.to_string()))
+            .await
+            .unwrap();
+    }
+
+    let config = IngesterConfig {
+        out_of_space_retry_base_delay: Duration::from_millis(10),
+        out_of_space_retry_max_delay: Duration::from_millis(50),
+        ..Default::default()
+    };
+    let block = BlockInfo {
+        metadata: BlockMetadata {
+            slot: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let indexing_db = db.clone();
+    let handle = tokio::spawn(async move {
+        index_block_batch_with_infinite_retries(indexing_db.as_ref(), vec![block], &config).await;
+    });
+
+    // The batch is retried instead of crashing the indexer, and is indexed once space is freed.
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(!handle.is_finished());
+    for statement in drop_statements {
+        db.execute(Statement::from_string(db_backend, statement.to_string()))
+            .await
+            .unwrap();
+    }
+    tokio::time::timeout(Duration::from_secs(10), handle)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let indexed_block = blocks::Entity::find_by_id(1i64)
+        .one(db.as_ref())
+        .await
+        .unwrap();
+    assert!(indexed_block.is_some());
+}
+
+#[tokio::test]
+async fn test_database_connection_retries() {
+    use photon_indexer::common::setup_sqlite_pool;
+
+    // The database cannot be opened, so connecting fails after the retry instead of panicking.
+    let result = setup_sqlite_pool("sqlite:////nonexistent-photon-directory/photon.db", 1, 1).await;
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_config_file_args() {
+    use clap::Parser;
+    use photon_indexer::common::config::parse_args_with_config_file_from;
+    use std::path::PathBuf;
+
+    #[derive(Parser, Debug)]
+    struct TestArgs {
+        #[arg(long)]
+        config: Option<PathBuf>,
+        #[arg(short, long, default_value_t = 8784)]
+        port: u16,
+        #[arg(long, default_value = "http://127.0.0.1:8899")]
+        rpc_url: String,
+        #[arg(long, default_value_t = false)]
+        disable_api: bool,
+        #[arg(long, value_delimiter = ',')]
+        prewarm_trees: Vec<String>,
+    }
+
+    let path = std::env::temp_dir().join("photon_test_config_file_args.toml");
+    std::fs::write(
+        &path,
+        concat!(
+            "port = 9000\n",
+            "rpc_url = \"http://rpc:8899\"\n",
+            "disable_api = true\n",
+            "prewarm_trees = [\"a\", \"b\"]\n",
+        ),
+    )
+    .unwrap();
+    let config = path.to_str().unwrap();
+
+    // Without a config file, the defaults are used.
+    let args: TestArgs = parse_args_with_config_file_from(["photon"]).unwrap();
+    assert_eq!(args.port, 8784);
+    assert!(!args.disable_api);
+
+    // The file takes precedence over the defaults.
+    let args: TestArgs = parse_args_with_config_file_from(["photon", "--config", config]).unwrap();
+    assert_eq!(args.config, Some(path.clone()));
+    assert_eq!(args.port, 9000);
+    assert_eq!(args.rpc_url, "http://rpc:8899");
+    assert!(args.disable_api);
+    assert_eq!(args.prewarm_trees, vec!["a", "b"]);
+
+    // Command line flags take precedence over the file.
+    let args: TestArgs = parse_args_with_config_file_from([
+        "photon",
+        "--config",
+        config,
+        "-p",
+        "9100",
+        "--prewarm-trees",
+        "c",
+    ])
+    .unwrap();
+    assert_eq!(args.port, 9100);
+    assert_eq!(args.rpc_url, "http://rpc:8899");
+    assert_eq!(args.prewarm_trees, vec!["c"]);
+
+    // Unknown keys are rejected with the list of valid keys.
+    std::fs::write(&path, "prot = 9000\n").unwrap();
+    let err = parse_args_with_config_file_from::<TestArgs>(["photon", "--config", config])
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("Unknown key `prot`"));
+    assert!(err.contains("port, rpc_url, disable_api, prewarm_trees"));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_rpc_failover() {
+    use photon_indexer::common::failover::FailoverRpcSender;
+    use photon_indexer::common::{Commitment, RetryBackoffConfig};
+    use photon_indexer::ingester::fetchers::poller::fetch_block_with_infinite_retries;
+    use solana_client::http_sender::HttpSender;
+    use solana_client::mock_sender::{MockSender, Mocks};
+    use solana_client::nonblocking::rpc_client::RpcClient;
+    use solana_client::rpc_client::RpcClientConfig;
+    use solana_client::rpc_request::RpcRequest;
+    use solana_client::rpc_sender::RpcSender;
+    use std::sync::Arc;
+
+    let blockhash = solana_sdk::hash::Hash::new_unique();
+    let mut mocks = Mocks::new();
+    mocks.insert(
+        RpcRequest::GetBlock,
+        serde_json::json!({
+            "previousBlockhash": solana_sdk::hash::Hash::new_unique().to_string(),
+            "blockhash": blockhash.to_string(),
+            "parentSlot": 9,
+            "transactions": [],
+            "blockTime": 1000,
+            "blockHeight": 10,
+        }),
+    );
+    // Nothing listens on the first endpoint, so requests to it fail with connection errors.
+    let senders: Vec<Box<dyn RpcSender + Send + Sync>> = vec![
+        Box::new(HttpSender::new("http://127.0.0.1:1")),
+        Box::new(MockSender::new_with_mocks("succeeds", mocks)),
+    ];
+    let rpc_client = Arc::new(RpcClient::new_sender(
+        FailoverRpcSender::new(senders),
+        RpcClientConfig::with_commitment(Commitment::Confirmed.into()),
+    ));
+
+    let block = fetch_block_with_infinite_retries(
+        rpc_client.clone(),
+        10,
+        Commitment::Confirmed,
+        RetryBackoffConfig::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(block.metadata.slot, 10);
+    assert_eq!(block.metadata.parent_slot, 9);
+    assert_eq!(block.metadata.blockhash, Hash::from(blockhash.to_bytes()));
+
+    // Requests keep going to the endpoint that answered.
+    assert_eq!(rpc_client.url(), "succeeds");
+}
+
+#[test]
+fn test_grpc_x_token_redaction() {
+    use photon_indexer::ingester::fetchers::grpc::GrpcXToken;
+    use std::str::FromStr;
+
+    let x_token = GrpcXToken::from_str("secret-token").unwrap();
+    assert!(!format!("{}", x_token).contains("secret-token"));
+    assert!(!format!("{:?}", x_token).contains("secret-token"));
+    assert!(!format!("{:#?}", Some(x_token)).contains("secret-token"));
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_batch_requests(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::api::PhotonApi;
+    use photon_indexer::api::rpc_server::run_server;
+    use serde_json::{json, Value};
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let account = Account {
+        hash: Hash::new_unique(),
+        owner: SerializablePubkey::new_unique(),
+        lamports: UnsignedInteger(1000),
+        tree: SerializablePubkey::new_unique(),
+        ..Default::default()
+    };
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts = vec![account.clone()];
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let max_batch_size = 2;
+    let server_handle = run_server(
+        PhotonApi::new(
+            setup.db_conn.clone(),
+            setup.client.clone(),
+            setup.prover_url.clone(),
+        ),
+        port,
+        false,
+        max_batch_size,
+        &["*".to_string()],
+        None,
+    )
+    .await
+    .unwrap();
+
+    let send_batch = |calls: Vec<Value>| async move {
+        let response = reqwest::Client::new()
+            .post(format!("http://127.0.0.1:{}", port))
+            .header("Content-Type", "application/json")
+            .body(Value::Array(calls).to_string())
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        serde_json::from_str::<Value>(&response).unwrap()
+    };
+    let call = |id: u64, method: &str| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": { "hash": account.hash.to_string() },
+        })
+    };
+
+    // The responses of a batch are returned in the order of the calls.
+    let responses = send_batch(vec![
+        call(2, "getCompressedAccount"),
+        call(1, "getCompressedBalance"),
+    ])
+    .await;
+    let responses = responses.as_array().unwrap();
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[0]["id"], 2);
+    assert_eq!(
+        responses[0]["result"]["value"]["hash"],
+        account.hash.to_string()
+    );
+    assert_eq!(responses[1]["id"], 1);
+    assert_eq!(responses[1]["result"]["value"], 1000);
+
+    // Batches larger than the maximum batch size are rejected.
+    let response = send_batch(
+        (0..max_batch_size as u64 + 1)
+            .map(|id| call(id, "getCompressedBalance"))
+            .collect(),
+    )
+    .await;
+    assert_eq!(response["error"]["code"], -32600);
+
+    server_handle.stop().unwrap();
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_subscribe_compressed_account(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use futures::{SinkExt, Stream, StreamExt};
+    use photon_indexer::api::api::PhotonApi;
+    use photon_indexer::api::rpc_server::run_server;
+    use photon_indexer::ingester::index_block_batch;
+    use photon_indexer::ingester::parser::indexer_events::{
+        CompressedAccount, MerkleTreeSequenceNumber, OutputCompressedAccountWithPackedContext,
+        PublicTransactionEvent,
+    };
+    use photon_indexer::ingester::parser::ACCOUNT_COMPRESSION_PROGRAM_ID;
+    use photon_indexer::ingester::typedefs::block_info::{
+        Instruction, InstructionGroup, TransactionInfo,
+    };
+    use serde_json::{json, Value};
+    use solana_sdk::signature::Signature;
+    use std::str::FromStr;
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::{Error, Message};
+
+    async fn next_json(ws: &mut (impl Stream<Item = Result<Message, Error>> + Unpin)) -> Value {
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(10), ws.next())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            if let Message::Text(text) = message {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let server_handle = run_server(
+        PhotonApi::new(
+            setup.db_conn.clone(),
+            setup.client.clone(),
+            setup.prover_url.clone(),
+        ),
+        port,
+        false,
+        1,
+        &["*".to_string()],
+        None,
+    )
+    .await
+    .unwrap();
+
+    let hash = Hash::new_unique();
+    let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://127.0.0.1:{}", port))
+        .await
+        .unwrap();
+    ws.send(Message::Text(
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "subscribeCompressedAccount",
+            "params": { "hash": hash.to_string() },
+        })
+        .to_string(),
+    ))
+    .await
+    .unwrap();
+    let response = next_json(&mut ws).await;
+    assert_eq!(response["id"], 1);
+    let subscription_id = response["result"].clone();
+
+    let tree = Pubkey::new_unique();
+    let event = PublicTransactionEvent {
+        output_compressed_account_hashes: vec![hash.0],
+        output_compressed_accounts: vec![OutputCompressedAccountWithPackedContext {
+            compressed_account: CompressedAccount {
+                owner: Pubkey::new_unique(),
+                lamports: 1000,
+                address: None,
+                data: None,
+            },
+            merkle_tree_index: 0,
+        }],
+        output_leaf_indices: vec![0],
+        sequence_numbers: vec![MerkleTreeSequenceNumber {
+            pubkey: tree,
+            seq: 0,
+        }],
+        pubkey_array: vec![tree],
+        ..Default::default()
+    };
+    let instruction = |program_id: Pubkey, data: Vec<u8>| Instruction {
+        program_id,
+        data,
+        accounts: vec![],
+    };
+    let block = BlockInfo {
+        metadata: BlockMetadata {
+            slot: 0,
+            ..Default::default()
+        },
+        transactions: vec![TransactionInfo {
+            instruction_groups: vec![InstructionGroup {
+                outer_instruction: instruction(ACCOUNT_COMPRESSION_PROGRAM_ID, vec![]),
+                inner_instructions: vec![
+                    instruction(
+                        Pubkey::from_str("11111111111111111111111111111111").unwrap(),
+                        vec![],
+                    ),
+                    instruction(
+                        Pubkey::from_str("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV").unwrap(),
+                        to_vec(&event).unwrap(),
+                    ),
+                ],
+            }],
+            signature: Signature::new_unique(),
+            error: None,
+        }],
+    };
+    index_block_batch(&setup.db_conn, &vec![block], &IngesterConfig::default())
+        .await
+        .unwrap();
+
+    let notification = next_json(&mut ws).await;
+    assert_eq!(notification["method"], "compressedAccountNotification");
+    assert_eq!(notification["params"]["subscription"], subscription_id);
+    assert_eq!(notification["params"]["result"]["type"], "accountCreated");
+    assert_eq!(
+        notification["params"]["result"]["account"]["hash"],
+        hash.to_string()
+    );
+    assert_eq!(
+        notification["params"]["result"]["account"]["lamports"],
+        1000
+    );
+
+    ws.send(Message::Text(
+        json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "unsubscribeCompressedAccount",
+            "params": [subscription_id],
+        })
+        .to_string(),
+    ))
+    .await
+    .unwrap();
+    let response = next_json(&mut ws).await;
+    assert_eq!(response["id"], 2);
+    assert_eq!(response["result"], true);
+
+    ws.close(None).await.unwrap();
+    server_handle.stop().unwrap();
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_cors_allowed_origins(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::api::PhotonApi;
+    use photon_indexer::api::rpc_server::run_server;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let server_handle = run_server(
+        PhotonApi::new(
+            setup.db_conn.clone(),
+            setup.client.clone(),
+            setup.prover_url.clone(),
+        ),
+        port,
+        false,
+        1,
+        &["https://app.example.com".to_string()],
+        None,
+    )
+    .await
+    .unwrap();
+
+    let preflight = |origin: &'static str| async move {
+        reqwest::Client::new()
+            .request(
+                reqwest::Method::OPTIONS,
+                format!("http://127.0.0.1:{}", port),
+            )
+            .header("Origin", origin)
+            .header("Access-Control-Request-Method", "POST")
+            .header("Access-Control-Request-Headers", "content-type")
+            .send()
+            .await
+            .unwrap()
+    };
+
+    let response = preflight("https://app.example.com").await;
+    let headers = response.headers();
+    assert_eq!(
+        headers["access-control-allow-origin"],
+        "https://app.example.com"
+    );
+    assert!(headers["access-control-allow-methods"]
+        .to_str()
+        .unwrap()
+        .contains("POST"));
+    assert_eq!(headers["access-control-allow-headers"], "content-type");
+
+    // Other origins are not allowed.
+    let response = preflight("https://other.example.com").await;
+    assert!(response
+        .headers()
+        .get("access-control-allow-origin")
+        .is_none());
+
+    server_handle.stop().unwrap();
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_tls_api_server(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use photon_indexer::api::api::PhotonApi;
+    use photon_indexer::api::rpc_server::run_server;
+    use photon_indexer::common::relative_project_path;
+    use photon_indexer::common::tls::load_tls_config;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    let cert_path = relative_project_path("tests/data/tls/cert.pem");
+    let key_path = relative_project_path("tests/data/tls/key.pem");
+
+    // Invalid files are reported before the server starts.
+    let missing_path = relative_project_path("tests/data/tls/missing.pem");
+    let err = load_tls_config(&cert_path, &missing_path).unwrap_err();
+    assert!(err.contains("Failed to open"), "{}", err);
+    let err = load_tls_config(&cert_path, &cert_path).unwrap_err();
+    assert!(err.contains("No private key found"), "{}", err);
+    let err = load_tls_config(&key_path, &key_path).unwrap_err();
+    assert!(err.contains("No certificate found"), "{}", err);
+
+    let tls_config = load_tls_config(&cert_path, &key_path).unwrap();
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let server_handle = run_server(
+        PhotonApi::new(
+            setup.db_conn.clone(),
+            setup.client.clone(),
+            setup.prover_url.clone(),
+        ),
+        port,
+        false,
+        1,
+        &["*".to_string()],
+        Some(tls_config),
+    )
+    .await
+    .unwrap();
+
+    // The test certificate is self-signed.
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    let response = client
+        .get(format!("https://127.0.0.1:{}/liveness", port))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    // Plain HTTP is not served anymore.
+    assert!(client
+        .get(format!("http://127.0.0.1:{}/liveness", port))
+        .send()
+        .await
+        .is_err());
+
+    server_handle.stop().unwrap();
+}
+
+#[named]
+#[rstest]
+#[tokio::test]
+#[serial]
+async fn test_get_validity_proof_rejects_stale_root_proof(
+    #[values(DatabaseBackend::Sqlite, DatabaseBackend::Postgres)] db_backend: DatabaseBackend,
+) {
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+    use photon_indexer::api::error::PhotonApiError;
+    use std::convert::Infallible;
+
+    let name = trim_test_name(function_name!());
+    let setup = setup(name, db_backend).await;
+
+    index_empty_block(&setup.db_conn).await;
+
+    let account = Account {
+        hash: Hash::new_unique(),
+        address: None,
+        data: None,
+        owner: SerializablePubkey::new_unique(),
+        lamports: UnsignedInteger(1000),
+        tree: SerializablePubkey::new_unique(),
+        leaf_index: UnsignedInteger(0),
+        seq: UnsignedInteger(0),
+        slot_created: UnsignedInteger(0),
+        data_truncated: false,
+    };
+    let mut state_update = StateUpdate::new();
+    state_update.out_accounts.push(account.clone());
+    persist_state_update_using_connection(&setup.db_conn, state_update)
+        .await
+        .unwrap();
+
+    // A prover that returns a proof that was not generated for the stored roots, like a proof
+    // for a stale root.
+    let prover_response =
+        r#"{"ar":["0x1","0x2"],"bs":[["0x3","0x4"],["0x5","0x6"]],"krs":["0x7","0x8"]}"#;
+    let make_service = make_service_fn(move |_| async move {
+        Ok::<_, Infallible>(service_fn(move |_: Request<Body>| async move {
+            Ok::<_, Infallible>(Response::new(Body::from(prover_response)))
+        }))
+    });
+    let prover = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+    let prover_url = format!("http://{}", prover.local_addr());
+    tokio::spawn(prover);
+
+    for request in [
+        GetValidityProofRequest {
+            hashes: vec![account.hash.clone()],
+            newAddresses: vec![],
+            newAddressesWithTrees: vec![],
+        },
+        GetValidityProofRequest {
+            hashes: vec![],
+            newAddresses: vec![SerializablePubkey::new_unique()],
+            newAddressesWithTrees: vec![],
+        },
+        GetValidityProofRequest {
+            hashes: vec![account.hash.clone()],
+            newAddresses: vec![SerializablePubkey::new_unique()],
+            newAddressesWithTrees: vec![],
+        },
+    ] {
+        // Without verification, the proof is returned as is.
+        get_validity_proof(&setup.db_conn, &prover_url, false, request.clone())
+            .await
+            .unwrap();
+
+        let err = get_validity_proof(&setup.db_conn, &prover_url, true, request)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, PhotonApiError::InvalidProof(_)));
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn test_account_change_subscribers_fall_behind_independently() {
+    use photon_indexer::ingester::sink::{
+        broadcast_account_changes, subscribe_to_account_changes, AccountChange, StateUpdateMessage,
+    };
+
+    let spent = |hash: &Hash| AccountChange {
+        hash: hash.clone(),
+        message: StateUpdateMessage::AccountSpent { hash: hash.clone() },
+    };
+    let (hash, busy_hash) = (Hash::new_unique(), Hash::new_unique());
+    let mut receiver = subscribe_to_account_changes(hash.clone());
+    let mut busy_receiver = subscribe_to_account_changes(busy_hash.clone());
+
+    // Changes of another account are not queued for the subscriber, so it doesn't fall behind
+    // when the subscriber of the other account does.
+    broadcast_account_changes((0..100).map(|_| spent(&busy_hash)).collect());
+    broadcast_account_changes(vec![spent(&hash)]);
+    assert_eq!(receiver.recv().await, Some(spent(&hash).message));
+
+    // The subscriber that fell behind receives the changes queued so far, and then its
+    // subscription ends.
+    let mut received_changes = 0;
+    while let Some(message) = busy_receiver.recv().await {
+        assert_eq!(message, spent(&busy_hash).message);
+        received_changes += 1;
+    }
+    assert!(received_changes > 0 && received_changes < 100);
+}
+
+#[tokio::test]
+async fn test_state_update_sink_publishes_concurrently_in_key_order() {
+    use photon_indexer::ingester::sink::{MessageProducer, SinkMessage, StateUpdateSink};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct SlowProducer {
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+        messages: Mutex<Vec<(String, Vec<u8>)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl MessageProducer for SlowProducer {
+        async fn send(&self, key: &str, payload: &[u8]) -> Result<(), String> {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            self.messages
+                .lock()
+                .unwrap()
+                .push((key.to_string(), payload.to_vec()));
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    let producer = Arc::new(SlowProducer::default());
+    let sink = StateUpdateSink::new(producer.clone(), 100);
+    let keys = (0..8).map(|key| key.to_string()).collect::<Vec<_>>();
+    let messages = (0..5u8)
+        .flat_map(|sequence| {
+            keys.iter().map(move |key| SinkMessage {
+                key: key.clone(),
+                payload: vec![sequence],
+            })
+        })
+        .collect::<Vec<_>>();
+    let number_of_messages = messages.len();
+    sink.publish(messages);
+
+    for _ in 0..100 {
+        if producer.messages.lock().unwrap().len() == number_of_messages {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let published = producer.messages.lock().unwrap().clone();
+    assert_eq!(published.len(), number_of_messages);
+    // Messages of different accounts are published concurrently, but the messages of each account
+    // are published in order.
+    assert!(producer.max_in_flight.load(Ordering::SeqCst) > 1);
+    for key in keys {
+        let sequences = published
+            .iter()
+            .filter(|(published_key, _)| *published_key == key)
+            .map(|(_, payload)| payload[0])
+            .collect::<Vec<_>>();
+        assert_eq!(sequences, vec![0, 1, 2, 3, 4]);
+    }
+}
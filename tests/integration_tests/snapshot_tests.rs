@@ -6,7 +6,8 @@ use photon_indexer::ingester::typedefs::block_info::{BlockInfo, BlockMetadata};
 use photon_indexer::snapshot::{
     create_snapshot_from_byte_stream, get_r2_bucket, get_snapshot_files_with_metadata,
     load_block_stream_from_directory_adapter, load_byte_stream_from_directory_adapter,
-    update_snapshot_helper, R2BucketArgs, R2DirectoryAdapter,
+    load_chunked_byte_stream_from_directory_adapter, update_snapshot_helper, R2BucketArgs,
+    R2DirectoryAdapter, DEFAULT_MAX_PENDING_SNAPSHOT_WRITES,
 };
 use s3::creds::Credentials;
 use s3::Region;
@@ -90,7 +91,15 @@ async fn test_basic_snapshotting() {
             directory_adapter.delete_file(file.file).await.unwrap();
         }
 
-        update_snapshot_helper(directory_adapter.clone(), blocks_stream, 0, 2, 4).await;
+        update_snapshot_helper(
+            directory_adapter.clone(),
+            blocks_stream,
+            0,
+            2,
+            4,
+            DEFAULT_MAX_PENDING_SNAPSHOT_WRITES,
+        )
+        .await;
         let snapshot_blocks =
             load_block_stream_from_directory_adapter(directory_adapter.clone()).await;
         let snapshot_blocks: Vec<Vec<BlockInfo>> = snapshot_blocks.collect().await;
@@ -114,3 +123,979 @@ async fn test_basic_snapshotting() {
         assert_eq!(snapshot_blocks_v2, blocks);
     }
 }
+
+#[tokio::test]
+async fn test_chunked_snapshot_byte_stream() {
+    use futures::StreamExt;
+    use std::env::temp_dir;
+
+    let snapshot_dir = temp_dir().join("chunked_snapshots");
+    let directory_adapter = Arc::new(photon_indexer::snapshot::DirectoryAdapter::new(
+        Some(photon_indexer::snapshot::FileSystemDirectoryApapter {
+            snapshot_dir: snapshot_dir.to_str().unwrap().to_string(),
+        }),
+        None,
+    ));
+    let snapshot_files = get_snapshot_files_with_metadata(directory_adapter.as_ref())
+        .await
+        .unwrap();
+    for file in snapshot_files {
+        directory_adapter.delete_file(file.file).await.unwrap();
+    }
+
+    let blocks: Vec<BlockInfo> = (0..30)
+        .map(|i| BlockInfo {
+            metadata: BlockMetadata {
+                slot: i,
+                parent_slot: if i == 0 { 0 } else { i - 1 },
+                block_time: 0,
+                blockhash: Hash::default(),
+                parent_blockhash: Hash::default(),
+                block_height: i,
+            },
+            transactions: vec![],
+        })
+        .collect();
+    update_snapshot_helper(
+        directory_adapter.clone(),
+        stream::iter(vec![blocks.clone()]),
+        0,
+        2,
+        4,
+        DEFAULT_MAX_PENDING_SNAPSHOT_WRITES,
+    )
+    .await;
+
+    let byte_stream = load_byte_stream_from_directory_adapter(directory_adapter.clone()).await;
+    let bytes: Vec<u8> = byte_stream
+        .map(|bytes| bytes.unwrap().to_vec())
+        .concat()
+        .await;
+
+    let chunk_size = 64;
+    let chunks: Vec<_> =
+        load_chunked_byte_stream_from_directory_adapter(directory_adapter.clone(), chunk_size)
+            .await
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+    let (last_chunk, full_chunks) = chunks.split_last().unwrap();
+    assert!(full_chunks.iter().all(|chunk| chunk.len() == chunk_size));
+    assert!(!last_chunk.is_empty() && last_chunk.len() <= chunk_size);
+    assert_eq!(chunks.concat(), bytes);
+
+    // A snapshot downloaded in chunks can be loaded again.
+    let snapshot_dir_v2 = temp_dir().join("chunked_snapshots_v2");
+    let directory_adapter_v2 = Arc::new(photon_indexer::snapshot::DirectoryAdapter::new(
+        Some(photon_indexer::snapshot::FileSystemDirectoryApapter {
+            snapshot_dir: snapshot_dir_v2.to_str().unwrap().to_string(),
+        }),
+        None,
+    ));
+    let snapshot_files = get_snapshot_files_with_metadata(directory_adapter_v2.as_ref())
+        .await
+        .unwrap();
+    for file in snapshot_files {
+        directory_adapter_v2.delete_file(file.file).await.unwrap();
+    }
+    let byte_stream =
+        load_chunked_byte_stream_from_directory_adapter(directory_adapter.clone(), chunk_size)
+            .await;
+    create_snapshot_from_byte_stream(byte_stream, directory_adapter_v2.as_ref())
+        .await
+        .unwrap();
+    let snapshot_blocks: Vec<Vec<BlockInfo>> =
+        load_block_stream_from_directory_adapter(directory_adapter_v2.clone())
+            .await
+            .collect()
+            .await;
+    let snapshot_blocks: Vec<BlockInfo> = snapshot_blocks.into_iter().flatten().collect();
+    assert_eq!(snapshot_blocks, blocks);
+}
+
+#[tokio::test]
+async fn test_compressed_snapshots() {
+    use futures::StreamExt;
+    use photon_indexer::snapshot::compression::SnapshotCompression;
+    use photon_indexer::snapshot::load_compressed_byte_stream_from_directory_adapter;
+    use std::env::temp_dir;
+
+    let blocks: Vec<BlockInfo> = (0..30)
+        .map(|i| BlockInfo {
+            metadata: BlockMetadata {
+                slot: i,
+                parent_slot: if i == 0 { 0 } else { i - 1 },
+                block_time: 0,
+                blockhash: Hash::default(),
+                parent_blockhash: Hash::default(),
+                block_height: i,
+            },
+            transactions: vec![],
+        })
+        .collect();
+
+    let mut snapshot_bytes = vec![];
+    for compression in [
+        SnapshotCompression::None,
+        SnapshotCompression::Gzip,
+        SnapshotCompression::Zstd,
+    ] {
+        let snapshot_dir = temp_dir().join(format!("compressed_snapshots_{:?}", compression));
+        let directory_adapter = Arc::new(
+            photon_indexer::snapshot::DirectoryAdapter::new(
+                Some(photon_indexer::snapshot::FileSystemDirectoryApapter {
+                    snapshot_dir: snapshot_dir.to_str().unwrap().to_string(),
+                }),
+                None,
+            )
+            .with_compression(compression),
+        );
+        let snapshot_files = get_snapshot_files_with_metadata(directory_adapter.as_ref())
+            .await
+            .unwrap();
+        for file in snapshot_files {
+            directory_adapter.delete_file(file.file).await.unwrap();
+        }
+
+        update_snapshot_helper(
+            directory_adapter.clone(),
+            stream::iter(vec![blocks.clone()]),
+            0,
+            2,
+            4,
+            DEFAULT_MAX_PENDING_SNAPSHOT_WRITES,
+        )
+        .await;
+        let snapshot_files = get_snapshot_files_with_metadata(directory_adapter.as_ref())
+            .await
+            .unwrap();
+        assert!(!snapshot_files.is_empty());
+        for file in snapshot_files {
+            assert!(file.file.ends_with(compression.extension()));
+            assert_eq!(file.compression, compression);
+        }
+
+        let snapshot_blocks: Vec<Vec<BlockInfo>> =
+            load_block_stream_from_directory_adapter(directory_adapter.clone())
+                .await
+                .collect()
+                .await;
+        let snapshot_blocks: Vec<BlockInfo> = snapshot_blocks.into_iter().flatten().collect();
+        assert_eq!(snapshot_blocks, blocks);
+
+        let bytes: Vec<u8> = load_byte_stream_from_directory_adapter(directory_adapter.clone())
+            .await
+            .map(|bytes| bytes.unwrap().to_vec())
+            .concat()
+            .await;
+
+        // The files as they are stored decompress to the same snapshot.
+        let compressed_byte_stream = load_compressed_byte_stream_from_directory_adapter(
+            directory_adapter.clone(),
+            compression,
+        )
+        .await;
+        let decompressed_bytes: Vec<u8> = compression
+            .decompress(compressed_byte_stream)
+            .map(|bytes| bytes.unwrap().to_vec())
+            .concat()
+            .await;
+        assert_eq!(decompressed_bytes, bytes);
+        snapshot_bytes.push(bytes);
+    }
+
+    // Compression does not change the snapshot.
+    assert!(snapshot_bytes
+        .iter()
+        .all(|bytes| *bytes == snapshot_bytes[0]));
+}
+
+#[tokio::test]
+async fn test_snapshot_byte_ranges() {
+    use futures::StreamExt;
+    use photon_indexer::snapshot::compression::SnapshotCompression;
+    use photon_indexer::snapshot::{
+        get_compressed_snapshot_size, load_compressed_byte_range_from_directory_adapter,
+        load_compressed_byte_stream_from_directory_adapter,
+    };
+    use std::env::temp_dir;
+
+    let blocks: Vec<BlockInfo> = (0..30)
+        .map(|i| BlockInfo {
+            metadata: BlockMetadata {
+                slot: i,
+                parent_slot: if i == 0 { 0 } else { i - 1 },
+                block_time: 0,
+                blockhash: Hash::default(),
+                parent_blockhash: Hash::default(),
+                block_height: i,
+            },
+            transactions: vec![],
+        })
+        .collect();
+
+    for compression in [SnapshotCompression::None, SnapshotCompression::Gzip] {
+        let snapshot_dir = temp_dir().join(format!("snapshot_byte_ranges_{:?}", compression));
+        let directory_adapter = Arc::new(
+            photon_indexer::snapshot::DirectoryAdapter::new(
+                Some(photon_indexer::snapshot::FileSystemDirectoryApapter {
+                    snapshot_dir: snapshot_dir.to_str().unwrap().to_string(),
+                }),
+                None,
+            )
+            .with_compression(compression),
+        );
+        let snapshot_files = get_snapshot_files_with_metadata(directory_adapter.as_ref())
+            .await
+            .unwrap();
+        for file in snapshot_files {
+            directory_adapter.delete_file(file.file).await.unwrap();
+        }
+
+        update_snapshot_helper(
+            directory_adapter.clone(),
+            stream::iter(vec![blocks.clone()]),
+            0,
+            2,
+            4,
+            DEFAULT_MAX_PENDING_SNAPSHOT_WRITES,
+        )
+        .await;
+        let snapshot_files = get_snapshot_files_with_metadata(directory_adapter.as_ref())
+            .await
+            .unwrap();
+        assert!(snapshot_files.len() > 1);
+        let mut cumulative_size = 0;
+        for file in snapshot_files.iter() {
+            cumulative_size += file.size;
+            assert_eq!(file.cumulative_size, cumulative_size);
+        }
+
+        let bytes: Vec<u8> = load_compressed_byte_stream_from_directory_adapter(
+            directory_adapter.clone(),
+            compression,
+        )
+        .await
+        .map(|bytes| bytes.unwrap().to_vec())
+        .concat()
+        .await;
+        let size = get_compressed_snapshot_size(directory_adapter.as_ref(), compression)
+            .await
+            .unwrap();
+        assert_eq!(size, bytes.len() as u64);
+
+        // Ranges within the header, within a file, spanning files and up to the end.
+        let header_len = size - cumulative_size;
+        let first_file_end = header_len + snapshot_files[0].cumulative_size;
+        for (start, end) in [
+            (0, size),
+            (0, 5),
+            (header_len - 1, header_len + 1),
+            (header_len + 1, first_file_end - 1),
+            (first_file_end - 3, first_file_end + 3),
+            (size - 10, size),
+        ] {
+            let range: Vec<u8> = load_compressed_byte_range_from_directory_adapter(
+                directory_adapter.clone(),
+                compression,
+                start,
+                end,
+            )
+            .await
+            .map(|bytes| bytes.unwrap().to_vec())
+            .concat()
+            .await;
+            assert_eq!(range, bytes[start as usize..end as usize]);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_snapshot_file_sha256() {
+    use photon_indexer::snapshot::compute_snapshot_file_sha256;
+    use sha2::{Digest, Sha256};
+    use std::env::temp_dir;
+
+    let snapshot_dir = temp_dir().join("snapshot_file_sha256");
+    let directory_adapter = Arc::new(photon_indexer::snapshot::DirectoryAdapter::new(
+        Some(photon_indexer::snapshot::FileSystemDirectoryApapter {
+            snapshot_dir: snapshot_dir.to_str().unwrap().to_string(),
+        }),
+        None,
+    ));
+    let snapshot_files = get_snapshot_files_with_metadata(directory_adapter.as_ref())
+        .await
+        .unwrap();
+    for file in snapshot_files {
+        directory_adapter.delete_file(file.file).await.unwrap();
+    }
+
+    let blocks: Vec<BlockInfo> = (0..30)
+        .map(|i| BlockInfo {
+            metadata: BlockMetadata {
+                slot: i,
+                parent_slot: if i == 0 { 0 } else { i - 1 },
+                block_time: 0,
+                blockhash: Hash::default(),
+                parent_blockhash: Hash::default(),
+                block_height: i,
+            },
+            transactions: vec![],
+        })
+        .collect();
+    update_snapshot_helper(
+        directory_adapter.clone(),
+        stream::iter(vec![blocks.clone()]),
+        0,
+        2,
+        4,
+        DEFAULT_MAX_PENDING_SNAPSHOT_WRITES,
+    )
+    .await;
+
+    let snapshot_files = get_snapshot_files_with_metadata(directory_adapter.as_ref())
+        .await
+        .unwrap();
+    assert!(!snapshot_files.is_empty());
+    for file in snapshot_files {
+        let bytes = std::fs::read(snapshot_dir.join(&file.file)).unwrap();
+        assert_eq!(file.size, bytes.len() as u64);
+        let sha256 = compute_snapshot_file_sha256(directory_adapter.as_ref(), &file.file)
+            .await
+            .unwrap();
+        assert_eq!(sha256, hex::encode(Sha256::digest(bytes)));
+    }
+}
+
+#[tokio::test]
+async fn test_verify_snapshot_directory() {
+    use photon_indexer::snapshot::compression::SnapshotCompression;
+    use photon_indexer::snapshot::{
+        compute_snapshot_file_sha256, read_snapshot_file_checksum, verify_snapshot_directory,
+    };
+    use std::env::temp_dir;
+    use std::io::Write;
+
+    let snapshot_dir = temp_dir().join("verify_snapshot_directory");
+    let directory_adapter = Arc::new(
+        photon_indexer::snapshot::DirectoryAdapter::new(
+            Some(photon_indexer::snapshot::FileSystemDirectoryApapter {
+                snapshot_dir: snapshot_dir.to_str().unwrap().to_string(),
+            }),
+            None,
+        )
+        .with_compression(SnapshotCompression::Gzip),
+    );
+    let snapshot_files = get_snapshot_files_with_metadata(directory_adapter.as_ref())
+        .await
+        .unwrap();
+    for file in snapshot_files {
+        directory_adapter.delete_file(file.file).await.unwrap();
+    }
+
+    let blocks: Vec<BlockInfo> = (0..30)
+        .map(|i| BlockInfo {
+            metadata: BlockMetadata {
+                slot: i,
+                parent_slot: if i == 0 { 0 } else { i - 1 },
+                block_time: 0,
+                blockhash: Hash::default(),
+                parent_blockhash: Hash::default(),
+                block_height: i,
+            },
+            transactions: vec![],
+        })
+        .collect();
+    update_snapshot_helper(
+        directory_adapter.clone(),
+        stream::iter(vec![blocks.clone()]),
+        0,
+        2,
+        4,
+        DEFAULT_MAX_PENDING_SNAPSHOT_WRITES,
+    )
+    .await;
+
+    // Every file written by the snapshotter has a checksum of its stored bytes.
+    let snapshot_files = get_snapshot_files_with_metadata(directory_adapter.as_ref())
+        .await
+        .unwrap();
+    assert!(!snapshot_files.is_empty());
+    for file in snapshot_files.iter() {
+        let sha256 = compute_snapshot_file_sha256(directory_adapter.as_ref(), &file.file)
+            .await
+            .unwrap();
+        let recorded_sha256 = read_snapshot_file_checksum(directory_adapter.as_ref(), file)
+            .await
+            .unwrap();
+        assert_eq!(recorded_sha256, Some(sha256));
+    }
+    let corrupt_files = verify_snapshot_directory(directory_adapter.as_ref())
+        .await
+        .unwrap();
+    assert!(corrupt_files.is_empty());
+
+    let corrupt_file = snapshot_files.last().unwrap().file.clone();
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(snapshot_dir.join(&corrupt_file))
+        .unwrap()
+        .write_all(&[0])
+        .unwrap();
+    let corrupt_files = verify_snapshot_directory(directory_adapter.as_ref())
+        .await
+        .unwrap();
+    assert_eq!(corrupt_files, vec![corrupt_file]);
+}
+
+#[tokio::test]
+async fn test_snapshot_upload_dead_letter() {
+    use bytes::Bytes;
+    use photon_indexer::snapshot::compression::SnapshotCompression;
+    use photon_indexer::snapshot::{
+        read_dead_letters, verify_snapshot_directory, DirectoryAdapter, UploadRetryPolicy,
+    };
+    use std::env::temp_dir;
+    use std::time::Duration;
+
+    // Writes fail because the snapshot directory is a regular file.
+    let snapshot_dir = temp_dir().join("snapshot_upload_dead_letter");
+    let _ = std::fs::remove_dir_all(&snapshot_dir);
+    std::fs::write(&snapshot_dir, b"not a directory").unwrap();
+    let dead_letter_dir = temp_dir().join("snapshot_upload_dead_letters");
+    let _ = std::fs::remove_dir_all(&dead_letter_dir);
+    let dead_letter_dir = dead_letter_dir.to_str().unwrap().to_string();
+
+    let directory_adapter =
+        DirectoryAdapter::from_local_directory(snapshot_dir.to_str().unwrap().to_string())
+            .with_compression(SnapshotCompression::Gzip)
+            .with_upload_retry_policy(UploadRetryPolicy {
+                max_attempts: 3,
+                initial_delay: Duration::from_millis(1),
+                dead_letter_dir: Some(dead_letter_dir.clone()),
+            });
+    let dead_letter = directory_adapter
+        .write_file_with_retries("snapshot-1-2".to_string(), Bytes::from(vec![1, 2, 3]))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(dead_letter.file, "snapshot-1-2.gz");
+    assert_eq!(dead_letter.attempts, 3);
+    assert_eq!(
+        read_dead_letters(&dead_letter_dir).unwrap(),
+        vec![dead_letter]
+    );
+
+    // The dead-lettered file is stored as it would have been uploaded, with its checksum.
+    let dead_letter_adapter = DirectoryAdapter::from_local_directory(dead_letter_dir);
+    let snapshot_files = get_snapshot_files_with_metadata(&dead_letter_adapter)
+        .await
+        .unwrap();
+    assert_eq!(snapshot_files.len(), 1);
+    assert_eq!(snapshot_files[0].file, "snapshot-1-2.gz");
+    assert!(snapshot_files[0].has_checksum);
+    let corrupt_files = verify_snapshot_directory(&dead_letter_adapter)
+        .await
+        .unwrap();
+    assert!(corrupt_files.is_empty());
+
+    std::fs::remove_file(&snapshot_dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_resume_interrupted_snapshot_download() {
+    use hyper::header::{CONTENT_LENGTH, CONTENT_RANGE, RANGE};
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server, StatusCode};
+    use photon_indexer::snapshot::compression::SnapshotCompression;
+    use photon_indexer::snapshot::download_snapshot_file;
+    use std::convert::Infallible;
+    use std::env::temp_dir;
+    use std::io;
+    use std::sync::Mutex;
+
+    let snapshot = (0..10_000u32).map(|i| i as u8).collect::<Vec<u8>>();
+    let interrupt_at = snapshot.len() / 2;
+
+    // A snapshotter whose first response is interrupted halfway through the snapshot.
+    let ranges = Arc::new(Mutex::new(Vec::new()));
+    let make_service = {
+        let snapshot = snapshot.clone();
+        let ranges = ranges.clone();
+        make_service_fn(move |_| {
+            let snapshot = snapshot.clone();
+            let ranges = ranges.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let snapshot = snapshot.clone();
+                    let range = request
+                        .headers()
+                        .get(RANGE)
+                        .map(|range| range.to_str().unwrap().to_string());
+                    ranges.lock().unwrap().push(range.clone());
+                    async move {
+                        let response = match range {
+                            None => Response::builder()
+                                .header(CONTENT_LENGTH, snapshot.len())
+                                .body(Body::wrap_stream(stream::iter(vec![
+                                    Ok(snapshot[..interrupt_at].to_vec()),
+                                    Err(io::Error::new(io::ErrorKind::Other, "Interrupted")),
+                                ]))),
+                            Some(range) => {
+                                let first = range
+                                    .trim_start_matches("bytes=")
+                                    .trim_end_matches('-')
+                                    .parse::<usize>()
+                                    .unwrap();
+                                Response::builder()
+                                    .status(StatusCode::PARTIAL_CONTENT)
+                                    .header(
+                                        CONTENT_RANGE,
+                                        format!(
+                                            "bytes {}-{}/{}",
+                                            first,
+                                            snapshot.len() - 1,
+                                            snapshot.len()
+                                        ),
+                                    )
+                                    .header(CONTENT_LENGTH, snapshot.len() - first)
+                                    .body(Body::from(snapshot[first..].to_vec()))
+                            }
+                        };
+                        Ok::<_, Infallible>(response.unwrap())
+                    }
+                }))
+            }
+        })
+    };
+    let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+    let snapshot_server_url = format!("http://{}", server.local_addr());
+    tokio::spawn(server);
+
+    let download_dir = temp_dir().join("resume_interrupted_snapshot_download");
+    let _ = std::fs::remove_dir_all(&download_dir);
+    let download_dir = download_dir.to_str().unwrap().to_string();
+
+    // The first run is interrupted and gives up, keeping the bytes it received.
+    assert!(
+        download_snapshot_file(&snapshot_server_url, &download_dir, 1)
+            .await
+            .is_err()
+    );
+    let received = std::fs::metadata(format!("{}/snapshot.download", download_dir))
+        .unwrap()
+        .len();
+    assert!(received > 0);
+    // The next run resumes from where the first one stopped.
+    let (path, compression) = download_snapshot_file(&snapshot_server_url, &download_dir, 1)
+        .await
+        .unwrap();
+    assert_eq!(compression, SnapshotCompression::None);
+    assert_eq!(std::fs::read(&path).unwrap(), snapshot);
+
+    let ranges = ranges.lock().unwrap().clone();
+    assert_eq!(ranges.len(), 2);
+    assert_eq!(ranges[0], None);
+    assert_eq!(ranges[1], Some(format!("bytes={}-", received)));
+
+    std::fs::remove_dir_all(&download_dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_restart_download_of_changed_snapshot() {
+    use hyper::header::{HeaderName, CONTENT_LENGTH, CONTENT_RANGE, ETAG, IF_RANGE, RANGE};
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server, StatusCode};
+    use photon_indexer::snapshot::download_snapshot_file;
+    use std::convert::Infallible;
+    use std::env::temp_dir;
+    use std::io;
+    use std::sync::Mutex;
+
+    let old_snapshot = (0..10_000u32).map(|i| i as u8).collect::<Vec<u8>>();
+    let new_snapshot = (0..12_000u32).map(|i| (i * 7) as u8).collect::<Vec<u8>>();
+    let interrupt_at = old_snapshot.len() / 2;
+
+    // A snapshotter whose first response is interrupted, after which its snapshot is merged into
+    // a new one. Like a proxy that doesn't support `If-Range`, it serves ranges regardless.
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let make_service = {
+        let old_snapshot = old_snapshot.clone();
+        let new_snapshot = new_snapshot.clone();
+        let requests = requests.clone();
+        make_service_fn(move |_| {
+            let old_snapshot = old_snapshot.clone();
+            let new_snapshot = new_snapshot.clone();
+            let requests = requests.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let header = |name: HeaderName| {
+                        request
+                            .headers()
+                            .get(name)
+                            .map(|value| value.to_str().unwrap().to_string())
+                    };
+                    let (range, if_range) = (header(RANGE), header(IF_RANGE));
+                    let mut requests = requests.lock().unwrap();
+                    requests.push((range.clone(), if_range));
+                    let response = match (requests.len(), range) {
+                        (1, _) => Response::builder()
+                            .header(CONTENT_LENGTH, old_snapshot.len())
+                            .header(ETAG, "\"old\"")
+                            .body(Body::wrap_stream(stream::iter(vec![
+                                Ok(old_snapshot[..interrupt_at].to_vec()),
+                                Err(io::Error::new(io::ErrorKind::Other, "Interrupted")),
+                            ]))),
+                        (_, None) => Response::builder()
+                            .header(CONTENT_LENGTH, new_snapshot.len())
+                            .header(ETAG, "\"new\"")
+                            .body(Body::from(new_snapshot.clone())),
+                        (_, Some(range)) => {
+                            let first = range
+                                .trim_start_matches("bytes=")
+                                .trim_end_matches('-')
+                                .parse::<usize>()
+                                .unwrap();
+                            Response::builder()
+                                .status(StatusCode::PARTIAL_CONTENT)
+                                .header(
+                                    CONTENT_RANGE,
+                                    format!(
+                                        "bytes {}-{}/{}",
+                                        first,
+                                        new_snapshot.len() - 1,
+                                        new_snapshot.len()
+                                    ),
+                                )
+                                .header(CONTENT_LENGTH, new_snapshot.len() - first)
+                                .header(ETAG, "\"new\"")
+                                .body(Body::from(new_snapshot[first..].to_vec()))
+                        }
+                    };
+                    async move { Ok::<_, Infallible>(response.unwrap()) }
+                }))
+            }
+        })
+    };
+    let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+    let snapshot_server_url = format!("http://{}", server.local_addr());
+    tokio::spawn(server);
+
+    let download_dir = temp_dir().join("restart_download_of_changed_snapshot");
+    let _ = std::fs::remove_dir_all(&download_dir);
+    let download_dir = download_dir.to_str().unwrap().to_string();
+
+    assert!(
+        download_snapshot_file(&snapshot_server_url, &download_dir, 1)
+            .await
+            .is_err()
+    );
+    let received = std::fs::metadata(format!("{}/snapshot.download", download_dir))
+        .unwrap()
+        .len();
+    assert!(received > 0);
+    // The range of the new snapshot doesn't match the partial download of the old one, so the
+    // download is restarted instead of appending the range to it.
+    let (path, _) = download_snapshot_file(&snapshot_server_url, &download_dir, 2)
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), new_snapshot);
+
+    let requests = requests.lock().unwrap().clone();
+    assert_eq!(
+        requests,
+        vec![
+            (None, None),
+            (
+                Some(format!("bytes={}-", received)),
+                Some("\"old\"".to_string())
+            ),
+            (None, None),
+        ]
+    );
+
+    std::fs::remove_dir_all(&download_dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_ingest_blocks_during_snapshot_write() {
+    use async_stream::stream;
+    use futures::StreamExt;
+    use photon_indexer::snapshot::{DirectoryAdapter, UploadRetryPolicy};
+    use std::env::temp_dir;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    // The first write of a snapshot file fails because the parent of the snapshot directory is a
+    // regular file, so the write is in progress until it is retried.
+    let blocking_file = temp_dir().join("snapshot_ingestion_during_write");
+    let _ = std::fs::remove_dir_all(&blocking_file);
+    std::fs::write(&blocking_file, b"not a directory").unwrap();
+    let snapshot_dir = blocking_file.join("snapshots");
+    let directory_adapter = Arc::new(
+        DirectoryAdapter::from_local_directory(snapshot_dir.to_str().unwrap().to_string())
+            .with_upload_retry_policy(UploadRetryPolicy {
+                max_attempts: 2,
+                initial_delay: Duration::from_secs(2),
+                dead_letter_dir: None,
+            }),
+    );
+
+    let blocks: Vec<BlockInfo> = (0..12)
+        .map(|i| BlockInfo {
+            metadata: BlockMetadata {
+                slot: i,
+                parent_slot: if i == 0 { 0 } else { i - 1 },
+                block_time: 0,
+                blockhash: Hash::default(),
+                parent_blockhash: Hash::default(),
+                block_height: i,
+            },
+            transactions: vec![],
+        })
+        .collect();
+    let (sender, mut receiver) = mpsc::channel(1);
+    let blocks_stream = stream! {
+        while let Some(blocks) = receiver.recv().await {
+            yield blocks;
+        }
+    };
+    let snapshotter = tokio::spawn(update_snapshot_helper(
+        directory_adapter.clone(),
+        blocks_stream,
+        0,
+        2,
+        1000,
+        DEFAULT_MAX_PENDING_SNAPSHOT_WRITES,
+    ));
+
+    // Every block is consumed by the snapshotter while the first snapshot file is being written.
+    for block in blocks.iter() {
+        tokio::time::timeout(Duration::from_secs(1), sender.send(vec![block.clone()]))
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    std::fs::remove_file(&blocking_file).unwrap();
+    drop(sender);
+    snapshotter.await.unwrap();
+    let snapshot_blocks = load_block_stream_from_directory_adapter(directory_adapter.clone()).await;
+    let snapshot_blocks: Vec<Vec<BlockInfo>> = snapshot_blocks.collect().await;
+    let snapshot_blocks: Vec<BlockInfo> = snapshot_blocks.into_iter().flatten().collect();
+    assert_eq!(snapshot_blocks, blocks);
+
+    std::fs::remove_dir_all(&blocking_file).unwrap();
+}
+
+#[tokio::test]
+async fn test_hold_back_snapshot_writes_after_dead_letter() {
+    use async_stream::stream;
+    use futures::StreamExt;
+    use photon_indexer::snapshot::{read_dead_letters, DirectoryAdapter, UploadRetryPolicy};
+    use std::env::temp_dir;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    // Writes of snapshot files fail while the parent of the snapshot directory is a regular file.
+    let blocking_file = temp_dir().join("snapshot_hold_back_after_dead_letter");
+    let _ = std::fs::remove_dir_all(&blocking_file);
+    std::fs::write(&blocking_file, b"not a directory").unwrap();
+    let snapshot_dir = blocking_file.join("snapshots");
+    let dead_letter_dir = temp_dir().join("snapshot_hold_back_dead_letters");
+    let _ = std::fs::remove_dir_all(&dead_letter_dir);
+    let directory_adapter = Arc::new(
+        DirectoryAdapter::from_local_directory(snapshot_dir.to_str().unwrap().to_string())
+            .with_upload_retry_policy(UploadRetryPolicy {
+                max_attempts: 1,
+                initial_delay: Duration::from_millis(1),
+                dead_letter_dir: Some(dead_letter_dir.to_str().unwrap().to_string()),
+            }),
+    );
+
+    let blocks: Vec<BlockInfo> = (0..12)
+        .map(|i| BlockInfo {
+            metadata: BlockMetadata {
+                slot: i,
+                parent_slot: if i == 0 { 0 } else { i - 1 },
+                block_time: 0,
+                blockhash: Hash::default(),
+                parent_blockhash: Hash::default(),
+                block_height: i,
+            },
+            transactions: vec![],
+        })
+        .collect();
+    let (sender, mut receiver) = mpsc::channel(1);
+    let blocks_stream = stream! {
+        while let Some(blocks) = receiver.recv().await {
+            yield blocks;
+        }
+    };
+    let snapshotter = tokio::spawn(update_snapshot_helper(
+        directory_adapter.clone(),
+        blocks_stream,
+        0,
+        2,
+        4,
+        DEFAULT_MAX_PENDING_SNAPSHOT_WRITES,
+    ));
+
+    // The first snapshot file is dead-lettered, and the files after it are held back even though
+    // the snapshot directory becomes writable again.
+    sender.send(blocks[..2].to_vec()).await.unwrap();
+    let dead_letter_dir = dead_letter_dir.to_str().unwrap().to_string();
+    let wait_for_dead_letters = |count: usize| {
+        let dead_letter_dir = dead_letter_dir.clone();
+        async move {
+            while read_dead_letters(&dead_letter_dir).unwrap().len() < count {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), wait_for_dead_letters(1))
+        .await
+        .unwrap();
+    std::fs::remove_file(&blocking_file).unwrap();
+    sender.send(blocks[2..6].to_vec()).await.unwrap();
+    tokio::time::timeout(Duration::from_secs(5), wait_for_dead_letters(3))
+        .await
+        .unwrap();
+    assert!(get_snapshot_files_with_metadata(directory_adapter.as_ref())
+        .await
+        .unwrap()
+        .is_empty());
+    let dead_letters = read_dead_letters(&dead_letter_dir).unwrap();
+    assert_eq!(dead_letters[0].attempts, 1);
+    assert!(dead_letters[1..]
+        .iter()
+        .all(|dead_letter| dead_letter.attempts == 0));
+
+    // Once the dead-lettered files are restored, later files are written and merged again.
+    std::fs::create_dir_all(&snapshot_dir).unwrap();
+    for entry in std::fs::read_dir(&dead_letter_dir).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_name().to_str().unwrap().starts_with("snapshot-") {
+            std::fs::copy(entry.path(), snapshot_dir.join(entry.file_name())).unwrap();
+        }
+    }
+    sender.send(blocks[6..].to_vec()).await.unwrap();
+    drop(sender);
+    snapshotter.await.unwrap();
+    assert_eq!(read_dead_letters(&dead_letter_dir).unwrap().len(), 3);
+    let snapshot_blocks = load_block_stream_from_directory_adapter(directory_adapter.clone()).await;
+    let snapshot_blocks: Vec<Vec<BlockInfo>> = snapshot_blocks.collect().await;
+    let snapshot_blocks: Vec<BlockInfo> = snapshot_blocks.into_iter().flatten().collect();
+    assert_eq!(snapshot_blocks, blocks);
+
+    std::fs::remove_dir_all(&blocking_file).unwrap();
+    std::fs::remove_dir_all(&dead_letter_dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_snapshot_diff() {
+    use crate::utils::cached_fetch_transaction;
+    use photon_indexer::ingester::parser::{parse_transaction, ParserConfig};
+    use photon_indexer::ingester::typedefs::block_info::TransactionInfo;
+    use photon_indexer::snapshot::diff::{diff_snapshots, AccountUpdate, SlotRange};
+    use photon_indexer::snapshot::DirectoryAdapter;
+    use solana_client::nonblocking::rpc_client::RpcClient;
+    use std::env::temp_dir;
+
+    // The transactions are cached, so the RPC client is never called.
+    let rpc_client = Arc::new(RpcClient::new("http://127.0.0.1:8899".to_string()));
+    let mut transactions: Vec<TransactionInfo> = Vec::new();
+    for tx in [
+        "5NLdbqznXqmTPTN8JBLquriDggb9qaRszVGLSvt6t5esy2Q8Z1iqAuXF4qoLK7HM6oGLySUNUkzhnSocwArpAqmV",
+        "4TFBPyvatWgjTdNesfaTo3YkbP2spvGmgZgLn6CvTeqRZSi1ZuPCkK7fLaDbPKskMSF4Azge6QPvtZt9VUV7KBF8",
+        "QBrbAZFq12LCbnv5dByn8vB8Znam4ieGQVzybapgPL5LCa9KHfuYZKV6Nah6UGsa6FUptmT6tSpexWZDrbp82iP",
+    ] {
+        let tx = cached_fetch_transaction("lamport_transfers", rpc_client.clone(), tx).await;
+        transactions.push(tx.try_into().unwrap());
+    }
+    let compressed_account = parse_transaction(&transactions[0], 1, &ParserConfig::default())
+        .unwrap()
+        .out_accounts[0]
+        .hash
+        .clone();
+
+    // The compression is in slot 1 and the two transfers from the payer to the receiver are in
+    // slots 2 and 3.
+    let mut transactions = transactions.into_iter();
+    let blocks: Vec<BlockInfo> = (0..4)
+        .map(|i| BlockInfo {
+            metadata: BlockMetadata {
+                slot: i,
+                parent_slot: if i == 0 { 0 } else { i - 1 },
+                block_time: 0,
+                blockhash: Hash::default(),
+                parent_blockhash: Hash::default(),
+                block_height: i,
+            },
+            transactions: match i {
+                0 => vec![],
+                _ => vec![transactions.next().unwrap()],
+            },
+        })
+        .collect();
+
+    let mut directory_adapters = Vec::new();
+    for (snapshot_dir, end_slot) in [("snapshot_diff_full", 1), ("snapshot_diff_incremental", 3)] {
+        let snapshot_dir = temp_dir().join(snapshot_dir);
+        let _ = std::fs::remove_dir_all(&snapshot_dir);
+        let directory_adapter = Arc::new(DirectoryAdapter::from_local_directory(
+            snapshot_dir.to_str().unwrap().to_string(),
+        ));
+        update_snapshot_helper(
+            directory_adapter.clone(),
+            stream::iter(vec![blocks[..=end_slot].to_vec()]),
+            0,
+            2,
+            1000,
+            DEFAULT_MAX_PENDING_SNAPSHOT_WRITES,
+        )
+        .await;
+        directory_adapters.push(directory_adapter);
+    }
+
+    let diff = diff_snapshots(
+        directory_adapters[0].clone(),
+        directory_adapters[1].clone(),
+        &ParserConfig::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        diff.base_slots,
+        SlotRange {
+            start_slot: 1,
+            end_slot: 1
+        }
+    );
+    assert_eq!(
+        diff.diff_slots,
+        Some(SlotRange {
+            start_slot: 2,
+            end_slot: 3
+        })
+    );
+    // The compressed account is superseded by the change of each transfer, and the receiver gets
+    // a new account from each transfer.
+    assert_eq!(
+        diff.updated_accounts,
+        vec![AccountUpdate {
+            input_hash: compressed_account,
+            output_hash: Hash::try_from("2veKUPKieajFG7yzPNGARHq7jYS1FiXcXG8P9Txhvhcn").unwrap(),
+        }]
+    );
+    let mut added_accounts = diff.added_accounts.clone();
+    added_accounts.sort_by_key(|hash| hash.to_base58());
+    assert_eq!(
+        added_accounts,
+        vec![
+            Hash::try_from("2eoEsJByEcWpLTdaEwr1DB6cwXkFxqAVZnbvWoQSDm4V").unwrap(),
+            Hash::try_from("3q5dSVJCK4Tk2VU9C4MaPPw4TdzKdtp1M5Dug3zERSx4").unwrap(),
+        ]
+    );
+    assert!(diff.spent_accounts.is_empty());
+}
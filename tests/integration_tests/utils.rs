@@ -10,9 +10,13 @@ use photon_indexer::{
         typedefs::{account::Account, token_data::TokenData},
     },
     ingester::{
-        parser::{parse_transaction, state_update::StateUpdate},
-        persist::persist_state_update,
-        typedefs::block_info::{parse_ui_confirmed_blocked, BlockInfo, TransactionInfo},
+        index_block,
+        parser::{parse_transaction, state_update::StateUpdate, ParserConfig},
+        persist::{persist_state_update, PersistConfig},
+        typedefs::block_info::{
+            parse_ui_confirmed_blocked, BlockInfo, BlockMetadata, TransactionInfo,
+        },
+        IngesterConfig,
     },
 };
 pub use sea_orm::DatabaseBackend;
@@ -363,11 +367,30 @@ pub async fn persist_state_update_using_connection(
     state_update: StateUpdate,
 ) -> Result<(), sea_orm::DbErr> {
     let txn = db.begin().await.unwrap();
-    persist_state_update(&txn, state_update).await.unwrap();
+    persist_state_update(&txn, state_update, &PersistConfig::default())
+        .await
+        .unwrap();
     txn.commit().await.unwrap();
     Ok(())
 }
 
+/// Indexes an empty block at slot 0, so that API methods can fetch the current slot.
+pub async fn index_empty_block(db: &DatabaseConnection) {
+    index_block(
+        db,
+        &BlockInfo {
+            metadata: BlockMetadata {
+                slot: 0,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        &IngesterConfig::default(),
+    )
+    .await
+    .unwrap();
+}
+
 pub async fn index_transaction(
     test_name: &str,
     db_conn: Arc<DatabaseConnection>,
@@ -375,7 +398,8 @@ pub async fn index_transaction(
     tx: &str,
 ) {
     let tx = cached_fetch_transaction(test_name, rpc_client, tx).await;
-    let state_update = parse_transaction(&tx.try_into().unwrap(), 0).unwrap();
+    let state_update =
+        parse_transaction(&tx.try_into().unwrap(), 0, &ParserConfig::default()).unwrap();
     persist_state_update_using_connection(db_conn.as_ref(), state_update)
         .await
         .unwrap();
@@ -394,7 +418,8 @@ pub async fn index_multiple_transactions(
     }
     let mut state_updates = Vec::new();
     for transaction_info in transactions_infos {
-        let tx_state_update = parse_transaction(&transaction_info, 0).unwrap();
+        let tx_state_update =
+            parse_transaction(&transaction_info, 0, &ParserConfig::default()).unwrap();
         state_updates.push(tx_state_update);
     }
     let state_update = StateUpdate::merge_updates(state_updates);